@@ -0,0 +1,311 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+#[cfg(unix)]
+use fsindex_core::RemoteIndex;
+use fsindex_core::{
+    AgeBucket, CancellationToken, CandidateGroup, DuplicateGroup, DuplicateSort, FileIndexer,
+    FileRecord, HashAlgorithm, IndexOptions, IndexReport, IndexStats, PruneReport, RootInfo,
+    ScoredRecord, SearchQuery, Suggestion, VolumeInfo,
+};
+
+// What the commands that also work with `--remote` need from the index:
+// the database itself, or a daemon serving it.
+pub trait Catalog {
+    // The database, for what only a local index can do.
+    fn local(&self) -> Option<&FileIndexer>;
+    fn search(&self, q: &SearchQuery) -> Result<Vec<FileRecord>>;
+    fn search_ranked(&self, q: &SearchQuery) -> Result<Vec<ScoredRecord>>;
+    fn count(&self, q: &SearchQuery) -> Result<i64>;
+    fn query_plan(&self, q: &SearchQuery) -> Result<Vec<String>>;
+    fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>>;
+    fn duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>>;
+    fn verified_duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>>;
+    fn candidate_groups(
+        &self,
+        by_name: bool,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<CandidateGroup>>;
+    fn hashed_candidate_groups(
+        &self,
+        by_name: bool,
+        algo: HashAlgorithm,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>>;
+    fn stats(&self) -> Result<IndexStats>;
+    fn age_histogram(&self) -> Result<Vec<AgeBucket>>;
+    fn suggest_extensions(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>>;
+    fn suggest_dirs(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>>;
+    fn suggest_name_tokens(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>>;
+    fn list_roots(&self) -> Result<Vec<RootInfo>>;
+    fn volumes(&self) -> Result<Vec<VolumeInfo>>;
+    fn disconnected_volumes(&self) -> Result<Vec<VolumeInfo>>;
+    fn index_dir(&self, root: &Path, opts: &IndexOptions) -> Result<IndexReport>;
+    fn reindex_all(&self) -> Result<IndexReport>;
+    fn prune(&self, root: Option<&Path>) -> Result<PruneReport>;
+    fn prune_dry_run(&self, root: Option<&Path>) -> Result<PruneReport>;
+
+    // Rows one at a time; a daemon sends them all at once.
+    fn for_each_row(
+        &self,
+        q: &SearchQuery,
+        f: &mut dyn FnMut(FileRecord) -> Result<()>,
+    ) -> Result<usize> {
+        let rows = self.search(q)?;
+        let count = rows.len();
+        for row in rows {
+            f(row)?;
+        }
+        Ok(count)
+    }
+}
+
+impl Catalog for FileIndexer {
+    fn local(&self) -> Option<&FileIndexer> {
+        Some(self)
+    }
+
+    fn search(&self, q: &SearchQuery) -> Result<Vec<FileRecord>> {
+        FileIndexer::search(self, q)
+    }
+
+    fn search_ranked(&self, q: &SearchQuery) -> Result<Vec<ScoredRecord>> {
+        FileIndexer::search_ranked(self, q)
+    }
+
+    fn count(&self, q: &SearchQuery) -> Result<i64> {
+        FileIndexer::count(self, q)
+    }
+
+    fn query_plan(&self, q: &SearchQuery) -> Result<Vec<String>> {
+        FileIndexer::query_plan(self, q)
+    }
+
+    fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>> {
+        FileIndexer::recently_added(self, limit)
+    }
+
+    fn duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        FileIndexer::duplicate_groups(self, limit, offset, sort)
+    }
+
+    fn verified_duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        FileIndexer::verified_duplicate_groups(self, limit, offset, sort)
+    }
+
+    fn candidate_groups(
+        &self,
+        by_name: bool,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<CandidateGroup>> {
+        FileIndexer::candidate_groups(self, by_name, limit, offset, sort)
+    }
+
+    fn hashed_candidate_groups(
+        &self,
+        by_name: bool,
+        algo: HashAlgorithm,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        FileIndexer::hashed_candidate_groups(self, by_name, algo, limit, offset, sort)
+    }
+
+    fn stats(&self) -> Result<IndexStats> {
+        FileIndexer::stats(self)
+    }
+
+    fn age_histogram(&self) -> Result<Vec<AgeBucket>> {
+        FileIndexer::age_histogram(self)
+    }
+
+    fn suggest_extensions(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        FileIndexer::suggest_extensions(self, partial, limit)
+    }
+
+    fn suggest_dirs(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        FileIndexer::suggest_dirs(self, partial, limit)
+    }
+
+    fn suggest_name_tokens(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        FileIndexer::suggest_name_tokens(self, partial, limit)
+    }
+
+    fn list_roots(&self) -> Result<Vec<RootInfo>> {
+        FileIndexer::list_roots(self)
+    }
+
+    fn volumes(&self) -> Result<Vec<VolumeInfo>> {
+        FileIndexer::volumes(self)
+    }
+
+    fn disconnected_volumes(&self) -> Result<Vec<VolumeInfo>> {
+        FileIndexer::disconnected_volumes(self)
+    }
+
+    fn index_dir(&self, root: &Path, opts: &IndexOptions) -> Result<IndexReport> {
+        FileIndexer::index_dir(self, root, opts)
+    }
+
+    fn reindex_all(&self) -> Result<IndexReport> {
+        FileIndexer::reindex_all(self, &CancellationToken::new())
+    }
+
+    fn prune(&self, root: Option<&Path>) -> Result<PruneReport> {
+        FileIndexer::prune(self, root)
+    }
+
+    fn prune_dry_run(&self, root: Option<&Path>) -> Result<PruneReport> {
+        FileIndexer::prune_dry_run(self, root)
+    }
+
+    fn for_each_row(
+        &self,
+        q: &SearchQuery,
+        f: &mut dyn FnMut(FileRecord) -> Result<()>,
+    ) -> Result<usize> {
+        FileIndexer::for_each_row(self, q, f)
+    }
+}
+
+#[cfg(unix)]
+impl Catalog for RemoteIndex {
+    fn local(&self) -> Option<&FileIndexer> {
+        None
+    }
+
+    fn search(&self, q: &SearchQuery) -> Result<Vec<FileRecord>> {
+        RemoteIndex::search(self, q)
+    }
+
+    fn search_ranked(&self, q: &SearchQuery) -> Result<Vec<ScoredRecord>> {
+        RemoteIndex::search_ranked(self, q)
+    }
+
+    fn count(&self, q: &SearchQuery) -> Result<i64> {
+        RemoteIndex::count(self, q)
+    }
+
+    fn query_plan(&self, q: &SearchQuery) -> Result<Vec<String>> {
+        RemoteIndex::query_plan(self, q)
+    }
+
+    fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>> {
+        RemoteIndex::recently_added(self, limit)
+    }
+
+    fn duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        RemoteIndex::duplicate_groups(self, limit, offset, sort)
+    }
+
+    fn verified_duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        RemoteIndex::verified_duplicate_groups(self, limit, offset, sort)
+    }
+
+    fn candidate_groups(
+        &self,
+        by_name: bool,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<CandidateGroup>> {
+        RemoteIndex::candidate_groups(self, by_name, limit, offset, sort)
+    }
+
+    fn hashed_candidate_groups(
+        &self,
+        by_name: bool,
+        algo: HashAlgorithm,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        RemoteIndex::hashed_candidate_groups(self, by_name, algo, limit, offset, sort)
+    }
+
+    fn stats(&self) -> Result<IndexStats> {
+        RemoteIndex::stats(self)
+    }
+
+    fn age_histogram(&self) -> Result<Vec<AgeBucket>> {
+        RemoteIndex::age_histogram(self)
+    }
+
+    fn suggest_extensions(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        RemoteIndex::suggest_extensions(self, partial, limit)
+    }
+
+    fn suggest_dirs(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        RemoteIndex::suggest_dirs(self, partial, limit)
+    }
+
+    fn suggest_name_tokens(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        RemoteIndex::suggest_name_tokens(self, partial, limit)
+    }
+
+    fn list_roots(&self) -> Result<Vec<RootInfo>> {
+        RemoteIndex::list_roots(self)
+    }
+
+    fn volumes(&self) -> Result<Vec<VolumeInfo>> {
+        RemoteIndex::volumes(self)
+    }
+
+    fn disconnected_volumes(&self) -> Result<Vec<VolumeInfo>> {
+        RemoteIndex::disconnected_volumes(self)
+    }
+
+    fn index_dir(&self, root: &Path, opts: &IndexOptions) -> Result<IndexReport> {
+        RemoteIndex::index_dir(self, root, opts)
+    }
+
+    fn reindex_all(&self) -> Result<IndexReport> {
+        RemoteIndex::reindex_all(self)
+    }
+
+    fn prune(&self, root: Option<&Path>) -> Result<PruneReport> {
+        RemoteIndex::prune(self, root)
+    }
+
+    fn prune_dry_run(&self, root: Option<&Path>) -> Result<PruneReport> {
+        RemoteIndex::prune_dry_run(self, root)
+    }
+}
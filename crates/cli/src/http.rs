@@ -0,0 +1,428 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use fsindex_core::{DuplicateSort, FileIndexer, IndexOptions, SearchQuery};
+
+use crate::{next_page, offline_counts, report_json, Page};
+
+// Requests larger than this are refused rather than read.
+const MAX_BODY: usize = 1 << 20;
+const MAX_HEADERS: usize = 100;
+// Longest request line or header accepted, in bytes.
+const MAX_LINE: u64 = 8 * 1024;
+// Connections handled at once; more are turned away with 503.
+const MAX_CONNECTIONS: usize = 64;
+const DEFAULT_LIMIT: i64 = 50;
+
+// The body of `POST /index`.
+#[derive(Deserialize)]
+struct IndexRequest {
+    path: PathBuf,
+    #[serde(default)]
+    options: IndexOptions,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    params: Vec<(String, String)>,
+    authorization: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+// An answer other than 200, with the message sent as `{"error": ...}`.
+#[derive(Debug)]
+struct Status(u16, String);
+
+impl From<anyhow::Error> for Status {
+    fn from(err: anyhow::Error) -> Self {
+        Status(500, format!("{:#}", err))
+    }
+}
+
+// Answers JSON requests on `addr` until interrupted, each connection on its
+// own thread. With `token`, requests must carry `Authorization: Bearer
+// <token>`, and responses then allow any origin so web frontends can call
+// it; without one, browsers keep other sites from reading the answers and
+// requests that change the index are refused.
+pub fn serve(indexer: &FileIndexer, addr: &str, token: Option<&str>) -> Result<()> {
+    let listener = TcpListener::bind(addr).with_context(|| format!("listening on {}", addr))?;
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+    let open = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                open.fetch_sub(1, Ordering::SeqCst);
+                let error = json!({ "error": "too many connections" });
+                let _ = respond(&stream, 503, Some(&error), token.is_some());
+                continue;
+            }
+            let open = &open;
+            scope.spawn(move || {
+                // A client that went away has nobody to tell.
+                let _ = handle(indexer, token, stream);
+                open.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle(indexer: &FileIndexer, token: Option<&str>, stream: TcpStream) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    let cors = token.is_some();
+    let answer = match read_request(&stream) {
+        Ok(Some(request)) => {
+            if request.method == "OPTIONS" {
+                return respond(&stream, 204, None, cors);
+            }
+            let authorized = token.is_none_or(|token| {
+                let expected = format!("Bearer {}", token);
+                request
+                    .authorization
+                    .as_deref()
+                    .is_some_and(|given| same_secret(given.as_bytes(), expected.as_bytes()))
+            });
+            if !authorized {
+                Err(Status(401, "missing or wrong bearer token".into()))
+            } else if token.is_none() && changes_index(&request) {
+                Err(Status(
+                    403,
+                    "changing the index needs a server started with --token".into(),
+                ))
+            } else {
+                route(indexer, &request)
+            }
+        }
+        Ok(None) => return Ok(()),
+        Err(status) => Err(status),
+    };
+    match answer {
+        Ok(value) => respond(&stream, 200, Some(&value), cors),
+        Err(Status(code, error)) => respond(&stream, code, Some(&json!({ "error": error })), cors),
+    }
+}
+
+// Compares in time independent of where the inputs differ, so the token
+// cannot be guessed byte by byte.
+fn same_secret(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// Requests that index paths or store hashes, which any client able to reach
+// the server could otherwise make.
+fn changes_index(request: &Request) -> bool {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/index") => true,
+        ("GET", "/duplicates") => request.flag("verify"),
+        _ => false,
+    }
+}
+
+fn route(indexer: &FileIndexer, request: &Request) -> Result<Value, Status> {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/search") => {
+            let mut query: SearchQuery = match request.param("q") {
+                Some(expr) => expr.parse().map_err(bad_request)?,
+                None => SearchQuery::default(),
+            };
+            query.limit = Some(request.number("limit")?.unwrap_or(DEFAULT_LIMIT));
+            query.offset = request.number("offset")?;
+            if let Some(after) = request.param("after") {
+                query.after = Some(after.parse().map_err(bad_request)?);
+            }
+            search(indexer, query)
+        }
+        ("POST", "/search") => {
+            let mut query: SearchQuery = request.json()?;
+            query.limit.get_or_insert(DEFAULT_LIMIT);
+            search(indexer, query)
+        }
+        ("GET", "/recent") => {
+            let limit = request.number("limit")?.unwrap_or(DEFAULT_LIMIT);
+            Ok(json!(indexer.recently_added(limit)?))
+        }
+        ("GET", "/duplicates") => {
+            let limit = request.number("limit")?.unwrap_or(DEFAULT_LIMIT);
+            let offset = request.number("offset")?.unwrap_or(0);
+            let sort = match request.param("sort") {
+                None | Some("count") => DuplicateSort::Count,
+                Some("wasted") => DuplicateSort::Wasted,
+                Some("size") => DuplicateSort::Size,
+                Some(other) => {
+                    return Err(Status(
+                        400,
+                        format!("unknown sort {:?}; use count, wasted or size", other),
+                    ))
+                }
+            };
+            let groups = if request.flag("verify") {
+                if indexer.is_read_only() {
+                    return Err(Status(
+                        403,
+                        "verify stores hashes; the index is read-only".into(),
+                    ));
+                }
+                indexer.verified_duplicate_groups(limit, offset, sort)?
+            } else {
+                indexer.duplicate_groups(limit, offset, sort)?
+            };
+            Ok(json!(groups))
+        }
+        ("GET", "/stats") => {
+            let stats = indexer.stats()?;
+            let ages = indexer.age_histogram()?;
+            Ok(json!({ "stats": stats, "ages": ages }))
+        }
+        ("POST", "/index") => {
+            let IndexRequest { path, options } = request.json()?;
+            let report = indexer.index_dir(path, &options)?;
+            #[cfg(feature = "tantivy")]
+            indexer.sync_text_index()?;
+            Ok(report_json(&report))
+        }
+        (_, "/search" | "/recent" | "/duplicates" | "/stats" | "/index") => Err(Status(
+            405,
+            format!("{} is not allowed on {}", request.method, request.path),
+        )),
+        _ => Err(Status(404, format!("no endpoint {}", request.path))),
+    }
+}
+
+// The same page `search --format json` prints.
+fn search(indexer: &FileIndexer, query: SearchQuery) -> Result<Value, Status> {
+    let rows = indexer.search(&query)?;
+    let page = Page {
+        next: next_page(&query, rows.last(), rows.len()).map(|cursor| cursor.to_string()),
+        offline: offline_counts(indexer, &rows)?,
+        total: indexer.count(&query)?,
+        results: rows,
+    };
+    Ok(json!(page))
+}
+
+fn bad_request(err: anyhow::Error) -> Status {
+    Status(400, format!("{:#}", err))
+}
+
+impl Request {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Set unless missing or `false`.
+    fn flag(&self, name: &str) -> bool {
+        self.param(name).is_some_and(|value| value != "false")
+    }
+
+    fn number(&self, name: &str) -> Result<Option<i64>, Status> {
+        self.param(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| Status(400, format!("{} must be a number", name)))
+            })
+            .transpose()
+    }
+
+    // Requiring the JSON content type makes browsers ask before sending
+    // such a request from another site.
+    fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, Status> {
+        let media_type = self
+            .content_type
+            .as_deref()
+            .and_then(|value| value.split(';').next())
+            .map(str::trim);
+        if !media_type.is_some_and(|t| t.eq_ignore_ascii_case("application/json")) {
+            return Err(Status(415, "the body must be application/json".into()));
+        }
+        serde_json::from_slice(&self.body).map_err(|err| Status(400, err.to_string()))
+    }
+}
+
+// `None` for a connection closed before sending anything.
+fn read_request<R: Read>(stream: R) -> Result<Option<Request>, Status> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    if read_line(&mut reader, &mut line).map_err(|status| Status(400, status.1))? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(Status(400, "malformed request line".into()));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        params: form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect(),
+        authorization: None,
+        content_type: None,
+        body: Vec::new(),
+    };
+    let mut length = 0;
+    for _ in 0..=MAX_HEADERS {
+        read_line(&mut reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            if length > MAX_BODY {
+                return Err(Status(413, "request body too large".into()));
+            }
+            request.body.resize(length, 0);
+            reader.read_exact(&mut request.body).map_err(bad_io)?;
+            return Ok(Some(request));
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(Status(400, "malformed header".into()));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value
+                .parse()
+                .map_err(|_| Status(400, "malformed Content-Length".into()))?;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            request.authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-type") {
+            request.content_type = Some(value.to_string());
+        }
+    }
+    Err(Status(431, "too many headers".into()))
+}
+
+// Replaces `line` with the next line, refusing one longer than `MAX_LINE`
+// instead of buffering whatever a client sends without a newline.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> Result<usize, Status> {
+    line.clear();
+    let read = reader
+        .by_ref()
+        .take(MAX_LINE)
+        .read_line(line)
+        .map_err(bad_io)?;
+    if read as u64 == MAX_LINE && !line.ends_with('\n') {
+        return Err(Status(431, "request line or header too long".into()));
+    }
+    Ok(read)
+}
+
+fn bad_io(err: std::io::Error) -> Status {
+    Status(400, anyhow!(err).context("reading the request").to_string())
+}
+
+fn respond(mut stream: &TcpStream, code: u16, body: Option<&Value>, cors: bool) -> Result<()> {
+    let reason = match code {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        403 => "Forbidden",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let body = match body {
+        Some(value) => serde_json::to_vec(value)?,
+        None => Vec::new(),
+    };
+    let cors = if cors {
+        "Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Authorization, Content-Type\r\n"
+    } else {
+        ""
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         {}Connection: close\r\n\r\n",
+        code,
+        reason,
+        body.len(),
+        cors
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(raw: &[u8]) -> Result<Request, Status> {
+        read_request(raw).map(|request| request.expect("a request"))
+    }
+
+    fn code(raw: &[u8]) -> u16 {
+        read(raw).err().expect("an error").0
+    }
+
+    #[test]
+    fn reads_requests() {
+        let request = read(
+            b"POST /index?verify=true HTTP/1.1\r\nContent-Type: application/json\r\n\
+              Authorization: Bearer x\r\nContent-Length: 2\r\n\r\n{}",
+        )
+        .unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/index")
+        );
+        assert!(request.flag("verify"));
+        assert_eq!(request.authorization.as_deref(), Some("Bearer x"));
+        assert_eq!(request.body, b"{}");
+        assert!(read_request(&b""[..]).unwrap().is_none());
+    }
+
+    #[test]
+    fn refuses_unbounded_input() {
+        let long = vec![b'a'; MAX_LINE as usize + 1];
+        assert_eq!(code(&[b"GET /", &long[..]].concat()), 400);
+        assert_eq!(code(&[b"GET / HTTP/1.1\r\nX: ", &long[..]].concat()), 431);
+        let headers = "X: y\r\n".repeat(MAX_HEADERS + 1);
+        assert_eq!(
+            code(format!("GET / HTTP/1.1\r\n{}\r\n", headers).as_bytes()),
+            431
+        );
+        let body = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert_eq!(code(body.as_bytes()), 413);
+        assert_eq!(code(b"GET\r\n\r\n"), 400);
+    }
+
+    #[test]
+    fn knows_which_requests_change_the_index() {
+        let changes = |raw: &[u8]| changes_index(&read(raw).unwrap());
+        assert!(changes(b"POST /index HTTP/1.1\r\n\r\n"));
+        assert!(changes(b"GET /duplicates?verify=1 HTTP/1.1\r\n\r\n"));
+        assert!(!changes(b"GET /duplicates?verify=false HTTP/1.1\r\n\r\n"));
+        assert!(!changes(b"POST /search HTTP/1.1\r\n\r\n"));
+    }
+}
@@ -1,11 +1,40 @@
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+#[cfg(unix)]
+use std::sync::Arc;
+#[cfg(unix)]
+use std::thread;
+use std::time::Duration;
 
-use anyhow::Result;
-use chrono::NaiveDate;
-use clap::{Parser, Subcommand, ValueEnum};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
 use comfy_table::{presets::UTF8_FULL, Cell, Row, Table};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
 
-use fsindex_core::{DuplicateGroup, FileIndexer, FileRecord, SearchQuery, SortKey};
+use fsindex_core::{
+    attributes, ActivityGroup, ActivityKind, AgeBucket, CancellationToken, CandidateGroup,
+    ChunkOverlap, CollectionInfo, Cursor, DedupeAction, DedupeOptions, DedupeReport, DeletedFile,
+    DirRecord, DuplicateGroup, DuplicateSort, EntryKind, ExportFormat, FileIndexer, FileRecord,
+    HashAlgorithm, HashMode, ImportFormat, IndexOptions, IndexReport, IndexStats, KeepStrategy,
+    MaintenanceOptions, MergeStrategy, RootInfo, S3Source, ScanDiff, ScanEntry, ScanError,
+    ScanInfo, ScanSource, ScoredRecord, SearchQuery, SftpSource, SimilarFile, SortKey, TagInfo,
+    VerifyReport, VolumeInfo, WatchEvent, Watcher, WebDavSource,
+};
+#[cfg(unix)]
+use fsindex_core::{socket_path, RemoteIndex};
+
+use crate::catalog::Catalog;
+
+mod catalog;
+mod http;
+mod tui;
 
 #[derive(Parser)]
 #[command(
@@ -16,47 +45,389 @@ use fsindex_core::{DuplicateGroup, FileIndexer, FileRecord, SearchQuery, SortKey
 struct Cli {
     #[arg(long, default_value = "index.db", help = "Path to the SQLite database")]
     db: PathBuf,
+    #[arg(long, help = "Open an existing database without writing to it")]
+    read_only: bool,
+    #[cfg(feature = "encryption")]
+    #[arg(
+        long,
+        conflicts_with = "read_only",
+        help = "Open or create an encrypted database; the passphrase is read from FSINDEX_PASSPHRASE or prompted for"
+    )]
+    encrypted: bool,
+    #[cfg(feature = "tantivy")]
+    #[arg(
+        long,
+        help = "Answer fuzzy name and content searches from a Tantivy index kept next to the database (<db>.tantivy)"
+    )]
+    text_index: bool,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        help = "Print results as tables and sentences, as JSON (or one object per line with ndjson) for scripts, or as CSV or TSV for spreadsheets"
+    )]
+    format: OutputFormat,
+    #[arg(
+        long,
+        global = true,
+        value_delimiter = ',',
+        help = "Fields printed with --format csv or tsv, e.g. path,size,modified (default: all)"
+    )]
+    columns: Vec<String>,
+    #[arg(
+        long,
+        help = "Send the command to `fsindex daemon` serving this database (<db>.sock) instead of opening it"
+    )]
+    remote: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+// Parsed once per run, so the size of the search variant does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Index a directory recursively
     Index {
-        #[arg(help = "Root directory to index")]
+        #[arg(
+            help = "Root directory to index, or an s3://bucket/prefix, sftp://[user@]host/path or http(s):// WebDAV URL"
+        )]
         path: PathBuf,
+        #[arg(
+            long,
+            value_name = "URL",
+            help = "Endpoint of an S3-compatible store (MinIO, Ceph, ...) for s3:// URLs"
+        )]
+        s3_endpoint: Option<String>,
+        #[arg(long, value_name = "NAME", help = "AWS profile for s3:// URLs")]
+        aws_profile: Option<String>,
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "SSH private key for sftp:// URLs (default: ssh's own keys and agent)"
+        )]
+        ssh_identity: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "User for WebDAV URLs; the password is read from FSINDEX_WEBDAV_PASSWORD or prompted for (default: ~/.netrc)"
+        )]
+        webdav_user: Option<String>,
         #[arg(long, help = "Compute and store file hashes")]
         hash: bool,
+        #[arg(
+            long,
+            value_enum,
+            help = "Hash whole files or only head/tail + size (implies --hash)"
+        )]
+        hash_mode: Option<HashModeArg>,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = HashAlgoArg::Blake3,
+            help = "Hash algorithm"
+        )]
+        hash_algo: HashAlgoArg,
+        #[arg(
+            long,
+            value_name = "BYTES",
+            default_value_t = fsindex_core::hash::DEFAULT_HASH_BUFFER_SIZE,
+            help = "Read buffer for hashing (files of 16 MiB and up are memory-mapped)"
+        )]
+        hash_buffer: usize,
+        #[arg(
+            long,
+            value_name = "BYTES",
+            help = "Do not hash files larger than this"
+        )]
+        hash_max_size: Option<u64>,
+        #[arg(
+            long,
+            value_name = "EXT",
+            help = "Do not hash files with this extension (repeatable)"
+        )]
+        no_hash_ext: Vec<String>,
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "Worker threads for metadata and hashing (0 = all cores)"
+        )]
+        threads: usize,
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            help = "Files read and hashed at once (0 = --threads); raise for fast SSDs"
+        )]
+        in_flight: usize,
+        #[arg(
+            long,
+            help = "Hash without filling the OS page cache (direct IO where supported)"
+        )]
+        uncached: bool,
+        #[arg(
+            long,
+            value_name = "SECS",
+            help = "Skip files that deliver no data for this long (stalled network shares)"
+        )]
+        io_timeout: Option<u64>,
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            help = "Retry files failing with network errors this many times"
+        )]
+        retries: u32,
+        #[arg(
+            long,
+            help = "Record files that timed out or kept failing as 'unreachable' entries"
+        )]
+        record_unreachable: bool,
+        #[arg(
+            long,
+            value_name = "GLOB",
+            help = "Only index files matching this glob"
+        )]
+        include: Vec<String>,
+        #[arg(long, value_name = "GLOB", help = "Skip paths matching this glob")]
+        exclude: Vec<String>,
+        #[arg(long, help = "Respect .gitignore and .ignore files")]
+        gitignore: bool,
+        #[arg(long, help = "Follow symbolic links (loops are skipped)")]
+        follow_symlinks: bool,
+        #[arg(long, help = "Record symbolic links as their own entries")]
+        record_symlinks: bool,
+        #[arg(long, help = "Maximum directory depth below the root")]
+        max_depth: Option<usize>,
+        #[arg(long, help = "Stop after indexing this many files")]
+        max_files: Option<usize>,
+        #[arg(long, help = "Stop once this many bytes of files were indexed")]
+        max_bytes: Option<u64>,
+        #[arg(long, help = "Skip hidden and system files (remembered per root)")]
+        skip_hidden: bool,
+        #[arg(
+            long,
+            conflicts_with = "skip_hidden",
+            help = "Index hidden files even if an earlier run skipped them"
+        )]
+        include_hidden: bool,
+        #[arg(
+            long,
+            default_value_t = fsindex_core::indexer::DEFAULT_BATCH_SIZE,
+            help = "Rows written per database transaction"
+        )]
+        batch_size: usize,
+        #[arg(
+            long,
+            help = "Detect MIME types from content when the extension is unknown"
+        )]
+        sniff_mime: bool,
+        #[arg(long, help = "Detect every file's type from its leading bytes")]
+        detect_type: bool,
+        #[arg(long, help = "Index the text of documents (txt, PDF, DOCX, ODT, ...)")]
+        extract_content: bool,
+        #[arg(long, help = "Read duration, resolution and tags of audio/video files")]
+        media: bool,
+        #[arg(
+            long,
+            help = "Index members of zip/tar archives as archive!/member entries"
+        )]
+        archives: bool,
+        #[arg(
+            long,
+            help = "Store content-defined chunk hashes of large files (see `shared`)"
+        )]
+        chunks: bool,
+        #[arg(
+            long,
+            default_value_t = fsindex_core::chunks::DEFAULT_CHUNK_THRESHOLD,
+            help = "Minimum file size in bytes for chunking"
+        )]
+        chunk_threshold: u64,
+        #[arg(long, help = "Store extended attributes (Linux/macOS)")]
+        xattrs: bool,
+        #[arg(
+            long,
+            help = "Record the files seen by this run so it can be diffed later"
+        )]
+        snapshot: bool,
+        #[arg(
+            long,
+            help = "Compute perceptual hashes of images (see similar-images)"
+        )]
+        image_hash: bool,
+        #[arg(
+            long,
+            help = "Compute similarity digests of file contents (see similar)"
+        )]
+        similarity: bool,
     },
     /// Search files using optional filters
     Search {
+        #[arg(
+            long,
+            help = "Query expression such as 'ext:pdf size>10MB name~report'; replaces the other filters"
+        )]
+        query: Option<String>,
         #[arg(long, help = "Filter by name fragment")]
         name: Option<String>,
-        #[arg(long, help = "Filter by file extension")]
-        ext: Option<String>,
+        #[arg(long, help = "Match the name fragment with typo tolerance")]
+        fuzzy: bool,
+        #[arg(
+            long,
+            help = "Match the name fragment exactly instead of ignoring case and accents"
+        )]
+        case_sensitive: bool,
+        #[arg(long, help = "Treat --name as a regular expression")]
+        regex: bool,
+        #[arg(long, help = "Filter by a regular expression over the full path")]
+        path_regex: Option<String>,
+        #[arg(
+            long,
+            help = "Filter by a glob over the full path (e.g. 'src/**/*.rs', '/srv/**/*.tar.gz')"
+        )]
+        glob: Option<String>,
+        #[arg(long, help = "Only entries under this directory")]
+        under: Option<PathBuf>,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            help = "Filter by file extension; repeat or comma-separate for any of several (jpg,png)"
+        )]
+        ext: Vec<String>,
+        #[arg(long, value_enum, help = "Filter by entry kind")]
+        kind: Option<KindArg>,
+        #[arg(long, help = "Filter by MIME type (e.g. image/png, video, audio/*)")]
+        mime: Option<String>,
+        #[arg(
+            long,
+            help = "Only files whose content does not match their extension (needs --detect-type)"
+        )]
+        mismatched: bool,
+        #[arg(long, help = "Full-text search in extracted document text")]
+        content: Option<String>,
+        #[arg(long, help = "Full-text search in notes")]
+        note: Option<String>,
+        #[arg(long, help = "Only members of this collection")]
+        collection: Option<String>,
+        #[arg(long, help = "Only files with this tag; repeat to require several")]
+        tag: Vec<String>,
+        #[arg(long, help = "Minimum media duration in seconds")]
+        min_duration: Option<i64>,
+        #[arg(long, help = "Maximum media duration in seconds")]
+        max_duration: Option<i64>,
+        #[arg(long, help = "Minimum video height in pixels (e.g. 1080)")]
+        min_height: Option<i64>,
+        #[arg(long, help = "Filter by artist tag")]
+        artist: Option<String>,
+        #[arg(long, help = "Filter by album tag")]
+        album: Option<String>,
+        #[arg(long, help = "Filter by title tag")]
+        title: Option<String>,
+        #[arg(long, help = "Filter by owning user (name or uid)")]
+        owner: Option<String>,
+        #[arg(long, help = "Filter by owning group (name or gid)")]
+        group: Option<String>,
+        #[arg(long, help = "Only files writable by everyone")]
+        world_writable: bool,
+        #[arg(
+            long = "attr",
+            value_enum,
+            help = "Require a Windows file attribute (repeatable)"
+        )]
+        attrs: Vec<AttrArg>,
+        #[arg(
+            long,
+            help = "Only files with this extended attribute (glob such as user.* allowed)"
+        )]
+        has_xattr: Option<String>,
+        #[arg(long, help = "Only files on this drive (label or id, see `volumes`)")]
+        volume: Option<String>,
+        #[arg(long, help = "Show media columns (duration, resolution, codec, tags)")]
+        media: bool,
         #[arg(long, help = "Minimum file size in bytes")]
         min_size: Option<i64>,
         #[arg(long, help = "Maximum file size in bytes")]
         max_size: Option<i64>,
+        #[arg(
+            long,
+            help = "Minimum space taken on disk in bytes (less than the size for sparse or compressed files)"
+        )]
+        min_allocated: Option<i64>,
+        #[arg(long, help = "Maximum space taken on disk in bytes")]
+        max_allocated: Option<i64>,
         #[arg(long, help = "Earliest modified date (YYYY-MM-DD)")]
         from: Option<String>,
         #[arg(long, help = "Latest modified date (YYYY-MM-DD)")]
         to: Option<String>,
+        #[arg(long, help = "Only files with this full content hash")]
+        hash: Option<String>,
+        #[arg(long, help = "Only files that have (true) or lack (false) a full hash")]
+        has_hash: Option<bool>,
+        #[arg(long, help = "Only files rated at least this (1-5)")]
+        min_rating: Option<u8>,
+        #[arg(long, help = "Earliest date first indexed (YYYY-MM-DD)")]
+        added_from: Option<String>,
+        #[arg(long, help = "Latest date first indexed (YYYY-MM-DD)")]
+        added_to: Option<String>,
         #[arg(long, value_enum, default_value_t = OrderKey::Name, help = "Sort column")]
         sort: OrderKey,
         #[arg(long, help = "Sort descending instead of ascending")]
         desc: bool,
+        #[arg(long, value_enum, help = "Secondary sort column for rows that tie")]
+        then_sort: Option<OrderKey>,
+        #[arg(
+            long,
+            requires = "then_sort",
+            help = "Sort the secondary column descending"
+        )]
+        then_desc: bool,
+        #[arg(long, help = "Include the relevance score in the output")]
+        score: bool,
+        #[arg(long, help = "Print SQLite's query plan instead of running the search")]
+        explain: bool,
+        #[arg(long, help = "Print only the paths, one per line, as rows are read")]
+        paths_only: bool,
+        #[arg(
+            long,
+            help = "Print only the paths, each ending in a NUL byte, for xargs -0 or rsync --from0"
+        )]
+        print0: bool,
         #[arg(long, default_value_t = 50, help = "Limit number of rows")]
         limit: i64,
         #[arg(long, default_value_t = 0, help = "Offset for pagination")]
         offset: i64,
+        #[arg(
+            long,
+            help = "Continue after the previous page, using the cursor it printed"
+        )]
+        after: Option<Cursor>,
     },
     /// Show most recently indexed files
     Recent {
         #[arg(long, default_value_t = 50, help = "Number of rows to fetch")]
         limit: i64,
     },
+    /// Show files that disappeared, newest first (recorded by prune and the watcher)
+    Deleted {
+        #[arg(long, default_value_t = 50, help = "Number of rows to fetch")]
+        limit: i64,
+    },
+    /// Show files detected as moved or renamed during re-indexing
+    Renames {
+        #[arg(long, default_value_t = 50, help = "Number of rows to fetch")]
+        limit: i64,
+    },
+    /// Show recent activity grouped by day and directory
+    Activity {
+        #[arg(long, default_value_t = 7, help = "Number of days to look back")]
+        days: i64,
+        #[arg(long, help = "Group by modified time instead of indexed time")]
+        modified: bool,
+        #[arg(long, default_value_t = 50, help = "Maximum number of groups")]
+        limit: i64,
+    },
     /// Display duplicate files grouped by hash
     Duplicates {
         #[arg(
@@ -65,86 +436,2165 @@ enum Commands {
             help = "Maximum number of duplicate groups"
         )]
         limit: i64,
+        #[arg(long, default_value_t = 0, help = "Skip this many groups, for paging")]
+        offset: i64,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = DuplicateOrder::Count,
+            help = "Show first the groups with the most copies, the most wasted space or the largest files"
+        )]
+        sort: DuplicateOrder,
+        #[arg(
+            long,
+            help = "Confirm quick-hash candidates by fully hashing them first"
+        )]
+        verify: bool,
+        #[arg(long, help = "Group unhashed files by size to show likely duplicates")]
+        by_size: bool,
+        #[arg(long, help = "Group unhashed files by size and name")]
+        by_name: bool,
+        #[arg(
+            long,
+            help = "Hash just the size/name candidates and show confirmed groups"
+        )]
+        hash_candidates: bool,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = HashAlgoArg::Blake3,
+            help = "Algorithm for --hash-candidates"
+        )]
+        hash_algo: HashAlgoArg,
+        #[arg(
+            long,
+            help = "Print only the paths of every copy, including the first, one per line"
+        )]
+        paths_only: bool,
+        #[arg(
+            long,
+            help = "Print only the paths of every copy, each ending in a NUL byte"
+        )]
+        print0: bool,
+    },
+    /// Delete or link away the redundant copies of fully hashed duplicates
+    Dedupe {
+        #[arg(
+            long,
+            value_parser = parse_keep,
+            help = "Copy kept in each group: newest, oldest, shortest-path, or path-prefix=DIR (groups without a copy under DIR are left alone)"
+        )]
+        keep: KeepStrategy,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = DedupeActionArg::Delete,
+            help = "What happens to the other copies"
+        )]
+        action: DedupeActionArg,
+        #[arg(long, help = "Only print what would be done")]
+        dry_run: bool,
+        #[arg(long, help = "Move deleted copies to the system trash")]
+        trash: bool,
+    },
+    /// Show totals, per-extension usage and the size distribution
+    Stats {
+        #[arg(long, default_value_t = 15, help = "Number of extensions to list")]
+        ext_limit: usize,
+    },
+    /// Show the largest files and the space wasted by duplicates
+    Top {
+        #[arg(long, default_value_t = 20, help = "Number of files to show")]
+        limit: i64,
+    },
+    /// Show the largest directories by recursive size
+    Dirs {
+        #[arg(long, default_value_t = 25, help = "Number of directories to show")]
+        limit: i64,
+    },
+    /// Group visually similar images (needs index --image-hash)
+    SimilarImages {
+        #[arg(
+            long,
+            default_value_t = 10,
+            help = "Maximum differing bits out of 64 (0 = visually identical)"
+        )]
+        threshold: u32,
+    },
+    /// Find files with contents similar to a given file
+    Similar {
+        #[arg(help = "File to compare against; need not be indexed")]
+        path: PathBuf,
+        #[arg(
+            long,
+            default_value_t = 50,
+            help = "Minimum similarity score from 0 to 100"
+        )]
+        threshold: u32,
+    },
+    /// Show pairs of chunked files that share data
+    Shared {
+        #[arg(long, help = "Only show pairs involving this file")]
+        path: Option<PathBuf>,
+        #[arg(long, default_value_t = 25, help = "Maximum number of pairs")]
+        limit: i64,
+    },
+    /// Remove index entries for files that no longer exist
+    Prune {
+        #[arg(long, help = "Only check entries under this directory")]
+        root: Option<PathBuf>,
+        #[arg(long, help = "Only list the entries that would be removed")]
+        dry_run: bool,
+    },
+    /// Write indexed entries as JSON Lines, CSV or Parquet
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportArg::Jsonl, help = "File format")]
+        file_format: ExportArg,
+        #[arg(
+            long,
+            short,
+            visible_alias = "out",
+            help = "Output file (default: standard output)"
+        )]
+        output: Option<PathBuf>,
+        #[arg(long, help = "Only entries matching this search expression")]
+        query: Option<String>,
+    },
+    /// Add entries from an export, an Everything EFU list, an mlocate database or `find -printf` output
+    Import {
+        #[arg(
+            long,
+            value_enum,
+            help = "Format of the file list (default: guessed from the extension)"
+        )]
+        file_format: Option<ImportArg>,
+        #[arg(help = "File list to import", required_unless_present = "from")]
+        file: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "file",
+            help = "Same as FILE"
+        )]
+        from: Option<PathBuf>,
+    },
+    /// Import the entries of another index database into this one
+    Merge {
+        #[arg(help = "Index database to import")]
+        other: PathBuf,
+        #[arg(long, value_enum, default_value_t = MergeArg::Newer, help = "Which copy wins when a path exists in both")]
+        strategy: MergeArg,
+        #[arg(
+            long,
+            help = "Store imported paths as LABEL:/path, e.g. a host or volume name"
+        )]
+        label: Option<String>,
+    },
+    /// Re-encrypt the database under a new passphrase
+    #[cfg(feature = "encryption")]
+    ChangePassphrase,
+    /// Check the database for corruption, reclaim free space and refresh statistics
+    Compact {
+        #[arg(long, help = "Skip VACUUM")]
+        no_vacuum: bool,
+        #[arg(long, help = "Skip ANALYZE")]
+        no_analyze: bool,
+        #[arg(long, help = "Skip the integrity check")]
+        no_check: bool,
+    },
+    /// Delete empty files and directories left empty
+    CleanEmpty {
+        #[arg(long, help = "Only list what would be removed")]
+        dry_run: bool,
+        #[arg(long, help = "Move to the system trash instead of deleting")]
+        trash: bool,
+    },
+    /// Re-hash unchanged files and report any whose contents no longer match; fails if any do
+    Verify {
+        #[arg(help = "Only check entries under this directory")]
+        path: Option<PathBuf>,
+        #[arg(long, help = "Also fail when indexed files are missing or unreadable")]
+        strict: bool,
+    },
+    /// Rate an indexed file from 1 to 5 stars, or 0 to clear
+    Rate {
+        #[arg(help = "Indexed file")]
+        path: PathBuf,
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=5))]
+        rating: u8,
+    },
+    /// Show, set or clear the note attached to an indexed file
+    Note {
+        #[arg(help = "Indexed file")]
+        path: PathBuf,
+        #[arg(help = "New note text; omit to show the current note")]
+        text: Option<String>,
+        #[arg(long, conflicts_with = "text", help = "Remove the note")]
+        clear: bool,
+    },
+    /// Manage named collections of indexed files
+    Collection {
+        #[command(subcommand)]
+        action: CollectionAction,
+    },
+    /// Label indexed files with tags and find them by tag
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// List past indexing runs
+    Scans {
+        #[arg(long, help = "Only runs of this root directory")]
+        root: Option<PathBuf>,
+        #[arg(long, default_value_t = 20, help = "Number of runs to show")]
+        limit: i64,
+    },
+    /// List the entries an indexing run skipped, and why
+    ScanErrors {
+        #[arg(help = "Scan id, as shown by `scans`")]
+        id: i64,
+    },
+    /// List files added, removed, resized or modified between two databases or two snapshot scans
+    Diff {
+        #[arg(help = "Older index database", required_unless_present = "from_scan")]
+        old: Option<PathBuf>,
+        #[arg(help = "Newer index database (default: --db)", requires = "old")]
+        new: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "old",
+            requires = "to_scan",
+            help = "Older snapshot scan of this database, as shown by `scans`"
+        )]
+        from_scan: Option<i64>,
+        #[arg(long, requires = "from_scan", help = "Newer snapshot scan")]
+        to_scan: Option<i64>,
+    },
+    /// List indexed root directories
+    Roots,
+    /// List the drives indexed files live on and whether they are connected
+    Volumes,
+    /// Stop tracking a root directory and drop its entries
+    Forget {
+        #[arg(help = "Root directory to forget")]
+        root: PathBuf,
+    },
+    /// Re-index every tracked root with its stored options
+    Reindex,
+    /// Suggest catalog values matching a partial input
+    Suggest {
+        #[arg(value_enum, help = "Kind of value to suggest")]
+        kind: SuggestKind,
+        #[arg(default_value = "", help = "Partial input to complete")]
+        input: String,
+        #[arg(long, default_value_t = 10, help = "Maximum number of suggestions")]
+        limit: i64,
+    },
+    /// Keep the index of directories current while their files change
+    Watch {
+        #[arg(
+            required = true,
+            help = "Directories to watch; ones indexed before keep their options"
+        )]
+        roots: Vec<PathBuf>,
+        #[arg(
+            long,
+            help = "Skip indexing and pruning the directories before watching"
+        )]
+        no_scan: bool,
+        #[arg(
+            long,
+            default_value_t = 300,
+            help = "Milliseconds without changes before they are applied"
+        )]
+        debounce: u64,
+    },
+    /// Own the database: watch the tracked roots, rescan them on a schedule and answer --remote commands
+    Daemon {
+        #[arg(long, help = "Answer commands without watching the tracked roots")]
+        no_watch: bool,
+        #[arg(
+            long,
+            value_name = "MINUTES",
+            help = "Re-index and prune every tracked root this often"
+        )]
+        rescan_every: Option<u64>,
+        #[arg(
+            long,
+            default_value_t = 300,
+            help = "Milliseconds without changes before they are applied"
+        )]
+        debounce: u64,
+    },
+    /// Answer search, recent, duplicates, stats and index requests over HTTP with JSON
+    Serve {
+        #[arg(
+            long,
+            default_value = "127.0.0.1:8080",
+            help = "Address to listen on; use 0.0.0.0:8080 to accept other machines"
+        )]
+        listen: String,
+        #[arg(
+            long,
+            help = "Require this bearer token in the Authorization header of every request; without one, indexing and verifying requests are refused"
+        )]
+        token: Option<String>,
+    },
+    /// Browse the index interactively in the terminal
+    Tui {
+        #[arg(long, default_value_t = 500, help = "Maximum number of results shown")]
+        limit: i64,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum, help = "Shell to complete for")]
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum CollectionAction {
+    /// List collections with their size
+    List,
+    /// List the files in a collection
+    Show { name: String },
+    /// Add indexed files, or everything indexed under a directory
+    Add {
+        name: String,
+        #[arg(required = true, help = "Indexed files or directories")]
+        paths: Vec<PathBuf>,
+    },
+    /// Remove files, or everything under a directory, from a collection
+    Remove {
+        name: String,
+        #[arg(required = true, help = "Files or directories to remove")]
+        paths: Vec<PathBuf>,
+    },
+    /// Delete a collection; its files stay indexed
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+enum TagAction {
+    /// List tags with their size, or the tags of one file
+    List {
+        #[arg(help = "Indexed file whose tags to show")]
+        path: Option<PathBuf>,
+    },
+    /// Tag indexed files, or everything indexed under a directory
+    Add {
+        tag: String,
+        #[arg(required = true, help = "Indexed files or directories")]
+        paths: Vec<PathBuf>,
+    },
+    /// Untag files, or everything under a directory
+    Remove {
+        tag: String,
+        #[arg(
+            required_unless_present = "all",
+            help = "Files or directories to untag"
+        )]
+        paths: Vec<PathBuf>,
+        #[arg(long, conflicts_with = "paths", help = "Untag every file")]
+        all: bool,
+    },
+    /// List the files carrying every given tag
+    Search {
+        #[arg(required = true)]
+        tags: Vec<String>,
+        #[arg(long, default_value_t = 50, help = "Limit number of rows")]
+        limit: i64,
     },
 }
 
 #[derive(Clone, Copy, ValueEnum)]
-enum OrderKey {
-    Name,
-    Size,
-    Modified,
+enum ExportArg {
+    Jsonl,
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
 }
 
-impl From<OrderKey> for SortKey {
-    fn from(value: OrderKey) -> Self {
+impl From<ExportArg> for ExportFormat {
+    fn from(value: ExportArg) -> Self {
         match value {
-            OrderKey::Name => SortKey::Name,
-            OrderKey::Size => SortKey::Size,
-            OrderKey::Modified => SortKey::Modified,
+            ExportArg::Jsonl => ExportFormat::JsonLines,
+            ExportArg::Csv => ExportFormat::Csv,
+            #[cfg(feature = "parquet")]
+            ExportArg::Parquet => ExportFormat::Parquet,
         }
     }
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let indexer = FileIndexer::new(&cli.db)?;
+#[derive(Clone, Copy, ValueEnum)]
+enum ImportArg {
+    Efu,
+    Locate,
+    /// find DIR -type f -printf '%s\t%T@\t%p\n'
+    Find,
+    /// Written by `export --file-format jsonl`
+    Jsonl,
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
 
-    match cli.command {
-        Commands::Index { path, hash } => {
-            let count = indexer.index_dir(path, hash)?;
-            println!("Indexed {} files", count);
+impl ImportArg {
+    fn from_extension(path: &Path) -> Result<Self> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match ext.as_deref() {
+            Some("jsonl" | "ndjson") => Ok(ImportArg::Jsonl),
+            Some("csv") => Ok(ImportArg::Csv),
+            #[cfg(feature = "parquet")]
+            Some("parquet") => Ok(ImportArg::Parquet),
+            Some("efu") => Ok(ImportArg::Efu),
+            _ => Err(anyhow!(
+                "cannot tell the format of {}; pass --file-format",
+                path.display()
+            )),
         }
-        Commands::Search {
-            name,
-            ext,
-            min_size,
-            max_size,
-            from,
+    }
+}
+
+impl From<ImportArg> for ImportFormat {
+    fn from(value: ImportArg) -> Self {
+        match value {
+            ImportArg::Efu => ImportFormat::Efu,
+            ImportArg::Locate => ImportFormat::Locate,
+            ImportArg::Find => ImportFormat::FindPrintf,
+            ImportArg::Jsonl => ImportFormat::JsonLines,
+            ImportArg::Csv => ImportFormat::Csv,
+            #[cfg(feature = "parquet")]
+            ImportArg::Parquet => ImportFormat::Parquet,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MergeArg {
+    Newer,
+    KeepExisting,
+    Replace,
+}
+
+impl From<MergeArg> for MergeStrategy {
+    fn from(value: MergeArg) -> Self {
+        match value {
+            MergeArg::Newer => MergeStrategy::Newer,
+            MergeArg::KeepExisting => MergeStrategy::KeepExisting,
+            MergeArg::Replace => MergeStrategy::Replace,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SuggestKind {
+    Ext,
+    Dir,
+    Name,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum KindArg {
+    File,
+    Symlink,
+    BrokenSymlink,
+    ArchiveMember,
+    Unreachable,
+}
+
+impl From<KindArg> for EntryKind {
+    fn from(value: KindArg) -> Self {
+        match value {
+            KindArg::File => EntryKind::File,
+            KindArg::Symlink => EntryKind::Symlink,
+            KindArg::BrokenSymlink => EntryKind::BrokenSymlink,
+            KindArg::ArchiveMember => EntryKind::ArchiveMember,
+            KindArg::Unreachable => EntryKind::Unreachable,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AttrArg {
+    Readonly,
+    Hidden,
+    System,
+    ReparsePoint,
+}
+
+impl AttrArg {
+    fn bit(self) -> u32 {
+        match self {
+            AttrArg::Readonly => attributes::READONLY,
+            AttrArg::Hidden => attributes::HIDDEN,
+            AttrArg::System => attributes::SYSTEM,
+            AttrArg::ReparsePoint => attributes::REPARSE_POINT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HashModeArg {
+    Full,
+    Quick,
+}
+
+impl From<HashModeArg> for HashMode {
+    fn from(value: HashModeArg) -> Self {
+        match value {
+            HashModeArg::Full => HashMode::Full,
+            HashModeArg::Quick => HashMode::Quick,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HashAlgoArg {
+    Blake3,
+    Sha256,
+    Xxh3,
+}
+
+impl From<HashAlgoArg> for HashAlgorithm {
+    fn from(value: HashAlgoArg) -> Self {
+        match value {
+            HashAlgoArg::Blake3 => HashAlgorithm::Blake3,
+            HashAlgoArg::Sha256 => HashAlgorithm::Sha256,
+            HashAlgoArg::Xxh3 => HashAlgorithm::Xxh3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OrderKey {
+    Name,
+    Size,
+    Modified,
+    Relevance,
+    Rating,
+    Ext,
+    Path,
+    Added,
+    Allocated,
+}
+
+impl From<OrderKey> for SortKey {
+    fn from(value: OrderKey) -> Self {
+        match value {
+            OrderKey::Name => SortKey::Name,
+            OrderKey::Size => SortKey::Size,
+            OrderKey::Modified => SortKey::Modified,
+            OrderKey::Relevance => SortKey::Relevance,
+            OrderKey::Rating => SortKey::Rating,
+            OrderKey::Ext => SortKey::Ext,
+            OrderKey::Path => SortKey::Path,
+            OrderKey::Added => SortKey::AddedAt,
+            OrderKey::Allocated => SortKey::Allocated,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    // One JSON object per line; search results are written as they are read.
+    Ndjson,
+    Csv,
+    Tsv,
+}
+
+// How results are printed.
+#[derive(Clone)]
+struct Output {
+    format: OutputFormat,
+    // For CSV and TSV; empty for every field.
+    columns: Vec<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DuplicateOrder {
+    Count,
+    Wasted,
+    Size,
+}
+
+impl From<DuplicateOrder> for DuplicateSort {
+    fn from(value: DuplicateOrder) -> Self {
+        match value {
+            DuplicateOrder::Count => DuplicateSort::Count,
+            DuplicateOrder::Wasted => DuplicateSort::Wasted,
+            DuplicateOrder::Size => DuplicateSort::Size,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DedupeActionArg {
+    Delete,
+    Hardlink,
+    Symlink,
+}
+
+impl From<DedupeActionArg> for DedupeAction {
+    fn from(value: DedupeActionArg) -> Self {
+        match value {
+            DedupeActionArg::Delete => DedupeAction::Delete,
+            DedupeActionArg::Hardlink => DedupeAction::Hardlink,
+            DedupeActionArg::Symlink => DedupeAction::Symlink,
+        }
+    }
+}
+
+fn parse_keep(input: &str) -> Result<KeepStrategy> {
+    match input {
+        "newest" => Ok(KeepStrategy::Newest),
+        "oldest" => Ok(KeepStrategy::Oldest),
+        "shortest-path" => Ok(KeepStrategy::ShortestPath),
+        _ => match input.strip_prefix("path-prefix=") {
+            Some(dir) if !dir.is_empty() => Ok(KeepStrategy::Under(dir.into())),
+            _ => Err(anyhow!(
+                "expected newest, oldest, shortest-path or path-prefix=DIR"
+            )),
+        },
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    // Needs no database, so printing a script does not create one.
+    if let Commands::Completions { shell } = cli.command {
+        return print_completions(shell);
+    }
+    let out = Output {
+        format: cli.format,
+        columns: cli.columns.clone(),
+    };
+    // Two other databases: --db is not opened.
+    if let Commands::Diff {
+        old: Some(old),
+        new,
+        ..
+    } = &cli.command
+    {
+        let new = new.as_deref().unwrap_or(&cli.db);
+        let old = FileIndexer::open_read_only(old)?;
+        return show(
+            &out,
+            old.diff_index(&FileIndexer::open_read_only(new)?)?,
+            render_diff,
+        );
+    }
+    if cli.remote {
+        #[cfg(unix)]
+        return run_catalog(cli.command, &RemoteIndex::new(socket_path(&cli.db)), &out);
+        #[cfg(not(unix))]
+        return Err(anyhow!("--remote is only supported on Unix"));
+    }
+    let indexer = open_index(&cli)?;
+    #[cfg(feature = "tantivy")]
+    let indexer = with_text_index(&cli, indexer)?;
+
+    match cli.command {
+        command @ (Commands::Index { .. }
+        | Commands::Search { .. }
+        | Commands::Recent { .. }
+        | Commands::Duplicates { .. }
+        | Commands::Stats { .. }
+        | Commands::Prune { .. }
+        | Commands::Roots
+        | Commands::Volumes
+        | Commands::Reindex
+        | Commands::Suggest { .. }) => run_catalog(command, &indexer, &out)?,
+        Commands::Deleted { limit } => {
+            show(&out, indexer.recently_deleted(limit)?, render_deleted)?;
+        }
+        Commands::Renames { limit } => {
+            show(&out, indexer.recent_renames(limit)?, |renames| {
+                for rename in renames {
+                    println!(
+                        "{}  {} -> {}",
+                        rename
+                            .detected_at
+                            .with_timezone(&Local)
+                            .format("%Y-%m-%d %H:%M"),
+                        rename.old_path,
+                        rename.new_path
+                    );
+                }
+            })?;
+        }
+        Commands::Activity {
+            days,
+            modified,
+            limit,
+        } => {
+            let kind = if modified {
+                ActivityKind::Modified
+            } else {
+                ActivityKind::Added
+            };
+            show(&out, indexer.activity(kind, days, limit)?, render_activity)?;
+        }
+        Commands::Dedupe {
+            keep,
+            action,
+            dry_run,
+            trash,
+        } => {
+            let action: DedupeAction = action.into();
+            let report = indexer.dedupe(&DedupeOptions {
+                keep,
+                action,
+                dry_run,
+                trash,
+            })?;
+            let failed = report.failed;
+            show(&out, report, |report| render_dedupe(report, action, trash))?;
+            if failed > 0 {
+                return Err(anyhow!("{} copies could not be deduplicated", failed));
+            }
+        }
+        Commands::Top { limit } => {
+            let files = indexer.largest_files(limit)?;
+            let wasted = indexer.wasted_space()?;
+            show(&out, json!({ "files": files, "wasted": wasted }), |_| {
+                render_records(files);
+                println!(
+                    "Duplicates waste {} in {} extra copies across {} groups",
+                    human_bytes(wasted.bytes as u64),
+                    wasted.redundant_files,
+                    wasted.groups
+                );
+            })?;
+        }
+        Commands::Dirs { limit } => {
+            show(&out, indexer.largest_dirs(limit)?, render_dirs)?;
+        }
+        Commands::SimilarImages { threshold } => {
+            show(&out, indexer.similar_images(threshold)?, |groups| {
+                for (idx, group) in groups.into_iter().enumerate() {
+                    println!("group {} ({} images)", idx + 1, group.paths.len());
+                    for path in group.paths {
+                        println!("  {}", path);
+                    }
+                }
+            })?;
+        }
+        Commands::Similar { path, threshold } => {
+            show(&out, indexer.similar_to(&path, threshold)?, render_similar)?;
+        }
+        Commands::Shared { path, limit } => {
+            show(
+                &out,
+                indexer.shared_data(path.as_deref(), limit)?,
+                render_shared,
+            )?;
+        }
+        Commands::Export {
+            file_format: export_format,
+            output,
+            query,
+        } => {
+            let query: SearchQuery = query.as_deref().unwrap_or("").parse()?;
+            let written = match &output {
+                Some(path) => {
+                    let file = File::create(path)
+                        .with_context(|| format!("creating {}", path.display()))?;
+                    indexer.export(export_format.into(), BufWriter::new(file), &query)?
+                }
+                None => {
+                    indexer.export(export_format.into(), BufWriter::new(io::stdout()), &query)?
+                }
+            };
+            if output.is_some() {
+                show(&out, json!({ "exported": written }), |_| {
+                    println!("Exported {} entries", written)
+                })?;
+            }
+        }
+        Commands::Import {
+            file_format: list_format,
+            file,
+            from,
+        } => {
+            let file = from.or(file).expect("clap requires FILE or --from");
+            let list_format = match list_format {
+                Some(format) => format,
+                None => ImportArg::from_extension(&file)?,
+            };
+            let input = File::open(&file).with_context(|| format!("opening {}", file.display()))?;
+            let report = indexer.import_list(list_format.into(), input)?;
+            show(&out, report, |report| {
+                println!(
+                    "Imported {} files, {} already indexed",
+                    report.imported, report.existing
+                )
+            })?;
+        }
+        Commands::Merge {
+            other,
+            strategy,
+            label,
+        } => {
+            let report = indexer.merge_from(&other, strategy.into(), label.as_deref())?;
+            show(&out, report, |report| {
+                println!(
+                    "Added {}, updated {}, kept {} existing entries",
+                    report.added, report.updated, report.skipped
+                )
+            })?;
+        }
+        #[cfg(feature = "encryption")]
+        Commands::ChangePassphrase => {
+            let passphrase = rpassword::prompt_password("New passphrase: ")?;
+            if rpassword::prompt_password("Repeat new passphrase: ")? != passphrase {
+                return Err(anyhow!("the passphrases do not match"));
+            }
+            indexer.change_passphrase(&passphrase)?;
+            println!("Passphrase changed");
+        }
+        Commands::Compact {
+            no_vacuum,
+            no_analyze,
+            no_check,
+        } => {
+            let report = indexer.maintain(&MaintenanceOptions {
+                vacuum: !no_vacuum,
+                analyze: !no_analyze,
+                integrity_check: !no_check,
+            })?;
+            show(&out, &report, |report| {
+                println!(
+                    "Database size {} -> {}",
+                    human_bytes(report.size_before),
+                    human_bytes(report.size_after)
+                );
+                match &report.integrity_errors {
+                    Some(errors) if errors.is_empty() => println!("Integrity check passed"),
+                    Some(errors) => {
+                        for error in errors {
+                            eprintln!("{}", error);
+                        }
+                    }
+                    None => {}
+                }
+            })?;
+            if let Some(errors) = report.integrity_errors.filter(|errors| !errors.is_empty()) {
+                return Err(anyhow!("integrity check found {} problems", errors.len()));
+            }
+        }
+        Commands::CleanEmpty { dry_run, trash } => {
+            show(&out, indexer.clean_empty(dry_run, trash)?, |report| {
+                let verb = if dry_run { "would remove" } else { "removed" };
+                for path in report.files.iter().chain(&report.dirs) {
+                    println!("{} {}", verb, path);
+                }
+                for path in &report.changed {
+                    println!("skipped {} (no longer empty)", path);
+                }
+                for (path, err) in &report.failed {
+                    eprintln!("failed {}: {}", path, err);
+                }
+                println!(
+                    "{} {} empty files and {} empty directories",
+                    if dry_run { "Would remove" } else { "Removed" },
+                    report.files.len(),
+                    report.dirs.len()
+                );
+            })?;
+        }
+        Commands::Verify { path, strict } => {
+            let report = indexer.verify(path.as_deref())?;
+            // The exit status tells monitoring whether anything needs a look.
+            let corrupted = report.corrupted.len();
+            let lost = report.missing.len() + report.unreadable.len();
+            show(&out, report, render_verify)?;
+            if corrupted > 0 {
+                return Err(anyhow!("{} files are corrupted", corrupted));
+            }
+            if strict && lost > 0 {
+                return Err(anyhow!("{} files are missing or unreadable", lost));
+            }
+        }
+        Commands::Scans { root, limit } => {
+            show(
+                &out,
+                indexer.list_scans(root.as_deref(), limit)?,
+                render_scans,
+            )?;
+        }
+        Commands::Rate { path, rating } => {
+            indexer.set_rating(&path, rating)?;
+            show(&out, json!({ "path": path, "rating": rating }), |_| {})?;
+        }
+        Commands::Note { path, text, clear } => {
+            if clear {
+                indexer.set_note(&path, "")?;
+            } else if let Some(text) = text {
+                indexer.set_note(&path, &text)?;
+            } else {
+                let note = indexer.note(&path)?;
+                show(
+                    &out,
+                    json!({ "path": path, "note": note }),
+                    |_| match note {
+                        Some(note) => println!("{}", note),
+                        None => println!("No note"),
+                    },
+                )?;
+            }
+        }
+        Commands::Collection { action } => match action {
+            CollectionAction::List => {
+                show(&out, indexer.list_collections()?, render_collections)?;
+            }
+            CollectionAction::Show { name } => {
+                show(&out, indexer.collection_members(&name)?, render_records)?;
+            }
+            CollectionAction::Add { name, paths } => {
+                let added = indexer.add_to_collection(&name, &paths)?;
+                show(&out, json!({ "collection": name, "added": added }), |_| {
+                    println!("Added {} entries to {}", added, name)
+                })?;
+            }
+            CollectionAction::Remove { name, paths } => {
+                let removed = indexer.remove_from_collection(&name, &paths)?;
+                show(
+                    &out,
+                    json!({ "collection": name, "removed": removed }),
+                    |_| println!("Removed {} entries from {}", removed, name),
+                )?;
+            }
+            CollectionAction::Delete { name } => {
+                indexer.delete_collection(&name)?;
+                show(&out, json!({ "collection": name, "deleted": true }), |_| {
+                    println!("Deleted collection {}", name)
+                })?;
+            }
+        },
+        Commands::Tag { action } => match action {
+            TagAction::List { path: Some(path) } => {
+                show(&out, indexer.tags_of(&path)?, |tags| {
+                    for tag in tags {
+                        println!("{}", tag);
+                    }
+                })?;
+            }
+            TagAction::List { path: None } => {
+                show(&out, indexer.list_tags()?, render_tags)?;
+            }
+            TagAction::Add { tag, paths } => {
+                let added = indexer.add_tag(&tag, &paths)?;
+                show(&out, json!({ "tag": tag, "added": added }), |_| {
+                    println!("Tagged {} entries with {}", added, tag)
+                })?;
+            }
+            TagAction::Remove { tag, paths, all } => {
+                let removed = if all {
+                    indexer.delete_tag(&tag)?
+                } else {
+                    indexer.remove_tag(&tag, &paths)?
+                };
+                show(&out, json!({ "tag": tag, "removed": removed }), |_| {
+                    println!("Removed {} from {} entries", tag, removed)
+                })?;
+            }
+            TagAction::Search { tags, limit } => {
+                let query = SearchQuery {
+                    tags,
+                    sort_key: Some(SortKey::Path),
+                    limit: Some(limit),
+                    ..SearchQuery::default()
+                };
+                show(&out, indexer.search(&query)?, render_records)?;
+            }
+        },
+        Commands::ScanErrors { id } => {
+            show(&out, indexer.scan_errors(id)?, render_scan_errors)?;
+        }
+        Commands::Diff {
+            from_scan: Some(from),
+            to_scan: Some(to),
+            ..
+        } => {
+            show(&out, indexer.diff_scans(from, to)?, render_diff)?;
+        }
+        Commands::Diff { .. } => unreachable!("clap requires databases or scans"),
+        Commands::Forget { root } => {
+            let removed = indexer.forget_root(&root)?;
+            show(&out, json!({ "root": root, "removed": removed }), |_| {
+                println!("Forgot {} ({} entries removed)", root.display(), removed)
+            })?;
+        }
+        Commands::Watch {
+            roots,
+            no_scan,
+            debounce,
+        } => {
+            if cli.read_only {
+                return Err(anyhow!("cannot watch with --read-only"));
+            }
+            // The watcher opens the database again, without a passphrase.
+            #[cfg(feature = "encryption")]
+            if cli.encrypted {
+                return Err(anyhow!("watching an encrypted database is not supported"));
+            }
+            watch(
+                &cli.db,
+                &indexer,
+                &out,
+                roots,
+                !no_scan,
+                Duration::from_millis(debounce),
+            )?;
+        }
+        Commands::Daemon {
+            no_watch,
+            rescan_every,
+            debounce,
+        } => {
+            if cli.read_only {
+                return Err(anyhow!("cannot run the daemon with --read-only"));
+            }
+            let watch = !no_watch;
+            #[cfg(feature = "encryption")]
+            let watch = if watch && cli.encrypted {
+                eprintln!("Not watching: the watcher cannot open an encrypted database");
+                false
+            } else {
+                watch
+            };
+            daemon(
+                &cli.db,
+                indexer,
+                &out,
+                watch.then(|| Duration::from_millis(debounce)),
+                rescan_every.map(|minutes| Duration::from_secs(minutes * 60)),
+            )?;
+        }
+        Commands::Serve { listen, token } => {
+            http::serve(&indexer, &listen, token.as_deref())?;
+        }
+        Commands::Tui { limit } => tui::run(&indexer, limit)?,
+        Commands::Completions { .. } => unreachable!(),
+    }
+
+    Ok(())
+}
+
+// The commands that work on a database and on a daemon serving it alike.
+fn run_catalog(command: Commands, indexer: &dyn Catalog, out: &Output) -> Result<()> {
+    match command {
+        Commands::Index {
+            path,
+            s3_endpoint,
+            aws_profile,
+            ssh_identity,
+            webdav_user,
+            hash,
+            hash_mode,
+            hash_algo,
+            hash_buffer,
+            hash_max_size,
+            no_hash_ext,
+            threads,
+            in_flight,
+            uncached,
+            io_timeout,
+            retries,
+            record_unreachable,
+            include,
+            exclude,
+            gitignore,
+            follow_symlinks,
+            record_symlinks,
+            max_depth,
+            max_files,
+            max_bytes,
+            skip_hidden,
+            include_hidden,
+            batch_size,
+            sniff_mime,
+            detect_type,
+            extract_content,
+            media,
+            archives,
+            chunks,
+            chunk_threshold,
+            xattrs,
+            snapshot,
+            image_hash,
+            similarity,
+        } => {
+            let opts = IndexOptions {
+                hash: hash || hash_mode.is_some(),
+                hash_mode: hash_mode.map(Into::into).unwrap_or_default(),
+                hash_algo: hash_algo.into(),
+                hash_buffer_size: hash_buffer,
+                hash_max_size,
+                hash_skip_exts: no_hash_ext,
+                uncached_io: uncached,
+                threads,
+                in_flight,
+                io_timeout: io_timeout.map(Duration::from_secs),
+                io_retries: retries,
+                record_unreachable,
+                include,
+                exclude,
+                respect_gitignore: gitignore,
+                follow_symlinks,
+                record_symlinks,
+                max_depth,
+                max_files,
+                max_total_bytes: max_bytes,
+                skip_hidden: match (skip_hidden, include_hidden) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+                batch_size,
+                sniff_mime,
+                detect_type,
+                extract_content,
+                media_metadata: media,
+                index_archives: archives,
+                chunking: chunks,
+                chunk_threshold,
+                xattrs,
+                snapshot,
+                perceptual_hash: image_hash,
+                similarity_digest: similarity,
+                ..IndexOptions::default()
+            };
+            let source: Option<Box<dyn ScanSource>> = match path.to_str() {
+                Some(url) if url.starts_with("s3://") => Some(Box::new(S3Source {
+                    endpoint: s3_endpoint,
+                    profile: aws_profile,
+                    ..S3Source::parse(url)?
+                })),
+                // Remote files are hashed on the server if it has the tool.
+                Some(url) if url.starts_with("sftp://") => Some(Box::new(SftpSource {
+                    identity: ssh_identity,
+                    hash: opts.hash.then_some(opts.hash_algo),
+                    ..SftpSource::parse(url)?
+                })),
+                Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                    let password = match &webdav_user {
+                        Some(_) => Some(match std::env::var("FSINDEX_WEBDAV_PASSWORD") {
+                            Ok(password) => password,
+                            Err(_) => rpassword::prompt_password("WebDAV password: ")?,
+                        }),
+                        None => None,
+                    };
+                    Some(Box::new(WebDavSource {
+                        user: webdav_user,
+                        password,
+                        ..WebDavSource::parse(url)?
+                    }))
+                }
+                _ => None,
+            };
+            let report = match (source, indexer.local()) {
+                (Some(source), Some(local)) => local.index_source(source.as_ref(), &opts)?,
+                (Some(_), None) => {
+                    return Err(anyhow!("remote sources cannot be indexed with --remote"))
+                }
+                (None, _) => indexer.index_dir(&path, &opts)?,
+            };
+            #[cfg(feature = "tantivy")]
+            if let Some(local) = indexer.local() {
+                local.sync_text_index()?;
+            }
+            print_index_report(out, "Indexed", report)?;
+        }
+        Commands::Search {
+            query: expr,
+            name,
+            fuzzy,
+            case_sensitive,
+            regex,
+            path_regex,
+            glob,
+            under,
+            ext,
+            kind,
+            mime,
+            mismatched,
+            content,
+            note,
+            collection,
+            tag,
+            min_duration,
+            max_duration,
+            min_height,
+            artist,
+            album,
+            title,
+            owner,
+            group,
+            world_writable,
+            attrs,
+            has_xattr,
+            volume,
+            media,
+            min_size,
+            max_size,
+            min_allocated,
+            max_allocated,
+            from,
             to,
+            hash,
+            has_hash,
+            min_rating,
+            added_from,
+            added_to,
             sort,
             desc,
+            then_sort,
+            then_desc,
+            score,
+            explain,
+            paths_only,
+            print0,
             limit,
             offset,
+            after,
         } => {
-            let mut query = SearchQuery::default();
-            query.name_like = name;
-            query.ext = ext;
-            query.min_size = min_size;
-            query.max_size = max_size;
-            query.date_from = parse_date_opt(from);
-            query.date_to = parse_date_opt(to);
-            query.sort_key = Some(sort.into());
-            query.desc = desc;
-            query.limit = Some(limit);
-            query.offset = Some(offset);
-
-            let rows = indexer.search(&query)?;
-            render_records(rows);
+            let query = SearchQuery {
+                name_like: name.clone().filter(|_| !regex),
+                name_regex: name.filter(|_| regex),
+                path_regex,
+                glob,
+                // Indexed paths are absolute, so `./src` is resolved first.
+                path_prefix: under
+                    .map(std::path::absolute)
+                    .transpose()?
+                    .map(|dir| dir.to_string_lossy().into_owned()),
+                ext: None,
+                ext_in: ext,
+                min_size,
+                max_size,
+                min_allocated,
+                max_allocated,
+                date_from: parse_date_opt(from),
+                date_to: parse_date_opt(to),
+                added_from: parse_date_opt(added_from),
+                added_to: parse_date_opt(added_to),
+                hash,
+                has_hash,
+                min_rating,
+                sort_key: Some(sort.into()),
+                desc,
+                then_sort: then_sort.map(Into::into),
+                then_desc,
+                limit: Some(limit),
+                offset: Some(offset),
+                after,
+                fuzzy,
+                case_sensitive: case_sensitive.then_some(true),
+                kind: kind.map(Into::into),
+                mime,
+                type_mismatch: mismatched,
+                content,
+                note,
+                collection,
+                tags: tag,
+                min_duration,
+                max_duration,
+                min_height,
+                artist,
+                album,
+                title,
+                owner: owner.as_deref().map(user_id).transpose()?,
+                group: group.as_deref().map(group_id).transpose()?,
+                world_writable,
+                attributes: (!attrs.is_empty())
+                    .then(|| attrs.iter().fold(0, |bits, attr| bits | attr.bit())),
+                has_xattr,
+                volume,
+            };
+            let query = match expr {
+                Some(expr) => {
+                    let mut parsed: SearchQuery = expr.parse()?;
+                    if parsed.sort_key.is_none() {
+                        parsed.sort_key = query.sort_key;
+                        parsed.desc = query.desc;
+                        parsed.then_sort = query.then_sort;
+                        parsed.then_desc = query.then_desc;
+                    }
+                    SearchQuery {
+                        limit: query.limit,
+                        offset: query.offset,
+                        after: query.after,
+                        ..parsed
+                    }
+                }
+                None => query,
+            };
+
+            if explain {
+                show(out, indexer.query_plan(&query)?, |plan| {
+                    for step in plan {
+                        println!("{}", step);
+                    }
+                })?;
+                return Ok(());
+            }
+            if paths_only || print0 {
+                let mut stdout = BufWriter::new(io::stdout().lock());
+                indexer.for_each_row(&query, &mut |rec| {
+                    write_path(&mut stdout, &rec.fs_path(), print0)
+                })?;
+                stdout.flush()?;
+                return Ok(());
+            }
+            if out.format == OutputFormat::Ndjson && !score {
+                // Streamed, so a huge result set never sits in memory.
+                let mut stdout = BufWriter::new(io::stdout().lock());
+                indexer.for_each_row(&query, &mut |rec| write_line(&mut stdout, &rec))?;
+                stdout.flush()?;
+                return Ok(());
+            }
+            if score {
+                let rows = indexer.search_ranked(&query)?;
+                let page = Page {
+                    next: next_page(&query, rows.last().map(|r| &r.record), rows.len())
+                        .map(|cursor| cursor.to_string()),
+                    offline: offline_counts(indexer, rows.iter().map(|r| &r.record))?,
+                    total: indexer.count(&query)?,
+                    results: rows,
+                };
+                print_page(out, page, render_scored)?;
+            } else {
+                let rows = indexer.search(&query)?;
+                let page = Page {
+                    next: next_page(&query, rows.last(), rows.len())
+                        .map(|cursor| cursor.to_string()),
+                    offline: offline_counts(indexer, &rows)?,
+                    total: indexer.count(&query)?,
+                    results: rows,
+                };
+                if media {
+                    print_page(out, page, render_media)?;
+                } else {
+                    print_page(out, page, render_records)?;
+                }
+            }
         }
         Commands::Recent { limit } => {
-            let rows = indexer.recently_added(limit)?;
-            render_records(rows);
+            show(out, indexer.recently_added(limit)?, render_records)?;
+        }
+        Commands::Duplicates {
+            limit,
+            offset,
+            sort,
+            verify,
+            by_size,
+            by_name,
+            hash_candidates,
+            hash_algo,
+            paths_only,
+            print0,
+        } => {
+            let groups = if hash_candidates {
+                indexer.hashed_candidate_groups(
+                    by_name,
+                    hash_algo.into(),
+                    limit,
+                    offset,
+                    sort.into(),
+                )?
+            } else if by_size || by_name {
+                let groups = indexer.candidate_groups(by_name, limit, offset, sort.into())?;
+                if paths_only || print0 {
+                    let paths = groups.iter().flat_map(|group| &group.paths);
+                    return write_paths(paths.map(Path::new), print0);
+                }
+                show(out, groups, render_candidates)?;
+                return Ok(());
+            } else if verify {
+                indexer.verified_duplicate_groups(limit, offset, sort.into())?
+            } else {
+                indexer.duplicate_groups(limit, offset, sort.into())?
+            };
+            if paths_only || print0 {
+                let paths = groups.iter().flat_map(|group| &group.paths);
+                return write_paths(paths.map(Path::new), print0);
+            }
+            show(out, groups, render_duplicates)?;
+        }
+        Commands::Stats { ext_limit } => {
+            let mut stats = indexer.stats()?;
+            stats.by_extension.truncate(ext_limit);
+            let ages = indexer.age_histogram()?;
+            show(out, json!({ "stats": stats, "ages": ages }), |_| {
+                render_stats(stats, ext_limit);
+                render_ages(ages);
+            })?;
+        }
+        Commands::Prune { root, dry_run } => {
+            let report = if dry_run {
+                indexer.prune_dry_run(root.as_deref())?
+            } else {
+                indexer.prune(root.as_deref())?
+            };
+            show(out, report, |report| {
+                if report.dry_run {
+                    for path in &report.paths {
+                        println!("would remove {}", path);
+                    }
+                }
+                println!(
+                    "Checked {} entries, {} {} ({})",
+                    report.checked,
+                    if report.dry_run {
+                        "would remove"
+                    } else {
+                        "removed"
+                    },
+                    report.removed,
+                    human_bytes(report.removed_bytes as u64)
+                );
+            })?;
+        }
+        Commands::Roots => {
+            show(out, indexer.list_roots()?, render_roots)?;
+        }
+        Commands::Volumes => {
+            show(out, indexer.volumes()?, render_volumes)?;
+        }
+        Commands::Reindex => {
+            print_index_report(out, "Re-indexed", indexer.reindex_all()?)?;
+        }
+        Commands::Suggest { kind, input, limit } => {
+            let suggestions = match kind {
+                SuggestKind::Ext => indexer.suggest_extensions(&input, limit)?,
+                SuggestKind::Dir => indexer.suggest_dirs(&input, limit)?,
+                SuggestKind::Name => indexer.suggest_name_tokens(&input, limit)?,
+            };
+            show(out, suggestions, |suggestions| {
+                for suggestion in suggestions {
+                    println!("{}", suggestion.value);
+                }
+            })?;
         }
-        Commands::Duplicates { limit } => {
-            let groups = indexer.duplicate_groups(limit)?;
-            render_duplicates(groups);
+        _ => {
+            return Err(anyhow!(
+                "only index, search, recent, duplicates, stats, prune, roots, volumes, reindex and suggest work with --remote"
+            ))
         }
     }
+    Ok(())
+}
+
+#[cfg(feature = "encryption")]
+fn open_index(cli: &Cli) -> Result<FileIndexer> {
+    if cli.read_only {
+        return FileIndexer::open_read_only(&cli.db);
+    }
+    if !cli.encrypted {
+        return FileIndexer::new(&cli.db);
+    }
+    let passphrase = match std::env::var("FSINDEX_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) => rpassword::prompt_password("Passphrase: ")?,
+    };
+    FileIndexer::new_encrypted(&cli.db, &passphrase)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open_index(cli: &Cli) -> Result<FileIndexer> {
+    if cli.read_only {
+        return FileIndexer::open_read_only(&cli.db);
+    }
+    FileIndexer::new(&cli.db)
+}
+
+#[cfg(feature = "tantivy")]
+fn with_text_index(cli: &Cli, mut indexer: FileIndexer) -> Result<FileIndexer> {
+    if cli.text_index {
+        let mut dir = cli.db.clone().into_os_string();
+        dir.push(".tantivy");
+        indexer.enable_text_index(dir)?;
+    }
+    Ok(indexer)
+}
+
+#[cfg(unix)]
+fn user_id(name: &str) -> Result<u32> {
+    name.parse().or_else(|_| {
+        uzers::get_user_by_name(name)
+            .map(|user| user.uid())
+            .ok_or_else(|| anyhow!("unknown user: {}", name))
+    })
+}
+
+#[cfg(unix)]
+fn group_id(name: &str) -> Result<u32> {
+    name.parse().or_else(|_| {
+        uzers::get_group_by_name(name)
+            .map(|group| group.gid())
+            .ok_or_else(|| anyhow!("unknown group: {}", name))
+    })
+}
+
+#[cfg(not(unix))]
+fn user_id(name: &str) -> Result<u32> {
+    name.parse()
+        .map_err(|_| anyhow!("owner must be a numeric uid here: {}", name))
+}
+
+#[cfg(not(unix))]
+fn group_id(name: &str) -> Result<u32> {
+    name.parse()
+        .map_err(|_| anyhow!("group must be a numeric gid here: {}", name))
+}
+
+// Cursor for the following page when this one came back full.
+fn next_page(query: &SearchQuery, last: Option<&FileRecord>, rows: usize) -> Option<Cursor> {
+    let full = query.limit.is_some_and(|limit| rows as i64 >= limit);
+    last.filter(|_| full)
+        .and_then(|last| query.cursor_after(last).ok())
+}
+
+fn parse_date_opt(input: Option<String>) -> Option<NaiveDate> {
+    input
+        .as_deref()
+        .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+}
+
+// Prints `value` as JSON, or the way `render` lays it out for reading.
+fn show<T: Serialize>(out: &Output, value: T, render: impl FnOnce(T)) -> Result<()> {
+    match out.format {
+        OutputFormat::Text => render(value),
+        OutputFormat::Json => {
+            // Written rather than printed, so a closed pipe ends the run
+            // with an error instead of a panic.
+            let mut stdout = io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &value)?;
+            writeln!(stdout)?;
+        }
+        OutputFormat::Ndjson => {
+            let mut stdout = io::stdout().lock();
+            match serde_json::to_value(value)? {
+                Value::Array(items) => {
+                    for item in items {
+                        write_line(&mut stdout, &item)?;
+                    }
+                }
+                value => write_line(&mut stdout, &value)?,
+            }
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => write_table(out, serde_json::to_value(value)?)?,
+    }
+    Ok(())
+}
+
+// For other tools: one path per line, or each ending in NUL with `print0`.
+// Unix paths are written as their bytes, so any name survives.
+fn write_path<W: Write>(out: &mut W, path: &Path, print0: bool) -> Result<()> {
+    #[cfg(unix)]
+    out.write_all(std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()))?;
+    #[cfg(not(unix))]
+    out.write_all(path.to_string_lossy().as_bytes())?;
+    out.write_all(if print0 { b"\0" } else { b"\n" })?;
+    Ok(())
+}
+
+fn write_paths<'a>(paths: impl Iterator<Item = &'a Path>, print0: bool) -> Result<()> {
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    for path in paths {
+        write_path(&mut stdout, path, print0)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn write_line<W: Write, T: Serialize>(out: &mut W, value: &T) -> Result<()> {
+    serde_json::to_writer(&mut *out, value)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+// A list, or a single report, as rows under a header. Nested fields become
+// `parent.child` columns; lists are written as JSON.
+fn write_table(out: &Output, value: Value) -> Result<()> {
+    let rows: Vec<Map<String, Value>> = match value {
+        Value::Array(items) => items.into_iter().map(flatten).collect(),
+        value => vec![flatten(value)],
+    };
+    let mut fields: Vec<String> = Vec::new();
+    for key in rows.iter().flat_map(|row| row.keys()) {
+        if !fields.contains(key) {
+            fields.push(key.clone());
+        }
+    }
+    // A field that is empty in some rows and nested in others keeps only
+    // its nested columns.
+    let nested = |field: &String| {
+        let prefix = format!("{}.", field);
+        fields.iter().any(|other| other.starts_with(&prefix))
+    };
+    let fields: Vec<String> = fields.iter().filter(|f| !nested(f)).cloned().collect();
+    let columns = if out.columns.is_empty() {
+        fields
+    } else {
+        // Fields of an object that is empty everywhere, like `media.duration`
+        // of files without media, are known too.
+        let known = |column: &String| {
+            fields.contains(column)
+                || fields
+                    .iter()
+                    .any(|field| column.starts_with(&format!("{}.", field)))
+        };
+        if let Some(unknown) = out.columns.iter().find(|c| !rows.is_empty() && !known(c)) {
+            return Err(anyhow!(
+                "unknown column {}; available: {}",
+                unknown,
+                fields.join(",")
+            ));
+        }
+        out.columns.clone()
+    };
+
+    let delimiter = if out.format == OutputFormat::Tsv {
+        b'\t'
+    } else {
+        b','
+    };
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(io::stdout().lock());
+    writer.write_record(&columns)?;
+    for row in &rows {
+        writer.write_record(columns.iter().map(|c| match row.get(c) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(text)) => text.clone(),
+            Some(value) => value.to_string(),
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn flatten(value: Value) -> Map<String, Value> {
+    let mut row = Map::new();
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                match value {
+                    Value::Object(_) => {
+                        for (inner, value) in flatten(value) {
+                            row.insert(format!("{}.{}", key, inner), value);
+                        }
+                    }
+                    value => {
+                        row.insert(key, value);
+                    }
+                }
+            }
+        }
+        value => {
+            row.insert("value".to_string(), value);
+        }
+    }
+    row
+}
+
+// One page of search results.
+#[derive(Serialize)]
+struct Page<T> {
+    results: Vec<T>,
+    // Matches across all pages.
+    total: i64,
+    // Cursor of the following page, for `--after`.
+    next: Option<String>,
+    offline: Vec<Offline>,
+}
+
+// Matches on a drive that is not plugged in.
+#[derive(Serialize)]
+struct Offline {
+    volume: String,
+    files: usize,
+}
+
+fn print_page<T: Serialize>(
+    out: &Output,
+    page: Page<T>,
+    render: impl FnOnce(Vec<T>),
+) -> Result<()> {
+    if matches!(
+        out.format,
+        OutputFormat::Ndjson | OutputFormat::Csv | OutputFormat::Tsv
+    ) {
+        return show(out, page.results, |_| {});
+    }
+    show(out, page, |page| {
+        render(page.results);
+        print_offline(page.offline);
+        println!("{} results", page.total);
+        if let Some(cursor) = page.next {
+            println!("Next page: --after {}", cursor);
+        }
+    })
+}
+
+// Completes `--ext` with the extensions of the database named by `--db` on
+// the command line, by calling `suggest ext` while the user types. Bash,
+// zsh and fish get this on top of clap's script; `{bin}` is the binary,
+// `{name}` the same usable in function names and `{clap}` clap's function.
+const BASH_EXTENSIONS: &str = r#"
+_{name}_extensions() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" db=index.db done="" ext i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            --db) db="${COMP_WORDS[i + 1]}" ;;
+            --db=*) db="${COMP_WORDS[i]#--db=}" ;;
+        esac
+    done
+    [[ "$cur" == *,* ]] && done="${cur%,*},"
+    COMPREPLY=()
+    while IFS= read -r ext; do
+        COMPREPLY+=("$done$ext")
+    done < <({bin} --db "${db/#\~/$HOME}" --read-only suggest ext "${cur##*,}" --limit 50 2>/dev/null)
+}
+
+_{name}_dynamic() {
+    if [[ "${COMP_WORDS[COMP_CWORD - 1]}" == --ext ]]; then
+        _{name}_extensions
+    else
+        {clap} "$@"
+    fi
+}
+"#;
+
+const ZSH_EXTENSIONS: &str = r#"
+_{name}_extensions() {
+    local -a args exts
+    local db=index.db i
+    # The words before the subcommand are no longer in $words here.
+    args=(${(z)BUFFER})
+    for ((i = 1; i < ${#args}; i++)); do
+        case $args[i] in
+            --db) db=${(Q)args[i+1]} ;;
+            --db=*) db=${(Q)args[i]#--db=} ;;
+        esac
+    done
+    exts=(${(f)"$({bin} --db ${~db} --read-only suggest ext ${PREFIX##*,} --limit 50 2>/dev/null)"})
+    _values -s , extension $exts
+}
+"#;
+
+const FISH_EXTENSIONS: &str = r#"
+function __{name}_extensions
+    set -l args (commandline -opc)
+    set -l db index.db
+    for i in (seq (count $args))
+        switch $args[$i]
+            case --db
+                test $i -lt (count $args); and set db $args[(math $i + 1)]
+            case '--db=*'
+                set db (string replace -- --db= '' $args[$i])
+        end
+    end
+    set -l token (commandline -ct)
+    set -l done (string match -r -- '^.*,' $token)
+    for ext in ({bin} --db $db --read-only suggest ext (string replace -r -- '^.*,' '' $token) --limit 50 2>/dev/null)
+        echo $done$ext
+    end
+end
+complete -c {bin} -n "__fish_{name}_using_subcommand search" -l ext -f -a "(__{name}_extensions)"
+"#;
+
+fn print_completions(shell: Shell) -> Result<()> {
+    // Named after the binary as installed, so the script applies to it.
+    let bin = std::env::args_os()
+        .next()
+        .and_then(|arg| Some(Path::new(&arg).file_stem()?.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "fsindex".to_string());
+    let name = bin.replace('-', "_");
+    // clap's bash script escapes a hyphenated name in two different ways,
+    // so it is generated for `name` and registered for `bin` afterwards.
+    let generated = if shell == Shell::Bash { &name } else { &bin };
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Cli::command(), generated, &mut script);
+    let mut script = String::from_utf8(script)?;
+    let render = |template: &str, clap: &str| {
+        template
+            .replace("{bin}", &bin)
+            .replace("{name}", &name)
+            .replace("{clap}", clap)
+    };
+    match shell {
+        Shell::Bash => {
+            let clap = format!("_{}", name);
+            script = script
+                .replace(
+                    &format!("complete -F {} ", clap),
+                    &format!("complete -F {}_dynamic ", clap),
+                )
+                .replace(
+                    &format!(" -o default {}\n", name),
+                    &format!(" -o default {}\n", bin),
+                );
+            script.push_str(&render(BASH_EXTENSIONS, &clap));
+        }
+        Shell::Zsh => {
+            let clap = format!("_{}", bin);
+            script = script.replace(":EXT:_default", &format!(":EXT:_{}_extensions", name));
+            // Defined before the script completes for the first time.
+            let call = format!("\nif [ \"$funcstack[1]\" = \"{}\" ]", clap);
+            let at = script.find(&call).unwrap_or(script.len());
+            script.insert_str(at, &render(ZSH_EXTENSIONS, &clap));
+        }
+        Shell::Fish => script.push_str(&render(FISH_EXTENSIONS, "")),
+        _ => {}
+    }
+    io::stdout().write_all(script.as_bytes())?;
+    Ok(())
+}
+
+// Catches up with changes made while nothing was watching, then applies
+// and prints changes until interrupted.
+fn watch(
+    db: &Path,
+    indexer: &FileIndexer,
+    out: &Output,
+    roots: Vec<PathBuf>,
+    scan: bool,
+    debounce: Duration,
+) -> Result<()> {
+    if matches!(out.format, OutputFormat::Csv | OutputFormat::Tsv) {
+        return Err(anyhow!(
+            "watch prints a stream of events; use --format ndjson"
+        ));
+    }
+    let known = indexer.list_roots()?;
+    let (tx, rx) = mpsc::channel();
+    let mut watchers = Vec::new();
+    for root in roots {
+        let opts = known
+            .iter()
+            .find(|info| Path::new(&info.path) == root)
+            .map(|info| info.options.clone())
+            .unwrap_or_default();
+        if scan {
+            let report = indexer.index_dir(&root, &opts)?;
+            let pruned = indexer.prune(Some(&root))?;
+            eprintln!(
+                "Caught up with {}: {} files indexed, {} removed",
+                root.display(),
+                report.indexed,
+                pruned.removed
+            );
+        }
+        watchers.push(start_watcher(db, root, opts, debounce, tx.clone())?);
+    }
+    drop(tx);
+    for event in rx {
+        print_watch_event(out, event)?;
+    }
+    Ok(())
+}
+
+fn start_watcher(
+    db: &Path,
+    root: PathBuf,
+    opts: IndexOptions,
+    debounce: Duration,
+    tx: mpsc::Sender<WatchEvent>,
+) -> Result<Watcher> {
+    let watcher = Watcher::start_debounced(
+        db,
+        std::slice::from_ref(&root),
+        opts,
+        debounce,
+        move |event| {
+            let _ = tx.send(event);
+        },
+    )?;
+    eprintln!("Watching {} ({})", root.display(), watcher.backend());
+    Ok(watcher)
+}
+
+// Answers --remote commands on <db>.sock until interrupted, while watchers
+// and, if asked, periodic rescans keep the tracked roots current.
+#[cfg(unix)]
+fn daemon(
+    db: &Path,
+    indexer: FileIndexer,
+    out: &Output,
+    debounce: Option<Duration>,
+    rescan: Option<Duration>,
+) -> Result<()> {
+    if matches!(out.format, OutputFormat::Csv | OutputFormat::Tsv) {
+        return Err(anyhow!(
+            "the daemon prints a stream of events; use --format ndjson"
+        ));
+    }
+    // Checked before watching too, so a second daemon changes nothing.
+    let socket = socket_path(db);
+    if UnixStream::connect(&socket).is_ok() {
+        return Err(anyhow!(
+            "a daemon is already listening on {}",
+            socket.display()
+        ));
+    }
+    let indexer = Arc::new(indexer);
+    if let Some(debounce) = debounce {
+        let (tx, rx) = mpsc::channel();
+        let mut watchers = Vec::new();
+        for root in indexer.list_roots()? {
+            // Remote sources and unmounted volumes have nothing to watch.
+            let path = PathBuf::from(&root.path);
+            if path.is_dir() {
+                watchers.push(start_watcher(db, path, root.options, debounce, tx.clone())?);
+            }
+        }
+        drop(tx);
+        let out = out.clone();
+        thread::spawn(move || {
+            let _watchers = watchers;
+            for event in rx {
+                if print_watch_event(&out, event).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    if let Some(every) = rescan {
+        let indexer = Arc::clone(&indexer);
+        thread::spawn(move || loop {
+            thread::sleep(every);
+            let time = Local::now().format("%Y-%m-%d %H:%M:%S");
+            match indexer
+                .reindex_all(&CancellationToken::new())
+                .and_then(|report| Ok((report, indexer.prune(None)?)))
+            {
+                Ok((report, pruned)) => eprintln!(
+                    "{} rescanned: {} files indexed, {} removed",
+                    time, report.indexed, pruned.removed
+                ),
+                Err(err) => eprintln!("{} rescan failed: {:#}", time, err),
+            }
+        });
+    }
+    eprintln!("Listening on {}", socket.display());
+    indexer.serve(&socket)
+}
+
+#[cfg(not(unix))]
+fn daemon(
+    _db: &Path,
+    _indexer: FileIndexer,
+    _out: &Output,
+    _debounce: Option<Duration>,
+    _rescan: Option<Duration>,
+) -> Result<()> {
+    Err(anyhow!("the daemon is only supported on Unix"))
+}
+
+// One line per change: readable with timestamps, or one JSON object for
+// the other formats.
+fn print_watch_event(out: &Output, event: WatchEvent) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    if out.format == OutputFormat::Text {
+        let time = Local::now().format("%Y-%m-%d %H:%M:%S");
+        match event {
+            WatchEvent::Upserted(path) => {
+                writeln!(stdout, "{} updated   {}", time, path.display())?
+            }
+            WatchEvent::Removed(path) => writeln!(stdout, "{} removed   {}", time, path.display())?,
+            WatchEvent::Renamed { from, to } => writeln!(
+                stdout,
+                "{} renamed   {} -> {}",
+                time,
+                from.display(),
+                to.display()
+            )?,
+            WatchEvent::Rescanned(dir) => writeln!(stdout, "{} rescanned {}", time, dir.display())?,
+            WatchEvent::Error(err) => eprintln!("{} error: {}", time, err),
+        }
+        return Ok(());
+    }
+    let time = Local::now().to_rfc3339();
+    let line = match event {
+        WatchEvent::Upserted(path) => json!({ "time": time, "event": "updated", "path": path }),
+        WatchEvent::Removed(path) => json!({ "time": time, "event": "removed", "path": path }),
+        WatchEvent::Renamed { from, to } => {
+            json!({ "time": time, "event": "renamed", "from": from, "to": to })
+        }
+        WatchEvent::Rescanned(dir) => json!({ "time": time, "event": "rescanned", "path": dir }),
+        WatchEvent::Error(err) => json!({ "time": time, "event": "error", "error": err }),
+    };
+    write_line(&mut stdout, &line)
+}
+
+fn print_index_report(out: &Output, verb: &str, report: IndexReport) -> Result<()> {
+    if out.format != OutputFormat::Text {
+        return show(out, report_json(&report), |_| {});
+    }
+    for (path, err) in &report.errors {
+        eprintln!("skipped {}: {:#}", path.display(), err);
+    }
+    let unlisted = report.skipped - report.errors.len();
+    if unlisted > 0 {
+        eprintln!("... and {} more", unlisted);
+    }
+    if report.skipped > 0 {
+        println!(
+            "{} {} files, skipped {}",
+            verb, report.indexed, report.skipped
+        );
+    } else {
+        println!("{} {} files", verb, report.indexed);
+    }
+    if report.unchanged > 0 {
+        println!("{} files unchanged since the last scan", report.unchanged);
+    }
+    if report.unreachable > 0 {
+        println!("{} files recorded as unreachable", report.unreachable);
+    }
+    Ok(())
+}
+
+fn report_json(report: &IndexReport) -> Value {
+    let errors: Vec<_> = report
+        .errors
+        .iter()
+        .map(|(path, err)| json!({ "path": path, "error": format!("{:#}", err) }))
+        .collect();
+    json!({
+        "indexed": report.indexed,
+        "skipped": report.skipped,
+        "unchanged": report.unchanged,
+        "unreachable": report.unreachable,
+        "errors": errors,
+    })
+}
+
+fn render_records(rows: Vec<FileRecord>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Name"),
+        Cell::new("Ext"),
+        Cell::new("Size"),
+        Cell::new("Modified"),
+        Cell::new("Path"),
+    ]));
+
+    for record in rows {
+        let path = display_path(&record);
+        table.add_row(Row::from(vec![
+            Cell::new(record.name),
+            Cell::new(record.ext.unwrap_or_default()),
+            Cell::new(human_bytes(record.size as u64)),
+            Cell::new(record.modified.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Cell::new(path),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+fn render_deleted(rows: Vec<DeletedFile>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Deleted"),
+        Cell::new("Size"),
+        Cell::new("Modified"),
+        Cell::new("Path"),
+    ]));
+
+    for file in rows {
+        table.add_row(Row::from(vec![
+            Cell::new(
+                file.deleted_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+            ),
+            Cell::new(human_bytes(file.size as u64)),
+            Cell::new(file.modified.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Cell::new(file.path),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+fn render_media(rows: Vec<FileRecord>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Name"),
+        Cell::new("Duration"),
+        Cell::new("Resolution"),
+        Cell::new("Codec"),
+        Cell::new("Bitrate"),
+        Cell::new("Artist"),
+        Cell::new("Album"),
+        Cell::new("Title"),
+        Cell::new("Path"),
+    ]));
+
+    for record in rows {
+        let media = record.media.unwrap_or_default();
+        let resolution = match (media.width, media.height) {
+            (Some(w), Some(h)) => format!("{}x{}", w, h),
+            _ => String::new(),
+        };
+        table.add_row(Row::from(vec![
+            Cell::new(record.name),
+            Cell::new(media.duration.map(format_duration).unwrap_or_default()),
+            Cell::new(resolution),
+            Cell::new(media.codec.unwrap_or_default()),
+            Cell::new(
+                media
+                    .bitrate
+                    .map(|b| format!("{} kb/s", b / 1000))
+                    .unwrap_or_default(),
+            ),
+            Cell::new(media.artist.unwrap_or_default()),
+            Cell::new(media.album.unwrap_or_default()),
+            Cell::new(media.title.unwrap_or_default()),
+            Cell::new(record.path),
+        ]));
+    }
 
-    Ok(())
+    println!("{}", table);
 }
 
-fn parse_date_opt(input: Option<String>) -> Option<NaiveDate> {
-    input
-        .as_deref()
-        .map(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
-        .flatten()
+fn format_duration(seconds: f64) -> String {
+    let total = seconds.round() as i64;
+    let (hours, minutes, secs) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
 }
 
-fn render_records(rows: Vec<FileRecord>) {
+fn display_path(record: &FileRecord) -> String {
+    match &record.link_target {
+        Some(target) => format!("{} -> {}", record.path, target),
+        None => record.path.clone(),
+    }
+}
+
+fn render_scored(rows: Vec<ScoredRecord>) {
     let mut table = Table::new();
     table.load_preset(UTF8_FULL);
     table.set_header(Row::from(vec![
+        Cell::new("Score"),
         Cell::new("Name"),
         Cell::new("Ext"),
         Cell::new("Size"),
@@ -152,33 +2602,631 @@ fn render_records(rows: Vec<FileRecord>) {
         Cell::new("Path"),
     ]));
 
-    for record in rows {
+    for ScoredRecord { record, score } in rows {
+        let path = display_path(&record);
         table.add_row(Row::from(vec![
+            Cell::new(format!("{:.2}", score)),
             Cell::new(record.name),
             Cell::new(record.ext.unwrap_or_default()),
             Cell::new(human_bytes(record.size as u64)),
             Cell::new(record.modified.format("%Y-%m-%d %H:%M:%S").to_string()),
-            Cell::new(record.path),
+            Cell::new(path),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+fn render_similar(rows: Vec<SimilarFile>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Similarity"),
+        Cell::new("Size"),
+        Cell::new("Modified"),
+        Cell::new("Path"),
+    ]));
+
+    for SimilarFile { record, score } in rows {
+        let path = display_path(&record);
+        table.add_row(Row::from(vec![
+            Cell::new(format!("{}%", score)),
+            Cell::new(human_bytes(record.size as u64)),
+            Cell::new(record.modified.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Cell::new(path),
         ]));
     }
 
     println!("{}", table);
 }
 
+fn render_activity(groups: Vec<ActivityGroup>) {
+    let today = Local::now().date_naive();
+    let mut current = None;
+    for group in groups {
+        if current != Some(group.day) {
+            current = Some(group.day);
+            let label = match today.signed_duration_since(group.day).num_days() {
+                0 => " (today)",
+                1 => " (yesterday)",
+                _ => "",
+            };
+            println!("{}{}", group.day, label);
+        }
+        println!(
+            "  {} files  {}  {}",
+            group.count,
+            human_bytes(group.bytes as u64),
+            group.dir
+        );
+    }
+}
+
 fn render_duplicates(groups: Vec<DuplicateGroup>) {
     for group in groups {
         println!(
-            "hash={} size={} count={}",
+            "hash={} size={} count={} wasted={}",
             group.hash,
             human_bytes(group.size as u64),
-            group.count
+            group.count,
+            human_bytes(group.wasted as u64)
+        );
+        for path in group.paths {
+            println!("  {}", path);
+        }
+    }
+}
+
+fn render_dedupe(report: DedupeReport, action: DedupeAction, trash: bool) {
+    let verb = match (action, report.dry_run) {
+        (DedupeAction::Delete, true) if trash => "would trash",
+        (DedupeAction::Delete, true) => "would delete",
+        (DedupeAction::Delete, false) if trash => "trashed",
+        (DedupeAction::Delete, false) => "deleted",
+        (DedupeAction::Hardlink, true) => "would hard-link",
+        (DedupeAction::Hardlink, false) => "hard-linked",
+        (DedupeAction::Symlink, true) => "would symlink",
+        (DedupeAction::Symlink, false) => "symlinked",
+    };
+    for step in &report.steps {
+        match &step.error {
+            Some(err) => eprintln!("failed {}: {}", step.path, err),
+            None if action == DedupeAction::Delete => {
+                println!("{} {} (keeping {})", verb, step.path, step.kept)
+            }
+            None => println!("{} {} to {}", verb, step.path, step.kept),
+        }
+    }
+    if report.dry_run {
+        println!(
+            "Would reclaim {} from {} copies",
+            human_bytes(report.bytes_reclaimed as u64),
+            report.steps.len()
+        );
+    } else {
+        println!(
+            "Reclaimed {} from {} copies, {} failed",
+            human_bytes(report.bytes_reclaimed as u64),
+            report.applied,
+            report.failed
+        );
+    }
+    if report.skipped_groups > 0 {
+        println!(
+            "{} groups have no copy to keep and were left alone",
+            report.skipped_groups
+        );
+    }
+}
+
+fn render_stats(stats: IndexStats, ext_limit: usize) {
+    let date = |ts: Option<DateTime<Utc>>| {
+        ts.map(|ts| ts.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    println!(
+        "{} files, {} ({} on disk)",
+        stats.total_files,
+        human_bytes(stats.total_bytes as u64),
+        human_bytes(stats.allocated_bytes as u64)
+    );
+    if stats.hash_skipped_files > 0 {
+        println!(
+            "{} hashed, {} unhashed ({} skipped by the hash policy)",
+            stats.hashed_files, stats.unhashed_files, stats.hash_skipped_files
+        );
+    } else {
+        println!(
+            "{} hashed, {} unhashed",
+            stats.hashed_files, stats.unhashed_files
+        );
+    }
+    println!(
+        "Modified between {} and {}",
+        date(stats.oldest_modified),
+        date(stats.newest_modified)
+    );
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Ext"),
+        Cell::new("Files"),
+        Cell::new("Size"),
+    ]));
+    for ext in stats.by_extension.into_iter().take(ext_limit) {
+        table.add_row(Row::from(vec![
+            Cell::new(ext.ext.unwrap_or_else(|| "(none)".to_string())),
+            Cell::new(ext.count),
+            Cell::new(human_bytes(ext.bytes as u64)),
+        ]));
+    }
+    println!("{}", table);
+
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("File size"),
+        Cell::new("Files"),
+        Cell::new("Total"),
+    ]));
+    for bucket in stats.size_distribution {
+        table.add_row(Row::from(vec![
+            Cell::new(size_range(bucket.min, bucket.max)),
+            Cell::new(bucket.count),
+            Cell::new(human_bytes(bucket.bytes as u64)),
+        ]));
+    }
+    println!("{}", table);
+}
+
+fn render_ages(buckets: Vec<AgeBucket>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Last modified"),
+        Cell::new("Files"),
+        Cell::new("Total"),
+    ]));
+    for bucket in buckets {
+        let label = match (bucket.min_days, bucket.max_days) {
+            (0, Some(max)) => format!("within {} days", max),
+            (min, Some(max)) => format!("{} – {} days ago", min, max),
+            (min, None) => format!("over {} days ago", min),
+        };
+        table.add_row(Row::from(vec![
+            Cell::new(label),
+            Cell::new(bucket.count),
+            Cell::new(human_bytes(bucket.bytes as u64)),
+        ]));
+    }
+    println!("{}", table);
+}
+
+fn size_range(min: i64, max: Option<i64>) -> String {
+    match max {
+        Some(max) => format!("{} – {}", human_bytes(min as u64), human_bytes(max as u64)),
+        None => format!("≥ {}", human_bytes(min as u64)),
+    }
+}
+
+fn render_dirs(dirs: Vec<DirRecord>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Size"),
+        Cell::new("On disk"),
+        Cell::new("Files"),
+        Cell::new("Last modified"),
+        Cell::new("Path"),
+    ]));
+
+    for dir in dirs {
+        let modified = dir
+            .modified
+            .map(|ts| ts.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        table.add_row(Row::from(vec![
+            Cell::new(human_bytes(dir.size as u64)),
+            Cell::new(human_bytes(dir.allocated as u64)),
+            Cell::new(dir.file_count),
+            Cell::new(modified),
+            Cell::new(dir.path),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+fn render_shared(pairs: Vec<ChunkOverlap>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Shared"),
+        Cell::new("File A"),
+        Cell::new("File B"),
+    ]));
+
+    for pair in pairs {
+        let percent = |size: i64| {
+            if size > 0 {
+                pair.shared_bytes as f64 * 100.0 / size as f64
+            } else {
+                0.0
+            }
+        };
+        table.add_row(Row::from(vec![
+            Cell::new(human_bytes(pair.shared_bytes as u64)),
+            Cell::new(format!("{} ({:.0}%)", pair.a, percent(pair.a_size))),
+            Cell::new(format!("{} ({:.0}%)", pair.b, percent(pair.b_size))),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+fn render_verify(report: VerifyReport) {
+    println!(
+        "Checked {} files: {} ok, {} corrupted; {} changed since indexing, {} missing, {} unreadable",
+        report.checked,
+        report.ok,
+        report.corrupted.len(),
+        report.changed.len(),
+        report.missing.len(),
+        report.unreadable.len()
+    );
+    if !report.corrupted.is_empty() {
+        let mut table = Table::new();
+        table.load_preset(UTF8_FULL);
+        table.set_header(Row::from(vec![
+            Cell::new("Corrupted"),
+            Cell::new("Expected"),
+            Cell::new("Actual"),
+        ]));
+        for mismatch in report.corrupted {
+            table.add_row(Row::from(vec![
+                Cell::new(mismatch.path),
+                Cell::new(mismatch.expected),
+                Cell::new(mismatch.actual),
+            ]));
+        }
+        println!("{}", table);
+    }
+    for (label, paths) in [
+        ("changed", report.changed),
+        ("missing", report.missing),
+        ("unreadable", report.unreadable),
+    ] {
+        for path in paths {
+            println!("{}: {}", label, path);
+        }
+    }
+}
+
+fn render_diff(diff: ScanDiff) {
+    for entry in &diff.added {
+        println!("+ {}", entry.path);
+    }
+    for entry in &diff.removed {
+        println!("- {}", entry.path);
+    }
+    for (before, after) in &diff.resized {
+        println!(
+            "~ {} ({} -> {})",
+            after.path,
+            human_bytes(before.size.max(0) as u64),
+            human_bytes(after.size.max(0) as u64)
         );
+    }
+    for (before, after) in &diff.modified {
+        let time = |entry: &ScanEntry| {
+            entry
+                .modified
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        };
+        println!("M {} ({} -> {})", after.path, time(before), time(after));
+    }
+    println!(
+        "{} added, {} removed, {} resized, {} modified",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.resized.len(),
+        diff.modified.len()
+    );
+}
+
+fn render_scan_errors(errors: Vec<ScanError>) {
+    if errors.is_empty() {
+        println!("No errors recorded");
+        return;
+    }
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Path"),
+        Cell::new("Kind"),
+        Cell::new("Error"),
+    ]));
+    for error in errors {
+        table.add_row(Row::from(vec![
+            Cell::new(error.path),
+            Cell::new(error.kind),
+            Cell::new(error.message),
+        ]));
+    }
+    println!("{}", table);
+}
+
+fn render_scans(scans: Vec<ScanInfo>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Id"),
+        Cell::new("Root"),
+        Cell::new("Started"),
+        Cell::new("Files"),
+        Cell::new("Status"),
+    ]));
+
+    for scan in scans {
+        let status = match (scan.finished_at, scan.snapshot) {
+            (None, _) => "incomplete",
+            (Some(_), true) => "snapshot",
+            (Some(_), false) => "done",
+        };
+        table.add_row(Row::from(vec![
+            Cell::new(scan.id),
+            Cell::new(scan.root),
+            Cell::new(
+                scan.started_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+            ),
+            Cell::new(scan.file_count),
+            Cell::new(status),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+fn render_candidates(groups: Vec<CandidateGroup>) {
+    for group in groups {
+        match &group.name {
+            Some(name) => println!(
+                "name={} size={} count={}",
+                name,
+                human_bytes(group.size as u64),
+                group.count
+            ),
+            None => println!(
+                "size={} count={}",
+                human_bytes(group.size as u64),
+                group.count
+            ),
+        }
         for path in group.paths {
             println!("  {}", path);
         }
     }
 }
 
+fn render_roots(roots: Vec<RootInfo>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Root"),
+        Cell::new("Files"),
+        Cell::new("Last scan"),
+        Cell::new("Options"),
+    ]));
+
+    for root in roots {
+        let last_scan = root.last_scan.map_or_else(
+            || "never".to_string(),
+            |ts| {
+                ts.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            },
+        );
+        table.add_row(Row::from(vec![
+            Cell::new(root.path),
+            Cell::new(root.file_count),
+            Cell::new(last_scan),
+            Cell::new(describe_options(&root.options)),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+fn render_volumes(volumes: Vec<VolumeInfo>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Label"),
+        Cell::new("Id"),
+        Cell::new("Files"),
+        Cell::new("Size"),
+        Cell::new("Mount"),
+        Cell::new("Last seen"),
+    ]));
+
+    for volume in volumes {
+        let mount = match (&volume.mount, volume.connected) {
+            (Some(mount), true) => mount.clone(),
+            (Some(mount), false) => format!("{} (not connected)", mount),
+            (None, _) => "-".to_string(),
+        };
+        let last_seen = volume
+            .last_seen
+            .map(|ts| {
+                ts.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        table.add_row(Row::from(vec![
+            Cell::new(volume.label.unwrap_or_default()),
+            Cell::new(volume.id),
+            Cell::new(volume.file_count),
+            Cell::new(human_bytes(volume.bytes as u64)),
+            Cell::new(mount),
+            Cell::new(last_seen),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+// How many of `rows` are on each drive that is not plugged in.
+fn offline_counts<'a>(
+    indexer: &dyn Catalog,
+    rows: impl IntoIterator<Item = &'a FileRecord>,
+) -> Result<Vec<Offline>> {
+    let mut counts: Vec<(VolumeInfo, usize)> = indexer
+        .disconnected_volumes()?
+        .into_iter()
+        .map(|volume| (volume, 0))
+        .collect();
+    for row in rows {
+        if row.scheme.is_some() {
+            continue;
+        }
+        if let Some((_, count)) = counts
+            .iter_mut()
+            .find(|(volume, _)| row.volume.as_ref() == Some(&volume.id))
+        {
+            *count += 1;
+        }
+    }
+    Ok(counts
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(volume, files)| Offline {
+            volume: volume.name().to_string(),
+            files,
+        })
+        .collect())
+}
+
+fn print_offline(offline: Vec<Offline>) {
+    for drive in offline {
+        println!(
+            "{} on drive '{}' (not connected)",
+            drive.files, drive.volume
+        );
+    }
+}
+
+fn render_tags(tags: Vec<TagInfo>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Tag"),
+        Cell::new("Files"),
+        Cell::new("Size"),
+    ]));
+    for tag in tags {
+        table.add_row(Row::from(vec![
+            Cell::new(tag.name),
+            Cell::new(tag.file_count),
+            Cell::new(human_bytes(tag.bytes as u64)),
+        ]));
+    }
+    println!("{}", table);
+}
+
+fn render_collections(collections: Vec<CollectionInfo>) {
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL);
+    table.set_header(Row::from(vec![
+        Cell::new("Collection"),
+        Cell::new("Files"),
+        Cell::new("Size"),
+        Cell::new("Created"),
+    ]));
+
+    for collection in collections {
+        table.add_row(Row::from(vec![
+            Cell::new(collection.name),
+            Cell::new(collection.file_count),
+            Cell::new(human_bytes(collection.bytes as u64)),
+            Cell::new(
+                collection
+                    .created_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M"),
+            ),
+        ]));
+    }
+
+    println!("{}", table);
+}
+
+fn describe_options(opts: &IndexOptions) -> String {
+    let mut parts = Vec::new();
+    if opts.hash {
+        parts.push("hash".to_string());
+    }
+    if let Some(max) = opts.hash_max_size {
+        parts.push(format!("hash-max-size={}", max));
+    }
+    for ext in &opts.hash_skip_exts {
+        parts.push(format!("no-hash-ext={}", ext));
+    }
+    if let Some(timeout) = opts.io_timeout {
+        parts.push(format!("io-timeout={}", timeout.as_secs()));
+    }
+    if opts.io_retries > 0 {
+        parts.push(format!("retries={}", opts.io_retries));
+    }
+    if opts.record_unreachable {
+        parts.push("record-unreachable".to_string());
+    }
+    if opts.respect_gitignore {
+        parts.push("gitignore".to_string());
+    }
+    if opts.follow_symlinks {
+        parts.push("follow-symlinks".to_string());
+    }
+    if opts.record_symlinks {
+        parts.push("record-symlinks".to_string());
+    }
+    if opts.chunking {
+        parts.push("chunks".to_string());
+    }
+    if opts.perceptual_hash {
+        parts.push("image-hash".to_string());
+    }
+    if opts.similarity_digest {
+        parts.push("similarity".to_string());
+    }
+    if opts.xattrs {
+        parts.push("xattrs".to_string());
+    }
+    if opts.snapshot {
+        parts.push("snapshot".to_string());
+    }
+    if opts.skip_hidden == Some(true) {
+        parts.push("skip-hidden".to_string());
+    }
+    if let Some(depth) = opts.max_depth {
+        parts.push(format!("max-depth={}", depth));
+    }
+    for glob in &opts.include {
+        parts.push(format!("include={}", glob));
+    }
+    for glob in &opts.exclude {
+        parts.push(format!("exclude={}", glob));
+    }
+    parts.join(" ")
+}
+
 fn human_bytes(bytes: u64) -> String {
     const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
     let mut unit = 0usize;
@@ -195,3 +3243,24 @@ fn human_bytes(bytes: u64) -> String {
         format!("{:.2} {}", value, UNITS[unit])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_format_follows_the_extension() {
+        let format =
+            |path: &str| ImportArg::from_extension(Path::new(path)).map(ImportFormat::from);
+        assert_eq!(format("a.jsonl").unwrap(), ImportFormat::JsonLines);
+        assert_eq!(format("a.NDJSON").unwrap(), ImportFormat::JsonLines);
+        assert_eq!(format("dir.v2/a.csv").unwrap(), ImportFormat::Csv);
+        assert_eq!(format("C.EFU").unwrap(), ImportFormat::Efu);
+        #[cfg(feature = "parquet")]
+        assert_eq!(format("a.parquet").unwrap(), ImportFormat::Parquet);
+        for path in ["mlocate.db", "list.txt", "noext", ".csv"] {
+            let err = format(path).unwrap_err().to_string();
+            assert!(err.contains("pass --file-format"), "{}", path);
+        }
+    }
+}
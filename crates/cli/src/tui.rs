@@ -0,0 +1,403 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use chrono::Local;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use fsindex_core::{EntryKind, FileIndexer, FileRecord, SearchQuery};
+
+use crate::{format_duration, human_bytes};
+
+// Typing searches again once it pauses for this long.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+const PREVIEW_BYTES: usize = 16 * 1024;
+const PAGE: isize = 10;
+const HELP: &str =
+    "Enter open · ^Y copy path · ^D trash · ↑↓ PgUp PgDn select · ^U clear · Esc quit";
+
+// The search tab of the GUI in a terminal: a query expression typed at the
+// top, matches below it, and details and the start of the selected file on
+// the right.
+pub fn run(indexer: &FileIndexer, limit: i64) -> Result<()> {
+    let mut terminal = ratatui::try_init().context("setting up the terminal")?;
+    let result = App::new(indexer, limit).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct App<'a> {
+    indexer: &'a FileIndexer,
+    limit: i64,
+    input: String,
+    // When the input changed without being searched yet.
+    edited: Option<Instant>,
+    results: Vec<FileRecord>,
+    total: Option<i64>,
+    table: TableState,
+    preview: Vec<Line<'static>>,
+    status: String,
+    // Set while asking whether the selected file should go to the trash.
+    confirm_trash: bool,
+    quit: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(indexer: &'a FileIndexer, limit: i64) -> Self {
+        Self {
+            indexer,
+            limit,
+            input: String::new(),
+            edited: None,
+            results: Vec::new(),
+            total: None,
+            table: TableState::default(),
+            preview: Vec::new(),
+            status: String::new(),
+            confirm_trash: false,
+            quit: false,
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        self.search();
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            let timeout = match self.edited {
+                Some(at) => DEBOUNCE.saturating_sub(at.elapsed()),
+                None => Duration::from_secs(1),
+            };
+            if event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.key(key);
+                    }
+                }
+            } else if self.edited.is_some_and(|at| at.elapsed() >= DEBOUNCE) {
+                self.search();
+            }
+        }
+        Ok(())
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if self.confirm_trash {
+            self.confirm_trash = false;
+            self.status = match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => self.trash_selected(),
+                _ => String::new(),
+            };
+            return;
+        }
+        self.status.clear();
+        match key.code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if ctrl => self.quit = true,
+            KeyCode::Char('u') if ctrl => self.edit(|input| input.clear()),
+            KeyCode::Char('w') if ctrl => self.edit(|input| {
+                let kept = input.trim_end().rfind(' ').map_or(0, |i| i + 1);
+                input.truncate(kept);
+            }),
+            KeyCode::Char('y') if ctrl => self.status = self.copy_selected(),
+            KeyCode::Char('d') if ctrl => self.ask_trash(),
+            KeyCode::Delete => self.ask_trash(),
+            KeyCode::Char('p') if ctrl => self.step(-1),
+            KeyCode::Char('n') if ctrl => self.step(1),
+            KeyCode::Char(c) if !ctrl => self.edit(|input| input.push(c)),
+            KeyCode::Backspace => self.edit(|input| {
+                input.pop();
+            }),
+            KeyCode::Up => self.step(-1),
+            KeyCode::Down => self.step(1),
+            KeyCode::PageUp => self.step(-PAGE),
+            KeyCode::PageDown => self.step(PAGE),
+            KeyCode::Home => self.step(-(self.results.len() as isize)),
+            KeyCode::End => self.step(self.results.len() as isize),
+            KeyCode::Enter => self.status = self.open_selected(),
+            _ => {}
+        }
+    }
+
+    fn edit(&mut self, change: impl FnOnce(&mut String)) {
+        change(&mut self.input);
+        self.edited = Some(Instant::now());
+    }
+
+    fn search(&mut self) {
+        self.edited = None;
+        let mut query = match self.input.parse::<SearchQuery>() {
+            Ok(query) => query,
+            Err(err) => {
+                self.status = format!("{:#}", err);
+                return;
+            }
+        };
+        query.limit = Some(self.limit);
+        match self.indexer.search(&query) {
+            Ok(rows) => {
+                self.total = self.indexer.count(&query).ok();
+                self.results = rows;
+                self.select((!self.results.is_empty()).then_some(0));
+            }
+            Err(err) => self.status = format!("{:#}", err),
+        }
+    }
+
+    fn selected(&self) -> Option<&FileRecord> {
+        self.results.get(self.table.selected()?)
+    }
+
+    fn select(&mut self, index: Option<usize>) {
+        self.table.select(index);
+        self.preview = match self.selected() {
+            Some(record) => preview(self.indexer, record),
+            None => Vec::new(),
+        };
+    }
+
+    fn step(&mut self, delta: isize) {
+        let Some(last) = self.results.len().checked_sub(1) else {
+            return;
+        };
+        let current = self.table.selected().unwrap_or(0) as isize;
+        self.select(Some((current + delta).clamp(0, last as isize) as usize));
+    }
+
+    fn open_selected(&self) -> String {
+        let Some(record) = self.selected() else {
+            return String::new();
+        };
+        match local_path(record).and_then(|path| open(&path)) {
+            Ok(()) => format!("Opened {}", record.path),
+            Err(err) => format!("{:#}", err),
+        }
+    }
+
+    fn copy_selected(&self) -> String {
+        let Some(record) = self.selected() else {
+            return String::new();
+        };
+        match copy(&record.path) {
+            Ok(()) => format!("Copied {}", record.path),
+            Err(err) => format!("{:#}", err),
+        }
+    }
+
+    fn ask_trash(&mut self) {
+        if let Some(record) = self.selected() {
+            self.status = format!("Move {} to the trash? (y/n)", record.path);
+            self.confirm_trash = true;
+        }
+    }
+
+    // Like the GUI, deletes only to the trash.
+    fn trash_selected(&mut self) -> String {
+        let Some(index) = self.table.selected().filter(|&i| i < self.results.len()) else {
+            return String::new();
+        };
+        let record = &self.results[index];
+        let result = local_path(record).and_then(|path| self.indexer.trash_file(&path));
+        if let Err(err) = result {
+            return format!("{:#}", err);
+        }
+        let record = self.results.remove(index);
+        self.total = self.total.map(|total| total - 1);
+        let next = index.min(self.results.len().saturating_sub(1));
+        self.select((!self.results.is_empty()).then_some(next));
+        format!("Moved {} to the trash", record.path)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [query, main, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list, details] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(main);
+
+        let title = match self.total {
+            Some(total) => format!(" Query · {} of {} files ", self.results.len(), total),
+            None => " Query ".to_string(),
+        };
+        // Only the end of a long query fits.
+        let width = query.width.saturating_sub(3) as usize;
+        let shown: String = {
+            let chars: Vec<char> = self.input.chars().collect();
+            chars[chars.len().saturating_sub(width)..].iter().collect()
+        };
+        let cursor = Line::from(shown.as_str()).width() as u16;
+        frame.render_widget(
+            Paragraph::new(shown).block(Block::bordered().title(title)),
+            query,
+        );
+        frame.set_cursor_position((query.x + 1 + cursor, query.y + 1));
+
+        let rows = self.results.iter().map(|record| {
+            Row::new(vec![
+                record.name.clone(),
+                human_bytes(record.size as u64),
+                record.modified.format("%Y-%m-%d %H:%M").to_string(),
+                record.path.clone(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(30),
+                Constraint::Length(10),
+                Constraint::Length(16),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new(["Name", "Size", "Modified", "Path"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title(" Results "));
+        frame.render_stateful_widget(table, list, &mut self.table);
+
+        frame.render_widget(
+            Paragraph::new(self.preview.clone())
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title(" Preview ")),
+            details,
+        );
+
+        let line = if self.status.is_empty() {
+            HELP
+        } else {
+            self.status.as_str()
+        };
+        frame.render_widget(Paragraph::new(line), status);
+    }
+}
+
+// What the index knows about `record`, followed by the first lines of the
+// file if it is local text.
+fn preview(indexer: &FileIndexer, record: &FileRecord) -> Vec<Line<'static>> {
+    let mut lines = vec![
+        Line::from(record.path.clone()),
+        Line::from(format!(
+            "{} ({} bytes)",
+            human_bytes(record.size as u64),
+            record.size
+        )),
+        Line::from(format!(
+            "Modified {}",
+            record
+                .modified
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        )),
+    ];
+    if record.kind != EntryKind::File {
+        lines.push(Line::from(format!("Kind {}", record.kind.as_str())));
+    }
+    if let Some(target) = &record.link_target {
+        lines.push(Line::from(format!("Target {}", target)));
+    }
+    if let Some(mime) = record.detected_mime.as_ref().or(record.mime.as_ref()) {
+        lines.push(Line::from(format!("Type {}", mime)));
+    }
+    if let Some(duration) = record.media.as_ref().and_then(|media| media.duration) {
+        lines.push(Line::from(format!(
+            "Duration {}",
+            format_duration(duration)
+        )));
+    }
+    if let Some(hash) = &record.hash {
+        lines.push(Line::from(format!("Hash {}", hash)));
+    }
+    if record.rating > 0 {
+        lines.push(Line::from("★".repeat(record.rating as usize)));
+    }
+    if let Ok(Some(note)) = indexer.note(record.fs_path()) {
+        lines.push(Line::from(format!("Note {}", note)));
+    }
+    if record.scheme.is_none() && record.kind == EntryKind::File {
+        lines.push(Line::default());
+        lines.extend(file_head(&record.fs_path()));
+    }
+    lines
+}
+
+fn file_head(path: &Path) -> Vec<Line<'static>> {
+    let mut head = Vec::new();
+    let read =
+        File::open(path).and_then(|file| file.take(PREVIEW_BYTES as u64).read_to_end(&mut head));
+    if let Err(err) = read {
+        return vec![Line::from(format!("(cannot read: {})", err))];
+    }
+    if head.contains(&0) {
+        return vec![Line::from("(binary file)")];
+    }
+    String::from_utf8_lossy(&head)
+        .lines()
+        .map(|line| {
+            let line: String = line
+                .replace('\t', "    ")
+                .chars()
+                .filter(|c| !c.is_control())
+                .collect();
+            Line::from(line)
+        })
+        .collect()
+}
+
+fn local_path(record: &FileRecord) -> Result<std::path::PathBuf> {
+    match &record.scheme {
+        Some(scheme) => Err(anyhow!(
+            "{} is a {} entry, not a local file",
+            record.path,
+            scheme
+        )),
+        None => Ok(record.fs_path()),
+    }
+}
+
+// Hands `path` to the desktop's default application.
+fn open(path: &Path) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut cmd = Command::new("open");
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", ""]);
+        cmd
+    };
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut cmd = Command::new("xdg-open");
+    let mut child = cmd
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("running {:?}", cmd.get_program()))?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// Sets the clipboard with an OSC 52 escape sequence, which the terminal
+// handles, so it works over SSH too.
+fn copy(text: &str) -> Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()?;
+    Ok(())
+}
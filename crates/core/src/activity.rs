@@ -0,0 +1,87 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::FileIndexer;
+use crate::paths;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum ActivityKind {
+    #[default]
+    Added,
+    Modified,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityGroup {
+    pub day: NaiveDate,
+    pub dir: String,
+    pub count: i64,
+    pub bytes: i64,
+    pub latest: DateTime<Utc>,
+}
+
+impl FileIndexer {
+    pub fn activity(
+        &self,
+        kind: ActivityKind,
+        days: i64,
+        limit: i64,
+    ) -> Result<Vec<ActivityGroup>> {
+        let conn = self.reader()?;
+        let column = match kind {
+            ActivityKind::Added => "added_at",
+            ActivityKind::Modified => "modified",
+        };
+        let since = Local::now().date_naive() - Duration::days(days.max(1) - 1);
+        let since_ts = since
+            .and_hms_opt(0, 0, 0)
+            .and_then(|t| t.and_local_timezone(Local).earliest())
+            .map_or(0, |t| t.timestamp());
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT path,size,{column} FROM files WHERE {column} >= ? ORDER BY {column} DESC"
+        ))?;
+        let rows = stmt.query_map(params![since_ts], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+
+        let mut groups: HashMap<(NaiveDate, String), ActivityGroup> = HashMap::new();
+        for (path, size, ts) in rows.filter_map(|r| r.ok()) {
+            let Some(when) = DateTime::<Utc>::from_timestamp(ts, 0) else {
+                continue;
+            };
+            let day = when.with_timezone(&Local).date_naive();
+            let dir = Path::new(&path)
+                .parent()
+                .map(paths::key)
+                .unwrap_or_default();
+            let group = groups
+                .entry((day, dir.clone()))
+                .or_insert_with(|| ActivityGroup {
+                    day,
+                    dir,
+                    count: 0,
+                    bytes: 0,
+                    latest: when,
+                });
+            group.count += 1;
+            group.bytes += size;
+            group.latest = group.latest.max(when);
+        }
+
+        let mut out: Vec<ActivityGroup> = groups.into_values().collect();
+        out.sort_by_key(|g| (Reverse(g.day), Reverse(g.count), g.dir.clone()));
+        out.truncate(limit.max(0) as usize);
+        Ok(out)
+    }
+}
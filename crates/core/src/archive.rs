@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::filetype;
+use crate::hash::{hash_reader, HashMode};
+use crate::indexer::{EntryKind, FileRecord, IndexOptions};
+
+// Joins an archive path and a member name: `backup.zip!/docs/a.txt`.
+pub(crate) const MEMBER_SEPARATOR: &str = "!/";
+
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn format_of(name: &str) -> Option<Format> {
+    let name = name.to_ascii_lowercase();
+    if name.ends_with(".zip") || name.ends_with(".jar") {
+        Some(Format::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Format::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn is_archive(name: &str) -> bool {
+    format_of(name).is_some()
+}
+
+// The file on disk that holds `path`, which may name an archive member.
+pub(crate) fn physical_path(path: &str) -> &str {
+    path.split_once(MEMBER_SEPARATOR)
+        .map_or(path, |(archive, _)| archive)
+}
+
+pub(crate) fn member_bounds(archive: &str) -> (String, String) {
+    let lower = format!("{}{}", archive, MEMBER_SEPARATOR);
+    let upper = format!("{}\u{10FFFF}", lower);
+    (lower, upper)
+}
+
+pub(crate) fn read_members(archive: &FileRecord, opts: &IndexOptions) -> Result<Vec<FileRecord>> {
+    let path = archive.fs_path();
+    let file = File::open(&path).with_context(|| format!("opening {}", path.display()))?;
+    match format_of(&archive.name) {
+        Some(Format::Zip) => zip_members(archive, file, opts),
+        Some(Format::Tar) => tar_members(archive, BufReader::new(file), opts),
+        Some(Format::TarGz) => tar_members(archive, GzDecoder::new(BufReader::new(file)), opts),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn zip_members(archive: &FileRecord, file: File, opts: &IndexOptions) -> Result<Vec<FileRecord>> {
+    let mut zip = ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("reading {} as a zip archive", archive.path))?;
+    let mut members = Vec::new();
+    for idx in 0..zip.len() {
+        let mut entry = zip.by_index(idx)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let modified = entry
+            .last_modified()
+            .and_then(|t| {
+                NaiveDate::from_ymd_opt(t.year().into(), t.month().into(), t.day().into())?
+                    .and_hms_opt(t.hour().into(), t.minute().into(), t.second().into())
+            })
+            .map_or(archive.modified, |t| t.and_utc());
+        let name = entry.name().to_string();
+        let size = entry.size() as i64;
+        let hash = if wants_hash(opts) {
+            Some(hash_reader(&mut entry, opts.hash_algo)?)
+        } else {
+            None
+        };
+        members.push(member_record(archive, &name, size, modified, hash, opts));
+    }
+    Ok(members)
+}
+
+// Members cannot be re-read cheaply for verification, so quick mode skips them.
+fn wants_hash(opts: &IndexOptions) -> bool {
+    opts.hash && opts.hash_mode == HashMode::Full
+}
+
+fn tar_members<R: Read>(
+    archive: &FileRecord,
+    reader: R,
+    opts: &IndexOptions,
+) -> Result<Vec<FileRecord>> {
+    let mut tar = tar::Archive::new(reader);
+    let mut members = Vec::new();
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().to_string();
+        let size = entry.size() as i64;
+        let modified = entry
+            .header()
+            .mtime()
+            .ok()
+            .and_then(|ts| DateTime::<Utc>::from_timestamp(ts as i64, 0))
+            .unwrap_or(archive.modified);
+        let hash = if wants_hash(opts) {
+            Some(hash_reader(&mut entry, opts.hash_algo)?)
+        } else {
+            None
+        };
+        members.push(member_record(archive, &name, size, modified, hash, opts));
+    }
+    Ok(members)
+}
+
+fn member_record(
+    archive: &FileRecord,
+    name: &str,
+    size: i64,
+    modified: DateTime<Utc>,
+    hash: Option<String>,
+    opts: &IndexOptions,
+) -> FileRecord {
+    let name = name.trim_start_matches("./");
+    let file_name = name.rsplit('/').next().unwrap_or(name).to_string();
+    let ext = Path::new(&file_name)
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_ascii_lowercase());
+    FileRecord {
+        path: format!("{}{}{}", archive.path, MEMBER_SEPARATOR, name),
+        name: file_name,
+        mime: ext.as_deref().and_then(filetype::mime_from_ext),
+        ext,
+        size,
+        modified,
+        added_at: Utc::now(),
+        kind: EntryKind::ArchiveMember,
+        link_target: None,
+        detected_mime: None,
+        type_mismatch: false,
+        media: None,
+        quick_hash: None,
+        hash_algo: hash.is_some().then_some(opts.hash_algo),
+        hash,
+        uid: None,
+        gid: None,
+        mode: None,
+        attributes: None,
+        image_hash: None,
+        similarity: None,
+        raw_path: None,
+        rating: 0,
+        hash_skipped: None,
+        scheme: None,
+        volume: None,
+        etag: None,
+        allocated: None,
+    }
+}
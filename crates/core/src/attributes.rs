@@ -0,0 +1,18 @@
+use std::fs::Metadata;
+
+// Windows file attribute bits, as stored in `FileRecord::attributes`.
+pub const READONLY: u32 = 0x1;
+pub const HIDDEN: u32 = 0x2;
+pub const SYSTEM: u32 = 0x4;
+pub const REPARSE_POINT: u32 = 0x400;
+
+#[cfg(windows)]
+pub(crate) fn of(metadata: &Metadata) -> Option<u32> {
+    use std::os::windows::fs::MetadataExt;
+    Some(metadata.file_attributes())
+}
+
+#[cfg(not(windows))]
+pub(crate) fn of(_metadata: &Metadata) -> Option<u32> {
+    None
+}
@@ -0,0 +1,62 @@
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
+
+use crate::indexer::{FileRecord, SearchQuery};
+
+const DEFAULT_CAPACITY: usize = 64;
+
+pub(crate) struct QueryCache {
+    entries: LruCache<SearchQuery, Vec<FileRecord>>,
+    data_version: Option<i64>,
+    // Bumped whenever entries are dropped, so a search that raced a write
+    // does not store what it read before the write.
+    generation: u64,
+}
+
+impl QueryCache {
+    pub(crate) fn new() -> Self {
+        let capacity = NonZeroUsize::new(DEFAULT_CAPACITY).expect("cache capacity is non-zero");
+        Self {
+            entries: LruCache::new(capacity),
+            data_version: None,
+            generation: 0,
+        }
+    }
+
+    pub(crate) fn get(&mut self, key: &SearchQuery, data_version: i64) -> Option<Vec<FileRecord>> {
+        self.sync(data_version);
+        self.entries.get(key).cloned()
+    }
+
+    pub(crate) fn put(
+        &mut self,
+        key: SearchQuery,
+        rows: Vec<FileRecord>,
+        data_version: i64,
+        generation: u64,
+    ) {
+        self.sync(data_version);
+        if generation == self.generation {
+            self.entries.put(key, rows);
+        }
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(crate) fn invalidate(&mut self) {
+        self.entries.clear();
+        self.generation += 1;
+    }
+
+    // SQLite bumps data_version whenever another connection commits, so
+    // writes made by other processes also drop stale entries.
+    fn sync(&mut self, data_version: i64) {
+        if self.data_version != Some(data_version) {
+            self.invalidate();
+            self.data_version = Some(data_version);
+        }
+    }
+}
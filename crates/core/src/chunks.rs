@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use fastcdc::v2020::StreamCDC;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::hash::HashAlgorithm;
+use crate::indexer::{EntryKind, FileIndexer, FileRecord, IndexOptions};
+use crate::paths;
+use crate::watchdog;
+
+pub const DEFAULT_CHUNK_THRESHOLD: u64 = 1024 * 1024;
+
+// FastCDC bounds; boundaries follow content, so an insertion only disturbs
+// the chunks around it.
+const MIN_CHUNK: u32 = 16 * 1024;
+const AVG_CHUNK: u32 = 64 * 1024;
+const MAX_CHUNK: u32 = 256 * 1024;
+
+pub(crate) struct Chunk {
+    pub(crate) offset: u64,
+    pub(crate) length: u64,
+    pub(crate) hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkOverlap {
+    pub a: String,
+    pub b: String,
+    pub shared_bytes: i64,
+    pub a_size: i64,
+    pub b_size: i64,
+}
+
+pub(crate) fn wants_chunks(record: &FileRecord, opts: &IndexOptions) -> bool {
+    let threshold = match opts.chunk_threshold {
+        0 => DEFAULT_CHUNK_THRESHOLD,
+        n => n,
+    };
+    opts.chunking && record.kind == EntryKind::File && record.size.max(0) as u64 >= threshold
+}
+
+pub(crate) fn compute_chunks(path: &Path, algo: HashAlgorithm) -> Result<Vec<Chunk>> {
+    let file =
+        File::open(path).with_context(|| format!("opening {} for chunking", path.display()))?;
+    let mut chunks = Vec::new();
+    for chunk in StreamCDC::new(BufReader::new(file), MIN_CHUNK, AVG_CHUNK, MAX_CHUNK) {
+        let chunk = chunk.with_context(|| format!("reading {} for chunking", path.display()))?;
+        let mut hasher = algo.hasher();
+        hasher.update(&chunk.data);
+        chunks.push(Chunk {
+            offset: chunk.offset,
+            length: chunk.length as u64,
+            hash: hasher.finish_hex(),
+        });
+        watchdog::tick();
+    }
+    Ok(chunks)
+}
+
+impl FileIndexer {
+    pub(crate) fn write_chunks(&self, path: &str, chunks: &[Chunk]) -> Result<()> {
+        let conn = self.conn();
+        conn.prepare_cached("DELETE FROM chunks WHERE path = ?")?
+            .execute(params![path])?;
+        let mut stmt =
+            conn.prepare_cached("INSERT INTO chunks(path,offset,length,hash) VALUES (?,?,?,?)")?;
+        for chunk in chunks {
+            stmt.execute(params![
+                path,
+                chunk.offset as i64,
+                chunk.length as i64,
+                chunk.hash
+            ])?;
+        }
+        Ok(())
+    }
+
+    // Pairs of chunked files ranked by how many bytes of distinct chunks they
+    // have in common; `path` restricts the report to pairs involving that file.
+    pub fn shared_data(&self, path: Option<&Path>, limit: i64) -> Result<Vec<ChunkOverlap>> {
+        let conn = self.reader()?;
+        let path = path.map(paths::key);
+        let mut stmt = conn.prepare(
+            "WITH c AS (SELECT DISTINCT path, hash, length FROM chunks)
+             SELECT a.path, b.path, SUM(a.length) AS shared, fa.size, fb.size
+             FROM c a
+             JOIN c b ON b.hash = a.hash AND a.path < b.path
+             JOIN files fa ON fa.path = a.path
+             JOIN files fb ON fb.path = b.path
+             WHERE ?1 IS NULL OR a.path = ?1 OR b.path = ?1
+             GROUP BY a.path, b.path
+             ORDER BY shared DESC
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![path, limit], |row| {
+            Ok(ChunkOverlap {
+                a: row.get(0)?,
+                b: row.get(1)?,
+                shared_bytes: row.get(2)?,
+                a_size: row.get(3)?,
+                b_size: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
@@ -0,0 +1,171 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{record_from_row, subtree_bounds, FileIndexer, FileRecord, RECORD_COLUMNS};
+use crate::paths;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub file_count: i64,
+    pub bytes: i64,
+}
+
+impl FileIndexer {
+    pub fn list_collections(&self) -> Result<Vec<CollectionInfo>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT c.name, c.created_at, COUNT(f.path), COALESCE(SUM(f.size), 0)
+             FROM collections c
+             LEFT JOIN collection_items i ON i.collection_id = c.id
+             LEFT JOIN files f ON f.path = i.path
+             GROUP BY c.id
+             ORDER BY c.name",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let created_at: i64 = row.get(1)?;
+            Ok(CollectionInfo {
+                name: row.get(0)?,
+                created_at: DateTime::<Utc>::from_timestamp(created_at, 0).unwrap_or_default(),
+                file_count: row.get(2)?,
+                bytes: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn create_collection(&self, name: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("collection name must not be empty"));
+        }
+        let created = conn.execute(
+            "INSERT OR IGNORE INTO collections(name,created_at) VALUES (?,?)",
+            params![name, Utc::now().timestamp()],
+        )?;
+        if created == 0 {
+            return Err(anyhow!("collection {} already exists", name));
+        }
+        Ok(())
+    }
+
+    // Drops the collection itself; its files stay indexed.
+    pub fn delete_collection(&self, name: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let id = self.collection_id(name)?;
+        let txn = conn.unchecked_transaction()?;
+        txn.execute(
+            "DELETE FROM collection_items WHERE collection_id = ?",
+            params![id],
+        )?;
+        txn.execute("DELETE FROM collections WHERE id = ?", params![id])?;
+        txn.commit()?;
+        self.invalidate_cache();
+        Ok(())
+    }
+
+    // Adds indexed entries to the collection, creating it if needed. A
+    // directory adds everything indexed below it. Returns how many entries
+    // were not already members.
+    pub fn add_to_collection<P: AsRef<Path>>(&self, name: &str, paths: &[P]) -> Result<usize> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("collection name must not be empty"));
+        }
+        let now = Utc::now().timestamp();
+        // Nothing is kept, not even a new collection, if any path fails.
+        let txn = conn.unchecked_transaction()?;
+        txn.execute(
+            "INSERT OR IGNORE INTO collections(name,created_at) VALUES (?,?)",
+            params![name, now],
+        )?;
+        let id: i64 = txn.query_row(
+            "SELECT id FROM collections WHERE name = ?",
+            params![name],
+            |row| row.get(0),
+        )?;
+        let mut added = 0;
+        {
+            let mut insert = txn.prepare(
+                "INSERT OR IGNORE INTO collection_items(collection_id,path,added_at)
+                 SELECT ?1, path, ?2 FROM files
+                 WHERE path = ?3 OR (path >= ?4 AND path < ?5)",
+            )?;
+            for path in paths {
+                let path = paths::key(path.as_ref());
+                let (lower, upper) = subtree_bounds(&path);
+                let matched: bool = txn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM files
+                                   WHERE path = ?1 OR (path >= ?2 AND path < ?3))",
+                    params![path, lower, upper],
+                    |row| row.get(0),
+                )?;
+                if !matched {
+                    return Err(anyhow!("{} is not indexed", path));
+                }
+                added += insert.execute(params![id, now, path, lower, upper])?;
+            }
+        }
+        txn.commit()?;
+        self.invalidate_cache();
+        Ok(added)
+    }
+
+    // Removes entries, or everything below a directory, from the collection.
+    pub fn remove_from_collection<P: AsRef<Path>>(&self, name: &str, paths: &[P]) -> Result<usize> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let id = self.collection_id(name)?;
+        let mut removed = 0;
+        for path in paths {
+            let path = paths::key(path.as_ref());
+            let (lower, upper) = subtree_bounds(&path);
+            removed += conn.execute(
+                "DELETE FROM collection_items
+                 WHERE collection_id = ?1 AND (path = ?2 OR (path >= ?3 AND path < ?4))",
+                params![id, path, lower, upper],
+            )?;
+        }
+        self.invalidate_cache();
+        Ok(removed)
+    }
+
+    pub fn collection_members(&self, name: &str) -> Result<Vec<FileRecord>> {
+        let conn = self.reader()?;
+        let id = self.collection_id(name)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files
+             WHERE path IN (SELECT path FROM collection_items WHERE collection_id = ?)
+             ORDER BY path",
+            RECORD_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![id], record_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn find_collection(&self, name: &str) -> Result<Option<i64>> {
+        let conn = self.reader()?;
+        Ok(conn
+            .query_row(
+                "SELECT id FROM collections WHERE name = ?",
+                params![name.trim()],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn collection_id(&self, name: &str) -> Result<i64> {
+        self.find_collection(name)?
+            .ok_or_else(|| anyhow!("no collection named {}", name.trim()))
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{FileRecord, SearchQuery, SortKey};
+
+// Where the previous page ended: the sort values and path of its last
+// row. Pass it back as `SearchQuery::after` to get the rows that follow,
+// which stays stable while files are added or removed, unlike an offset.
+// It prints as an opaque token for command lines and URLs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cursor {
+    order: Vec<(SortKey, bool)>,
+    values: Vec<SortValue>,
+    path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SortValue {
+    Int(i64),
+    Text(String),
+}
+
+impl SearchQuery {
+    // Cursor for the page that follows `last`, the final row of a page
+    // returned for this query.
+    pub fn cursor_after(&self, last: &FileRecord) -> Result<Cursor> {
+        let order = self.sort_order()?;
+        let values = order
+            .iter()
+            .map(|(key, _)| sort_value(*key, last))
+            .collect::<Result<_>>()?;
+        Ok(Cursor {
+            order,
+            values,
+            path: last.path.clone(),
+        })
+    }
+}
+
+impl Cursor {
+    // SQL condition and parameters selecting the rows after the cursor in
+    // `order`, then path.
+    pub(crate) fn condition(&self, order: &[(SortKey, bool)]) -> Result<(String, Vec<Value>)> {
+        self.check(order)?;
+        let desc = order[0].1;
+        let op = |desc: bool| if desc { "<" } else { ">" };
+        let values: Vec<Value> = self
+            .values
+            .iter()
+            .map(|value| match value {
+                SortValue::Int(v) => Value::Integer(*v),
+                SortValue::Text(v) => Value::Text(v.clone()),
+            })
+            .collect();
+        let path = Value::Text(self.path.clone());
+
+        if order.iter().all(|(_, d)| *d == desc) {
+            // A row value comparison can use the sort index.
+            let columns: Vec<&str> = order.iter().map(|(key, _)| key.column()).collect();
+            let marks = vec!["?"; columns.len() + 1].join(", ");
+            let cond = format!("({}, path) {} ({})", columns.join(", "), op(desc), marks);
+            return Ok((cond, values.into_iter().chain([path]).collect()));
+        }
+        // Mixed directions: past the first key, or equal on it and past
+        // the rest.
+        let mut cond = format!("path {} ?", op(desc));
+        let mut params = vec![path];
+        for ((key, desc), value) in order.iter().zip(values).rev() {
+            let column = key.column();
+            cond = format!(
+                "({} {} ? OR ({} = ? AND {}))",
+                column,
+                op(*desc),
+                column,
+                cond
+            );
+            params.splice(0..0, [value.clone(), value]);
+        }
+        Ok((cond, params))
+    }
+
+    // Whether `rec` sorts after the cursor.
+    pub(crate) fn precedes(&self, q: &SearchQuery, rec: &FileRecord) -> Result<bool> {
+        let order = q.sort_order()?;
+        self.check(&order)?;
+        let directed = |ord: std::cmp::Ordering, desc: bool| if desc { ord.reverse() } else { ord };
+        let mut ord = std::cmp::Ordering::Equal;
+        for ((key, desc), value) in order.iter().zip(&self.values) {
+            ord = ord.then(directed(sort_value(*key, rec)?.cmp(value), *desc));
+        }
+        ord = ord.then(directed(rec.path.cmp(&self.path), order[0].1));
+        Ok(ord.is_gt())
+    }
+
+    fn check(&self, order: &[(SortKey, bool)]) -> Result<()> {
+        if self.order != order {
+            return Err(anyhow!("the cursor belongs to a different sort order"));
+        }
+        Ok(())
+    }
+}
+
+fn sort_value(sort: SortKey, rec: &FileRecord) -> Result<SortValue> {
+    Ok(match sort {
+        SortKey::Name => SortValue::Text(rec.name.clone()),
+        SortKey::Size => SortValue::Int(rec.size),
+        SortKey::Modified => SortValue::Int(rec.modified.timestamp()),
+        SortKey::Rating => SortValue::Int(rec.rating.into()),
+        SortKey::Ext => SortValue::Text(rec.ext.clone().unwrap_or_default()),
+        SortKey::Path => SortValue::Text(rec.path.clone()),
+        SortKey::AddedAt => SortValue::Int(rec.added_at.timestamp()),
+        SortKey::Allocated => SortValue::Int(rec.allocated_size()),
+        SortKey::Relevance => return Err(anyhow!("relevance order cannot be paged by cursor")),
+    })
+}
+
+// Hex of the JSON form, which keeps the token free of characters that
+// need quoting in a shell or URL.
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_vec(self).map_err(|_| fmt::Error)?;
+        for byte in json {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| {
+                s.get(i..i + 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| anyhow!("malformed cursor"))?;
+        serde_json::from_slice(&bytes).context("malformed cursor")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{imported_record, ImportFormat};
+
+    fn query(sort: SortKey, desc: bool, then: Option<(SortKey, bool)>) -> SearchQuery {
+        SearchQuery {
+            sort_key: Some(sort),
+            desc,
+            then_sort: then.map(|(key, _)| key),
+            then_desc: then.is_some_and(|(_, desc)| desc),
+            ..SearchQuery::default()
+        }
+    }
+
+    fn record(path: &str, size: i64) -> FileRecord {
+        imported_record(path, size, 1_700_000_000)
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        let q = query(SortKey::Size, true, Some((SortKey::Name, false)));
+        let cursor = q.cursor_after(&record("/data/a b\"c.txt", 42)).unwrap();
+        let token = cursor.to_string();
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(token.parse::<Cursor>().unwrap(), cursor);
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        for token in ["abc", "zz", "7b7d", "not a cursor", "é0"] {
+            let err = token.parse::<Cursor>().unwrap_err();
+            assert_eq!(err.to_string(), "malformed cursor", "{}", token);
+        }
+    }
+
+    #[test]
+    fn same_direction_uses_a_row_value() {
+        let q = query(SortKey::Size, false, Some((SortKey::Name, false)));
+        let cursor = q.cursor_after(&record("/d/b.txt", 7)).unwrap();
+        let (cond, params) = cursor.condition(&q.sort_order().unwrap()).unwrap();
+        assert_eq!(cond, "(size, name, path) > (?, ?, ?)");
+        assert_eq!(
+            params,
+            [
+                Value::Integer(7),
+                Value::Text("b.txt".into()),
+                Value::Text("/d/b.txt".into())
+            ]
+        );
+
+        let q = query(SortKey::Modified, true, None);
+        let cursor = q.cursor_after(&record("/d/b.txt", 7)).unwrap();
+        let (cond, params) = cursor.condition(&q.sort_order().unwrap()).unwrap();
+        assert_eq!(cond, "(modified, path) < (?, ?)");
+        assert_eq!(params[0], Value::Integer(1_700_000_000));
+    }
+
+    #[test]
+    fn mixed_directions_nest_comparisons() {
+        let q = query(SortKey::Size, true, Some((SortKey::Name, false)));
+        let cursor = q.cursor_after(&record("/d/b.txt", 7)).unwrap();
+        let (cond, params) = cursor.condition(&q.sort_order().unwrap()).unwrap();
+        assert_eq!(
+            cond,
+            "(size < ? OR (size = ? AND (name > ? OR (name = ? AND path < ?))))"
+        );
+        assert_eq!(
+            params,
+            [
+                Value::Integer(7),
+                Value::Integer(7),
+                Value::Text("b.txt".into()),
+                Value::Text("b.txt".into()),
+                Value::Text("/d/b.txt".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_another_sort_order() {
+        let cursor = query(SortKey::Size, false, None)
+            .cursor_after(&record("/d/b.txt", 7))
+            .unwrap();
+        let other = query(SortKey::Size, true, None).sort_order().unwrap();
+        assert!(cursor.condition(&other).is_err());
+        assert!(query(SortKey::Relevance, false, None)
+            .cursor_after(&record("/d/b.txt", 7))
+            .is_err());
+    }
+
+    #[test]
+    fn precedes_follows_the_sort_order() {
+        let q = query(SortKey::Size, true, None);
+        let cursor = q.cursor_after(&record("/d/m.txt", 10)).unwrap();
+        assert!(cursor.precedes(&q, &record("/d/a.txt", 5)).unwrap());
+        assert!(!cursor.precedes(&q, &record("/d/a.txt", 20)).unwrap());
+        // Ties on size fall back to the path, in the same direction.
+        assert!(cursor.precedes(&q, &record("/d/a.txt", 10)).unwrap());
+        assert!(!cursor.precedes(&q, &record("/d/z.txt", 10)).unwrap());
+        assert!(!cursor.precedes(&q, &record("/d/m.txt", 10)).unwrap());
+    }
+
+    #[test]
+    fn pages_visit_every_row_once() {
+        let db = tempfile::tempdir().unwrap();
+        let indexer = crate::indexer::FileIndexer::new(db.path().join("index.db")).unwrap();
+        let list: String = (0..25)
+            .map(|i| format!("{}\t1700000000\t/d/f{:02}.txt\n", i % 4, i))
+            .collect();
+        indexer
+            .import_list(ImportFormat::FindPrintf, list.as_bytes())
+            .unwrap();
+
+        for (desc, then) in [(false, None), (true, Some((SortKey::Name, false)))] {
+            let mut q = query(SortKey::Size, desc, then);
+            q.limit = Some(4);
+            let mut seen = Vec::new();
+            loop {
+                let page = indexer.search(&q).unwrap();
+                let Some(last) = page.last() else { break };
+                q.after = Some(q.cursor_after(last).unwrap());
+                seen.extend(page.into_iter().map(|r| r.path));
+            }
+            let mut all = seen.clone();
+            all.sort();
+            all.dedup();
+            assert_eq!((seen.len(), all.len()), (25, 25));
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::hash::{self, HashAlgorithm, HashIo};
+use crate::indexer::{build_symlink_record, FileIndexer};
+use crate::paths;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeepStrategy {
+    Newest,
+    Oldest,
+    ShortestPath,
+    // Keep the copy under this directory; groups without one are left alone.
+    Under(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DedupeAction {
+    Delete,
+    // Replace redundant copies with hard links to the kept file.
+    Hardlink,
+    // Replace redundant copies with symbolic links to the kept file.
+    Symlink,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeOptions {
+    pub keep: KeepStrategy,
+    pub action: DedupeAction,
+    // Plan only; nothing on disk or in the index changes.
+    pub dry_run: bool,
+    // Deleted copies go to the trash instead of being removed outright.
+    pub trash: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DedupeReport {
+    pub dry_run: bool,
+    pub steps: Vec<DedupeStep>,
+    pub applied: usize,
+    pub failed: usize,
+    // Planned savings for a dry run.
+    pub bytes_reclaimed: i64,
+    // Groups where the keep strategy matched no copy.
+    pub skipped_groups: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupeStep {
+    pub kept: String,
+    pub path: String,
+    pub size: i64,
+    pub error: Option<String>,
+}
+
+struct Duplicate {
+    path: String,
+    raw_path: Option<Vec<u8>>,
+    hash: String,
+    size: i64,
+    modified: i64,
+    algo: Option<HashAlgorithm>,
+}
+
+impl FileIndexer {
+    // Acts on every group of fully hashed duplicates. Before touching a file
+    // both it and the kept copy are re-hashed, so a stale index can never
+    // cause data loss.
+    pub fn dedupe(&self, opts: &DedupeOptions) -> Result<DedupeReport> {
+        if !opts.dry_run {
+            self.ensure_writable()?;
+        }
+        let mut report = DedupeReport {
+            dry_run: opts.dry_run,
+            ..DedupeReport::default()
+        };
+        let strategy = match &opts.keep {
+            KeepStrategy::Under(dir) => KeepStrategy::Under(resolved(dir)),
+            other => other.clone(),
+        };
+        for group in self.hashed_duplicates()? {
+            let Some(keep) = pick_keep(&group, &strategy) else {
+                report.skipped_groups += 1;
+                continue;
+            };
+            let keep_check = if opts.dry_run {
+                Ok(())
+            } else {
+                unchanged(keep)
+            };
+            for copy in &group {
+                if copy.path == keep.path {
+                    continue;
+                }
+                // Existing hard links already share their data; only deleting
+                // them changes anything. Two paths reaching one file through
+                // a symlink are no copies at all, and deleting either would
+                // lose the data.
+                let linked = same_file(&keep.fs_path(), &copy.fs_path());
+                if linked
+                    && (opts.action != DedupeAction::Delete
+                        || !separate_links(&keep.fs_path(), &copy.fs_path()))
+                {
+                    continue;
+                }
+                let mut step = DedupeStep {
+                    kept: keep.path.clone(),
+                    path: copy.path.clone(),
+                    size: copy.size,
+                    error: None,
+                };
+                if !opts.dry_run {
+                    let result = match &keep_check {
+                        Ok(()) => self.apply(keep, copy, opts),
+                        Err(err) => Err(anyhow!("kept copy: {:#}", err)),
+                    };
+                    match result {
+                        Ok(()) => report.applied += 1,
+                        Err(err) => {
+                            step.error = Some(format!("{:#}", err));
+                            report.failed += 1;
+                        }
+                    }
+                }
+                if step.error.is_none() && !linked {
+                    report.bytes_reclaimed += copy.size;
+                }
+                report.steps.push(step);
+            }
+        }
+
+        if report.applied > 0 && opts.action != DedupeAction::Hardlink {
+            for root in self.list_roots()? {
+                self.refresh_dirs(Path::new(&root.path))?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn apply(&self, keep: &Duplicate, copy: &Duplicate, opts: &DedupeOptions) -> Result<()> {
+        // Re-hashing can take a while, so other writers are not held up.
+        unchanged(copy)?;
+        let fs_path = copy.fs_path();
+        let path = fs_path.as_path();
+        match opts.action {
+            DedupeAction::Delete if opts.trash => self.trash_file(path)?,
+            DedupeAction::Delete => {
+                fs::remove_file(path).with_context(|| format!("deleting {}", copy.path))?;
+                self.remove_deleted(path)?;
+            }
+            DedupeAction::Hardlink => {
+                replace_with_link(path, &keep.fs_path(), opts.action)?;
+                // The link shares the kept file's inode and metadata.
+                self.conn().execute(
+                    "UPDATE files SET (modified,uid,gid,mode,attributes,allocated) =
+                         (SELECT modified,uid,gid,mode,attributes,allocated
+                          FROM files WHERE path = ?1)
+                     WHERE path = ?2",
+                    params![keep.path, copy.path],
+                )?;
+                self.invalidate_cache();
+            }
+            DedupeAction::Symlink => {
+                replace_with_link(path, &keep.fs_path(), opts.action)?;
+                self.remove(path)?;
+                self.upsert(&build_symlink_record(path)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Regular files sharing a full hash and size, grouped and ordered by path.
+    fn hashed_duplicates(&self) -> Result<Vec<Vec<Duplicate>>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT path,raw_path,hash,size,modified,hash_algo FROM files
+             WHERE kind = 'file' AND hash IS NOT NULL AND (hash, size) IN (
+                 SELECT hash, size FROM files WHERE kind = 'file' AND hash IS NOT NULL
+                 GROUP BY hash, size HAVING COUNT(*) > 1
+             )
+             ORDER BY hash, size, path",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Duplicate {
+                path: row.get(0)?,
+                raw_path: row.get(1)?,
+                hash: row.get(2)?,
+                size: row.get(3)?,
+                modified: row.get(4)?,
+                algo: row.get(5)?,
+            })
+        })?;
+        let mut groups: Vec<Vec<Duplicate>> = Vec::new();
+        for copy in rows {
+            let copy = copy?;
+            match groups.last_mut() {
+                Some(group) if group[0].hash == copy.hash && group[0].size == copy.size => {
+                    group.push(copy)
+                }
+                _ => groups.push(vec![copy]),
+            }
+        }
+        Ok(groups)
+    }
+}
+
+impl Duplicate {
+    fn fs_path(&self) -> PathBuf {
+        paths::decode(&self.path, self.raw_path.as_deref())
+    }
+}
+
+// Ties go to the first path in sort order.
+fn pick_keep<'a>(group: &'a [Duplicate], keep: &KeepStrategy) -> Option<&'a Duplicate> {
+    match keep {
+        KeepStrategy::Newest => group.iter().rev().max_by_key(|c| c.modified),
+        KeepStrategy::Oldest => group.iter().min_by_key(|c| c.modified),
+        KeepStrategy::ShortestPath => group.iter().min_by_key(|c| c.path.len()),
+        KeepStrategy::Under(dir) => group
+            .iter()
+            .find(|c| resolved(&c.fs_path()).starts_with(dir)),
+    }
+}
+
+// The path with symlinks resolved, so relative or aliased paths compare
+// like the file they lead to.
+fn resolved(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn unchanged(copy: &Duplicate) -> Result<()> {
+    let actual = hash::hash_file(
+        &copy.fs_path(),
+        copy.algo.unwrap_or_default(),
+        HashIo::default(),
+    )?;
+    if actual != copy.hash {
+        return Err(anyhow!("{} changed since it was indexed", copy.path));
+    }
+    Ok(())
+}
+
+// Builds the link next to `path` and renames it into place, so `path` is
+// never missing if linking fails.
+fn replace_with_link(path: &Path, target: &Path, action: DedupeAction) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.fsindex-dedupe", name.to_string_lossy()));
+    let linked = match action {
+        DedupeAction::Hardlink => fs::hard_link(target, &tmp),
+        _ => symlink(target, &tmp),
+    };
+    linked.with_context(|| format!("linking {} to {}", tmp.display(), target.display()))?;
+    if let Err(err) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err).with_context(|| format!("replacing {}", path.display()));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (file_id(a), file_id(b)) {
+        (Some(a), Some(b)) => a.volume == b.volume && a.index == b.index,
+        // Without file numbers, at least catch paths leading to one file.
+        _ => matches!(
+            (fs::canonicalize(a), fs::canonicalize(b)),
+            (Ok(a), Ok(b)) if a == b
+        ),
+    }
+}
+
+// Whether `copy` is a hard link of its own beside `keep`, rather than the
+// same directory entry reached another way.
+fn separate_links(keep: &Path, copy: &Path) -> bool {
+    file_id(copy).is_some_and(|id| id.links > 1)
+        && match (fs::canonicalize(keep), fs::canonicalize(copy)) {
+            (Ok(keep), Ok(copy)) => keep != copy,
+            _ => false,
+        }
+}
+
+// Identifies the file `path` leads to, whatever name reaches it.
+struct FileId {
+    volume: u64,
+    index: u64,
+    links: u64,
+}
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = fs::metadata(path).ok()?;
+    Some(FileId {
+        volume: meta.dev(),
+        index: meta.ino(),
+        links: meta.nlink(),
+    })
+}
+
+#[cfg(windows)]
+fn file_id(path: &Path) -> Option<FileId> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+    let file = fs::File::open(path).ok()?;
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+        return None;
+    }
+    Some(FileId {
+        volume: info.dwVolumeSerialNumber.into(),
+        index: (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow),
+        links: info.nNumberOfLinks.into(),
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn file_id(_path: &Path) -> Option<FileId> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::indexer::IndexOptions;
+
+    // Writes `files` under a fresh directory, with modification times one
+    // minute apart in the order given.
+    fn tree(files: &[&str]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let base = SystemTime::now() - Duration::from_secs(3600);
+        for (i, name) in files.iter().enumerate() {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "same content").unwrap();
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(base + Duration::from_secs(60 * i as u64))
+                .unwrap();
+        }
+        dir
+    }
+
+    fn indexed(roots: &[&Path]) -> (TempDir, FileIndexer) {
+        let db = tempfile::tempdir().unwrap();
+        let indexer = FileIndexer::new(db.path().join("index.db")).unwrap();
+        let opts = IndexOptions {
+            hash: true,
+            ..IndexOptions::default()
+        };
+        for root in roots {
+            indexer.index_dir(root, &opts).unwrap();
+        }
+        (db, indexer)
+    }
+
+    fn options(keep: KeepStrategy, action: DedupeAction) -> DedupeOptions {
+        DedupeOptions {
+            keep,
+            action,
+            dry_run: false,
+            trash: false,
+        }
+    }
+
+    fn kept(report: &DedupeReport) -> Vec<String> {
+        let mut kept: Vec<_> = report.steps.iter().map(|s| s.kept.clone()).collect();
+        kept.dedup();
+        kept
+    }
+
+    fn key(path: &Path) -> String {
+        paths::key(path)
+    }
+
+    #[test]
+    fn keeps_newest() {
+        let dir = tree(&["a/one.txt", "b/two.txt", "c/three.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let report = indexer
+            .dedupe(&options(KeepStrategy::Newest, DedupeAction::Delete))
+            .unwrap();
+        assert_eq!(kept(&report), [key(&dir.path().join("c/three.txt"))]);
+        assert_eq!(report.applied, 2);
+        assert!(dir.path().join("c/three.txt").exists());
+        assert!(!dir.path().join("a/one.txt").exists());
+        assert!(!dir.path().join("b/two.txt").exists());
+    }
+
+    #[test]
+    fn keeps_oldest() {
+        let dir = tree(&["a/one.txt", "b/two.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let report = indexer
+            .dedupe(&options(KeepStrategy::Oldest, DedupeAction::Delete))
+            .unwrap();
+        assert_eq!(kept(&report), [key(&dir.path().join("a/one.txt"))]);
+        assert!(!dir.path().join("b/two.txt").exists());
+    }
+
+    #[test]
+    fn keeps_shortest_path() {
+        let dir = tree(&["deep/er/one.txt", "x.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let report = indexer
+            .dedupe(&options(KeepStrategy::ShortestPath, DedupeAction::Delete))
+            .unwrap();
+        assert_eq!(kept(&report), [key(&dir.path().join("x.txt"))]);
+        assert!(!dir.path().join("deep/er/one.txt").exists());
+    }
+
+    #[test]
+    fn keeps_copy_under_directory() {
+        let dir = tree(&["archive/one.txt", "inbox/two.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let keep = KeepStrategy::Under(dir.path().join("inbox"));
+        let report = indexer
+            .dedupe(&options(keep, DedupeAction::Delete))
+            .unwrap();
+        assert_eq!(kept(&report), [key(&dir.path().join("inbox/two.txt"))]);
+        assert!(!dir.path().join("archive/one.txt").exists());
+
+        let keep = KeepStrategy::Under(dir.path().join("elsewhere"));
+        let dir = tree(&["a.txt", "b.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let report = indexer
+            .dedupe(&options(keep, DedupeAction::Delete))
+            .unwrap();
+        assert_eq!(report.skipped_groups, 1);
+        assert!(report.steps.is_empty());
+    }
+
+    #[test]
+    fn dry_run_changes_nothing() {
+        let dir = tree(&["a.txt", "bb.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let mut opts = options(KeepStrategy::ShortestPath, DedupeAction::Delete);
+        opts.dry_run = true;
+        let report = indexer.dedupe(&opts).unwrap();
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(report.applied, 0);
+        assert_eq!(report.bytes_reclaimed, 12);
+        assert!(dir.path().join("bb.txt").exists());
+        assert_eq!(indexer.dedupe(&opts).unwrap().steps.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlinks_copies() {
+        use std::os::unix::fs::MetadataExt;
+        let dir = tree(&["a.txt", "bb.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let report = indexer
+            .dedupe(&options(KeepStrategy::ShortestPath, DedupeAction::Hardlink))
+            .unwrap();
+        assert_eq!(report.applied, 1);
+        let a = fs::metadata(dir.path().join("a.txt")).unwrap();
+        let b = fs::metadata(dir.path().join("bb.txt")).unwrap();
+        assert_eq!((a.ino(), a.nlink()), (b.ino(), 2));
+
+        // Already linked: nothing left to do.
+        let report = indexer
+            .dedupe(&options(KeepStrategy::ShortestPath, DedupeAction::Hardlink))
+            .unwrap();
+        assert!(report.steps.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_copies() {
+        let dir = tree(&["a.txt", "bb.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let report = indexer
+            .dedupe(&options(KeepStrategy::ShortestPath, DedupeAction::Symlink))
+            .unwrap();
+        assert_eq!(report.applied, 1);
+        let link = dir.path().join("bb.txt");
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), dir.path().join("a.txt"));
+        assert_eq!(fs::read_to_string(&link).unwrap(), "same content");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn deletes_extra_hard_links() {
+        let dir = tree(&["a.txt"]);
+        fs::hard_link(dir.path().join("a.txt"), dir.path().join("bb.txt")).unwrap();
+        let (_db, indexer) = indexed(&[dir.path()]);
+        let report = indexer
+            .dedupe(&options(KeepStrategy::ShortestPath, DedupeAction::Delete))
+            .unwrap();
+        assert_eq!(report.applied, 1);
+        // Removing a second link frees nothing.
+        assert_eq!(report.bytes_reclaimed, 0);
+        assert!(!dir.path().join("bb.txt").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "same content"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tells_hard_links_from_aliases() {
+        let dir = tree(&["a.txt", "b.txt"]);
+        let a = dir.path().join("a.txt");
+        let alias = dir.path().join("alias");
+        let link = dir.path().join("link.txt");
+        std::os::unix::fs::symlink(dir.path(), &alias).unwrap();
+        fs::hard_link(&a, &link).unwrap();
+
+        assert!(!same_file(&a, &dir.path().join("b.txt")));
+        assert!(same_file(&a, &alias.join("a.txt")));
+        assert!(!separate_links(&a, &alias.join("a.txt")));
+        assert!(same_file(&a, &link));
+        assert!(separate_links(&a, &link));
+        assert!(!same_file(&a, &dir.path().join("missing")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn never_deletes_a_file_reached_through_a_symlinked_root() {
+        let dir = tree(&["real/f.txt"]);
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(dir.path().join("real"), &link).unwrap();
+        let (_db, indexer) = indexed(&[&dir.path().join("real"), &link]);
+        for action in [
+            DedupeAction::Delete,
+            DedupeAction::Hardlink,
+            DedupeAction::Symlink,
+        ] {
+            let report = indexer
+                .dedupe(&options(KeepStrategy::ShortestPath, action))
+                .unwrap();
+            assert!(report.steps.is_empty(), "{:?}: {:?}", action, report.steps);
+        }
+        let keep = KeepStrategy::Under(link.clone());
+        let report = indexer
+            .dedupe(&options(keep, DedupeAction::Delete))
+            .unwrap();
+        assert!(report.steps.is_empty());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("real/f.txt")).unwrap(),
+            "same content"
+        );
+    }
+
+    #[test]
+    fn leaves_copies_changed_since_indexing() {
+        let dir = tree(&["a.txt", "bb.txt"]);
+        let (_db, indexer) = indexed(&[dir.path()]);
+        fs::write(dir.path().join("bb.txt"), "same contenT").unwrap();
+        let report = indexer
+            .dedupe(&options(KeepStrategy::ShortestPath, DedupeAction::Delete))
+            .unwrap();
+        assert_eq!(report.failed, 1);
+        assert!(dir.path().join("bb.txt").exists());
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{subtree_bounds, FileIndexer};
+use crate::paths;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedFile {
+    pub path: String,
+    pub name: String,
+    pub ext: Option<String>,
+    pub size: i64,
+    pub modified: DateTime<Utc>,
+    pub added_at: DateTime<Utc>,
+    pub hash: Option<String>,
+    // When the scan or watcher noticed the file was gone.
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl FileIndexer {
+    pub fn recently_deleted(&self, limit: i64) -> Result<Vec<DeletedFile>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT path,name,ext,size,modified,added_at,hash,deleted_at FROM deleted_files
+             ORDER BY deleted_at DESC, rowid DESC LIMIT ?",
+        )?;
+        let timestamp = |ts: i64| DateTime::<Utc>::from_timestamp(ts, 0).unwrap_or_default();
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(DeletedFile {
+                path: row.get(0)?,
+                name: row.get(1)?,
+                ext: row.get(2)?,
+                size: row.get(3)?,
+                modified: timestamp(row.get(4)?),
+                added_at: timestamp(row.get(5)?),
+                hash: row.get(6)?,
+                deleted_at: timestamp(row.get(7)?),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // Tombstones `path` and everything indexed below it, then drops the rows.
+    pub(crate) fn remove_deleted(&self, path: &Path) -> Result<usize> {
+        let path = paths::key(path);
+        if !self.may_contain_path(&path)? {
+            return Ok(0);
+        }
+        let conn = self.conn();
+        let (lower, upper) = subtree_bounds(&path);
+        conn.execute(
+            "INSERT INTO deleted_files(path,name,ext,size,modified,added_at,hash,deleted_at)
+             SELECT path,name,ext,size,modified,added_at,hash,?
+             FROM files WHERE path = ? OR (path >= ? AND path < ?)",
+            params![Utc::now().timestamp(), path, lower, upper],
+        )?;
+        self.remove(&path)
+    }
+}
+
+pub(crate) fn tombstone(conn: &Connection, path: &str, deleted_at: i64) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO deleted_files(path,name,ext,size,modified,added_at,hash,deleted_at)
+         SELECT path,name,ext,size,modified,added_at,hash,? FROM files WHERE path = ?",
+    )?
+    .execute(params![deleted_at, path])?;
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{subtree_bounds, FileIndexer};
+use crate::paths;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirRecord {
+    pub path: String,
+    // Recursive totals over regular files below the directory.
+    pub size: i64,
+    // Bytes on disk; see `FileRecord::allocated_size`.
+    #[serde(default)]
+    pub allocated: i64,
+    pub file_count: i64,
+    // Newest modification time of any file below the directory; `None`
+    // when there are none.
+    pub modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct DirTotals {
+    size: i64,
+    allocated: i64,
+    file_count: i64,
+    modified: i64,
+}
+
+impl FileIndexer {
+    pub fn largest_dirs(&self, limit: i64) -> Result<Vec<DirRecord>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT path,size,allocated,file_count,modified FROM dirs
+             ORDER BY size DESC, path LIMIT ?",
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            let file_count: i64 = row.get(3)?;
+            let modified: i64 = row.get(4)?;
+            Ok(DirRecord {
+                path: row.get(0)?,
+                size: row.get(1)?,
+                allocated: row.get(2)?,
+                file_count,
+                modified: DateTime::<Utc>::from_timestamp(modified, 0).filter(|_| file_count > 0),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // Recomputes the `dirs` rows for `root` and everything below it from the
+    // file rows. Archive members and links do not take up space of their own.
+    // Directories recorded by a scan keep a row even without files.
+    pub(crate) fn refresh_dirs(&self, root: &Path) -> Result<()> {
+        let conn = self.conn();
+        let root_str = paths::key(root);
+        let root_str = root_str.trim_end_matches(['/', '\\']);
+        let (lower, upper) = subtree_bounds(root_str);
+
+        let mut totals: HashMap<String, DirTotals> = self
+            .dir_paths(root_str)?
+            .into_iter()
+            .map(|path| (path, DirTotals::default()))
+            .collect();
+        {
+            let mut stmt = conn.prepare(
+                "SELECT path,size,coalesce(allocated,size),modified FROM files
+                 WHERE kind = 'file' AND path >= ? AND path < ?",
+            )?;
+            let rows = stmt.query_map(params![lower, upper], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?;
+            for row in rows {
+                let (path, size, allocated, modified) = row?;
+                for dir in Path::new(&path).ancestors().skip(1) {
+                    if !dir.starts_with(root) {
+                        break;
+                    }
+                    let entry = totals.entry(paths::key(dir)).or_default();
+                    entry.size += size;
+                    entry.allocated += allocated;
+                    entry.file_count += 1;
+                    entry.modified = entry.modified.max(modified);
+                }
+            }
+        }
+
+        let txn = conn.unchecked_transaction()?;
+        txn.execute(
+            "DELETE FROM dirs WHERE path = ? OR (path >= ? AND path < ?)",
+            params![root_str, lower, upper],
+        )?;
+        {
+            let mut stmt = txn.prepare(
+                "INSERT OR REPLACE INTO dirs(path,size,allocated,file_count,modified)
+                 VALUES (?,?,?,?,?)",
+            )?;
+            for (path, t) in totals {
+                stmt.execute(params![path, t.size, t.allocated, t.file_count, t.modified])?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    // Makes the directory rows under `root` match the directories a scan
+    // walked; rows of directories that still exist keep their totals.
+    pub(crate) fn record_dirs(&self, root: &Path, seen: &[String]) -> Result<()> {
+        let conn = self.conn();
+        let root_str = paths::key(root);
+        let root_str = root_str.trim_end_matches(['/', '\\']);
+        let seen: HashSet<&str> = seen
+            .iter()
+            .map(|dir| dir.trim_end_matches(['/', '\\']))
+            .collect();
+        let txn = conn.unchecked_transaction()?;
+        for path in self.dir_paths(root_str)? {
+            if !seen.contains(path.as_str()) {
+                txn.execute("DELETE FROM dirs WHERE path = ?", params![path])?;
+            }
+        }
+        {
+            let mut stmt = txn.prepare(
+                "INSERT OR IGNORE INTO dirs(path,size,file_count,modified) VALUES (?,0,0,0)",
+            )?;
+            for path in seen {
+                stmt.execute(params![path])?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    pub(crate) fn forget_dirs(&self, root: &Path) -> Result<()> {
+        let conn = self.conn();
+        let root_str = paths::key(root);
+        let root_str = root_str.trim_end_matches(['/', '\\']);
+        let (lower, upper) = subtree_bounds(root_str);
+        conn.execute(
+            "DELETE FROM dirs WHERE path = ? OR (path >= ? AND path < ?)",
+            params![root_str, lower, upper],
+        )?;
+        Ok(())
+    }
+
+    fn dir_paths(&self, root: &str) -> Result<Vec<String>> {
+        let conn = self.reader()?;
+        let (lower, upper) = subtree_bounds(root);
+        let mut stmt =
+            conn.prepare("SELECT path FROM dirs WHERE path = ? OR (path >= ? AND path < ?)")?;
+        let rows = stmt.query_map(params![root, lower, upper], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{record_from_row, subtree_bounds, FileIndexer, FileRecord, RECORD_COLUMNS};
+use crate::trash;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CleanReport {
+    pub dry_run: bool,
+    // Removed, or planned for removal in a dry run.
+    pub files: Vec<String>,
+    pub dirs: Vec<String>,
+    // Files indexed as empty that no longer are, or are no longer regular
+    // files; they and the directories holding them are left alone.
+    pub changed: Vec<String>,
+    // Paths that could not be removed, with the reason.
+    pub failed: Vec<(String, String)>,
+}
+
+impl FileIndexer {
+    pub fn empty_files(&self) -> Result<Vec<FileRecord>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files WHERE kind = 'file' AND size = 0 AND scheme IS NULL
+             ORDER BY path",
+            RECORD_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], record_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // Directories with nothing indexed below them, including ones that only
+    // hold other empty directories. Indexed roots are never reported.
+    // Hidden or excluded files are not in the index, so a directory listed
+    // here may still hold some on disk.
+    pub fn empty_dirs(&self) -> Result<Vec<String>> {
+        self.dirs_holding_only(false)
+    }
+
+    // Deletes empty files, then directories left with nothing in them,
+    // deepest first. Each file is checked on disk first, since it may have
+    // been written to since it was indexed. Directories are removed with
+    // `remove_dir`, which fails rather than deleting anything the index did
+    // not know about.
+    pub fn clean_empty(&self, dry_run: bool, use_trash: bool) -> Result<CleanReport> {
+        if !dry_run {
+            self.ensure_writable()?;
+        }
+        let mut report = CleanReport {
+            dry_run,
+            ..CleanReport::default()
+        };
+        let mut files = self.empty_files()?;
+        let mut dirs = self.dirs_holding_only(true)?;
+        files.retain(|file| {
+            let still_empty = fs::symlink_metadata(file.fs_path())
+                .is_ok_and(|meta| meta.is_file() && meta.len() == 0);
+            if !still_empty {
+                report.changed.push(file.path.clone());
+            }
+            still_empty
+        });
+        dirs.retain(|dir| {
+            let (lower, upper) = subtree_bounds(dir);
+            !report
+                .changed
+                .iter()
+                .any(|path| *path >= lower && *path < upper)
+        });
+        // Deepest first, so parents are already empty when their turn comes.
+        dirs.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+
+        if dry_run {
+            report.files = files.into_iter().map(|f| f.path).collect();
+            report.dirs = dirs;
+            return Ok(report);
+        }
+
+        for file in files {
+            let fs_path = file.fs_path();
+            let result = if use_trash {
+                self.trash_file(&fs_path)
+            } else {
+                fs::remove_file(&fs_path)
+                    .with_context(|| format!("deleting {}", file.path))
+                    .and_then(|()| self.remove_deleted(&fs_path).map(|_| ()))
+            };
+            match result {
+                Ok(()) => report.files.push(file.path),
+                Err(err) => report.failed.push((file.path, format!("{:#}", err))),
+            }
+        }
+        for path in dirs {
+            let result = if use_trash {
+                // Trashing moves the whole tree, so check it really is empty.
+                match fs::read_dir(&path).map(|mut entries| entries.next().is_none()) {
+                    Ok(true) => trash::move_to_trash(Path::new(&path)),
+                    Ok(false) => Err(anyhow!("{} is not empty", path)),
+                    Err(err) => Err(err).with_context(|| format!("reading {}", path)),
+                }
+            } else {
+                fs::remove_dir(&path).with_context(|| format!("removing {}", path))
+            };
+            match result {
+                Ok(()) => {
+                    self.conn()
+                        .execute("DELETE FROM dirs WHERE path = ?", params![path])?;
+                    report.dirs.push(path);
+                }
+                Err(err) => report.failed.push((path, format!("{:#}", err))),
+            }
+        }
+
+        if !report.files.is_empty() || !report.dirs.is_empty() {
+            for root in self.list_roots()? {
+                self.refresh_dirs(Path::new(&root.path))?;
+            }
+        }
+        Ok(report)
+    }
+
+    // Directories whose subtree holds no indexed entries, or with
+    // `empty_files` only zero-byte regular files.
+    fn dirs_holding_only(&self, empty_files: bool) -> Result<Vec<String>> {
+        let conn = self.reader()?;
+        let roots: Vec<String> = self.list_roots()?.into_iter().map(|r| r.path).collect();
+        let candidates: Vec<String> = {
+            let mut stmt =
+                conn.prepare("SELECT path FROM dirs WHERE file_count = 0 OR ?1 ORDER BY path")?;
+            let rows = stmt.query_map(params![empty_files], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        let mut stmt = conn.prepare(
+            "SELECT EXISTS(SELECT 1 FROM files WHERE path >= ?1 AND path < ?2
+                           AND NOT (?3 AND kind = 'file' AND size = 0))",
+        )?;
+        let mut out = Vec::new();
+        for path in candidates {
+            if roots.contains(&path) {
+                continue;
+            }
+            let (lower, upper) = subtree_bounds(&path);
+            let occupied: bool =
+                stmt.query_row(params![lower, upper, empty_files], |row| row.get(0))?;
+            if !occupied {
+                out.push(path);
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::indexer::IndexOptions;
+    use crate::paths;
+
+    fn indexed(files: &[(&str, &str)]) -> (TempDir, TempDir, FileIndexer) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let db = tempfile::tempdir().unwrap();
+        let indexer = FileIndexer::new(db.path().join("index.db")).unwrap();
+        indexer
+            .index_dir(dir.path(), &IndexOptions::default())
+            .unwrap();
+        (dir, db, indexer)
+    }
+
+    #[test]
+    fn dry_run_lists_without_removing() {
+        let (dir, _db, indexer) = indexed(&[("keep.txt", "data"), ("logs/empty.log", "")]);
+        let report = indexer.clean_empty(true, false).unwrap();
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.dirs.len(), 1);
+        assert!(dir.path().join("logs/empty.log").exists());
+    }
+
+    #[test]
+    fn removes_empty_files_and_their_directories() {
+        let (dir, _db, indexer) = indexed(&[("keep.txt", "data"), ("logs/a/empty.log", "")]);
+        let report = indexer.clean_empty(false, false).unwrap();
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.dirs.len(), 2);
+        assert!(!dir.path().join("logs").exists());
+        assert!(dir.path().join("keep.txt").exists());
+        assert!(indexer.empty_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn leaves_files_written_since_indexing() {
+        let (dir, _db, indexer) = indexed(&[("logs/app.log", ""), ("other/empty", "")]);
+        let log = dir.path().join("logs/app.log");
+        fs::write(&log, "a line\n").unwrap();
+        let report = indexer.clean_empty(false, false).unwrap();
+        assert_eq!(report.changed, [paths::key(&log)]);
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert_eq!(fs::read_to_string(&log).unwrap(), "a line\n");
+        assert!(!dir.path().join("other").exists());
+        assert!(!report.dirs.iter().any(|d| d.ends_with("logs")));
+    }
+}
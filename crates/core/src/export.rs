@@ -0,0 +1,241 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{FileIndexer, FileRecord, SearchQuery};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    // One JSON object per line with every field of `FileRecord`.
+    JsonLines,
+    // Flat columns, see `COLUMNS`.
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+// Columns of the flat CSV and Parquet exports.
+pub(crate) const COLUMNS: [&str; 16] = [
+    "path", "name", "ext", "kind", "size", "modified", "added_at", "hash", "mime", "rating",
+    "duration", "width", "height", "artist", "album", "title",
+];
+
+trait RecordSink {
+    fn write(&mut self, rec: &FileRecord) -> Result<()>;
+    fn finish(self) -> Result<()>;
+}
+
+impl FileIndexer {
+    // Writes every entry matching `query`, honouring its order, limit and
+    // offset, and returns how many were written.
+    pub fn export<W: Write + Send>(
+        &self,
+        format: ExportFormat,
+        writer: W,
+        query: &SearchQuery,
+    ) -> Result<usize> {
+        match format {
+            ExportFormat::JsonLines => self.export_to(query, JsonLines(writer)),
+            ExportFormat::Csv => self.export_to(query, Csv::new(writer)?),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => self.export_to(query, columnar::ParquetSink::new(writer)?),
+        }
+    }
+
+    fn export_to<S: RecordSink>(&self, query: &SearchQuery, mut sink: S) -> Result<usize> {
+        let written = self.for_each_row(query, |rec| sink.write(&rec))?;
+        sink.finish()?;
+        Ok(written)
+    }
+}
+
+struct JsonLines<W>(W);
+
+impl<W: Write> RecordSink for JsonLines<W> {
+    fn write(&mut self, rec: &FileRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.0, rec)?;
+        self.0.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.0.flush()?;
+        Ok(())
+    }
+}
+
+struct Csv<W: Write>(csv::Writer<W>);
+
+impl<W: Write> Csv<W> {
+    fn new(out: W) -> Result<Self> {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(COLUMNS)?;
+        Ok(Self(writer))
+    }
+}
+
+impl<W: Write> RecordSink for Csv<W> {
+    fn write(&mut self, rec: &FileRecord) -> Result<()> {
+        let media = rec.media.clone().unwrap_or_default();
+        self.0.write_record([
+            rec.path.clone(),
+            rec.name.clone(),
+            text(rec.ext.as_ref()),
+            rec.kind.as_str().to_string(),
+            rec.size.to_string(),
+            rec.modified.to_rfc3339(),
+            rec.added_at.to_rfc3339(),
+            text(rec.hash.as_ref()),
+            text(rec.mime.as_ref()),
+            rec.rating.to_string(),
+            text(media.duration),
+            text(media.width),
+            text(media.height),
+            text(media.artist),
+            text(media.album),
+            text(media.title),
+        ])?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.0.flush()?;
+        Ok(())
+    }
+}
+
+fn text<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+#[cfg(feature = "parquet")]
+mod columnar {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use anyhow::{anyhow, Result};
+    use parquet::data_type::{
+        ByteArray, ByteArrayType, DataType, DoubleType, Int32Type, Int64Type,
+    };
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+    use parquet::schema::parser::parse_message_type;
+
+    use super::RecordSink;
+    use crate::indexer::FileRecord;
+
+    // Column order must match `super::COLUMNS` and `write_group`.
+    const SCHEMA: &str = "
+        message file {
+            REQUIRED BYTE_ARRAY path (UTF8);
+            REQUIRED BYTE_ARRAY name (UTF8);
+            OPTIONAL BYTE_ARRAY ext (UTF8);
+            REQUIRED BYTE_ARRAY kind (UTF8);
+            REQUIRED INT64 size;
+            REQUIRED INT64 modified (TIMESTAMP(MILLIS,true));
+            REQUIRED INT64 added_at (TIMESTAMP(MILLIS,true));
+            OPTIONAL BYTE_ARRAY hash (UTF8);
+            OPTIONAL BYTE_ARRAY mime (UTF8);
+            REQUIRED INT32 rating;
+            OPTIONAL DOUBLE duration;
+            OPTIONAL INT64 width;
+            OPTIONAL INT64 height;
+            OPTIONAL BYTE_ARRAY artist (UTF8);
+            OPTIONAL BYTE_ARRAY album (UTF8);
+            OPTIONAL BYTE_ARRAY title (UTF8);
+        }
+    ";
+
+    // Rows buffered per row group, which bounds memory use.
+    const ROW_GROUP_ROWS: usize = 8192;
+
+    pub(super) struct ParquetSink<W: Write + Send> {
+        writer: SerializedFileWriter<W>,
+        rows: Vec<FileRecord>,
+    }
+
+    impl<W: Write + Send> ParquetSink<W> {
+        pub(super) fn new(out: W) -> Result<Self> {
+            let schema = Arc::new(parse_message_type(SCHEMA)?);
+            let props = Arc::new(WriterProperties::builder().build());
+            Ok(Self {
+                writer: SerializedFileWriter::new(out, schema, props)?,
+                rows: Vec::with_capacity(ROW_GROUP_ROWS),
+            })
+        }
+
+        fn write_group(&mut self) -> Result<()> {
+            if self.rows.is_empty() {
+                return Ok(());
+            }
+            let rows = std::mem::take(&mut self.rows);
+            let media: Vec<_> = rows
+                .iter()
+                .map(|r| r.media.clone().unwrap_or_default())
+                .collect();
+            let text = |value: Option<&String>| value.map(|v| ByteArray::from(v.as_str()));
+            let mut group = self.writer.next_row_group()?;
+            column::<ByteArrayType, _>(&mut group, rows.iter().map(|r| text(Some(&r.path))))?;
+            column::<ByteArrayType, _>(&mut group, rows.iter().map(|r| text(Some(&r.name))))?;
+            column::<ByteArrayType, _>(&mut group, rows.iter().map(|r| text(r.ext.as_ref())))?;
+            column::<ByteArrayType, _>(
+                &mut group,
+                rows.iter().map(|r| Some(ByteArray::from(r.kind.as_str()))),
+            )?;
+            column::<Int64Type, _>(&mut group, rows.iter().map(|r| Some(r.size)))?;
+            column::<Int64Type, _>(
+                &mut group,
+                rows.iter().map(|r| Some(r.modified.timestamp_millis())),
+            )?;
+            column::<Int64Type, _>(
+                &mut group,
+                rows.iter().map(|r| Some(r.added_at.timestamp_millis())),
+            )?;
+            column::<ByteArrayType, _>(&mut group, rows.iter().map(|r| text(r.hash.as_ref())))?;
+            column::<ByteArrayType, _>(&mut group, rows.iter().map(|r| text(r.mime.as_ref())))?;
+            column::<Int32Type, _>(&mut group, rows.iter().map(|r| Some(r.rating as i32)))?;
+            column::<DoubleType, _>(&mut group, media.iter().map(|m| m.duration))?;
+            column::<Int64Type, _>(&mut group, media.iter().map(|m| m.width))?;
+            column::<Int64Type, _>(&mut group, media.iter().map(|m| m.height))?;
+            column::<ByteArrayType, _>(&mut group, media.iter().map(|m| text(m.artist.as_ref())))?;
+            column::<ByteArrayType, _>(&mut group, media.iter().map(|m| text(m.album.as_ref())))?;
+            column::<ByteArrayType, _>(&mut group, media.iter().map(|m| text(m.title.as_ref())))?;
+            group.close()?;
+            Ok(())
+        }
+    }
+
+    impl<W: Write + Send> RecordSink for ParquetSink<W> {
+        fn write(&mut self, rec: &FileRecord) -> Result<()> {
+            self.rows.push(rec.clone());
+            if self.rows.len() == ROW_GROUP_ROWS {
+                self.write_group()?;
+            }
+            Ok(())
+        }
+
+        fn finish(mut self) -> Result<()> {
+            self.write_group()?;
+            self.writer.close()?;
+            Ok(())
+        }
+    }
+
+    fn column<T: DataType, W: Write + Send>(
+        group: &mut SerializedRowGroupWriter<'_, W>,
+        values: impl Iterator<Item = Option<T::T>>,
+    ) -> Result<()> {
+        let mut column = group
+            .next_column()?
+            .ok_or_else(|| anyhow!("export schema is missing a column"))?;
+        let writer = column.typed::<T>();
+        let (levels, values): (Vec<i16>, Vec<Option<T::T>>) =
+            values.map(|v| (v.is_some() as i16, v)).unzip();
+        let values: Vec<T::T> = values.into_iter().flatten().collect();
+        let optional = writer.get_descriptor().max_def_level() > 0;
+        writer.write_batch(&values, optional.then_some(levels.as_slice()), None)?;
+        column.close()?;
+        Ok(())
+    }
+}
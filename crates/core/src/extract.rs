@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use zip::ZipArchive;
+
+// Larger text files are truncated rather than skipped.
+const MAX_TEXT_BYTES: u64 = 4 * 1024 * 1024;
+
+pub trait ContentExtractor: Send + Sync {
+    // Lowercase extensions without the leading dot.
+    fn extensions(&self) -> &[&str];
+    fn extract(&self, path: &Path) -> Result<String>;
+}
+
+pub struct PlainTextExtractor;
+
+impl ContentExtractor for PlainTextExtractor {
+    fn extensions(&self) -> &[&str] {
+        &[
+            "txt", "md", "markdown", "rst", "org", "log", "csv", "tsv", "json", "toml", "yaml",
+            "yml", "ini", "cfg", "xml", "html", "htm", "tex", "rs", "py", "js", "ts", "c", "h",
+            "cpp", "hpp", "cs", "go", "java", "kt", "rb", "php", "sh", "sql",
+        ]
+    }
+
+    fn extract(&self, path: &Path) -> Result<String> {
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let mut buf = Vec::new();
+        file.take(MAX_TEXT_BYTES).read_to_end(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+}
+
+#[cfg(feature = "pdf")]
+pub struct PdfExtractor;
+
+#[cfg(feature = "pdf")]
+impl ContentExtractor for PdfExtractor {
+    fn extensions(&self) -> &[&str] {
+        &["pdf"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<String> {
+        // The PDF parser panics on some malformed files.
+        std::panic::catch_unwind(|| pdf_extract::extract_text(path))
+            .map_err(|_| anyhow::anyhow!("PDF parser panicked on {}", path.display()))?
+            .with_context(|| format!("extracting text from {}", path.display()))
+    }
+}
+
+// DOCX/PPTX and OpenDocument files are zip archives with the text in XML parts.
+pub struct OfficeExtractor;
+
+impl ContentExtractor for OfficeExtractor {
+    fn extensions(&self) -> &[&str] {
+        &["docx", "pptx", "odt", "odp", "ods"]
+    }
+
+    fn extract(&self, path: &Path) -> Result<String> {
+        let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let mut archive = ZipArchive::new(BufReader::new(file))
+            .with_context(|| format!("reading {} as a zip archive", path.display()))?;
+        let mut parts: Vec<String> = archive
+            .file_names()
+            .filter(|name| {
+                *name == "word/document.xml"
+                    || *name == "content.xml"
+                    || (name.starts_with("ppt/slides/slide") && name.ends_with(".xml"))
+            })
+            .map(str::to_string)
+            .collect();
+        parts.sort();
+
+        let mut text = String::new();
+        for part in parts {
+            let member = archive.by_name(&part)?;
+            xml_text(BufReader::new(member.take(MAX_TEXT_BYTES)), &mut text)?;
+        }
+        Ok(text)
+    }
+}
+
+fn xml_text<R: std::io::BufRead>(input: R, out: &mut String) -> Result<()> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Text(text) => out.push_str(&text.unescape()?),
+            // Paragraph ends in both WordprocessingML (w:p), DrawingML (a:p)
+            // and OpenDocument (text:p).
+            Event::End(end) if end.local_name().as_ref() == b"p" => out.push('\n'),
+            Event::Empty(empty) if matches!(empty.local_name().as_ref(), b"tab" | b"s") => {
+                out.push(' ')
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub(crate) struct Extractors(Vec<Arc<dyn ContentExtractor>>);
+
+impl Default for Extractors {
+    fn default() -> Self {
+        Self(vec![
+            Arc::new(PlainTextExtractor),
+            Arc::new(OfficeExtractor),
+            #[cfg(feature = "pdf")]
+            Arc::new(PdfExtractor),
+        ])
+    }
+}
+
+impl Extractors {
+    // Later registrations win over built-ins for the same extension.
+    pub(crate) fn register(&mut self, extractor: Box<dyn ContentExtractor>) {
+        self.0.insert(0, Arc::from(extractor));
+    }
+
+    // Unsupported or unreadable documents simply have no indexed text.
+    pub(crate) fn extract(&self, path: &Path, ext: Option<&str>) -> Option<String> {
+        let ext = ext?;
+        self.0
+            .iter()
+            .find(|extractor| extractor.extensions().contains(&ext))
+            .and_then(|extractor| extractor.extract(path).ok())
+    }
+}
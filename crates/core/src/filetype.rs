@@ -0,0 +1,53 @@
+use std::path::Path;
+
+pub(crate) fn mime_from_ext(ext: &str) -> Option<String> {
+    mime_guess::from_ext(ext)
+        .first()
+        .map(|mime| mime.essence_str().to_string())
+}
+
+// Reads the first bytes of the file; unreadable or unknown content yields None.
+pub(crate) fn sniff_mime(path: &Path) -> Option<String> {
+    infer::get_from_path(path)
+        .ok()
+        .flatten()
+        .map(|kind| kind.mime_type().to_string())
+}
+
+pub(crate) fn ext_matches_mime(ext: &str, mime: &str) -> bool {
+    mime_guess::from_ext(ext)
+        .iter()
+        .any(|guess| guess.essence_str() == mime)
+}
+
+// "image/png" matches exactly; "image" or "image/*" matches the whole top-level type.
+pub(crate) enum MimeFilter {
+    Exact(String),
+    TopLevel(String),
+}
+
+impl MimeFilter {
+    pub(crate) fn parse(input: &str) -> Option<Self> {
+        let input = input.trim().to_ascii_lowercase();
+        if input.is_empty() {
+            return None;
+        }
+        match input.split_once('/') {
+            Some((top, "*" | "")) => Some(MimeFilter::TopLevel(top.to_string())),
+            Some(_) => Some(MimeFilter::Exact(input)),
+            None => Some(MimeFilter::TopLevel(input)),
+        }
+    }
+
+    pub(crate) fn matches(&self, mime: Option<&str>) -> bool {
+        let Some(mime) = mime else {
+            return false;
+        };
+        match self {
+            MimeFilter::Exact(exact) => mime == exact,
+            MimeFilter::TopLevel(top) => mime
+                .split_once('/')
+                .is_some_and(|(mime_top, _)| mime_top == top),
+        }
+    }
+}
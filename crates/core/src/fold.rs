@@ -0,0 +1,51 @@
+use anyhow::Result;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// Form used for case- and accent-insensitive matching: lowercased, then
+// decomposed with the combining marks dropped, so "Café" and "CAFE" both
+// become "cafe".
+pub(crate) fn fold(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_ascii_lowercase();
+    }
+    text.to_lowercase()
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect()
+}
+
+// Makes `fold(text)` available to SQL.
+pub(crate) fn register(conn: &Connection) -> Result<()> {
+    conn.create_scalar_function(
+        "fold",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let text: Option<String> = ctx.get(0)?;
+            Ok(text.map(|text| fold(&text)))
+        },
+    )?;
+    Ok(())
+}
+
+// Turns a LIKE pattern into the equivalent GLOB pattern, which compares
+// case-sensitively.
+pub(crate) fn like_to_glob(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        match c {
+            '%' => out.push('*'),
+            '_' => out.push('?'),
+            '*' | '?' | '[' => {
+                out.push('[');
+                out.push(c);
+                out.push(']');
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,64 @@
+pub(crate) fn max_distance(pattern_len: usize) -> usize {
+    match pattern_len {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+// Smallest edit distance between `pattern` and any substring of `text`
+// (Sellers' approximate substring matching), compared case-insensitively.
+pub(crate) fn substring_distance(pattern: &str, text: &str) -> usize {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut prev: Vec<usize> = (0..=pattern.len()).collect();
+    let mut best = prev[pattern.len()];
+    for &tc in &text {
+        let mut cur = vec![0; pattern.len() + 1];
+        for (i, &pc) in pattern.iter().enumerate() {
+            let cost = usize::from(pc != tc);
+            cur[i + 1] = (prev[i] + cost).min(prev[i + 1] + 1).min(cur[i] + 1);
+        }
+        best = best.min(cur[pattern.len()]);
+        prev = cur;
+    }
+    best
+}
+
+pub(crate) fn matches(pattern: &str, text: &str) -> bool {
+    substring_distance(pattern, text) <= max_distance(pattern.chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_to_the_closest_substring() {
+        assert_eq!(substring_distance("report", "annual_report_2023.pdf"), 0);
+        assert_eq!(substring_distance("reprot", "annual_report.pdf"), 2);
+        assert_eq!(substring_distance("reort", "report"), 1);
+        assert_eq!(substring_distance("repport", "report"), 1);
+        assert_eq!(substring_distance("rexort", "report"), 1);
+        assert_eq!(substring_distance("abc", "xyz"), 3);
+        assert_eq!(substring_distance("abc", ""), 3);
+        assert_eq!(substring_distance("", "anything"), 0);
+    }
+
+    #[test]
+    fn ignores_case_and_counts_characters() {
+        assert_eq!(substring_distance("REPORT", "Report.PDF"), 0);
+        assert_eq!(substring_distance("café", "CAFÉ au lait"), 0);
+        assert_eq!(substring_distance("cafe", "café"), 1);
+    }
+
+    #[test]
+    fn allowed_typos_grow_with_the_pattern() {
+        assert!(matches("ab", "xaby"));
+        assert!(!matches("ab", "axb"));
+        assert!(matches("invoce", "Invoice-March.pdf"));
+        assert!(!matches("invxxe", "invoice.pdf"));
+        assert!(matches("holidya", "holiday photos"));
+        assert!(!matches("hloidya", "holiday photos"));
+    }
+}
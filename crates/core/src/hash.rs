@@ -0,0 +1,361 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::ToSql;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::uncached;
+use crate::watchdog;
+
+pub const QUICK_HASH_CHUNK: u64 = 64 * 1024;
+pub const DEFAULT_HASH_BUFFER_SIZE: usize = 1024 * 1024;
+// Files at least this large are memory-mapped rather than read, and
+// hashed on all cores with BLAKE3.
+pub const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+// Mapped files are hashed in blocks of this size, reporting progress to
+// the scan's IO timeout after each.
+const MAPPED_BLOCK: usize = 64 * 1024 * 1024;
+// Large files that cannot be mapped are read in blocks of this size for
+// BLAKE3, still enough to keep every core busy.
+const LARGE_BLOCK: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum HashMode {
+    #[default]
+    Full,
+    // Size plus the first and last `QUICK_HASH_CHUNK` bytes; a cheap
+    // pre-screen for duplicates, not proof of equality.
+    Quick,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    pub fn hasher(self) -> Box<dyn Hasher> {
+        match self {
+            HashAlgorithm::Blake3 => Box::new(blake3::Hasher::new()),
+            HashAlgorithm::Sha256 => Box::new(sha2::Sha256::new()),
+            HashAlgorithm::Xxh3 => Box::new(xxhash_rust::xxh3::Xxh3::new()),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "xxh3" => Ok(HashAlgorithm::Xxh3),
+            other => Err(anyhow!("unknown hash algorithm: {}", other)),
+        }
+    }
+}
+
+// Why a file was left unhashed by `IndexOptions::hash_max_size` or
+// `IndexOptions::hash_skip_exts`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum HashSkip {
+    TooLarge,
+    Extension,
+}
+
+impl HashSkip {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashSkip::TooLarge => "too_large",
+            HashSkip::Extension => "extension",
+        }
+    }
+}
+
+impl FromStr for HashSkip {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "too_large" => Ok(HashSkip::TooLarge),
+            "extension" => Ok(HashSkip::Extension),
+            other => Err(anyhow!("unknown hash skip reason: {}", other)),
+        }
+    }
+}
+
+impl ToSql for HashSkip {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for HashSkip {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err: anyhow::Error| FromSqlError::Other(err.into()))
+    }
+}
+
+impl ToSql for HashAlgorithm {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for HashAlgorithm {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err: anyhow::Error| FromSqlError::Other(err.into()))
+    }
+}
+
+pub trait Hasher {
+    fn update(&mut self, data: &[u8]);
+    fn finish_hex(self: Box<Self>) -> String;
+}
+
+impl Hasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finish_hex(self: Box<Self>) -> String {
+        self.finalize().to_hex().to_string()
+    }
+}
+
+impl Hasher for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finish_hex(self: Box<Self>) -> String {
+        self.finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl Hasher for xxhash_rust::xxh3::Xxh3 {
+    fn update(&mut self, data: &[u8]) {
+        xxhash_rust::xxh3::Xxh3::update(self, data);
+    }
+
+    fn finish_hex(self: Box<Self>) -> String {
+        format!("{:032x}", self.digest128())
+    }
+}
+
+// How `hash_file` reads; see the matching `IndexOptions` fields.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct HashIo {
+    // 0 uses `DEFAULT_HASH_BUFFER_SIZE`.
+    pub(crate) buffer_size: usize,
+    pub(crate) uncached: bool,
+}
+
+pub(crate) fn hash_file(path: &Path, algo: HashAlgorithm, io: HashIo) -> Result<String> {
+    let reading = || format!("reading {} for hashing", path.display());
+    let buffer_size = match io.buffer_size {
+        0 => DEFAULT_HASH_BUFFER_SIZE,
+        size => size,
+    };
+    if io.uncached {
+        if let Some(hash) = uncached::hash_direct(path, algo, buffer_size).with_context(reading)? {
+            return Ok(hash);
+        }
+    }
+    let mut file = if io.uncached {
+        uncached::open(path)
+    } else {
+        File::open(path)
+    }
+    .with_context(|| format!("opening {} for hashing", path.display()))?;
+    let len = file.metadata().map(|m| m.len()).unwrap_or(u64::MAX);
+    // Mapped pages always go through the cache.
+    if len >= MMAP_THRESHOLD && !io.uncached {
+        if let Some(hash) = hash_mapped(&file, algo) {
+            return Ok(hash);
+        }
+        if algo == HashAlgorithm::Blake3 {
+            return hash_large(&mut file).with_context(reading);
+        }
+    }
+    // No point in a buffer larger than the file.
+    let buffer_size = buffer_size.min(len.saturating_add(1).try_into().unwrap_or(usize::MAX));
+    let hash = hash_buffered(&mut file, algo.hasher(), buffer_size).with_context(reading)?;
+    if io.uncached {
+        uncached::drop_cache(&file);
+    }
+    Ok(hash)
+}
+
+// `None` when the file cannot be mapped (some network and FUSE
+// filesystems), leaving it to the read paths.
+fn hash_mapped(file: &File, algo: HashAlgorithm) -> Option<String> {
+    // SAFETY: the map is only read. A file truncated by another process
+    // while it is hashed can fault, the accepted trade-off of mapping.
+    let map = unsafe { memmap2::Mmap::map(file) }.ok()?;
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    if algo == HashAlgorithm::Blake3 {
+        let mut hasher = blake3::Hasher::new();
+        for block in map.chunks(MAPPED_BLOCK) {
+            hasher.update_rayon(block);
+            watchdog::tick();
+        }
+        return Some(hasher.finalize().to_hex().to_string());
+    }
+    let mut hasher = algo.hasher();
+    for block in map.chunks(MAPPED_BLOCK) {
+        hasher.update(block);
+        watchdog::tick();
+    }
+    Some(hasher.finish_hex())
+}
+
+// Each block is filled completely before hashing so every one is large
+// enough to spread over the cores.
+fn hash_large<R: Read>(reader: &mut R) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; LARGE_BLOCK];
+    loop {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match reader.read(&mut buf[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        hasher.update_rayon(&buf[..filled]);
+        watchdog::tick();
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+pub(crate) fn quick_hash_file(path: &Path, size: i64, algo: HashAlgorithm) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("opening {} for hashing", path.display()))?;
+    let size = size.max(0) as u64;
+    let mut hasher = algo.hasher();
+    hasher.update(&size.to_le_bytes());
+    if size <= 2 * QUICK_HASH_CHUNK {
+        return hash_into(&mut file, hasher);
+    }
+    let mut buf = vec![0u8; QUICK_HASH_CHUNK as usize];
+    file.read_exact(&mut buf)?;
+    hasher.update(&buf);
+    file.seek(SeekFrom::End(-(QUICK_HASH_CHUNK as i64)))?;
+    file.read_exact(&mut buf)?;
+    hasher.update(&buf);
+    Ok(hasher.finish_hex())
+}
+
+pub(crate) fn hash_reader<R: Read>(reader: &mut R, algo: HashAlgorithm) -> Result<String> {
+    hash_into(reader, algo.hasher())
+}
+
+fn hash_into<R: Read>(reader: &mut R, hasher: Box<dyn Hasher>) -> Result<String> {
+    hash_buffered(reader, hasher, 64 * 1024)
+}
+
+fn hash_buffered<R: Read>(
+    reader: &mut R,
+    mut hasher: Box<dyn Hasher>,
+    buffer_size: usize,
+) -> Result<String> {
+    let mut buf = vec![0u8; buffer_size.max(1)];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        watchdog::tick();
+    }
+    Ok(hasher.finish_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hands out at most 1000 bytes per read.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(1000);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn large_files_hash_like_small_ones() {
+        for len in [0, 1, LARGE_BLOCK - 1, LARGE_BLOCK, 2 * LARGE_BLOCK + 123] {
+            let data = data(len);
+            let expected = blake3::hash(&data).to_hex().to_string();
+            assert_eq!(hash_large(&mut &data[..]).unwrap(), expected, "{}", len);
+            assert_eq!(
+                hash_large(&mut Trickle(&data)).unwrap(),
+                expected,
+                "{}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn hash_file_maps_past_the_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.bin");
+        let data = data(MMAP_THRESHOLD as usize + 4096);
+        std::fs::write(&path, &data).unwrap();
+        for algo in [
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Xxh3,
+        ] {
+            let mut hasher = algo.hasher();
+            hasher.update(&data);
+            assert_eq!(
+                hash_file(&path, algo, HashIo::default()).unwrap(),
+                hasher.finish_hex(),
+                "{:?}",
+                algo
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::FileIndexer;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageGroup {
+    pub paths: Vec<String>,
+}
+
+// 64-bit difference hash: the image is shrunk to 9x8 grey pixels and each
+// bit records whether a pixel is brighter than its right neighbour. Resizing,
+// recompression and small edits flip only a few bits.
+#[cfg(feature = "images")]
+pub(crate) fn dhash(path: &std::path::Path) -> Option<u64> {
+    use image::imageops::FilterType;
+
+    let small = image::open(path)
+        .ok()?
+        .resize_exact(9, 8, FilterType::Triangle)
+        .to_luma8();
+    let mut bits = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            bits = (bits << 1) | brighter as u64;
+        }
+    }
+    Some(bits)
+}
+
+#[cfg(not(feature = "images"))]
+pub(crate) fn dhash(_path: &std::path::Path) -> Option<u64> {
+    None
+}
+
+impl FileIndexer {
+    // Groups images whose hashes differ in at most `threshold` bits (out of
+    // 64); around 10 catches resized and re-encoded copies. Largest groups
+    // come first.
+    pub fn similar_images(&self, threshold: u32) -> Result<Vec<ImageGroup>> {
+        let conn = self.reader()?;
+        let mut stmt =
+            conn.prepare("SELECT path,image_hash FROM files WHERE image_hash IS NOT NULL")?;
+        let rows: Vec<(String, i64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut tree = BkTree::default();
+        for (idx, (_, hash)) in rows.iter().enumerate() {
+            tree.insert(*hash as u64, idx);
+        }
+        let mut sets = DisjointSet::new(rows.len());
+        for (idx, (_, hash)) in rows.iter().enumerate() {
+            for other in tree.within(*hash as u64, threshold) {
+                sets.union(idx, other);
+            }
+        }
+
+        let mut groups: HashMap<usize, Vec<String>> = HashMap::new();
+        for (idx, (path, _)) in rows.into_iter().enumerate() {
+            groups.entry(sets.find(idx)).or_default().push(path);
+        }
+        let mut out: Vec<ImageGroup> = groups
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .map(|mut paths| {
+                paths.sort();
+                ImageGroup { paths }
+            })
+            .collect();
+        out.sort_by(|a, b| {
+            b.paths
+                .len()
+                .cmp(&a.paths.len())
+                .then(a.paths.cmp(&b.paths))
+        });
+        Ok(out)
+    }
+}
+
+// Burkhard-Keller tree over Hamming distance, so lookups skip most of the
+// collection instead of comparing every pair.
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    hash: u64,
+    items: Vec<usize>,
+    children: HashMap<u32, usize>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, item: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode::new(hash, item));
+            return;
+        }
+        let mut node = 0;
+        loop {
+            let dist = (self.nodes[node].hash ^ hash).count_ones();
+            if dist == 0 {
+                self.nodes[node].items.push(item);
+                return;
+            }
+            match self.nodes[node].children.get(&dist) {
+                Some(&child) => node = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(BkNode::new(hash, item));
+                    self.nodes[node].children.insert(dist, child);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn within(&self, hash: u64, threshold: u32) -> Vec<usize> {
+        let mut out = Vec::new();
+        let mut stack = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let dist = (node.hash ^ hash).count_ones();
+            if dist <= threshold {
+                out.extend_from_slice(&node.items);
+            }
+            for (&edge, &child) in &node.children {
+                if edge.abs_diff(dist) <= threshold {
+                    stack.push(child);
+                }
+            }
+        }
+        out
+    }
+}
+
+impl BkNode {
+    fn new(hash: u64, item: usize) -> Self {
+        Self {
+            hash,
+            items: vec![item],
+            children: HashMap::new(),
+        }
+    }
+}
+
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, mut idx: usize) -> usize {
+        while self.parent[idx] != idx {
+            self.parent[idx] = self.parent[self.parent[idx]];
+            idx = self.parent[idx];
+        }
+        idx
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
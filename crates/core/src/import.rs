@@ -0,0 +1,556 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::export::COLUMNS;
+use crate::filetype;
+use crate::indexer::{EntryKind, FileIndexer, FileRecord};
+use crate::media::MediaInfo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportFormat {
+    // File list exported by voidtools Everything.
+    Efu,
+    // mlocate database as written by `updatedb`.
+    Locate,
+    // Output of `find DIR -type f -printf '%s\t%T@\t%p\n'`.
+    FindPrintf,
+    // What `export` writes in the matching `ExportFormat`.
+    JsonLines,
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    // Paths that were already indexed; their rows are left untouched.
+    pub existing: usize,
+}
+
+// Seconds between 1601-01-01, the Windows FILETIME epoch, and 1970-01-01.
+const FILETIME_UNIX_OFFSET: i64 = 11_644_473_600;
+
+impl FileIndexer {
+    // Adds the regular files listed in an existing file list, or the
+    // entries of an earlier export, without touching the disk they live on.
+    // Only what the list records is kept: locate databases have neither
+    // sizes nor file times, so those rows get a size of 0 and their
+    // directory's time until the tree is re-indexed. JSON Lines exports
+    // carry every field; CSV and Parquet ones lack the hash algorithm and
+    // the less common metadata.
+    pub fn import_list<R: Read>(&self, format: ImportFormat, reader: R) -> Result<ImportReport> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let mut report = ImportReport::default();
+        let txn = conn.unchecked_transaction()?;
+        let mut add = |rec: FileRecord| -> Result<()> {
+            if self.contains_path(&rec.path)? {
+                report.existing += 1;
+            } else {
+                self.write_record(&rec)?;
+                // Only exports carry ratings, and `write_record` leaves them out.
+                if rec.rating > 0 {
+                    txn.execute(
+                        "UPDATE files SET rating = ? WHERE path = ?",
+                        params![rec.rating, rec.path],
+                    )?;
+                }
+                report.imported += 1;
+            }
+            Ok(())
+        };
+        let mut reader = BufReader::new(reader);
+        match format {
+            ImportFormat::Efu => parse_efu(reader, &mut add)?,
+            ImportFormat::Locate => parse_mlocate(&mut reader, &mut add)?,
+            ImportFormat::FindPrintf => parse_find(reader, &mut add)?,
+            ImportFormat::JsonLines => parse_jsonl(reader, &mut add)?,
+            ImportFormat::Csv => parse_csv(reader, &mut add)?,
+            #[cfg(feature = "parquet")]
+            ImportFormat::Parquet => columnar::parse_parquet(reader, &mut add)?,
+        }
+        txn.commit()?;
+        self.invalidate_cache();
+        Ok(report)
+    }
+}
+
+type Emit<'a> = dyn FnMut(FileRecord) -> Result<()> + 'a;
+
+fn parse_efu<R: Read>(reader: R, emit: &mut Emit<'_>) -> Result<()> {
+    let mut csv = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = csv.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim_start_matches('\u{feff}').eq_ignore_ascii_case(name))
+    };
+    let path_col = column("Filename").ok_or_else(|| anyhow!("not an EFU file list"))?;
+    let size_col = column("Size");
+    let modified_col = column("Date Modified");
+    let attr_col = column("Attributes");
+
+    for (line, row) in csv.records().enumerate() {
+        let row = row.with_context(|| format!("EFU line {}", line + 2))?;
+        let field = |col: Option<usize>| {
+            col.and_then(|c| row.get(c))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let number = |col: Option<usize>| -> Result<Option<i64>> {
+            field(col)
+                .map(|v| v.parse::<i64>())
+                .transpose()
+                .with_context(|| format!("EFU line {}", line + 2))
+        };
+        let attributes = number(attr_col)?;
+        // FILE_ATTRIBUTE_DIRECTORY
+        if attributes.is_some_and(|a| a & 0x10 != 0) {
+            continue;
+        }
+        let Some(path) = field(Some(path_col)) else {
+            continue;
+        };
+        let modified = number(modified_col)?
+            .map(|ft| ft / 10_000_000 - FILETIME_UNIX_OFFSET)
+            .unwrap_or(0);
+        let mut rec = imported_record(path, number(size_col)?.unwrap_or(0), modified);
+        rec.attributes = attributes.map(|a| a as u32);
+        emit(rec)?;
+    }
+    Ok(())
+}
+
+fn parse_find<R: BufRead>(reader: R, emit: &mut Emit<'_>) -> Result<()> {
+    for (line, text) in reader.lines().enumerate() {
+        let text = text?;
+        if text.is_empty() {
+            continue;
+        }
+        let (size, modified, path) =
+            parse_find_line(&text).with_context(|| format!("line {}", line + 1))?;
+        emit(imported_record(path, size, modified))?;
+    }
+    Ok(())
+}
+
+fn parse_jsonl<R: BufRead>(reader: R, emit: &mut Emit<'_>) -> Result<()> {
+    for (line, text) in reader.lines().enumerate() {
+        let text = text?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let rec = serde_json::from_str(&text).with_context(|| format!("line {}", line + 1))?;
+        emit(rec)?;
+    }
+    Ok(())
+}
+
+fn parse_csv<R: Read>(reader: R, emit: &mut Emit<'_>) -> Result<()> {
+    let mut csv = csv::Reader::from_reader(reader);
+    let headers = csv.headers()?.clone();
+    if headers.iter().ne(COLUMNS) {
+        return Err(anyhow!(
+            "not a CSV export; expected the columns {}",
+            COLUMNS.join(",")
+        ));
+    }
+    for (line, row) in csv.records().enumerate() {
+        let row = row.with_context(|| format!("CSV line {}", line + 2))?;
+        let rec = flat_record(|col| row.get(col).filter(|v| !v.is_empty()))
+            .with_context(|| format!("CSV line {}", line + 2))?;
+        emit(rec)?;
+    }
+    Ok(())
+}
+
+// Builds a record from the `COLUMNS` of a flat export; `field` returns the
+// value in a column, or `None` when it is empty.
+fn flat_record<'a>(field: impl Fn(usize) -> Option<&'a str>) -> Result<FileRecord> {
+    let text = |col: usize| field(col).map(str::to_string);
+    fn number<T: FromStr>(value: Option<&str>, name: &str) -> Result<Option<T>> {
+        value
+            .map(|v| v.parse().map_err(|_| anyhow!("bad {} {}", name, v)))
+            .transpose()
+    }
+    let time = |col: usize| -> Result<DateTime<Utc>> {
+        let value = field(col).unwrap_or_default();
+        Ok(DateTime::parse_from_rfc3339(value)
+            .with_context(|| format!("bad time {}", value))?
+            .with_timezone(&Utc))
+    };
+    let path = text(0).ok_or_else(|| anyhow!("missing path"))?;
+    let media = MediaInfo {
+        duration: number(field(10), "duration")?,
+        width: number(field(11), "width")?,
+        height: number(field(12), "height")?,
+        artist: text(13),
+        album: text(14),
+        title: text(15),
+        ..Default::default()
+    };
+    let mut rec = imported_record(&path, 0, 0);
+    rec.name = text(1).unwrap_or(rec.name);
+    rec.ext = text(2);
+    rec.kind = field(3).unwrap_or("file").parse()?;
+    rec.size = number(field(4), "size")?.unwrap_or(0);
+    rec.modified = time(5)?;
+    rec.added_at = time(6)?;
+    rec.hash = text(7);
+    rec.mime = text(8);
+    rec.rating = number(field(9), "rating")?.unwrap_or(0);
+    rec.media = (media != MediaInfo::default()).then_some(media);
+    Ok(rec)
+}
+
+// Size, modification time and path of a `%s\t%T@\t%p` line.
+pub(crate) fn parse_find_line(text: &str) -> Result<(i64, i64, &str)> {
+    let mut fields = text.splitn(3, '\t');
+    let (Some(size), Some(modified), Some(path)) = (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(anyhow!(
+            "expected size, modification time and path separated by tabs"
+        ));
+    };
+    let size: i64 = size.parse().with_context(|| format!("bad size {}", size))?;
+    // `%T@` prints fractional seconds.
+    let secs = modified.split('.').next().unwrap_or(modified);
+    let modified_secs: i64 = secs
+        .parse()
+        .with_context(|| format!("bad time {}", modified))?;
+    Ok((size, modified_secs, path))
+}
+
+// See mlocate.db(5): a header naming the root and a configuration block,
+// then for each directory its time and path followed by typed entries.
+fn parse_mlocate<R: BufRead>(reader: &mut R, emit: &mut Emit<'_>) -> Result<()> {
+    let mut magic = [0u8; 8];
+    reader
+        .read_exact(&mut magic)
+        .context("reading locate header")?;
+    match &magic {
+        b"\0mlocate" => {}
+        b"\0plocate" => {
+            return Err(anyhow!(
+                "plocate databases are not supported; import `find -printf` output instead"
+            ))
+        }
+        _ => return Err(anyhow!("not an mlocate database")),
+    }
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    let config_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    if header[4] != 0 {
+        return Err(anyhow!("unsupported mlocate format version {}", header[4]));
+    }
+    read_cstr(reader)?;
+    std::io::copy(
+        &mut reader.by_ref().take(config_len as u64),
+        &mut std::io::sink(),
+    )?;
+
+    let mut dir_header = [0u8; 16];
+    loop {
+        match reader.read_exact(&mut dir_header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+        let mut secs = [0u8; 8];
+        secs.copy_from_slice(&dir_header[..8]);
+        let dir_time = u64::from_be_bytes(secs) as i64;
+        let dir = read_cstr(reader)?;
+        loop {
+            let mut kind = [0u8; 1];
+            reader.read_exact(&mut kind)?;
+            match kind[0] {
+                0 => {
+                    let name = read_cstr(reader)?;
+                    let path = if dir.ends_with('/') {
+                        format!("{}{}", dir, name)
+                    } else {
+                        format!("{}/{}", dir, name)
+                    };
+                    emit(imported_record(&path, 0, dir_time))?;
+                }
+                1 => {
+                    read_cstr(reader)?;
+                }
+                2 => break,
+                other => return Err(anyhow!("corrupt mlocate entry type {}", other)),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_cstr<R: BufRead>(reader: &mut R) -> Result<String> {
+    let mut buf = Vec::new();
+    reader.read_until(0, &mut buf)?;
+    if buf.pop() != Some(0) {
+        return Err(anyhow!("truncated locate database"));
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+pub(crate) fn imported_record(path: &str, size: i64, modified: i64) -> FileRecord {
+    // Lists from Windows use backslashes whatever platform imports them.
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path).to_string();
+    let ext = Path::new(&name)
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_ascii_lowercase());
+    FileRecord {
+        path: path.to_string(),
+        mime: ext.as_deref().and_then(filetype::mime_from_ext),
+        name,
+        ext,
+        size,
+        modified: DateTime::from_timestamp(modified, 0).unwrap_or_default(),
+        added_at: Utc::now(),
+        hash: None,
+        kind: EntryKind::File,
+        link_target: None,
+        detected_mime: None,
+        type_mismatch: false,
+        media: None,
+        quick_hash: None,
+        hash_algo: None,
+        uid: None,
+        gid: None,
+        mode: None,
+        attributes: None,
+        image_hash: None,
+        similarity: None,
+        raw_path: None,
+        rating: 0,
+        hash_skipped: None,
+        scheme: None,
+        volume: None,
+        etag: None,
+        allocated: None,
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod columnar {
+    use std::io::Read;
+
+    use anyhow::{anyhow, Context, Result};
+    use bytes::Bytes;
+    use chrono::DateTime;
+    use parquet::file::reader::FileReader;
+    use parquet::file::serialized_reader::SerializedFileReader;
+    use parquet::record::Field;
+
+    use super::{flat_record, Emit, COLUMNS};
+
+    // Parquet footers sit at the end, so the whole file is read first.
+    pub(super) fn parse_parquet<R: Read>(mut reader: R, emit: &mut Emit<'_>) -> Result<()> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let file = SerializedFileReader::new(Bytes::from(data)).context("not a Parquet file")?;
+        let names: Vec<&str> = file
+            .metadata()
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|c| c.name())
+            .collect();
+        if names != COLUMNS {
+            return Err(anyhow!("not a Parquet export of this index"));
+        }
+        for (n, row) in file.get_row_iter(None)?.enumerate() {
+            let values: Vec<Option<String>> = row?
+                .into_columns()
+                .into_iter()
+                .map(|(_, field)| text(field))
+                .collect();
+            let rec = flat_record(|col| values.get(col).and_then(|v| v.as_deref()))
+                .with_context(|| format!("row {}", n + 1))?;
+            emit(rec)?;
+        }
+        Ok(())
+    }
+
+    // The value as the CSV export would write it.
+    fn text(field: Field) -> Option<String> {
+        match field {
+            Field::Null => None,
+            Field::Str(s) => Some(s),
+            Field::Int(n) => Some(n.to_string()),
+            Field::Long(n) => Some(n.to_string()),
+            Field::Double(n) => Some(n.to_string()),
+            Field::TimestampMillis(ms) => {
+                DateTime::from_timestamp_millis(ms).map(|t| t.to_rfc3339())
+            }
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::ExportFormat;
+    use crate::indexer::{SearchQuery, SortKey};
+
+    fn indexer() -> (tempfile::TempDir, FileIndexer) {
+        let db = tempfile::tempdir().unwrap();
+        let indexer = FileIndexer::new(db.path().join("index.db")).unwrap();
+        (db, indexer)
+    }
+
+    fn import(format: ImportFormat, data: &[u8]) -> Result<Vec<FileRecord>> {
+        let (_db, indexer) = indexer();
+        indexer.import_list(format, data)?;
+        indexer.search(&SearchQuery {
+            sort_key: Some(SortKey::Path),
+            ..SearchQuery::default()
+        })
+    }
+
+    fn error(format: ImportFormat, data: &[u8]) -> String {
+        format!("{:#}", import(format, data).unwrap_err())
+    }
+
+    // An mlocate database with one directory holding a file and a
+    // subdirectory.
+    fn mlocate(magic: &[u8; 8]) -> Vec<u8> {
+        let mut db = magic.to_vec();
+        db.extend(3u32.to_be_bytes());
+        db.extend([0, 0, 0, 0]);
+        db.extend(b"/\0");
+        db.extend(b"cfg");
+        db.extend(1_700_000_000u64.to_be_bytes());
+        db.extend([0; 8]);
+        db.extend(b"/home\0");
+        db.extend(b"\0notes.txt\0");
+        db.extend(b"\x01sub\0");
+        db.push(2);
+        db
+    }
+
+    #[test]
+    fn reads_mlocate_databases() {
+        let recs = import(ImportFormat::Locate, &mlocate(b"\0mlocate")).unwrap();
+        assert_eq!(recs.len(), 1);
+        assert_eq!(recs[0].path, "/home/notes.txt");
+        assert_eq!(recs[0].ext.as_deref(), Some("txt"));
+        assert_eq!(recs[0].modified.timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn tells_other_databases_apart() {
+        assert!(error(ImportFormat::Locate, &mlocate(b"\0plocate")).contains("plocate"));
+        assert!(error(ImportFormat::Locate, b"Filename,Size\n").contains("not an mlocate"));
+        assert!(error(ImportFormat::Locate, b"\0mloc").contains("reading locate header"));
+        let mut truncated = mlocate(b"\0mlocate");
+        truncated.truncate(truncated.len() - 8);
+        assert!(import(ImportFormat::Locate, &truncated).is_err());
+    }
+
+    #[test]
+    fn reads_efu_lists() {
+        let efu = "\u{feff}Filename,Size,Date Modified,Date Created,Attributes\n\
+                   C:\\Docs,0,0,0,16\n\
+                   C:\\Docs\\Report.PDF,1234,133500000000000000,0,32\n";
+        let recs = import(ImportFormat::Efu, efu.as_bytes()).unwrap();
+        assert_eq!(recs.len(), 1);
+        assert_eq!(recs[0].name, "Report.PDF");
+        assert_eq!(recs[0].ext.as_deref(), Some("pdf"));
+        assert_eq!(recs[0].size, 1234);
+        assert_eq!(
+            recs[0].modified.timestamp(),
+            13_350_000_000 - FILETIME_UNIX_OFFSET
+        );
+        assert_eq!(recs[0].attributes, Some(32));
+    }
+
+    #[test]
+    fn rejects_lists_that_are_not_efu() {
+        assert!(error(ImportFormat::Efu, b"path,size\n/a,1\n").contains("not an EFU file list"));
+        let bad_size = b"Filename,Size\nC:\\a.txt,big\n";
+        assert!(error(ImportFormat::Efu, bad_size).contains("EFU line 2"));
+    }
+
+    #[test]
+    fn reads_find_output() {
+        let list = b"12\t1700000000.25\t/srv/a b.txt\n\n0\t1\t/srv/empty\n";
+        let recs = import(ImportFormat::FindPrintf, list).unwrap();
+        let paths: Vec<_> = recs.iter().map(|r| (r.path.as_str(), r.size)).collect();
+        assert_eq!(paths, [("/srv/a b.txt", 12), ("/srv/empty", 0)]);
+        assert_eq!(recs[0].modified.timestamp(), 1_700_000_000);
+    }
+
+    #[test]
+    fn rejects_lines_not_from_find() {
+        assert!(error(ImportFormat::FindPrintf, b"/srv/a.txt\n").contains("separated by tabs"));
+        assert!(error(ImportFormat::FindPrintf, b"1\t2\t/a\nx\t2\t/b\n").contains("line 2"));
+        assert!(error(ImportFormat::FindPrintf, b"1\tnow\t/a\n").contains("bad time now"));
+    }
+
+    #[test]
+    fn exports_import_back() {
+        let (_db, source) = indexer();
+        source
+            .import_list(
+                ImportFormat::FindPrintf,
+                &b"5\t1700000000\t/d/a.txt\n7\t1700000100\t/d/b.mp3\n"[..],
+            )
+            .unwrap();
+        let all = SearchQuery {
+            sort_key: Some(SortKey::Path),
+            ..SearchQuery::default()
+        };
+        let expected = source.search(&all).unwrap();
+        for (export, format) in [
+            (ExportFormat::JsonLines, ImportFormat::JsonLines),
+            (ExportFormat::Csv, ImportFormat::Csv),
+        ] {
+            let mut data = Vec::new();
+            source.export(export, &mut data, &all).unwrap();
+            let recs = import(format, &data).unwrap();
+            let summary = |recs: &[FileRecord]| -> Vec<String> {
+                recs.iter()
+                    .map(|r| {
+                        format!(
+                            "{} {} {} {:?} {:?}",
+                            r.path, r.size, r.modified, r.ext, r.mime
+                        )
+                    })
+                    .collect()
+            };
+            assert_eq!(summary(&recs), summary(&expected), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn rejects_data_that_is_not_an_export() {
+        assert!(error(ImportFormat::Csv, b"Filename,Size\nC:\\a,1\n").contains("not a CSV export"));
+        assert!(error(ImportFormat::JsonLines, b"{\"path\":1}\n").contains("line 1"));
+        assert!(error(ImportFormat::JsonLines, b"\n\nnot json\n").contains("line 3"));
+    }
+
+    #[test]
+    fn keeps_existing_rows() {
+        let (_db, indexer) = indexer();
+        let list = b"1\t1\t/a\n2\t2\t/b\n";
+        let first = indexer
+            .import_list(ImportFormat::FindPrintf, &list[..])
+            .unwrap();
+        assert_eq!((first.imported, first.existing), (2, 0));
+        let again = indexer
+            .import_list(ImportFormat::FindPrintf, &list[..])
+            .unwrap();
+        assert_eq!((again.imported, again.existing), (0, 2));
+    }
+}
@@ -1,18 +1,46 @@
+use std::collections::HashMap;
 use std::fmt;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
-use blake3::Hasher;
 use chrono::{DateTime, NaiveDate, Utc};
+use parking_lot::ReentrantMutexGuard;
 use rusqlite::{
     params, params_from_iter,
-    types::{Type, Value},
-    Connection,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Type, ValueRef},
+    Connection, OpenFlags, OptionalExtension, Row, ToSql,
 };
 use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
+
+use crate::archive;
+use crate::attributes;
+use crate::cache::QueryCache;
+use crate::cancel::CancellationToken;
+use crate::chunks;
+use crate::cursor::Cursor;
+use crate::extract::{ContentExtractor, Extractors};
+use crate::filetype;
+use crate::fold;
+use crate::fuzzy;
+use crate::hash::{self, HashAlgorithm, HashIo, HashMode, HashSkip};
+use crate::imagehash;
+use crate::media::{self, MediaInfo};
+use crate::path_filter::PathFilter;
+use crate::paths;
+use crate::pattern;
+use crate::pool::{Pool, Reader};
+use crate::rank::{self, ScoredRecord};
+use crate::scan::{self, Found, ScannedFile};
+use crate::schema;
+use crate::select::{post_page, Select};
+use crate::similarity;
+use crate::store::IndexStore;
+use crate::volume;
+use crate::xattrs;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileRecord {
@@ -23,6 +51,131 @@ pub struct FileRecord {
     pub modified: DateTime<Utc>,
     pub added_at: DateTime<Utc>,
     pub hash: Option<String>,
+    #[serde(default)]
+    pub kind: EntryKind,
+    #[serde(default)]
+    pub link_target: Option<String>,
+    #[serde(default)]
+    pub mime: Option<String>,
+    #[serde(default)]
+    pub detected_mime: Option<String>,
+    // The content signature contradicts the extension.
+    #[serde(default)]
+    pub type_mismatch: bool,
+    #[serde(default)]
+    pub media: Option<MediaInfo>,
+    #[serde(default)]
+    pub quick_hash: Option<String>,
+    // Algorithm behind `hash` and `quick_hash`.
+    #[serde(default)]
+    pub hash_algo: Option<HashAlgorithm>,
+    // Unix owner, group and permission bits (including setuid/setgid/sticky).
+    #[serde(default)]
+    pub uid: Option<u32>,
+    #[serde(default)]
+    pub gid: Option<u32>,
+    #[serde(default)]
+    pub mode: Option<u32>,
+    // Windows attribute bitmask; see the `attributes` module.
+    #[serde(default)]
+    pub attributes: Option<u32>,
+    // Perceptual difference hash of images; see `similar_images`.
+    #[serde(default)]
+    pub image_hash: Option<u64>,
+    // Piecewise content digest; see `similar_to`.
+    #[serde(default)]
+    pub similarity: Option<String>,
+    // Exact OS encoding of the path when it is not valid Unicode; `path`
+    // then holds an escaped form. Use `fs_path` to open the file.
+    #[serde(default)]
+    pub raw_path: Option<Vec<u8>>,
+    // User rating, 0 (unrated) to 5; see `set_rating`.
+    #[serde(default)]
+    pub rating: u8,
+    // Set when hashing was requested but the hash policy left this file out.
+    #[serde(default)]
+    pub hash_skipped: Option<HashSkip>,
+    // Where entries listed by a `ScanSource` live, e.g. `s3` and the
+    // bucket; `None` for the local file system, whose files carry the
+    // identity of their drive as volume (see `volumes`) where known.
+    #[serde(default)]
+    pub scheme: Option<String>,
+    #[serde(default)]
+    pub volume: Option<String>,
+    // Version tag of a remote object, changed by the store on every write.
+    #[serde(default)]
+    pub etag: Option<String>,
+    // Bytes the file takes up on disk, which sparse files and file system
+    // compression keep below `size`; `None` where unknown, e.g. remote
+    // and imported entries.
+    #[serde(default)]
+    pub allocated: Option<i64>,
+}
+
+impl FileRecord {
+    pub fn fs_path(&self) -> PathBuf {
+        paths::decode(&self.path, self.raw_path.as_deref())
+    }
+
+    // What sorting, filtering and totals use: an unknown allocation counts
+    // as the logical size.
+    pub fn allocated_size(&self) -> i64 {
+        self.allocated.unwrap_or(self.size)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+pub enum EntryKind {
+    #[default]
+    File,
+    Symlink,
+    BrokenSymlink,
+    ArchiveMember,
+    // Timed out or kept failing during a scan; the row keeps whatever an
+    // earlier scan recorded.
+    Unreachable,
+}
+
+impl EntryKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Symlink => "symlink",
+            EntryKind::BrokenSymlink => "broken_symlink",
+            EntryKind::ArchiveMember => "archive_member",
+            EntryKind::Unreachable => "unreachable",
+        }
+    }
+}
+
+impl FromStr for EntryKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "file" => Ok(EntryKind::File),
+            "symlink" => Ok(EntryKind::Symlink),
+            "broken_symlink" => Ok(EntryKind::BrokenSymlink),
+            "archive_member" => Ok(EntryKind::ArchiveMember),
+            "unreachable" => Ok(EntryKind::Unreachable),
+            other => Err(anyhow!("unknown entry kind: {}", other)),
+        }
+    }
+}
+
+impl ToSql for EntryKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for EntryKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err: anyhow::Error| FromSqlError::Other(err.into()))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,289 +183,1521 @@ pub struct DuplicateGroup {
     pub hash: String,
     pub size: i64,
     pub count: i64,
+    // Bytes the copies beyond the first take up.
+    #[serde(default)]
+    pub wasted: i64,
     pub paths: Vec<String>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+// How duplicate groups are ordered, always largest first.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
+pub enum DuplicateSort {
+    // Number of copies.
+    #[default]
+    Count,
+    // Space the extra copies take up.
+    Wasted,
+    // Size of a single copy.
+    Size,
+}
+
+impl DuplicateSort {
+    fn column(self) -> &'static str {
+        match self {
+            DuplicateSort::Count => "c",
+            DuplicateSort::Wasted => "(c - 1) * size",
+            DuplicateSort::Size => "size",
+        }
+    }
+
+    pub(crate) fn key(self, group: &DuplicateGroup) -> i64 {
+        match self {
+            DuplicateSort::Count => group.count,
+            DuplicateSort::Wasted => group.wasted,
+            DuplicateSort::Size => group.size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CandidateGroup {
+    pub size: i64,
+    // Set when grouping by name as well as size.
+    pub name: Option<String>,
+    pub count: i64,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Hash)]
 pub enum SortKey {
     #[default]
     Name,
     Size,
     Modified,
+    Relevance,
+    Rating,
+    Ext,
+    Path,
+    AddedAt,
+    // See `FileRecord::allocated_size`.
+    Allocated,
+}
+
+impl SortKey {
+    // What SQL orders by. Entries without an extension sort first rather
+    // than as NULL, which cursors could not compare against.
+    pub(crate) fn column(self) -> &'static str {
+        match self {
+            SortKey::Name | SortKey::Relevance => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+            SortKey::Rating => "rating",
+            SortKey::Ext => "coalesce(ext, '')",
+            SortKey::Path => "path",
+            SortKey::AddedAt => "added_at",
+            SortKey::Allocated => "coalesce(allocated, size)",
+        }
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SearchQuery {
     pub name_like: Option<String>,
     pub ext: Option<String>,
+    // Any of these extensions; a leading dot is ignored.
+    pub ext_in: Vec<String>,
     pub min_size: Option<i64>,
     pub max_size: Option<i64>,
+    // Bytes on disk; see `FileRecord::allocated_size`.
+    pub min_allocated: Option<i64>,
+    pub max_allocated: Option<i64>,
     pub date_from: Option<NaiveDate>,
     pub date_to: Option<NaiveDate>,
+    // When the file was first indexed, e.g. during a particular scan.
+    pub added_from: Option<NaiveDate>,
+    pub added_to: Option<NaiveDate>,
+    // Full content hash in hex, compared case-insensitively.
+    pub hash: Option<String>,
+    // Whether a full hash has been computed for the entry.
+    pub has_hash: Option<bool>,
+    // Lowest user rating to include; `Some(1)` lists every rated file.
+    pub min_rating: Option<u8>,
+    // Only members of this collection.
+    pub collection: Option<String>,
+    // Only entries carrying every one of these tags.
+    pub tags: Vec<String>,
     pub sort_key: Option<SortKey>,
     pub desc: bool,
+    // Orders rows that tie on `sort_key`, e.g. size descending then name.
+    pub then_sort: Option<SortKey>,
+    pub then_desc: bool,
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    // Continue after the last row of a previous page, see `cursor_after`.
+    pub after: Option<Cursor>,
+    // Typo-tolerant name matching; always ignores case.
+    pub fuzzy: bool,
+    // `None` or `Some(false)` ignores case and accents in `name_like`, so
+    // "cafe" finds "Café.txt"; `Some(true)` matches it exactly.
+    pub case_sensitive: Option<bool>,
+    // Regular expressions (Rust `regex` syntax) that must match somewhere in
+    // the name or full path. Case-sensitive unless the pattern says `(?i)`.
+    pub name_regex: Option<String>,
+    pub path_regex: Option<String>,
+    // Shell-style glob over the full path, e.g. `src/**/*.rs`; see
+    // `pattern::plan_glob`. Follows `case_sensitive` like `name_like`.
+    pub glob: Option<String>,
+    // Directory whose subtree results must lie in (the directory itself
+    // included), e.g. one of several indexed roots.
+    pub path_prefix: Option<String>,
+    pub kind: Option<EntryKind>,
+    // Exact type ("image/png") or a top-level type ("video", "video/*").
+    pub mime: Option<String>,
+    pub type_mismatch: bool,
+    // Full-text query over extracted document text (SQLite FTS5 syntax).
+    pub content: Option<String>,
+    // Full-text query over notes set with `set_note` (SQLite FTS5 syntax).
+    pub note: Option<String>,
+    // Media filters only match files indexed with `media_metadata`.
+    pub min_duration: Option<i64>,
+    pub max_duration: Option<i64>,
+    pub min_height: Option<i64>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    // Numeric uid/gid; only match files indexed on Unix.
+    pub owner: Option<u32>,
+    pub group: Option<u32>,
+    pub world_writable: bool,
+    // Windows attribute bits that must all be set, e.g. `attributes::HIDDEN`.
+    pub attributes: Option<u32>,
+    // Extended attribute key, or a GLOB pattern such as `user.*`.
+    pub has_xattr: Option<String>,
+    // Volume id or label (see `volumes`), or the bucket or host of remote
+    // entries.
+    pub volume: Option<String>,
+}
+
+impl SearchQuery {
+    pub(crate) fn normalized(&self) -> SearchQuery {
+        let non_empty = |s: &Option<String>| s.clone().filter(|s| !s.is_empty());
+        SearchQuery {
+            name_like: non_empty(&self.name_like),
+            name_regex: non_empty(&self.name_regex),
+            path_regex: non_empty(&self.path_regex),
+            glob: non_empty(&self.glob),
+            path_prefix: non_empty(&self.path_prefix),
+            hash: non_empty(&self.hash).map(|s| s.trim().to_ascii_lowercase()),
+            ext: non_empty(&self.ext).map(|s| s.to_ascii_lowercase()),
+            ext_in: normalized_exts(&self.ext_in),
+            mime: non_empty(&self.mime).map(|s| s.to_ascii_lowercase()),
+            content: non_empty(&self.content),
+            note: non_empty(&self.note),
+            collection: non_empty(&self.collection).map(|s| s.trim().to_string()),
+            tags: self
+                .tags
+                .iter()
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            artist: non_empty(&self.artist),
+            album: non_empty(&self.album),
+            title: non_empty(&self.title),
+            has_xattr: non_empty(&self.has_xattr),
+            volume: non_empty(&self.volume),
+            sort_key: Some(self.sort_key.unwrap_or_default()),
+            ..self.clone()
+        }
+    }
+
+    // Sort keys with their direction, primary first. Path always breaks
+    // the remaining ties, in the primary direction.
+    pub(crate) fn sort_order(&self) -> Result<Vec<(SortKey, bool)>> {
+        let mut order = vec![(self.sort_key.unwrap_or_default(), self.desc)];
+        if let Some(then) = self.then_sort {
+            if order[0].0 == SortKey::Relevance || then == SortKey::Relevance {
+                return Err(anyhow!(
+                    "relevance order cannot be combined with another sort"
+                ));
+            }
+            order.push((then, self.then_desc));
+        }
+        Ok(order)
+    }
+}
+
+// Outcome of an indexing run. Unreadable entries are skipped rather than
+// ending the run; `errors` holds the first of them.
+#[derive(Debug, Default)]
+pub struct IndexReport {
+    pub indexed: usize,
+    pub skipped: usize,
+    pub errors: Vec<(PathBuf, anyhow::Error)>,
+    // Files a replay of the NTFS change journal left alone; see `ntfs`.
+    pub unchanged: usize,
+    // Skipped files recorded with `IndexOptions::record_unreachable`.
+    pub unreachable: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexOptions {
+    pub hash: bool,
+    pub hash_mode: HashMode,
+    pub hash_algo: HashAlgorithm,
+    // Read size for hashing files that are not memory-mapped: those below
+    // `hash::MMAP_THRESHOLD`, uncached reads, and files that cannot be
+    // mapped unless BLAKE3 reads them in its own larger blocks. 0 uses
+    // `DEFAULT_HASH_BUFFER_SIZE`.
+    pub hash_buffer_size: usize,
+    // Files larger than this many bytes, or with one of these extensions,
+    // are not hashed; the row records why in `hash_skipped`.
+    pub hash_max_size: Option<u64>,
+    pub hash_skip_exts: Vec<String>,
+    // Read files for hashing without filling the OS page cache: direct IO
+    // on Linux, F_NOCACHE on macOS. For cold scans of trees far larger
+    // than memory.
+    pub uncached_io: bool,
+    pub threads: usize,
+    // Files read and hashed at once; 0 uses `threads`. More than the core
+    // count keeps fast SSDs busy while other files wait on IO.
+    pub in_flight: usize,
+    // Give up on a file that delivers no data for this long, e.g. on a
+    // stalled SMB or NFS server, and on the walk when listing a directory
+    // takes as long. `None` waits forever.
+    pub io_timeout: Option<Duration>,
+    // Further attempts after an IO error a flaky share tends to produce
+    // (timeouts, dropped connections, stale handles).
+    pub io_retries: u32,
+    // Write files that timed out or kept failing as `unreachable` entries
+    // instead of only reporting them, so searches still find them.
+    pub record_unreachable: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub respect_gitignore: bool,
+    pub follow_symlinks: bool,
+    pub record_symlinks: bool,
+    pub max_depth: Option<usize>,
+    pub max_files: Option<usize>,
+    pub max_total_bytes: Option<u64>,
+    // `None` reuses the setting recorded for the root by earlier runs.
+    pub skip_hidden: Option<bool>,
+    // Rows written per transaction; 0 uses `DEFAULT_BATCH_SIZE`.
+    pub batch_size: usize,
+    // Look at file contents when the extension does not map to a MIME type.
+    pub sniff_mime: bool,
+    // Read magic bytes of every file and record the detected type.
+    pub detect_type: bool,
+    // Store document text for content search.
+    pub extract_content: bool,
+    // Read duration, resolution and tags of audio and video files.
+    pub media_metadata: bool,
+    // Record zip/tar members as `archive!/member` entries.
+    pub index_archives: bool,
+    // Content-defined chunking for files of at least `chunk_threshold`
+    // bytes (0 uses `DEFAULT_CHUNK_THRESHOLD`).
+    pub chunking: bool,
+    pub chunk_threshold: u64,
+    // Store extended attributes (Linux/macOS) for `has_xattr` queries.
+    pub xattrs: bool,
+    // Keep the list of files seen by each run so runs can be diffed.
+    pub snapshot: bool,
+    // Compute perceptual hashes of images for `similar_images`.
+    pub perceptual_hash: bool,
+    // Compute similarity digests of regular files for `similar_to`.
+    pub similarity_digest: bool,
+    // Checked between files; a cancelled run keeps what it already wrote.
+    #[serde(skip)]
+    pub cancel: CancellationToken,
 }
 
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+const UPSERT_SQL: &str =
+    "INSERT INTO files(path,name,ext,size,modified,added_at,hash,kind,link_target,mime,
+                       detected_mime,type_mismatch,duration,bitrate,width,height,codec,
+                       artist,album,title,quick_hash,hash_algo,uid,gid,mode,
+                       attributes,image_hash,similarity,raw_path,hash_skipped,scheme,volume,
+                       etag,allocated)
+     VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)
+     ON CONFLICT(path) DO UPDATE SET
+         name=excluded.name,
+         ext=excluded.ext,
+         size=excluded.size,
+         modified=excluded.modified,
+         hash=excluded.hash,
+         kind=excluded.kind,
+         link_target=excluded.link_target,
+         mime=excluded.mime,
+         detected_mime=excluded.detected_mime,
+         type_mismatch=excluded.type_mismatch,
+         duration=excluded.duration,
+         bitrate=excluded.bitrate,
+         width=excluded.width,
+         height=excluded.height,
+         codec=excluded.codec,
+         artist=excluded.artist,
+         album=excluded.album,
+         title=excluded.title,
+         quick_hash=excluded.quick_hash,
+         hash_algo=excluded.hash_algo,
+         uid=excluded.uid,
+         gid=excluded.gid,
+         mode=excluded.mode,
+         attributes=excluded.attributes,
+         image_hash=excluded.image_hash,
+         similarity=excluded.similarity,
+         raw_path=excluded.raw_path,
+         hash_skipped=excluded.hash_skipped,
+         scheme=excluded.scheme,
+         volume=excluded.volume,
+         etag=excluded.etag,
+         allocated=excluded.allocated";
+
+// Scans writing at least this many rows refresh the planner statistics.
+const ANALYZE_AFTER_ROWS: usize = 50_000;
+
 pub struct FileIndexer {
-    conn: Connection,
+    pool: Pool,
+    cache: Mutex<QueryCache>,
+    pub(crate) path_filter: Mutex<Option<PathFilter>>,
+    // Shared with scan workers, which may outlive a scan that gave up on
+    // a stalled read.
+    extractors: Arc<Extractors>,
+    read_only: bool,
+    #[cfg(feature = "tantivy")]
+    pub(crate) text: Option<crate::text_index::TextIndex>,
 }
 
+// Searches may run on any thread while another one indexes.
+const _: fn() = || {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<FileIndexer>();
+};
+
 impl FileIndexer {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    // Opens an existing index without creating or upgrading tables, so it
+    // works on read-only media and shares; anything that would write fails.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(anyhow!("no index at {}", path.display()));
+        }
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let mut source = path.to_string_lossy().into_owned();
+        let mut conn = Connection::open_with_flags(&source, flags)?;
+        if schema::check(&conn).is_err() {
+            // A WAL database needs its shared-memory file, which cannot be
+            // created in a read-only directory; `immutable` skips it.
+            source = immutable_uri(path);
+            conn = Connection::open_with_flags(&source, flags)?;
+        }
+        schema::check(&conn).with_context(|| {
+            format!(
+                "{} is not a current index; open it read-write once to upgrade it",
+                path.display()
+            )
+        })?;
+        fold::register(&conn)?;
+        pattern::register(&conn)?;
+        Ok(Self {
+            pool: Pool::new(conn, Some(source)),
+            cache: Mutex::new(QueryCache::new()),
+            path_filter: Mutex::new(None),
+            extractors: Arc::default(),
+            read_only: true,
+            #[cfg(feature = "tantivy")]
+            text: None,
+        })
+    }
+
+    // Opens or creates a SQLCipher-encrypted index. The passphrase is needed
+    // on every open; a wrong one is reported rather than treated as an
+    // empty database.
+    #[cfg(feature = "encryption")]
+    pub fn new_encrypted<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self> {
+        if passphrase.is_empty() {
+            return Err(anyhow!("the passphrase must not be empty"));
+        }
         let conn = Connection::open(path)?;
-        conn.execute_batch(
-            "PRAGMA journal_mode=WAL;\n
-             CREATE TABLE IF NOT EXISTS files (
-                 path TEXT PRIMARY KEY,
-                 name TEXT NOT NULL,
-                 ext TEXT,
-                 size INTEGER NOT NULL,
-                 modified INTEGER NOT NULL,
-                 added_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
-                 hash TEXT
-             );
-             CREATE INDEX IF NOT EXISTS idx_files_name ON files(name);
-             CREATE INDEX IF NOT EXISTS idx_files_ext ON files(ext);
-             CREATE INDEX IF NOT EXISTS idx_files_modified ON files(modified);
-             CREATE INDEX IF NOT EXISTS idx_files_hash ON files(hash)
-            ",
-        )?;
-        Ok(Self { conn })
+        conn.pragma_update(None, "key", passphrase)?;
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+            .map_err(|_| anyhow!("wrong passphrase, or the index is not encrypted"))?;
+        let indexer = Self::with_connection(conn)?;
+        indexer.pool.set_key(passphrase);
+        Ok(indexer)
     }
 
-    pub fn index_dir<P: AsRef<Path>>(&self, root: P, hash: bool) -> Result<usize> {
-        let mut count = 0usize;
-        for entry in WalkDir::new(root) {
-            let entry = entry?;
-            if !entry.file_type().is_file() {
-                continue;
+    // Re-encrypts an encrypted index under a new passphrase.
+    #[cfg(feature = "encryption")]
+    pub fn change_passphrase(&self, passphrase: &str) -> Result<()> {
+        self.ensure_writable()?;
+        if passphrase.is_empty() {
+            return Err(anyhow!("the passphrase must not be empty"));
+        }
+        self.conn().pragma_update(None, "rekey", passphrase)?;
+        self.pool.set_key(passphrase);
+        Ok(())
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        schema::init(&conn)?;
+        fold::register(&conn)?;
+        pattern::register(&conn)?;
+        let source = conn.path().map(str::to_string);
+        Ok(Self {
+            pool: Pool::new(conn, source),
+            cache: Mutex::new(QueryCache::new()),
+            path_filter: Mutex::new(None),
+            extractors: Arc::default(),
+            read_only: false,
+            #[cfg(feature = "tantivy")]
+            text: None,
+        })
+    }
+
+    // The connection every write goes through. Holding it keeps other
+    // threads' writes out until it is dropped.
+    pub(crate) fn conn(&self) -> ReentrantMutexGuard<'_, Connection> {
+        self.pool.writer()
+    }
+
+    // The writer if no other thread holds it.
+    pub(crate) fn try_conn(&self) -> Option<ReentrantMutexGuard<'_, Connection>> {
+        self.pool.try_writer()
+    }
+
+    // A connection for queries, which does not wait for a running write.
+    pub(crate) fn reader(&self) -> Result<Reader<'_>> {
+        self.pool.reader()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // Called first by every public method that writes.
+    pub(crate) fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(anyhow!("the index was opened read-only"));
+        }
+        Ok(())
+    }
+
+    pub fn add_extractor(&mut self, extractor: Box<dyn ContentExtractor>) {
+        Arc::make_mut(&mut self.extractors).register(extractor);
+    }
+
+    pub fn index_dir<P: AsRef<Path>>(&self, root: P, opts: &IndexOptions) -> Result<IndexReport> {
+        self.ensure_writable()?;
+        // Paths are stored as found below the root, and a relative one would
+        // only lead anywhere from the directory it was indexed in.
+        let root = std::path::absolute(root.as_ref())?;
+        let root = root.as_path();
+        let skip_hidden = match opts.skip_hidden {
+            Some(skip) => skip,
+            None => self.root_skip_hidden(root)?.unwrap_or(false),
+        };
+        let opts = IndexOptions {
+            skip_hidden: Some(skip_hidden),
+            ..opts.clone()
+        };
+        self.record_root(root, &opts)?;
+        let scan_id = self.begin_scan(root, opts.snapshot)?;
+        let started = Utc::now().timestamp();
+        let report = self.scan_root(root, &opts, opts.snapshot.then_some(scan_id))?;
+        self.record_scan_errors(scan_id, &report.errors)?;
+        if !opts.cancel.is_cancelled() {
+            if opts.hash {
+                self.detect_renames(root, started)?;
             }
-            let record = self.build_record(entry.path(), hash)?;
-            self.upsert(&record)?;
-            count += 1;
-        }
-        Ok(count)
-    }
-
-    fn build_record(&self, path: &Path, hash: bool) -> Result<FileRecord> {
-        let metadata = path
-            .metadata()
-            .with_context(|| format!("reading metadata for {}", path.display()))?;
-        if !metadata.is_file() {
-            return Err(anyhow!("{} is not a regular file", path.display()));
-        }
-        let name = path
-            .file_name()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| anyhow!("file name is not valid UTF-8: {}", path.display()))?
-            .to_string();
-        let ext = path
-            .extension()
-            .and_then(|s| s.to_str())
-            .map(|s| s.to_ascii_lowercase());
-        let size = i64::try_from(metadata.len())
-            .with_context(|| format!("file is larger than 9 exabytes: {}", path.display()))?;
-        let modified = metadata
-            .modified()
-            .with_context(|| format!("missing modified time for {}", path.display()))?;
-        let modified = DateTime::<Utc>::from(modified);
-        let added_at = Utc::now();
-        let hash = if hash {
-            Some(compute_hash(path)?)
-        } else {
-            None
+            self.finish_root_scan(root, report.indexed + report.unchanged)?;
+            self.finish_scan(scan_id, report.indexed)?;
+            self.refresh_dirs(root)?;
+            if report.indexed >= ANALYZE_AFTER_ROWS {
+                self.refresh_statistics()?;
+            }
+        }
+        Ok(report)
+    }
+
+    // NTFS volumes have their own way; see `ntfs`.
+    #[cfg(not(windows))]
+    fn scan_root(
+        &self,
+        root: &Path,
+        opts: &IndexOptions,
+        snapshot: Option<i64>,
+    ) -> Result<IndexReport> {
+        self.index_subtree(root, opts, snapshot)
+    }
+
+    // Scans without touching the `roots` table, for refreshing part of an
+    // already-indexed tree. With `snapshot`, every record seen is also
+    // written to that scan's file list.
+    pub(crate) fn index_subtree(
+        &self,
+        root: &Path,
+        opts: &IndexOptions,
+        snapshot: Option<i64>,
+    ) -> Result<IndexReport> {
+        self.index_found(root, opts, snapshot, None)
+    }
+
+    // `index_subtree` over a volume listing instead of a walk when given.
+    pub(crate) fn index_found(
+        &self,
+        root: &Path,
+        opts: &IndexOptions,
+        snapshot: Option<i64>,
+        listing: Option<Vec<Found>>,
+    ) -> Result<IndexReport> {
+        let conn = self.conn();
+        let batch_size = match opts.batch_size {
+            0 => DEFAULT_BATCH_SIZE,
+            n => n,
         };
-        Ok(FileRecord {
-            path: path.to_string_lossy().to_string(),
-            name,
-            ext,
-            size,
-            modified,
-            added_at,
-            hash,
-        })
+        let mut txn = Some(conn.unchecked_transaction()?);
+        let mut pending = 0usize;
+        let extractors = opts.extract_content.then(|| Arc::clone(&self.extractors));
+        let result = scan::scan(root, opts, extractors, listing, |scanned| {
+            let record = &scanned.record;
+            if record.kind == EntryKind::Unreachable {
+                self.write_unreachable(record)?;
+            } else {
+                self.write_scanned(&scanned, opts)?;
+                if let Some(scan_id) = snapshot {
+                    self.write_scan_entry(scan_id, record)?;
+                }
+            }
+            pending += 1;
+            if pending >= batch_size {
+                if let Some(txn) = txn.take() {
+                    txn.commit()?;
+                }
+                txn = Some(conn.unchecked_transaction()?);
+                pending = 0;
+            }
+            Ok(())
+        });
+        // Rows from earlier batches are already committed, so drop cached
+        // results even if the scan failed part-way.
+        self.invalidate_cache();
+        let output = result?;
+        if let Some(txn) = txn {
+            txn.commit()?;
+        }
+        if !opts.cancel.is_cancelled() {
+            self.record_dirs(root, &output.dirs)?;
+        }
+        self.record_volumes()?;
+        Ok(output.report)
     }
 
-    fn upsert(&self, rec: &FileRecord) -> Result<()> {
-        self.conn.execute(
-            "INSERT INTO files(path,name,ext,size,modified,added_at,hash)
-             VALUES(?,?,?,?,?,?,?)
-             ON CONFLICT(path) DO UPDATE SET
-                 name=excluded.name,
-                 ext=excluded.ext,
-                 size=excluded.size,
-                 modified=excluded.modified,
-                 hash=excluded.hash",
-            params![
+    fn write_scanned(&self, scanned: &ScannedFile, opts: &IndexOptions) -> Result<()> {
+        let record = &scanned.record;
+        // Members are re-sent right after their archive; drop stale ones.
+        if opts.index_archives
+            && record.kind == EntryKind::File
+            && archive::is_archive(&record.name)
+        {
+            self.remove_archive_members(&record.path)?;
+        }
+        self.write_record(record)?;
+        if opts.extract_content {
+            self.write_content(&record.path, scanned.content.as_deref())?;
+        }
+        // Files that shrank below the threshold drop their old chunks.
+        if opts.chunking && record.kind == EntryKind::File {
+            self.write_chunks(&record.path, scanned.chunks.as_deref().unwrap_or_default())?;
+        }
+        if opts.xattrs && record.kind == EntryKind::File {
+            self.write_xattrs(&record.path, scanned.xattrs.as_deref().unwrap_or_default())?;
+        }
+        Ok(())
+    }
+
+    // An indexed file keeps its size, hash and text and only changes kind
+    // until a scan reaches it again.
+    fn write_unreachable(&self, rec: &FileRecord) -> Result<()> {
+        self.conn()
+            .prepare_cached(
+                "INSERT INTO files(path,name,ext,size,modified,added_at,kind,mime,raw_path)
+                 VALUES(?,?,?,?,?,?,?,?,?)
+                 ON CONFLICT(path) DO UPDATE SET kind=excluded.kind",
+            )?
+            .execute(params![
                 rec.path,
                 rec.name,
-                rec.ext.as_deref(),
+                rec.ext,
                 rec.size,
                 rec.modified.timestamp(),
                 rec.added_at.timestamp(),
-                rec.hash.as_deref()
-            ],
-        )?;
+                rec.kind,
+                rec.mime,
+                rec.raw_path.as_deref()
+            ])?;
+        self.add_to_path_filter(&rec.path);
+        Ok(())
+    }
+
+    pub(crate) fn index_file(&self, path: &Path, opts: &IndexOptions) -> Result<()> {
+        let record = build_record(path, opts)?;
+        self.write_record(&record)?;
+        if opts.extract_content {
+            let content = self.extractors.extract(path, record.ext.as_deref());
+            self.write_content(&record.path, content.as_deref())?;
+        }
+        if opts.chunking && record.kind == EntryKind::File {
+            let chunks = if chunks::wants_chunks(&record, opts) {
+                chunks::compute_chunks(path, opts.hash_algo)?
+            } else {
+                Vec::new()
+            };
+            self.write_chunks(&record.path, &chunks)?;
+        }
+        if opts.xattrs {
+            let xattrs = xattrs::read_xattrs(path).unwrap_or_default();
+            self.write_xattrs(&record.path, &xattrs)?;
+        }
+        if opts.index_archives && archive::is_archive(&record.name) {
+            self.remove_archive_members(&record.path)?;
+            for member in archive::read_members(&record, opts).unwrap_or_default() {
+                self.write_record(&member)?;
+            }
+        }
+        self.invalidate_cache();
+        Ok(())
+    }
+
+    pub(crate) fn upsert(&self, rec: &FileRecord) -> Result<()> {
+        self.ensure_writable()?;
+        self.write_record(rec)?;
+        self.invalidate_cache();
+        Ok(())
+    }
+
+    // Upserts `recs` in one transaction.
+    pub(crate) fn upsert_many(&self, recs: &[FileRecord]) -> Result<()> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let txn = conn.unchecked_transaction()?;
+        for rec in recs {
+            self.write_record(rec)?;
+        }
+        txn.commit()?;
+        self.invalidate_cache();
+        Ok(())
+    }
+
+    // Replaces the indexed text for `path`; `None` drops it.
+    pub(crate) fn write_content(&self, path: &str, text: Option<&str>) -> Result<()> {
+        let conn = self.conn();
+        let existing: Option<i64> = conn
+            .prepare_cached("SELECT id FROM content_docs WHERE path = ?")?
+            .query_row(params![path], |row| row.get(0))
+            .optional()?;
+        if let Some(id) = existing {
+            conn.prepare_cached("DELETE FROM content_fts WHERE rowid = ?")?
+                .execute(params![id])?;
+        }
+        match (text, existing) {
+            (Some(text), Some(id)) => {
+                conn.prepare_cached("INSERT INTO content_fts(rowid, body) VALUES(?, ?)")?
+                    .execute(params![id, text])?;
+            }
+            (Some(text), None) => {
+                conn.prepare_cached("INSERT INTO content_docs(path) VALUES(?)")?
+                    .execute(params![path])?;
+                let id = conn.last_insert_rowid();
+                conn.prepare_cached("INSERT INTO content_fts(rowid, body) VALUES(?, ?)")?
+                    .execute(params![id, text])?;
+            }
+            (None, Some(_)) => {
+                conn.prepare_cached("DELETE FROM content_docs WHERE path = ?")?
+                    .execute(params![path])?;
+            }
+            (None, None) => {}
+        }
         Ok(())
     }
 
+    pub(crate) fn write_record(&self, rec: &FileRecord) -> Result<()> {
+        let conn = self.conn();
+        let media = rec.media.clone().unwrap_or_default();
+        conn.prepare_cached(UPSERT_SQL)?.execute(params![
+            rec.path,
+            rec.name,
+            rec.ext.as_deref(),
+            rec.size,
+            rec.modified.timestamp(),
+            rec.added_at.timestamp(),
+            rec.hash.as_deref(),
+            rec.kind,
+            rec.link_target.as_deref(),
+            rec.mime.as_deref(),
+            rec.detected_mime.as_deref(),
+            rec.type_mismatch,
+            media.duration,
+            media.bitrate,
+            media.width,
+            media.height,
+            media.codec,
+            media.artist,
+            media.album,
+            media.title,
+            rec.quick_hash.as_deref(),
+            rec.hash_algo,
+            rec.uid,
+            rec.gid,
+            rec.mode,
+            rec.attributes,
+            // Stored bit for bit; SQLite integers are signed.
+            rec.image_hash.map(|hash| hash as i64),
+            rec.similarity.as_deref(),
+            rec.raw_path.as_deref(),
+            rec.hash_skipped,
+            rec.scheme.as_deref(),
+            rec.volume.as_deref(),
+            rec.etag.as_deref(),
+            rec.allocated
+        ])?;
+        self.add_to_path_filter(&rec.path);
+        Ok(())
+    }
+
+    pub fn remove<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        self.ensure_writable()?;
+        let path = paths::key(path.as_ref());
+        if !self.may_contain_path(&path)? {
+            return Ok(0);
+        }
+        let conn = self.conn();
+        let (prefix, upper) = subtree_bounds(&path);
+        let removed = conn.execute(
+            "DELETE FROM files WHERE path = ? OR (path >= ? AND path < ?)",
+            params![path, prefix, upper],
+        )?;
+        let members = self.remove_archive_members(&path)?;
+        self.invalidate_cache();
+        Ok(removed + members)
+    }
+
+    fn remove_archive_members(&self, archive_path: &str) -> Result<usize> {
+        let conn = self.conn();
+        let (lower, upper) = archive::member_bounds(archive_path);
+        let removed = conn
+            .prepare_cached("DELETE FROM files WHERE path >= ? AND path < ?")?
+            .execute(params![lower, upper])?;
+        Ok(removed)
+    }
+
+    // With a text index enabled, fuzzy names and content queries are
+    // answered by Tantivy (see `enable_text_index`), everything else by
+    // SQLite; `search_ranked` and `count` pick the same way.
     pub fn search(&self, q: &SearchQuery) -> Result<Vec<FileRecord>> {
-        let mut sql = String::from("SELECT path,name,ext,size,modified,added_at,hash FROM files");
-        let mut conds: Vec<String> = Vec::new();
-        let mut params_vec: Vec<Value> = Vec::new();
-
-        if let Some(name) = q.name_like.as_ref().filter(|s| !s.is_empty()) {
-            conds.push("name LIKE ?".to_string());
-            params_vec.push(Value::Text(format!("%{}%", name)));
-        }
-        if let Some(ext) = q.ext.as_ref().filter(|s| !s.is_empty()) {
-            conds.push("ext = ?".to_string());
-            params_vec.push(Value::Text(ext.to_ascii_lowercase()));
-        }
-        if let Some(min_size) = q.min_size {
-            conds.push("size >= ?".to_string());
-            params_vec.push(Value::Integer(min_size));
-        }
-        if let Some(max_size) = q.max_size {
-            conds.push("size <= ?".to_string());
-            params_vec.push(Value::Integer(max_size));
-        }
-        if let Some(date) = q.date_from {
-            let ts = date
-                .and_hms_opt(0, 0, 0)
-                .ok_or_else(|| anyhow!("invalid from date"))?
-                .and_utc()
-                .timestamp();
-            conds.push("modified >= ?".to_string());
-            params_vec.push(Value::Integer(ts));
-        }
-        if let Some(date) = q.date_to {
-            let ts = date
-                .and_hms_opt(23, 59, 59)
-                .ok_or_else(|| anyhow!("invalid to date"))?
-                .and_utc()
-                .timestamp();
-            conds.push("modified <= ?".to_string());
-            params_vec.push(Value::Integer(ts));
-        }
-
-        if !conds.is_empty() {
-            sql.push_str(" WHERE ");
-            sql.push_str(&conds.join(" AND "));
-        }
-        sql.push_str(" ORDER BY ");
-        match q.sort_key.unwrap_or_default() {
-            SortKey::Name => sql.push_str("name"),
-            SortKey::Size => sql.push_str("size"),
-            SortKey::Modified => sql.push_str("modified"),
-        }
-        if q.desc {
-            sql.push_str(" DESC");
-        }
-        if let Some(limit) = q.limit {
-            sql.push_str(&format!(" LIMIT {}", limit));
-        }
-        if let Some(offset) = q.offset {
-            sql.push_str(&format!(" OFFSET {}", offset));
-        }
-
-        let params = params_from_iter(params_vec.into_iter());
-        let mut stmt = self.conn.prepare(&sql)?;
-        let rows = stmt.query_map(params, |row| {
-            let modified_ts = row.get::<_, i64>(4)?;
-            let added_ts = row.get::<_, i64>(5)?;
-            Ok(FileRecord {
-                path: row.get(0)?,
-                name: row.get(1)?,
-                ext: row.get(2)?,
-                size: row.get(3)?,
-                modified: decode_timestamp(modified_ts, "modified", 4)?,
-                added_at: decode_timestamp(added_ts, "added_at", 5)?,
-                hash: row.get(6)?,
-            })
+        #[cfg(feature = "tantivy")]
+        if let Some(rows) = self.search_text(q)? {
+            return Ok(rows.into_iter().map(|row| row.record).collect());
+        }
+        let key = q.normalized();
+        let Some(version) = self.data_version()? else {
+            // Another thread is writing, so nothing would stay valid.
+            return self.search_uncached(&key);
+        };
+        let generation = {
+            let mut cache = self.lock_cache();
+            if let Some(rows) = cache.get(&key, version) {
+                return Ok(rows);
+            }
+            cache.generation()
+        };
+        let rows = self.search_uncached(&key)?;
+        self.lock_cache()
+            .put(key, rows.clone(), version, generation);
+        Ok(rows)
+    }
+
+    // Like `search`, but hands each match to `f` as it is read instead of
+    // collecting them, so memory stays flat however many rows match. Only
+    // relevance ordering has to collect all matches first. Results are not
+    // cached. Returns how many rows were passed to `f`.
+    pub fn for_each_row<F>(&self, q: &SearchQuery, mut f: F) -> Result<usize>
+    where
+        F: FnMut(FileRecord) -> Result<()>,
+    {
+        let q = q.normalized();
+        let mut seen = 0;
+        if q.sort_key == Some(SortKey::Relevance) {
+            for rec in self.search(&q)? {
+                f(rec)?;
+                seen += 1;
+            }
+            return Ok(seen);
+        }
+
+        let conn = self.reader()?;
+        let (sql, params) = Select::filtered(&q)?.rows(&q)?;
+        let (mut skip, limit) = if post_page(&q) {
+            (
+                q.offset.unwrap_or(0).max(0) as usize,
+                q.limit.map_or(usize::MAX, |l| l.max(0) as usize),
+            )
+        } else {
+            (0, usize::MAX)
+        };
+        let fuzzy_name = q.name_like.as_deref().filter(|_| q.fuzzy);
+        let mut stmt = conn.prepare(&sql)?;
+        for rec in stmt.query_map(params_from_iter(params), record_from_row)? {
+            if seen == limit {
+                break;
+            }
+            let rec = rec?;
+            if fuzzy_name.is_some_and(|name| !fuzzy::matches(name, &rec.name)) {
+                continue;
+            }
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            f(rec)?;
+            seen += 1;
+        }
+        Ok(seen)
+    }
+
+    // Total number of entries matching `q`, ignoring its limit, offset and
+    // cursor, e.g. to show "N results" next to one page of them.
+    pub fn count(&self, q: &SearchQuery) -> Result<i64> {
+        let q = SearchQuery {
+            limit: None,
+            offset: None,
+            after: None,
+            ..q.normalized()
+        };
+        #[cfg(feature = "tantivy")]
+        if let Some(rows) = self.search_text(&q)? {
+            return Ok(rows.len() as i64);
+        }
+        if q.fuzzy && q.name_like.is_some() {
+            // Fuzzy matches are only known once rows are read.
+            let q = SearchQuery {
+                sort_key: Some(SortKey::Name),
+                ..q
+            };
+            return Ok(self.for_each_row(&q, |_| Ok(()))? as i64);
+        }
+        let conn = self.reader()?;
+        let (sql, params) = Select::filtered(&q)?.count();
+        Ok(conn.query_row(&sql, params_from_iter(params), |row| row.get(0))?)
+    }
+
+    // SQLite's plan for `search(q)`, one step per line and indented under
+    // its parent, to see which index a slow query uses.
+    pub fn query_plan(&self, q: &SearchQuery) -> Result<Vec<String>> {
+        let q = q.normalized();
+        let (sql, params) = Select::filtered(&q)?.rows(&q)?;
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+        let rows = stmt.query_map(params_from_iter(params), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(3)?,
+            ))
         })?;
-        Ok(rows.filter_map(|r| r.ok()).collect())
+        let mut depths: HashMap<i64, usize> = HashMap::new();
+        let mut plan = Vec::new();
+        for row in rows {
+            let (id, parent, detail) = row?;
+            let depth = depths.get(&parent).map_or(0, |d| d + 1);
+            depths.insert(id, depth);
+            plan.push(format!("{}{}", "  ".repeat(depth), detail));
+        }
+        Ok(plan)
     }
 
-    pub fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT path,name,ext,size,modified,added_at,hash
-             FROM files
-             ORDER BY added_at DESC
-             LIMIT ?",
-        )?;
-        let rows = stmt.query_map(params![limit], |row| {
-            let modified_ts = row.get::<_, i64>(4)?;
-            let added_ts = row.get::<_, i64>(5)?;
-            Ok(FileRecord {
-                path: row.get(0)?,
-                name: row.get(1)?,
-                ext: row.get(2)?,
-                size: row.get(3)?,
-                modified: decode_timestamp(modified_ts, "modified", 4)?,
-                added_at: decode_timestamp(added_ts, "added_at", 5)?,
-                hash: row.get(6)?,
+    pub fn invalidate_cache(&self) {
+        self.lock_cache().invalidate();
+    }
+
+    fn lock_cache(&self) -> MutexGuard<'_, QueryCache> {
+        self.cache.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // Read on the writer, whose counter moves only when other processes
+    // commit; `None` while another thread holds it.
+    fn data_version(&self) -> Result<Option<i64>> {
+        let Some(conn) = self.try_conn() else {
+            return Ok(None);
+        };
+        Ok(Some(
+            conn.query_row("PRAGMA data_version", [], |row| row.get(0))?,
+        ))
+    }
+
+    fn search_uncached(&self, q: &SearchQuery) -> Result<Vec<FileRecord>> {
+        let conn = self.reader()?;
+        let (sql, params_vec) = Select::filtered(q)?.rows(q)?;
+        let name = q.name_like.as_ref().filter(|s| !s.is_empty());
+        let fuzzy_name = name.filter(|_| q.fuzzy);
+        let ranked = q.sort_key == Some(SortKey::Relevance);
+
+        let params = params_from_iter(params_vec);
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params, record_from_row)?;
+        let mut rows: Vec<FileRecord> = rows.filter_map(|r| r.ok()).collect();
+        if let Some(name) = fuzzy_name {
+            rows.retain(|rec| fuzzy::matches(name, &rec.name));
+        }
+        if ranked {
+            rows = rank::sort_by_relevance(rows, name.map(String::as_str), q.desc);
+        }
+        if post_page(q) {
+            rows = rows
+                .into_iter()
+                .skip(q.offset.unwrap_or(0).max(0) as usize)
+                .take(q.limit.map_or(usize::MAX, |l| l.max(0) as usize))
+                .collect();
+        }
+        Ok(rows)
+    }
+
+    pub fn search_ranked(&self, q: &SearchQuery) -> Result<Vec<ScoredRecord>> {
+        #[cfg(feature = "tantivy")]
+        if let Some(rows) = self.search_text(q)? {
+            return Ok(rows);
+        }
+        let rows = self.search(q)?;
+        let name = q.name_like.as_deref();
+        let now = Utc::now();
+        Ok(rows
+            .into_iter()
+            .map(|record| ScoredRecord {
+                score: rank::relevance_score(&record, name, now),
+                record,
             })
-        })?;
+            .collect())
+    }
+
+    // Every indexed copy of the file with this full hash, whatever its name.
+    pub fn find_by_hash(&self, hash: &str) -> Result<Vec<FileRecord>> {
+        self.search(&SearchQuery {
+            hash: Some(hash.to_string()),
+            ..SearchQuery::default()
+        })
+    }
+
+    pub fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files ORDER BY added_at DESC LIMIT ?",
+            RECORD_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![limit], record_from_row)?;
         Ok(rows.filter_map(|r| r.ok()).collect())
     }
 
-    pub fn duplicate_groups(&self, limit: i64) -> Result<Vec<DuplicateGroup>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT hash,size,COUNT(*) as c
-             FROM files
-             WHERE hash IS NOT NULL
-             GROUP BY hash,size
+    // Uses quick hashes to find candidates, fully hashes just those files
+    // and then reports groups confirmed by their full hash. The hashes are
+    // stored, so this needs write access.
+    pub fn verified_duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        self.ensure_writable()?;
+        let candidates: Vec<HashCandidate> = {
+            let conn = self.reader()?;
+            let mut stmt = conn.prepare(
+                "SELECT path,raw_path,hash_algo FROM files
+                 WHERE hash IS NULL AND (quick_hash, size) IN (
+                     SELECT quick_hash, size FROM files
+                     WHERE quick_hash IS NOT NULL
+                     GROUP BY quick_hash, size
+                     HAVING COUNT(*) > 1
+                 )",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.filter_map(|r| r.ok()).collect()
+        };
+        self.fill_hashes(candidates, HashAlgorithm::default())?;
+        self.duplicate_groups(limit, offset, sort)
+    }
+
+    // Likely duplicates in an index built without hashes: regular files of
+    // the same size, and also the same name with `by_name`. Empty files are
+    // left out since they would all land in one group.
+    pub fn candidate_groups(
+        &self,
+        by_name: bool,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<CandidateGroup>> {
+        let conn = self.reader()?;
+        let (key, ties) = if by_name {
+            ("size, name", "size, name")
+        } else {
+            ("size, NULL", "size")
+        };
+        // Ties are broken by the key so pages do not overlap.
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {key}, COUNT(*) AS c FROM files
+             WHERE kind = 'file' AND size > 0
+             GROUP BY {key}
              HAVING c > 1
-             ORDER BY c DESC
-             LIMIT ?",
+             ORDER BY {} DESC, c DESC, {ties}
+             LIMIT ?1 OFFSET ?2",
+            sort.column()
+        ))?;
+        let groups: Vec<(i64, Option<String>, i64)> = stmt
+            .query_map(params![limit, offset], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut stmt_paths = conn.prepare(
+            "SELECT path FROM files
+             WHERE kind = 'file' AND size = ?1 AND (?2 IS NULL OR name = ?2)
+             ORDER BY path",
         )?;
-        let groups = stmt.query_map(params![limit], |row| {
+        let mut out = Vec::new();
+        for (size, name, count) in groups {
+            let paths = stmt_paths
+                .query_map(params![size, name], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            out.push(CandidateGroup {
+                size,
+                name,
+                count,
+                paths,
+            });
+        }
+        Ok(out)
+    }
+
+    // Fully hashes the unhashed members of candidate groups with `algo`,
+    // then reports the groups confirmed by their hash.
+    pub fn hashed_candidate_groups(
+        &self,
+        by_name: bool,
+        algo: HashAlgorithm,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        self.ensure_writable()?;
+        let key = if by_name { "size, name" } else { "size" };
+        let candidates: Vec<HashCandidate> = {
+            let conn = self.reader()?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT path,raw_path,hash_algo FROM files
+                 WHERE kind = 'file' AND hash IS NULL AND ({key}) IN (
+                     SELECT {key} FROM files
+                     WHERE kind = 'file' AND size > 0
+                     GROUP BY {key}
+                     HAVING COUNT(*) > 1
+                 )",
+            ))?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        self.fill_hashes(candidates, algo)?;
+        self.duplicate_groups(limit, offset, sort)
+    }
+
+    // Quick-hashed rows keep their algorithm so both hashes stay comparable.
+    // Files are hashed without holding the writer, which only stores each
+    // batch of results, so the watcher and other writers are not held up.
+    fn fill_hashes(
+        &self,
+        candidates: Vec<HashCandidate>,
+        default_algo: HashAlgorithm,
+    ) -> Result<()> {
+        for batch in candidates.chunks(HASH_BATCH) {
+            // Files that vanished or became unreadable simply stay unhashed.
+            let hashed: Vec<(&str, String, HashAlgorithm)> = batch
+                .iter()
+                .filter_map(|(path, raw_path, algo)| {
+                    let algo = algo.unwrap_or(default_algo);
+                    let fs_path = paths::decode(path, raw_path.as_deref());
+                    let hash = hash::hash_file(&fs_path, algo, HashIo::default()).ok()?;
+                    Some((path.as_str(), hash, algo))
+                })
+                .collect();
+            let conn = self.conn();
+            let txn = conn.unchecked_transaction()?;
+            for (path, hash, algo) in hashed {
+                txn.execute(
+                    "UPDATE files SET hash = ?, hash_algo = ? WHERE path = ?",
+                    params![hash, algo, path],
+                )?;
+            }
+            txn.commit()?;
+            self.invalidate_cache();
+        }
+        Ok(())
+    }
+
+    // One page of the groups of files with the same hash and size, largest
+    // groups first, read with their paths in a single query.
+    pub fn duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "WITH page AS (
+                 SELECT hash, size, c,
+                        ROW_NUMBER() OVER (ORDER BY {} DESC, c DESC, hash, size) AS n
+                 FROM (SELECT hash, size, COUNT(*) AS c FROM files
+                       WHERE hash IS NOT NULL
+                       GROUP BY hash, size
+                       HAVING c > 1)
+                 ORDER BY n
+                 LIMIT ?1 OFFSET ?2
+             )
+             SELECT p.hash, p.size, p.c, f.path
+             FROM page p JOIN files f ON f.hash = p.hash AND f.size = p.size
+             ORDER BY p.n, f.name, f.path",
+            sort.column()
+        ))?;
+        let rows = stmt.query_map(params![limit, offset.max(0)], |row| {
             Ok((
                 row.get::<_, String>(0)?,
                 row.get::<_, i64>(1)?,
                 row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
             ))
         })?;
 
-        let mut out = Vec::new();
-        for group in groups.filter_map(|r| r.ok()) {
-            let mut stmt_paths = self
-                .conn
-                .prepare("SELECT path FROM files WHERE hash = ? ORDER BY name")?;
-            let paths = stmt_paths.query_map(params![&group.0], |row| row.get::<_, String>(0))?;
-            let mut collected = Vec::new();
-            for path in paths.filter_map(|r| r.ok()) {
-                collected.push(path);
+        let mut out: Vec<DuplicateGroup> = Vec::new();
+        for row in rows {
+            let (hash, size, count, path) = row?;
+            match out.last_mut() {
+                Some(group) if group.hash == hash && group.size == size => group.paths.push(path),
+                _ => out.push(DuplicateGroup {
+                    hash,
+                    size,
+                    count,
+                    wasted: (count - 1) * size,
+                    paths: vec![path],
+                }),
             }
-            out.push(DuplicateGroup {
-                hash: group.0,
-                size: group.1,
-                count: group.2,
-                paths: collected,
-            });
         }
         Ok(out)
     }
 }
 
-fn compute_hash(path: &Path) -> Result<String> {
-    let mut file =
-        File::open(path).with_context(|| format!("opening {} for hashing", path.display()))?;
-    let mut hasher = Hasher::new();
-    let mut buf = [0u8; 8192];
-    loop {
-        let read = file
-            .read(&mut buf)
-            .with_context(|| format!("reading {} for hashing", path.display()))?;
-        if read == 0 {
-            break;
+impl IndexStore for FileIndexer {
+    fn index_dir(&self, root: &Path, opts: &IndexOptions) -> Result<IndexReport> {
+        FileIndexer::index_dir(self, root, opts)
+    }
+
+    fn upsert(&self, rec: &FileRecord) -> Result<()> {
+        FileIndexer::upsert(self, rec)
+    }
+
+    fn upsert_many(&self, recs: &[FileRecord]) -> Result<()> {
+        FileIndexer::upsert_many(self, recs)
+    }
+
+    fn remove(&self, path: &Path) -> Result<usize> {
+        FileIndexer::remove(self, path)
+    }
+
+    fn search(&self, q: &SearchQuery) -> Result<Vec<FileRecord>> {
+        FileIndexer::search(self, q)
+    }
+
+    fn count(&self, q: &SearchQuery) -> Result<i64> {
+        FileIndexer::count(self, q)
+    }
+
+    fn find_by_hash(&self, hash: &str) -> Result<Vec<FileRecord>> {
+        FileIndexer::find_by_hash(self, hash)
+    }
+
+    fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>> {
+        FileIndexer::recently_added(self, limit)
+    }
+
+    fn duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        FileIndexer::duplicate_groups(self, limit, offset, sort)
+    }
+}
+
+// Path, raw path and recorded algorithm of a file to hash.
+type HashCandidate = (String, Option<Vec<u8>>, Option<HashAlgorithm>);
+
+// Files hashed between writes when filling in missing hashes.
+const HASH_BATCH: usize = 64;
+
+// Columns of `files` in the order `record_from_row` reads them.
+pub(crate) const RECORD_COLUMNS: &str =
+    "path,name,ext,size,modified,added_at,hash,kind,link_target,mime,detected_mime,type_mismatch,
+     duration,bitrate,width,height,codec,artist,album,title,quick_hash,hash_algo,uid,gid,mode,
+     attributes,image_hash,similarity,raw_path,rating,hash_skipped,scheme,volume,etag,
+     allocated";
+
+pub(crate) fn record_from_row(row: &Row<'_>) -> rusqlite::Result<FileRecord> {
+    let modified_ts = row.get::<_, i64>(4)?;
+    let added_ts = row.get::<_, i64>(5)?;
+    Ok(FileRecord {
+        path: row.get(0)?,
+        name: row.get(1)?,
+        ext: row.get(2)?,
+        size: row.get(3)?,
+        modified: decode_timestamp(modified_ts, "modified", 4)?,
+        added_at: decode_timestamp(added_ts, "added_at", 5)?,
+        hash: row.get(6)?,
+        kind: row.get(7)?,
+        link_target: row.get(8)?,
+        mime: row.get(9)?,
+        detected_mime: row.get(10)?,
+        type_mismatch: row.get(11)?,
+        media: Some(MediaInfo {
+            duration: row.get(12)?,
+            bitrate: row.get(13)?,
+            width: row.get(14)?,
+            height: row.get(15)?,
+            codec: row.get(16)?,
+            artist: row.get(17)?,
+            album: row.get(18)?,
+            title: row.get(19)?,
+        })
+        .filter(|media| !media.is_empty()),
+        quick_hash: row.get(20)?,
+        hash_algo: row.get(21)?,
+        uid: row.get(22)?,
+        gid: row.get(23)?,
+        mode: row.get(24)?,
+        attributes: row.get(25)?,
+        image_hash: row.get::<_, Option<i64>>(26)?.map(|hash| hash as u64),
+        similarity: row.get(27)?,
+        raw_path: row.get(28)?,
+        rating: row.get(29)?,
+        hash_skipped: row.get(30)?,
+        scheme: row.get(31)?,
+        volume: row.get(32)?,
+        etag: row.get(33)?,
+        allocated: row.get(34)?,
+    })
+}
+
+pub(crate) fn normalized_exts(exts: &[String]) -> Vec<String> {
+    let mut exts: Vec<String> = exts
+        .iter()
+        .map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect();
+    exts.sort();
+    exts.dedup();
+    exts
+}
+
+// Half-open range of path strings that sort under `dir`, usable against the
+// primary key index: `path >= lower AND path < upper`.
+pub(crate) fn subtree_bounds(dir: &str) -> (String, String) {
+    let lower = format!("{}{}", dir.trim_end_matches(['/', '\\']), MAIN_SEPARATOR);
+    let upper = format!("{}\u{10FFFF}", lower);
+    (lower, upper)
+}
+
+// SQLite URI opening `path` as immutable: no locking and no WAL files.
+fn immutable_uri(path: &Path) -> String {
+    let mut uri = String::from("file:");
+    let path = path.to_string_lossy().replace('\\', "/");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for c in path.chars() {
+        match c {
+            '%' => uri.push_str("%25"),
+            '?' => uri.push_str("%3f"),
+            '#' => uri.push_str("%23"),
+            c => uri.push(c),
+        }
+    }
+    uri.push_str("?immutable=1");
+    uri
+}
+
+pub(crate) fn build_record(path: &Path, opts: &IndexOptions) -> Result<FileRecord> {
+    let metadata = path
+        .metadata()
+        .with_context(|| format!("reading metadata for {}", path.display()))?;
+    if !metadata.is_file() {
+        return Err(anyhow!("{} is not a regular file", path.display()));
+    }
+    let mut record = base_record(path, &metadata)?;
+    record.hash_skipped = opts.hash.then(|| hash_skip(opts, &record)).flatten();
+    if opts.hash && record.hash_skipped.is_none() {
+        match opts.hash_mode {
+            HashMode::Full => {
+                let io = HashIo {
+                    buffer_size: opts.hash_buffer_size,
+                    uncached: opts.uncached_io,
+                };
+                record.hash = Some(hash::hash_file(path, opts.hash_algo, io)?)
+            }
+            HashMode::Quick => {
+                record.quick_hash = Some(hash::quick_hash_file(path, record.size, opts.hash_algo)?)
+            }
         }
-        hasher.update(&buf[..read]);
+        record.hash_algo = Some(opts.hash_algo);
+    }
+    let sniffed = if opts.detect_type || (opts.sniff_mime && record.mime.is_none()) {
+        filetype::sniff_mime(path)
+    } else {
+        None
+    };
+    if opts.detect_type {
+        record.type_mismatch = match (&record.ext, &sniffed) {
+            (Some(ext), Some(mime)) => !filetype::ext_matches_mime(ext, mime),
+            _ => false,
+        };
+        record.detected_mime = sniffed.clone();
+    }
+    if record.mime.is_none() {
+        record.mime = sniffed;
+    }
+    let is_media = record
+        .mime
+        .as_deref()
+        .is_some_and(|m| m.starts_with("audio/") || m.starts_with("video/"));
+    if opts.media_metadata && is_media {
+        record.media = media::read_media(path, record.ext.as_deref(), record.size);
+    }
+    let is_image = record
+        .mime
+        .as_deref()
+        .is_some_and(|m| m.starts_with("image/"));
+    if opts.perceptual_hash && is_image {
+        record.image_hash = imagehash::dhash(path);
+    }
+    if opts.similarity_digest {
+        record.similarity = similarity::digest(path)?;
+    }
+    Ok(record)
+}
+
+pub(crate) fn build_symlink_record(path: &Path) -> Result<FileRecord> {
+    let metadata = path
+        .symlink_metadata()
+        .with_context(|| format!("reading link metadata for {}", path.display()))?;
+    let target = fs::read_link(path)
+        .with_context(|| format!("reading link target for {}", path.display()))?;
+    let mut record = base_record(path, &metadata)?;
+    record.kind = if path.exists() {
+        EntryKind::Symlink
+    } else {
+        EntryKind::BrokenSymlink
+    };
+    record.link_target = Some(target.to_string_lossy().to_string());
+    Ok(record)
+}
+
+// Stands in for a file a scan could not read; see `EntryKind::Unreachable`.
+pub(crate) fn unreachable_record(path: &Path) -> Result<FileRecord> {
+    let mut record = named_record(path)?;
+    record.kind = EntryKind::Unreachable;
+    Ok(record)
+}
+
+fn base_record(path: &Path, metadata: &Metadata) -> Result<FileRecord> {
+    let mut record = named_record(path)?;
+    record.size = i64::try_from(metadata.len())
+        .with_context(|| format!("file is larger than 9 exabytes: {}", path.display()))?;
+    let modified = metadata
+        .modified()
+        .with_context(|| format!("missing modified time for {}", path.display()))?;
+    record.modified = DateTime::<Utc>::from(modified);
+    (record.uid, record.gid, record.mode) = ownership(metadata);
+    record.attributes = attributes::of(metadata);
+    if metadata.is_file() {
+        record.allocated = allocated_size(path, metadata);
+    }
+    record.volume = volume::of(path, metadata).map(|volume| volume.id);
+    Ok(record)
+}
+
+// What the path alone tells about a file.
+fn named_record(path: &Path) -> Result<FileRecord> {
+    let name = path
+        .file_name()
+        .map(|name| paths::key(Path::new(name)))
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))?;
+    let (key, raw_path) = paths::encode(path);
+    let ext = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_ascii_lowercase());
+    let mime = ext.as_deref().and_then(filetype::mime_from_ext);
+    Ok(FileRecord {
+        path: key,
+        name,
+        ext,
+        size: 0,
+        modified: DateTime::UNIX_EPOCH,
+        added_at: Utc::now(),
+        hash: None,
+        kind: EntryKind::File,
+        link_target: None,
+        mime,
+        detected_mime: None,
+        type_mismatch: false,
+        media: None,
+        quick_hash: None,
+        hash_algo: None,
+        uid: None,
+        gid: None,
+        mode: None,
+        attributes: None,
+        image_hash: None,
+        similarity: None,
+        raw_path,
+        rating: 0,
+        hash_skipped: None,
+        scheme: None,
+        volume: None,
+        etag: None,
+        allocated: None,
+    })
+}
+
+pub(crate) fn hash_skip(opts: &IndexOptions, record: &FileRecord) -> Option<HashSkip> {
+    if opts
+        .hash_max_size
+        .is_some_and(|max| record.size as u64 > max)
+    {
+        return Some(HashSkip::TooLarge);
+    }
+    let ext = record.ext.as_deref()?;
+    normalized_exts(&opts.hash_skip_exts)
+        .iter()
+        .any(|skip| skip == ext)
+        .then_some(HashSkip::Extension)
+}
+
+#[cfg(unix)]
+fn ownership(metadata: &Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    use std::os::unix::fs::MetadataExt;
+    (
+        Some(metadata.uid()),
+        Some(metadata.gid()),
+        Some(metadata.mode() & 0o7777),
+    )
+}
+
+#[cfg(not(unix))]
+fn ownership(_metadata: &Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    (None, None, None)
+}
+
+#[cfg(unix)]
+fn allocated_size(_path: &Path, metadata: &Metadata) -> Option<i64> {
+    use std::os::unix::fs::MetadataExt;
+    // Counted in 512-byte units whatever the file system block size.
+    i64::try_from(metadata.blocks()).ok()?.checked_mul(512)
+}
+
+// Compressed or sparse files report less than their size here.
+#[cfg(windows)]
+fn allocated_size(path: &Path, _metadata: &Metadata) -> Option<i64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{GetLastError, NO_ERROR};
+    use windows_sys::Win32::Storage::FileSystem::{GetCompressedFileSizeW, INVALID_FILE_SIZE};
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut high = 0u32;
+    // SAFETY: `wide` is NUL-terminated and `high` outlives the call.
+    let low = unsafe { GetCompressedFileSizeW(wide.as_ptr(), &mut high) };
+    if low == INVALID_FILE_SIZE && unsafe { GetLastError() } != NO_ERROR {
+        return None;
     }
-    Ok(hasher.finalize().to_hex().to_string())
+    Some(((high as i64) << 32) | low as i64)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn allocated_size(_path: &Path, _metadata: &Metadata) -> Option<i64> {
+    None
 }
 
 fn decode_timestamp(
@@ -342,3 +1727,44 @@ impl fmt::Display for TimestampOutOfRange {
 }
 
 impl std::error::Error for TimestampOutOfRange {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_hashes_across_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        // Pairs of equal files, all the same size, plus one of a kind.
+        let pairs = HASH_BATCH + 3;
+        for i in 0..pairs {
+            for copy in ["a", "b"] {
+                let path = dir.path().join(format!("{}{:03}", copy, i));
+                std::fs::write(path, format!("{:08}", i)).unwrap();
+            }
+        }
+        std::fs::write(dir.path().join("single"), "99999999").unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let indexer = FileIndexer::new(db.path().join("index.db")).unwrap();
+        indexer
+            .index_dir(dir.path(), &IndexOptions::default())
+            .unwrap();
+
+        let groups = indexer
+            .hashed_candidate_groups(
+                false,
+                HashAlgorithm::default(),
+                1000,
+                0,
+                DuplicateSort::Count,
+            )
+            .unwrap();
+        assert_eq!(groups.len(), pairs);
+        assert!(groups.iter().all(|g| g.count == 2));
+        let unhashed = SearchQuery {
+            has_hash: Some(false),
+            ..SearchQuery::default()
+        };
+        assert!(indexer.search(&unhashed).unwrap().is_empty());
+    }
+}
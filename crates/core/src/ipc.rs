@@ -0,0 +1,435 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cancel::CancellationToken;
+use crate::hash::HashAlgorithm;
+use crate::indexer::{
+    CandidateGroup, DuplicateGroup, DuplicateSort, FileIndexer, FileRecord, IndexOptions,
+    IndexReport, SearchQuery,
+};
+use crate::prune::PruneReport;
+use crate::rank::ScoredRecord;
+use crate::roots::RootInfo;
+use crate::stats::{AgeBucket, IndexStats};
+use crate::suggest::Suggestion;
+use crate::volume::VolumeInfo;
+
+// One call of a `FileIndexer` method, sent by `RemoteIndex` to a process
+// that serves the database with `FileIndexer::serve`. Each connection
+// carries one request and one response, as a line of JSON each.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum Request {
+    Search(SearchQuery),
+    SearchRanked(SearchQuery),
+    Count(SearchQuery),
+    QueryPlan(SearchQuery),
+    RecentlyAdded {
+        limit: i64,
+    },
+    DuplicateGroups {
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    },
+    VerifiedDuplicateGroups {
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    },
+    CandidateGroups {
+        by_name: bool,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    },
+    HashedCandidateGroups {
+        by_name: bool,
+        algo: HashAlgorithm,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    },
+    Stats,
+    AgeHistogram,
+    SuggestExtensions {
+        partial: String,
+        limit: i64,
+    },
+    SuggestDirs {
+        partial: String,
+        limit: i64,
+    },
+    SuggestNameTokens {
+        partial: String,
+        limit: i64,
+    },
+    ListRoots,
+    Volumes,
+    DisconnectedVolumes,
+    IndexDir {
+        root: PathBuf,
+        options: IndexOptions,
+    },
+    ReindexAll,
+    Prune {
+        root: Option<PathBuf>,
+    },
+    PruneDryRun {
+        root: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ok(Value),
+    Error(String),
+}
+
+// `IndexReport` with its errors as text.
+#[derive(Debug, Serialize, Deserialize)]
+struct Report {
+    indexed: usize,
+    skipped: usize,
+    unchanged: usize,
+    unreachable: usize,
+    errors: Vec<(PathBuf, String)>,
+}
+
+impl From<IndexReport> for Report {
+    fn from(report: IndexReport) -> Self {
+        Self {
+            indexed: report.indexed,
+            skipped: report.skipped,
+            unchanged: report.unchanged,
+            unreachable: report.unreachable,
+            errors: report
+                .errors
+                .into_iter()
+                .map(|(path, err)| (path, format!("{:#}", err)))
+                .collect(),
+        }
+    }
+}
+
+impl From<Report> for IndexReport {
+    fn from(report: Report) -> Self {
+        Self {
+            indexed: report.indexed,
+            skipped: report.skipped,
+            unchanged: report.unchanged,
+            unreachable: report.unreachable,
+            errors: report
+                .errors
+                .into_iter()
+                .map(|(path, err)| (path, anyhow!(err)))
+                .collect(),
+        }
+    }
+}
+
+// Where the daemon of the database at `db` listens: `<db>.sock`.
+pub fn socket_path(db: &Path) -> PathBuf {
+    let mut path = db.as_os_str().to_owned();
+    path.push(".sock");
+    path.into()
+}
+
+impl FileIndexer {
+    // Answers `RemoteIndex` calls on a Unix socket at `socket` until the
+    // process ends, each connection on its own thread. Only the owner may
+    // connect. A socket left behind by a daemon that died is replaced.
+    pub fn serve(&self, socket: &Path) -> Result<()> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(anyhow!(
+                    "a daemon is already listening on {}",
+                    socket.display()
+                ));
+            }
+            fs::remove_file(socket).with_context(|| format!("removing {}", socket.display()))?;
+        }
+        let listener = UnixListener::bind(socket)
+            .with_context(|| format!("listening on {}", socket.display()))?;
+        fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                scope.spawn(move || {
+                    // A client that went away has nobody to tell.
+                    let _ = self.answer(stream);
+                });
+            }
+        });
+        Ok(())
+    }
+
+    fn answer(&self, stream: UnixStream) -> Result<()> {
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        let response = match serde_json::from_str(&line)
+            .context("reading the request")
+            .and_then(|request| self.call(request))
+        {
+            Ok(value) => Response::Ok(value),
+            Err(err) => Response::Error(format!("{:#}", err)),
+        };
+        let mut stream = &stream;
+        serde_json::to_writer(&mut stream, &response)?;
+        stream.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn call(&self, request: Request) -> Result<Value> {
+        let value = match request {
+            Request::Search(q) => serde_json::to_value(self.search(&q)?)?,
+            Request::SearchRanked(q) => serde_json::to_value(self.search_ranked(&q)?)?,
+            Request::Count(q) => serde_json::to_value(self.count(&q)?)?,
+            Request::QueryPlan(q) => serde_json::to_value(self.query_plan(&q)?)?,
+            Request::RecentlyAdded { limit } => serde_json::to_value(self.recently_added(limit)?)?,
+            Request::DuplicateGroups {
+                limit,
+                offset,
+                sort,
+            } => serde_json::to_value(self.duplicate_groups(limit, offset, sort)?)?,
+            Request::VerifiedDuplicateGroups {
+                limit,
+                offset,
+                sort,
+            } => serde_json::to_value(self.verified_duplicate_groups(limit, offset, sort)?)?,
+            Request::CandidateGroups {
+                by_name,
+                limit,
+                offset,
+                sort,
+            } => serde_json::to_value(self.candidate_groups(by_name, limit, offset, sort)?)?,
+            Request::HashedCandidateGroups {
+                by_name,
+                algo,
+                limit,
+                offset,
+                sort,
+            } => serde_json::to_value(
+                self.hashed_candidate_groups(by_name, algo, limit, offset, sort)?,
+            )?,
+            Request::Stats => serde_json::to_value(self.stats()?)?,
+            Request::AgeHistogram => serde_json::to_value(self.age_histogram()?)?,
+            Request::SuggestExtensions { partial, limit } => {
+                serde_json::to_value(self.suggest_extensions(&partial, limit)?)?
+            }
+            Request::SuggestDirs { partial, limit } => {
+                serde_json::to_value(self.suggest_dirs(&partial, limit)?)?
+            }
+            Request::SuggestNameTokens { partial, limit } => {
+                serde_json::to_value(self.suggest_name_tokens(&partial, limit)?)?
+            }
+            Request::ListRoots => serde_json::to_value(self.list_roots()?)?,
+            Request::Volumes => serde_json::to_value(self.volumes()?)?,
+            Request::DisconnectedVolumes => serde_json::to_value(self.disconnected_volumes()?)?,
+            Request::IndexDir { root, options } => {
+                serde_json::to_value(Report::from(self.index_dir(root, &options)?))?
+            }
+            Request::ReindexAll => {
+                serde_json::to_value(Report::from(self.reindex_all(&CancellationToken::new())?))?
+            }
+            Request::Prune { root } => serde_json::to_value(self.prune(root.as_deref())?)?,
+            Request::PruneDryRun { root } => {
+                serde_json::to_value(self.prune_dry_run(root.as_deref())?)?
+            }
+        };
+        Ok(value)
+    }
+}
+
+// The methods of `FileIndexer` that a daemon answers, called over its
+// socket, so several processes can share one database without contending
+// for its locks.
+#[derive(Debug, Clone)]
+pub struct RemoteIndex {
+    socket: PathBuf,
+}
+
+impl RemoteIndex {
+    pub fn new<P: AsRef<Path>>(socket: P) -> Self {
+        Self {
+            socket: socket.as_ref().to_path_buf(),
+        }
+    }
+
+    fn call<T: DeserializeOwned>(&self, request: &Request) -> Result<T> {
+        let stream = UnixStream::connect(&self.socket).with_context(|| {
+            format!(
+                "connecting to the daemon at {}; is it running?",
+                self.socket.display()
+            )
+        })?;
+        let mut writer = &stream;
+        serde_json::to_writer(&mut writer, request)?;
+        writer.write_all(b"\n")?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        if line.is_empty() {
+            return Err(anyhow!("the daemon closed the connection"));
+        }
+        match serde_json::from_str(&line).context("reading the daemon's answer")? {
+            Response::Ok(value) => Ok(serde_json::from_value(value)?),
+            Response::Error(err) => Err(anyhow!(err)),
+        }
+    }
+
+    pub fn search(&self, q: &SearchQuery) -> Result<Vec<FileRecord>> {
+        self.call(&Request::Search(q.clone()))
+    }
+
+    pub fn search_ranked(&self, q: &SearchQuery) -> Result<Vec<ScoredRecord>> {
+        self.call(&Request::SearchRanked(q.clone()))
+    }
+
+    pub fn count(&self, q: &SearchQuery) -> Result<i64> {
+        self.call(&Request::Count(q.clone()))
+    }
+
+    pub fn query_plan(&self, q: &SearchQuery) -> Result<Vec<String>> {
+        self.call(&Request::QueryPlan(q.clone()))
+    }
+
+    pub fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>> {
+        self.call(&Request::RecentlyAdded { limit })
+    }
+
+    pub fn duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        self.call(&Request::DuplicateGroups {
+            limit,
+            offset,
+            sort,
+        })
+    }
+
+    pub fn verified_duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        self.call(&Request::VerifiedDuplicateGroups {
+            limit,
+            offset,
+            sort,
+        })
+    }
+
+    pub fn candidate_groups(
+        &self,
+        by_name: bool,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<CandidateGroup>> {
+        self.call(&Request::CandidateGroups {
+            by_name,
+            limit,
+            offset,
+            sort,
+        })
+    }
+
+    pub fn hashed_candidate_groups(
+        &self,
+        by_name: bool,
+        algo: HashAlgorithm,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        self.call(&Request::HashedCandidateGroups {
+            by_name,
+            algo,
+            limit,
+            offset,
+            sort,
+        })
+    }
+
+    pub fn stats(&self) -> Result<IndexStats> {
+        self.call(&Request::Stats)
+    }
+
+    pub fn age_histogram(&self) -> Result<Vec<AgeBucket>> {
+        self.call(&Request::AgeHistogram)
+    }
+
+    pub fn suggest_extensions(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        self.call(&Request::SuggestExtensions {
+            partial: partial.to_string(),
+            limit,
+        })
+    }
+
+    pub fn suggest_dirs(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        self.call(&Request::SuggestDirs {
+            partial: partial.to_string(),
+            limit,
+        })
+    }
+
+    pub fn suggest_name_tokens(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        self.call(&Request::SuggestNameTokens {
+            partial: partial.to_string(),
+            limit,
+        })
+    }
+
+    pub fn list_roots(&self) -> Result<Vec<RootInfo>> {
+        self.call(&Request::ListRoots)
+    }
+
+    pub fn volumes(&self) -> Result<Vec<VolumeInfo>> {
+        self.call(&Request::Volumes)
+    }
+
+    pub fn disconnected_volumes(&self) -> Result<Vec<VolumeInfo>> {
+        self.call(&Request::DisconnectedVolumes)
+    }
+
+    // The daemon may run elsewhere in the file system, so relative paths
+    // are resolved here first.
+    pub fn index_dir<P: AsRef<Path>>(&self, root: P, opts: &IndexOptions) -> Result<IndexReport> {
+        let report: Report = self.call(&Request::IndexDir {
+            root: std::path::absolute(root)?,
+            options: opts.clone(),
+        })?;
+        Ok(report.into())
+    }
+
+    pub fn reindex_all(&self) -> Result<IndexReport> {
+        let report: Report = self.call(&Request::ReindexAll)?;
+        Ok(report.into())
+    }
+
+    pub fn prune(&self, root: Option<&Path>) -> Result<PruneReport> {
+        self.call(&Request::Prune {
+            root: root.map(std::path::absolute).transpose()?,
+        })
+    }
+
+    pub fn prune_dry_run(&self, root: Option<&Path>) -> Result<PruneReport> {
+        self.call(&Request::PruneDryRun {
+            root: root.map(std::path::absolute).transpose()?,
+        })
+    }
+}
@@ -1,3 +1,108 @@
+pub mod activity;
+mod archive;
+pub mod attributes;
+mod cache;
+pub mod cancel;
+pub mod chunks;
+pub mod collections;
+pub mod cursor;
+pub mod dedupe;
+pub mod deleted;
+pub mod dirs;
+pub mod empty;
+pub mod export;
+pub mod extract;
+mod filetype;
+mod fold;
+mod fuzzy;
+pub mod hash;
+pub mod imagehash;
+pub mod import;
 pub mod indexer;
+#[cfg(unix)]
+pub mod ipc;
+pub mod maintenance;
+pub mod media;
+pub mod merge;
+pub mod notes;
+#[cfg(windows)]
+mod ntfs;
+mod path_filter;
+mod paths;
+mod pattern;
+mod pool;
+pub mod prune;
+pub mod query;
+pub mod rank;
+pub mod rating;
+#[cfg(feature = "redb")]
+pub mod redb_store;
+pub mod renames;
+pub mod roots;
+pub mod s3;
+mod scan;
+pub mod scans;
+mod schema;
+mod select;
+pub mod sftp;
+pub mod similarity;
+pub mod source;
+pub mod stats;
+pub mod store;
+pub mod suggest;
+pub mod tags;
+#[cfg(feature = "tantivy")]
+mod text_index;
+pub mod trash;
+mod uncached;
+pub mod verify;
+pub mod volume;
+mod watchdog;
+pub mod watcher;
+pub mod webdav;
+pub mod xattrs;
 
-pub use indexer::{DuplicateGroup, FileIndexer, FileRecord, SearchQuery, SortKey};
+pub use activity::{ActivityGroup, ActivityKind};
+pub use cancel::CancellationToken;
+pub use chunks::ChunkOverlap;
+pub use collections::CollectionInfo;
+pub use cursor::Cursor;
+pub use dedupe::{DedupeAction, DedupeOptions, DedupeReport, DedupeStep, KeepStrategy};
+pub use deleted::DeletedFile;
+pub use dirs::DirRecord;
+pub use empty::CleanReport;
+pub use export::ExportFormat;
+pub use extract::ContentExtractor;
+pub use hash::{HashAlgorithm, HashMode, HashSkip, Hasher};
+pub use imagehash::ImageGroup;
+pub use import::{ImportFormat, ImportReport};
+pub use indexer::{
+    CandidateGroup, DuplicateGroup, DuplicateSort, EntryKind, FileIndexer, FileRecord,
+    IndexOptions, IndexReport, SearchQuery, SortKey,
+};
+#[cfg(unix)]
+pub use ipc::{socket_path, RemoteIndex};
+pub use maintenance::{MaintenanceOptions, MaintenanceReport};
+pub use media::MediaInfo;
+pub use merge::{MergeReport, MergeStrategy};
+pub use prune::PruneReport;
+pub use rank::ScoredRecord;
+pub use rating::MAX_RATING;
+#[cfg(feature = "redb")]
+pub use redb_store::RedbStore;
+pub use renames::RenameRecord;
+pub use roots::RootInfo;
+pub use s3::S3Source;
+pub use scans::{ScanDiff, ScanEntry, ScanError, ScanInfo};
+pub use sftp::SftpSource;
+pub use similarity::SimilarFile;
+pub use source::{FolderTags, ScanSource, SourceEntry};
+pub use stats::{AgeBucket, ExtensionStats, IndexStats, SizeBucket, WastedSpace};
+pub use store::IndexStore;
+pub use suggest::Suggestion;
+pub use tags::TagInfo;
+pub use verify::{HashMismatch, VerifyReport};
+pub use volume::VolumeInfo;
+pub use watcher::{WatchEvent, Watcher, DEFAULT_DEBOUNCE};
+pub use webdav::WebDavSource;
+pub use xattrs::Xattr;
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::FileIndexer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceOptions {
+    // Rebuild the database file, returning pages freed by deletes to the OS.
+    pub vacuum: bool,
+    // Refresh the statistics the query planner uses to pick indexes.
+    pub analyze: bool,
+    pub integrity_check: bool,
+}
+
+impl Default for MaintenanceOptions {
+    fn default() -> Self {
+        Self {
+            vacuum: true,
+            analyze: true,
+            integrity_check: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    pub size_before: u64,
+    pub size_after: u64,
+    pub vacuumed: bool,
+    pub analyzed: bool,
+    // `None` when the check was not run; an empty list means no problems.
+    pub integrity_errors: Option<Vec<String>>,
+}
+
+impl FileIndexer {
+    // Runs the integrity check first so a damaged database is reported
+    // before VACUUM rewrites it.
+    pub fn maintain(&self, opts: &MaintenanceOptions) -> Result<MaintenanceReport> {
+        let conn = self.conn();
+        if opts.vacuum || opts.analyze {
+            self.ensure_writable()?;
+        }
+        let mut report = MaintenanceReport {
+            size_before: self.database_size()?,
+            ..MaintenanceReport::default()
+        };
+        if opts.integrity_check {
+            let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            let errors: Vec<String> = rows
+                .collect::<rusqlite::Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|line| line != "ok")
+                .collect();
+            report.integrity_errors = Some(errors);
+        }
+        if opts.vacuum {
+            conn.execute_batch("VACUUM")?;
+            // VACUUM goes through the WAL; fold it back so the file shrinks now.
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+            report.vacuumed = true;
+        }
+        if opts.analyze {
+            conn.execute_batch("ANALYZE")?;
+            report.analyzed = true;
+        }
+        report.size_after = self.database_size()?;
+        Ok(report)
+    }
+
+    // A sampled ANALYZE, cheap enough to run after every large scan so the
+    // planner picks the right index once a table has grown.
+    pub(crate) fn refresh_statistics(&self) -> Result<()> {
+        self.conn()
+            .execute_batch("PRAGMA analysis_limit = 1000; ANALYZE; PRAGMA analysis_limit = 0;")?;
+        Ok(())
+    }
+
+    // Bytes used by the main database file, excluding the WAL.
+    fn database_size(&self) -> Result<u64> {
+        let conn = self.reader()?;
+        let size: i64 = conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(size as u64)
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+// Refuse to buffer absurd `moov` boxes from corrupt files.
+const MAX_MOOV_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub duration: Option<f64>,
+    // Average bits per second over the whole file.
+    pub bitrate: Option<i64>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub codec: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+}
+
+impl MediaInfo {
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == MediaInfo::default()
+    }
+}
+
+pub(crate) fn read_media(path: &Path, ext: Option<&str>, size: i64) -> Option<MediaInfo> {
+    let mut info = MediaInfo::default();
+    #[cfg(feature = "media")]
+    probe_audio(path, ext, &mut info);
+    if matches!(ext, Some("mp4" | "m4v" | "mov" | "m4a" | "3gp")) {
+        read_mp4(path, &mut info);
+    }
+    if let Some(duration) = info.duration.filter(|d| *d > 0.0) {
+        info.bitrate = Some((size as f64 * 8.0 / duration) as i64);
+    }
+    (!info.is_empty()).then_some(info)
+}
+
+#[cfg(feature = "media")]
+fn probe_audio(path: &Path, ext: Option<&str>, info: &mut MediaInfo) {
+    use symphonia::core::codecs::CODEC_TYPE_NULL;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+    use symphonia::core::probe::Hint;
+
+    fn read_tags(rev: &MetadataRevision, info: &mut MediaInfo) {
+        for tag in rev.tags() {
+            let slot = match tag.std_key {
+                Some(StandardTagKey::Artist) => &mut info.artist,
+                Some(StandardTagKey::Album) => &mut info.album,
+                Some(StandardTagKey::TrackTitle) => &mut info.title,
+                _ => continue,
+            };
+            if slot.is_none() {
+                *slot = Some(tag.value.to_string());
+            }
+        }
+    }
+
+    let Ok(file) = File::open(path) else {
+        return;
+    };
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = ext {
+        hint.with_extension(ext);
+    }
+    let Ok(mut probed) = symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    ) else {
+        return;
+    };
+
+    // Tags may sit in front of the container (ID3v2) or inside it.
+    if let Some(rev) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        read_tags(rev, info);
+    }
+    if let Some(rev) = probed.format.metadata().current() {
+        read_tags(rev, info);
+    }
+
+    let Some(track) = probed
+        .format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+    else {
+        return;
+    };
+    let params = &track.codec_params;
+    if let (Some(frames), Some(time_base)) = (params.n_frames, params.time_base) {
+        let time = time_base.calc_time(frames);
+        info.duration = Some(time.seconds as f64 + time.frac);
+    }
+    info.codec = symphonia::default::get_codecs()
+        .get_codec(params.codec)
+        .map(|codec| codec.short_name.to_string());
+}
+
+// Reads duration, frame size and video codec from the `moov` box of
+// MP4/QuickTime files, which symphonia only parses for audio.
+fn read_mp4(path: &Path, info: &mut MediaInfo) -> Option<()> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let mut pos = 0u64;
+    let moov = loop {
+        if pos + 8 > len {
+            return None;
+        }
+        file.seek(SeekFrom::Start(pos)).ok()?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8]).ok()?;
+        let mut size = u32::from_be_bytes(header[..4].try_into().ok()?) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            file.read_exact(&mut header[8..]).ok()?;
+            size = u64::from_be_bytes(header[8..].try_into().ok()?);
+            header_len = 16;
+        } else if size == 0 {
+            size = len - pos;
+        }
+        if size < header_len {
+            return None;
+        }
+        if &header[4..8] == b"moov" {
+            let body = size - header_len;
+            if body > MAX_MOOV_BYTES {
+                return None;
+            }
+            let mut buf = vec![0u8; body as usize];
+            file.read_exact(&mut buf).ok()?;
+            break buf;
+        }
+        pos += size;
+    };
+
+    for (kind, body) in boxes(&moov) {
+        match kind {
+            b"mvhd" if info.duration.is_none() => {
+                let (timescale, duration) = match body.first()? {
+                    1 => (be_u32(body, 20)? as u64, be_u64(body, 24)?),
+                    _ => (be_u32(body, 12)? as u64, be_u32(body, 16)? as u64),
+                };
+                if timescale > 0 {
+                    info.duration = Some(duration as f64 / timescale as f64);
+                }
+            }
+            b"trak" => {
+                read_track(body, info);
+            }
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+fn read_track(trak: &[u8], info: &mut MediaInfo) -> Option<()> {
+    if info.width.is_some() {
+        return None;
+    }
+    // Width and height are the last two 16.16 fixed-point fields.
+    let tkhd = find_box(trak, b"tkhd")?;
+    let width = be_u32(tkhd, tkhd.len().checked_sub(8)?)? >> 16;
+    let height = be_u32(tkhd, tkhd.len() - 4)? >> 16;
+    // Audio tracks have a zero frame size.
+    if width > 0 && height > 0 {
+        info.width = Some(width as i64);
+        info.height = Some(height as i64);
+        info.codec = video_codec(trak).or(info.codec.take());
+    }
+    Some(())
+}
+
+fn video_codec(trak: &[u8]) -> Option<String> {
+    let mdia = find_box(trak, b"mdia")?;
+    let minf = find_box(mdia, b"minf")?;
+    let stbl = find_box(minf, b"stbl")?;
+    let stsd = find_box(stbl, b"stsd")?;
+    // Version/flags and entry count precede the first sample entry.
+    let fourcc = stsd.get(12..16)?;
+    Some(String::from_utf8_lossy(fourcc).trim().to_string())
+}
+
+fn find_box<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data).find(|(k, _)| *k == kind).map(|(_, body)| body)
+}
+
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8; 4], &[u8])> {
+    let mut pos = 0usize;
+    std::iter::from_fn(move || {
+        let size = be_u32(data, pos)? as usize;
+        let kind: &[u8; 4] = data.get(pos + 4..pos + 8)?.try_into().ok()?;
+        if size < 8 {
+            return None;
+        }
+        let body = data.get(pos + 8..pos + size)?;
+        pos += size;
+        Some((kind, body))
+    })
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{record_from_row, FileIndexer, RECORD_COLUMNS};
+use crate::notes;
+
+// What to do when an incoming entry has a path that is already indexed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeStrategy {
+    // Take whichever copy was modified more recently.
+    #[default]
+    Newer,
+    KeepExisting,
+    Replace,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub added: usize,
+    pub updated: usize,
+    // Incoming entries that lost a conflict.
+    pub skipped: usize,
+}
+
+impl FileIndexer {
+    // Imports every entry of another index, with its extracted text, rating
+    // and note. With a `label` paths are stored as `label:/original/path` so
+    // indexes of different machines stay apart; those paths do not exist
+    // locally, so limit `prune` to a root afterwards. The other database is
+    // opened like any index, which upgrades its schema if needed.
+    pub fn merge_from<P: AsRef<Path>>(
+        &self,
+        other_db: P,
+        strategy: MergeStrategy,
+        label: Option<&str>,
+    ) -> Result<MergeReport> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let other_db = other_db.as_ref();
+        if let Some(own) = conn.path().filter(|own| !own.is_empty()) {
+            if Path::new(own).canonicalize().ok() == other_db.canonicalize().ok() {
+                return Err(anyhow!("cannot merge an index into itself"));
+            }
+        }
+        let other = FileIndexer::new(other_db)
+            .with_context(|| format!("opening {}", other_db.display()))?;
+        let label = label.map(str::trim).filter(|l| !l.is_empty());
+
+        let mut report = MergeReport::default();
+        let source = other.reader()?;
+        let mut rows = source.prepare(&format!(
+            "SELECT {} FROM files ORDER BY path",
+            RECORD_COLUMNS
+        ))?;
+        let mut content = source.prepare(
+            "SELECT f.body FROM content_docs d JOIN content_fts f ON f.rowid = d.id
+             WHERE d.path = ?",
+        )?;
+        let txn = conn.unchecked_transaction()?;
+        for rec in rows.query_map([], record_from_row)? {
+            let mut rec = rec?;
+            let source = rec.path.clone();
+            if let Some(label) = label {
+                rec.path = format!("{}:{}", label, rec.path);
+                rec.raw_path = rec
+                    .raw_path
+                    .map(|raw| [format!("{}:", label).as_bytes(), &raw].concat());
+            }
+
+            let existing: Option<i64> = if self.may_contain_path(&rec.path)? {
+                txn.prepare_cached("SELECT modified FROM files WHERE path = ?")?
+                    .query_row(params![rec.path], |row| row.get(0))
+                    .optional()?
+            } else {
+                None
+            };
+            let take = match (existing, strategy) {
+                (None, _) => true,
+                (Some(_), MergeStrategy::KeepExisting) => false,
+                (Some(_), MergeStrategy::Replace) => true,
+                (Some(modified), MergeStrategy::Newer) => rec.modified.timestamp() > modified,
+            };
+            if !take {
+                report.skipped += 1;
+                continue;
+            }
+
+            self.write_record(&rec)?;
+            let text: Option<String> = content
+                .query_row(params![source], |row| row.get(0))
+                .optional()?;
+            self.write_content(&rec.path, text.as_deref())?;
+            if rec.rating > 0 {
+                txn.execute(
+                    "UPDATE files SET rating = ? WHERE path = ?",
+                    params![rec.rating, rec.path],
+                )?;
+            }
+            if let Some(note) = other.note(Path::new(&source))? {
+                notes::write_note(&txn, &rec.path, &note)?;
+            }
+            match existing {
+                Some(_) => report.updated += 1,
+                None => report.added += 1,
+            }
+        }
+        txn.commit()?;
+        self.invalidate_cache();
+        Ok(report)
+    }
+}
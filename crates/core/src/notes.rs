@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::indexer::FileIndexer;
+use crate::paths;
+
+impl FileIndexer {
+    // Attaches free text to an indexed entry, replacing any earlier note; an
+    // empty `text` removes it. Notes are dropped with the entry but follow
+    // it across detected renames.
+    pub fn set_note<P: AsRef<Path>>(&self, path: P, text: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let path = paths::key(path.as_ref());
+        if !self.contains_path(&path)? {
+            return Err(anyhow!("{} is not indexed", path));
+        }
+
+        let txn = conn.unchecked_transaction()?;
+        write_note(&txn, &path, text)?;
+        txn.commit()?;
+        self.invalidate_cache();
+        Ok(())
+    }
+
+    pub fn note<P: AsRef<Path>>(&self, path: P) -> Result<Option<String>> {
+        let conn = self.reader()?;
+        let path = paths::key(path.as_ref());
+        Ok(conn
+            .query_row(
+                "SELECT body FROM notes WHERE path = ?",
+                params![path],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+}
+
+// Replaces the note on `path` without checking that it is indexed.
+pub(crate) fn write_note(conn: &Connection, path: &str, text: &str) -> Result<()> {
+    let id: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM notes WHERE path = ?",
+            params![path],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(id) = id {
+        conn.execute("DELETE FROM notes_fts WHERE rowid = ?", params![id])?;
+        conn.execute("DELETE FROM notes WHERE id = ?", params![id])?;
+    }
+    let text = text.trim();
+    if !text.is_empty() {
+        conn.execute(
+            "INSERT INTO notes(path,body,updated_at) VALUES (?,?,?)",
+            params![path, text, Utc::now().timestamp()],
+        )?;
+        conn.execute(
+            "INSERT INTO notes_fts(rowid, body) VALUES (?, ?)",
+            params![conn.last_insert_rowid(), text],
+        )?;
+    }
+    Ok(())
+}
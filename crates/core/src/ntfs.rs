@@ -0,0 +1,567 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{c_void, OsStr, OsString};
+use std::io;
+use std::mem;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf};
+use std::ptr;
+
+use anyhow::Result;
+use ignore::overrides::Override;
+use rusqlite::{params, OptionalExtension};
+use windows_sys::Win32::Foundation::{
+    CloseHandle, ERROR_HANDLE_EOF, ERROR_JOURNAL_ENTRY_DELETED, GENERIC_READ, HANDLE,
+    INVALID_HANDLE_VALUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, FileIdType, GetFileInformationByHandle, GetFinalPathNameByHandleW,
+    GetVolumeInformationW, GetVolumePathNameW, OpenFileById, BY_HANDLE_FILE_INFORMATION,
+    FILE_ATTRIBUTE_DIRECTORY, FILE_FLAG_BACKUP_SEMANTICS, FILE_ID_DESCRIPTOR, FILE_ID_DESCRIPTOR_0,
+    FILE_NAME_NORMALIZED, FILE_READ_ATTRIBUTES, FILE_SHARE_DELETE, FILE_SHARE_READ,
+    FILE_SHARE_WRITE, OPEN_EXISTING,
+};
+use windows_sys::Win32::System::Ioctl::{
+    FSCTL_ENUM_USN_DATA, FSCTL_QUERY_USN_JOURNAL, FSCTL_READ_USN_JOURNAL, MFT_ENUM_DATA_V0,
+    READ_USN_JOURNAL_DATA_V0, USN_JOURNAL_DATA_V0, USN_REASON_FILE_CREATE,
+    USN_REASON_RENAME_NEW_NAME, USN_RECORD_V2,
+};
+use windows_sys::Win32::System::IO::DeviceIoControl;
+
+use crate::attributes;
+use crate::indexer::{FileIndexer, IndexOptions, IndexReport};
+use crate::paths;
+use crate::scan::{self, Found};
+
+// Output buffer for MFT enumeration and journal reads.
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+// Full scans of an NTFS volume list its master file table instead of walking
+// directories, and later scans of the same root only replay the USN journal
+// since then. Both need administrator rights to open the volume; without
+// them, or on other filesystems, scans walk the tree as everywhere else.
+impl FileIndexer {
+    pub(crate) fn scan_root(
+        &self,
+        root: &Path,
+        opts: &IndexOptions,
+        snapshot: Option<i64>,
+    ) -> Result<IndexReport> {
+        let Some(volume) = listable(opts).then(|| Volume::open(root)).flatten() else {
+            return self.index_subtree(root, opts, snapshot);
+        };
+        // Without a journal the table can still be listed, just not replayed.
+        let journal = volume.journal().ok();
+        if let (Some(journal), None) = (&journal, snapshot) {
+            if let Some(report) = self.replay_journal(&volume, journal, root, opts)? {
+                self.set_journal_position(root, journal)?;
+                return Ok(report);
+            }
+        }
+        let high_usn = journal.as_ref().map_or(i64::MAX, |j| j.next_usn);
+        let Some(listing) = volume.list(root, opts, high_usn)? else {
+            return self.index_subtree(root, opts, snapshot);
+        };
+        let report = self.index_found(root, opts, snapshot, Some(listing))?;
+        if let Some(journal) = journal.filter(|_| !opts.cancel.is_cancelled()) {
+            self.set_journal_position(root, &journal)?;
+        }
+        Ok(report)
+    }
+
+    // Reindexes what changed below `root` since the recorded position, or
+    // `None` when that is unknown or no longer in the journal.
+    fn replay_journal(
+        &self,
+        volume: &Volume,
+        journal: &Journal,
+        root: &Path,
+        opts: &IndexOptions,
+    ) -> Result<Option<IndexReport>> {
+        let Some((id, since)) = self.journal_position(root)? else {
+            return Ok(None);
+        };
+        if id != journal.id || since < journal.lowest_valid_usn {
+            return Ok(None);
+        }
+        let Some(changes) = volume.changes(journal, since)? else {
+            return Ok(None);
+        };
+        let Some(real_root) = std::fs::canonicalize(root).ok().map(strip_verbatim) else {
+            return Ok(None);
+        };
+        let filter = Filter::new(root, opts)?;
+
+        // Path below `root` -> attributes and every reason recorded for it.
+        let mut touched: BTreeMap<PathBuf, (u32, u32)> = BTreeMap::new();
+        let mut parents: HashMap<u64, Option<PathBuf>> = HashMap::new();
+        for change in changes {
+            // A deleted parent has its own record, which covers this one.
+            let Some(parent) = parents
+                .entry(change.parent)
+                .or_insert_with(|| volume.path_of(change.parent))
+            else {
+                continue;
+            };
+            let Some(relative) = relative_to(&parent.join(&change.name), &real_root) else {
+                continue;
+            };
+            let entry = touched.entry(root.join(relative)).or_default();
+            entry.0 = change.attributes;
+            entry.1 |= change.reason;
+        }
+
+        let mut report = IndexReport::default();
+        for (path, (attributes, reason)) in touched {
+            if opts.cancel.is_cancelled() {
+                break;
+            }
+            let is_dir = attributes & FILE_ATTRIBUTE_DIRECTORY != 0;
+            if !filter.wants(root, &path, is_dir, attributes) {
+                continue;
+            }
+            let result = match path.symlink_metadata() {
+                Err(_) => self.remove_deleted(&path).map(|_| ()),
+                // Only new or renamed directories bring entries of their own.
+                Ok(meta) if meta.is_dir() => {
+                    if reason & (USN_REASON_FILE_CREATE | USN_REASON_RENAME_NEW_NAME) != 0 {
+                        let sub = self.index_subtree(&path, opts, None)?;
+                        report.indexed += sub.indexed;
+                        report.skipped += sub.skipped;
+                        report.errors.extend(sub.errors);
+                    }
+                    Ok(())
+                }
+                Ok(_) => self.index_file(&path, opts).map(|()| report.indexed += 1),
+            };
+            if let Err(err) = result {
+                report.skipped += 1;
+                report.errors.push((path, err));
+            }
+        }
+        let indexed = self.file_count_below(root)?;
+        report.unchanged = indexed.saturating_sub(report.indexed);
+        Ok(Some(report))
+    }
+
+    fn journal_position(&self, root: &Path) -> Result<Option<(u64, i64)>> {
+        let conn = self.reader()?;
+        let position: Option<(Option<i64>, Option<i64>)> = conn
+            .query_row(
+                "SELECT usn_journal, usn_next FROM roots WHERE path = ?",
+                params![paths::key(root)],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(match position {
+            Some((Some(id), Some(next))) => Some((id as u64, next)),
+            _ => None,
+        })
+    }
+
+    fn set_journal_position(&self, root: &Path, journal: &Journal) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE roots SET usn_journal = ?, usn_next = ? WHERE path = ?",
+            // Stored bit for bit; SQLite integers are signed.
+            params![journal.id as i64, journal.next_usn, paths::key(root)],
+        )?;
+        Ok(())
+    }
+
+    fn file_count_below(&self, root: &Path) -> Result<usize> {
+        let conn = self.reader()?;
+        let (lower, upper) = crate::indexer::subtree_bounds(&paths::key(root));
+        let count: i64 = conn.query_row(
+            "SELECT count(*) FROM files WHERE path >= ? AND path < ?",
+            params![lower, upper],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+}
+
+// A listing cannot apply ignore files or follow links, and a replay cannot
+// tell which files a capped scan never reached.
+fn listable(opts: &IndexOptions) -> bool {
+    !opts.respect_gitignore
+        && !opts.follow_symlinks
+        && opts.max_files.is_none()
+        && opts.max_total_bytes.is_none()
+}
+
+struct Journal {
+    id: u64,
+    next_usn: i64,
+    lowest_valid_usn: i64,
+}
+
+struct Change {
+    parent: u64,
+    name: OsString,
+    attributes: u32,
+    reason: u32,
+}
+
+struct Node {
+    parent: u64,
+    name: OsString,
+    attributes: u32,
+}
+
+// The filters a walk of `root` would apply.
+struct Filter {
+    overrides: Override,
+    skip_hidden: bool,
+    max_depth: Option<usize>,
+}
+
+impl Filter {
+    fn new(root: &Path, opts: &IndexOptions) -> Result<Self> {
+        Ok(Self {
+            overrides: scan::overrides(root, opts)?,
+            skip_hidden: opts.skip_hidden.unwrap_or(false),
+            max_depth: opts.max_depth,
+        })
+    }
+
+    fn wants(&self, root: &Path, path: &Path, is_dir: bool, attributes: u32) -> bool {
+        let depth = path
+            .strip_prefix(root)
+            .map_or(0, |p| p.components().count());
+        if self.max_depth.is_some_and(|max| depth > max) {
+            return false;
+        }
+        if self.skip_hidden {
+            let dotted = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if dotted || attributes & (attributes::HIDDEN | attributes::SYSTEM) != 0 {
+                return false;
+            }
+        }
+        !self.overrides.matched(path, is_dir).is_ignore()
+    }
+}
+
+struct Volume {
+    handle: HANDLE,
+}
+
+impl Volume {
+    // `None` unless `path` is on an NTFS volume with a drive letter that this
+    // process may open.
+    fn open(path: &Path) -> Option<Volume> {
+        let mut mount = [0u16; 261];
+        let path = wide(path.as_os_str());
+        if unsafe { GetVolumePathNameW(path.as_ptr(), mount.as_mut_ptr(), mount.len() as u32) } == 0
+        {
+            return None;
+        }
+        let mut fs_name = [0u16; 32];
+        let ok = unsafe {
+            GetVolumeInformationW(
+                mount.as_ptr(),
+                ptr::null_mut(),
+                0,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                fs_name.as_mut_ptr(),
+                fs_name.len() as u32,
+            )
+        };
+        if ok == 0 || from_wide(&fs_name) != "NTFS" {
+            return None;
+        }
+        // `C:\` opens as the device `\\.\C:`; folder mount points have no
+        // such name.
+        let mount = from_wide(&mount).to_string_lossy().into_owned();
+        let letter = mount
+            .strip_suffix('\\')
+            .filter(|m| m.len() == 2 && m.ends_with(':'))?;
+        let device = wide(OsStr::new(&format!(r"\\.\{}", letter)));
+        let handle = unsafe {
+            CreateFileW(
+                device.as_ptr(),
+                GENERIC_READ,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                ptr::null(),
+                OPEN_EXISTING,
+                0,
+                ptr::null_mut(),
+            )
+        };
+        (handle != INVALID_HANDLE_VALUE).then_some(Volume { handle })
+    }
+
+    fn journal(&self) -> io::Result<Journal> {
+        let mut out = [0u8; mem::size_of::<USN_JOURNAL_DATA_V0>()];
+        self.control(FSCTL_QUERY_USN_JOURNAL, &(), &mut out)?;
+        let data: USN_JOURNAL_DATA_V0 = unsafe { ptr::read_unaligned(out.as_ptr().cast()) };
+        Ok(Journal {
+            id: data.UsnJournalID,
+            next_usn: data.NextUsn,
+            lowest_valid_usn: data.LowestValidUsn,
+        })
+    }
+
+    // Every directory and file below `root` that passes the filters of
+    // `opts`, in the order a walk would find them: each directory before
+    // its entries. `None` when `root` cannot be found in the table.
+    fn list(&self, root: &Path, opts: &IndexOptions, high_usn: i64) -> Result<Option<Vec<Found>>> {
+        let Some(root_id) = file_id(root) else {
+            return Ok(None);
+        };
+        let nodes = self.enumerate(high_usn)?;
+        let mut children: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (id, node) in &nodes {
+            children.entry(node.parent).or_default().push(*id);
+        }
+        let filter = Filter::new(root, opts)?;
+        let mut listing = vec![Found::Dir(root.to_path_buf())];
+        let mut pending = vec![(root_id, root.to_path_buf())];
+        while let Some((dir, dir_path)) = pending.pop() {
+            for id in children.get(&dir).into_iter().flatten() {
+                let node = &nodes[id];
+                let path = dir_path.join(&node.name);
+                let is_dir = node.attributes & FILE_ATTRIBUTE_DIRECTORY != 0;
+                if !filter.wants(root, &path, is_dir, node.attributes) {
+                    continue;
+                }
+                if !is_dir {
+                    listing.push(Found::File(path));
+                    continue;
+                }
+                listing.push(Found::Dir(path.clone()));
+                // Junctions and mounted folders are not followed, like links.
+                if node.attributes & attributes::REPARSE_POINT == 0 {
+                    pending.push((*id, path));
+                }
+            }
+        }
+        Ok(Some(listing))
+    }
+
+    fn enumerate(&self, high_usn: i64) -> Result<HashMap<u64, Node>> {
+        let mut nodes = HashMap::new();
+        let mut input = MFT_ENUM_DATA_V0 {
+            StartFileReferenceNumber: 0,
+            LowUsn: 0,
+            HighUsn: high_usn,
+        };
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        loop {
+            let len = match self.control(FSCTL_ENUM_USN_DATA, &input, &mut buf) {
+                Ok(len) => len,
+                Err(err) if err.raw_os_error() == Some(ERROR_HANDLE_EOF as i32) => break,
+                Err(err) => return Err(err.into()),
+            };
+            if len <= 8 {
+                break;
+            }
+            // The output starts with where the next call continues.
+            input.StartFileReferenceNumber = u64::from_le_bytes(buf[..8].try_into()?);
+            for (id, change) in records(&buf[8..len]) {
+                nodes.insert(
+                    id,
+                    Node {
+                        parent: change.parent,
+                        name: change.name,
+                        attributes: change.attributes,
+                    },
+                );
+            }
+        }
+        Ok(nodes)
+    }
+
+    // Journal records from `since` up to the journal's position when it was
+    // queried; `None` when some of them were already discarded.
+    fn changes(&self, journal: &Journal, since: i64) -> Result<Option<Vec<Change>>> {
+        let mut input = READ_USN_JOURNAL_DATA_V0 {
+            StartUsn: since,
+            ReasonMask: u32::MAX,
+            ReturnOnlyOnClose: 0,
+            Timeout: 0,
+            BytesToWaitFor: 0,
+            UsnJournalID: journal.id,
+        };
+        let mut changes = Vec::new();
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        while input.StartUsn < journal.next_usn {
+            let len = match self.control(FSCTL_READ_USN_JOURNAL, &input, &mut buf) {
+                Ok(len) => len,
+                Err(err) if err.raw_os_error() == Some(ERROR_JOURNAL_ENTRY_DELETED as i32) => {
+                    return Ok(None)
+                }
+                Err(err) => return Err(err.into()),
+            };
+            if len <= 8 {
+                break;
+            }
+            input.StartUsn = i64::from_le_bytes(buf[..8].try_into()?);
+            changes.extend(records(&buf[8..len]).map(|(_, change)| change));
+        }
+        Ok(Some(changes))
+    }
+
+    // Current path of a file by its reference number, if it still exists.
+    fn path_of(&self, id: u64) -> Option<PathBuf> {
+        let descriptor = FILE_ID_DESCRIPTOR {
+            dwSize: mem::size_of::<FILE_ID_DESCRIPTOR>() as u32,
+            Type: FileIdType,
+            Anonymous: FILE_ID_DESCRIPTOR_0 { FileId: id as i64 },
+        };
+        let handle = unsafe {
+            OpenFileById(
+                self.handle,
+                &descriptor,
+                FILE_READ_ATTRIBUTES,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                ptr::null(),
+                FILE_FLAG_BACKUP_SEMANTICS,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let mut buf = vec![0u16; 32 * 1024];
+        let len = unsafe {
+            GetFinalPathNameByHandleW(
+                handle,
+                buf.as_mut_ptr(),
+                buf.len() as u32,
+                FILE_NAME_NORMALIZED,
+            )
+        } as usize;
+        unsafe { CloseHandle(handle) };
+        (len > 0 && len < buf.len())
+            .then(|| strip_verbatim(PathBuf::from(OsString::from_wide(&buf[..len]))))
+    }
+
+    // Bytes written to `out`.
+    fn control<I>(&self, code: u32, input: &I, out: &mut [u8]) -> io::Result<usize> {
+        let mut returned = 0u32;
+        let ok = unsafe {
+            DeviceIoControl(
+                self.handle,
+                code,
+                (input as *const I).cast::<c_void>(),
+                mem::size_of::<I>() as u32,
+                out.as_mut_ptr().cast::<c_void>(),
+                out.len() as u32,
+                &mut returned,
+                ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(returned as usize)
+    }
+}
+
+impl Drop for Volume {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.handle) };
+    }
+}
+
+// The USN_RECORD_V2 entries packed in `buf`, with their file reference
+// numbers.
+fn records(buf: &[u8]) -> impl Iterator<Item = (u64, Change)> + '_ {
+    let header = mem::offset_of!(USN_RECORD_V2, FileName);
+    let mut offset = 0;
+    std::iter::from_fn(move || loop {
+        if offset + header > buf.len() {
+            return None;
+        }
+        let record: USN_RECORD_V2 = unsafe { ptr::read_unaligned(buf[offset..].as_ptr().cast()) };
+        let start = offset;
+        let len = record.RecordLength as usize;
+        if len == 0 || start + len > buf.len() {
+            return None;
+        }
+        offset += len;
+        // Version 2 is all the V0 requests return.
+        if record.MajorVersion != 2 {
+            continue;
+        }
+        let name_start = start + record.FileNameOffset as usize;
+        let name_end = name_start + record.FileNameLength as usize;
+        let Some(name) = buf.get(name_start..name_end) else {
+            continue;
+        };
+        let name: Vec<u16> = name
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        return Some((
+            record.FileReferenceNumber,
+            Change {
+                parent: record.ParentFileReferenceNumber,
+                name: OsString::from_wide(&name),
+                attributes: record.FileAttributes,
+                reason: record.Reason,
+            },
+        ));
+    })
+}
+
+// NTFS file index of `path`, which is its reference number in the table.
+fn file_id(path: &Path) -> Option<u64> {
+    let path = wide(path.as_os_str());
+    let handle = unsafe {
+        CreateFileW(
+            path.as_ptr(),
+            FILE_READ_ATTRIBUTES,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            ptr::null(),
+            OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS,
+            ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return None;
+    }
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { mem::zeroed() };
+    let ok = unsafe { GetFileInformationByHandle(handle, &mut info) };
+    unsafe { CloseHandle(handle) };
+    (ok != 0).then(|| (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow))
+}
+
+// `path` below `root`, comparing case-insensitively like NTFS does.
+fn relative_to(path: &Path, root: &Path) -> Option<PathBuf> {
+    let mut path = path.components();
+    for expected in root.components() {
+        let actual = path.next()?;
+        let same = match (expected, actual) {
+            (Component::Normal(a), Component::Normal(b)) => {
+                a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+            }
+            (a, b) => a.as_os_str().eq_ignore_ascii_case(b.as_os_str()),
+        };
+        if !same {
+            return None;
+        }
+    }
+    Some(path.as_path().to_path_buf())
+}
+
+// `\\?\C:\dir` as `C:\dir`.
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        Some(stripped) if !stripped.starts_with("UNC\\") => PathBuf::from(stripped),
+        _ => path,
+    }
+}
+
+fn wide(s: &OsStr) -> Vec<u16> {
+    s.encode_wide().chain(Some(0)).collect()
+}
+
+fn from_wide(s: &[u16]) -> OsString {
+    let len = s.iter().position(|&c| c == 0).unwrap_or(s.len());
+    OsString::from_wide(&s[..len])
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+use std::sync::MutexGuard;
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use xxhash_rust::xxh3::xxh3_128;
+
+use crate::indexer::FileIndexer;
+use crate::paths;
+
+// About 1% false positives at capacity.
+const BITS_PER_PATH: usize = 10;
+const HASHES: u64 = 7;
+const MIN_CAPACITY: usize = 1 << 16;
+
+// A Bloom filter of the indexed paths and their ancestors, which answers
+// "certainly not indexed" without a query. Watching and incremental scans
+// mostly ask about paths that are new, so most lookups stop here. Paths
+// are only ever added; removals leave bits set, which costs a query now
+// and then but never a wrong answer.
+pub(crate) struct PathFilter {
+    bits: Vec<u64>,
+    capacity: usize,
+    // Distinct paths added so far, give or take false positives.
+    len: usize,
+    // The writer's `PRAGMA data_version` when the filter was filled; other
+    // processes' writes move it and make the filter stale.
+    data_version: Option<i64>,
+}
+
+impl PathFilter {
+    fn empty() -> Self {
+        Self {
+            bits: Vec::new(),
+            capacity: 0,
+            len: 0,
+            data_version: None,
+        }
+    }
+
+    // Every path of `files`, sized for twice as many so a growing index
+    // does not need a rebuild right away.
+    fn load(&mut self, conn: &Connection, data_version: i64) -> Result<()> {
+        let count: i64 = conn.query_row("SELECT count(*) FROM files", [], |row| row.get(0))?;
+        let capacity = (count.max(0) as usize * 2).max(MIN_CAPACITY);
+        self.data_version = None;
+        self.bits = vec![0; (capacity * BITS_PER_PATH).div_ceil(64)];
+        self.capacity = capacity;
+        self.len = 0;
+        let mut stmt = conn.prepare("SELECT path FROM files ORDER BY path")?;
+        let mut rows = stmt.query(params![])?;
+        let mut parent = String::new();
+        while let Some(row) = rows.next()? {
+            let path: String = row.get(0)?;
+            // Sorted, so siblings come together and share their ancestors.
+            match Path::new(&path).parent() {
+                Some(dir) if dir.as_os_str() == parent.as_str() => self.add(&path),
+                Some(dir) => {
+                    parent = dir.to_string_lossy().into_owned();
+                    self.insert(&path);
+                }
+                None => self.insert(&path),
+            }
+        }
+        self.data_version = Some(data_version);
+        Ok(())
+    }
+
+    fn fresh(&self, data_version: i64) -> bool {
+        self.data_version == Some(data_version) && self.len <= self.capacity
+    }
+
+    // `path` and every directory above it, so a subtree that is certainly
+    // not indexed is known from its root.
+    fn insert(&mut self, path: &str) {
+        for ancestor in Path::new(path).ancestors() {
+            let ancestor = ancestor.to_string_lossy();
+            if !ancestor.is_empty() {
+                self.add(&ancestor);
+            }
+        }
+    }
+
+    fn add(&mut self, path: &str) {
+        let mut added = false;
+        for bit in self.positions(path) {
+            let word = &mut self.bits[bit / 64];
+            let mask = 1 << (bit % 64);
+            added |= *word & mask == 0;
+            *word |= mask;
+        }
+        if added {
+            self.len += 1;
+        }
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.positions(path)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    // Double hashing over the two halves of one 128-bit hash.
+    fn positions(&self, path: &str) -> impl Iterator<Item = usize> {
+        let hash = xxh3_128(path.as_bytes());
+        let (h1, h2) = (hash as u64, (hash >> 64) as u64);
+        let len = (self.bits.len() * 64) as u64;
+        (0..HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+impl FileIndexer {
+    // Keeps a filter of the indexed paths in memory (about 1.25 bytes per
+    // path and directory), so existence checks of the watcher, imports and
+    // merges skip the database for paths that were never indexed. It is
+    // filled on first use and again after other processes write.
+    pub fn enable_path_filter(&self) {
+        self.lock_path_filter()
+            .get_or_insert_with(PathFilter::empty);
+    }
+
+    pub fn is_indexed<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        self.contains_path(&paths::key(path.as_ref()))
+    }
+
+    // Whether `files` has a row for `path`, from the filter where it can
+    // tell.
+    pub(crate) fn contains_path(&self, path: &str) -> Result<bool> {
+        if !self.may_contain_path(path)? {
+            return Ok(false);
+        }
+        Ok(self.conn().query_row(
+            "SELECT EXISTS(SELECT 1 FROM files WHERE path = ?)",
+            params![path],
+            |row| row.get(0),
+        )?)
+    }
+
+    // False only if neither `path` nor anything below it is indexed. Without
+    // a filter, or while another thread writes, the answer is true.
+    pub(crate) fn may_contain_path(&self, path: &str) -> Result<bool> {
+        if self.lock_path_filter().is_none() {
+            return Ok(true);
+        }
+        // Taken before the filter, in the same order as writes take them.
+        let Some(conn) = self.try_conn() else {
+            return Ok(true);
+        };
+        let version: i64 = conn.query_row("PRAGMA data_version", [], |row| row.get(0))?;
+        let mut filter = self.lock_path_filter();
+        let Some(filter) = filter.as_mut() else {
+            return Ok(true);
+        };
+        if !filter.fresh(version) {
+            filter.load(&conn, version)?;
+        }
+        Ok(filter.contains(path))
+    }
+
+    // Called for every row written to `files`, with the writer held.
+    pub(crate) fn add_to_path_filter(&self, path: &str) {
+        if let Some(filter) = self.lock_path_filter().as_mut() {
+            if filter.data_version.is_some() {
+                filter.insert(path);
+            }
+        }
+    }
+
+    fn lock_path_filter(&self) -> MutexGuard<'_, Option<PathFilter>> {
+        self.path_filter
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
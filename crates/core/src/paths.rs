@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+// Index key for `path`: the path itself when it is valid Unicode, otherwise
+// a form with every undecodable byte (or unpaired UTF-16 surrogate on
+// Windows) escaped, used for display and lookups.
+pub(crate) fn key(path: &Path) -> String {
+    encode(path).0
+}
+
+// The key plus, for paths that are not valid Unicode, the exact OS encoding
+// needed to open the file again.
+pub(crate) fn encode(path: &Path) -> (String, Option<Vec<u8>>) {
+    match path.to_str() {
+        Some(path) => (path.to_string(), None),
+        None => {
+            let (key, raw) = escape(path);
+            (key, Some(raw))
+        }
+    }
+}
+
+pub(crate) fn decode(key: &str, raw: Option<&[u8]>) -> PathBuf {
+    match raw {
+        Some(raw) => from_raw(raw),
+        None => PathBuf::from(key),
+    }
+}
+
+#[cfg(unix)]
+fn escape(path: &Path) -> (String, Vec<u8>) {
+    use std::os::unix::ffi::OsStrExt;
+
+    let bytes = path.as_os_str().as_bytes();
+    let mut key = String::new();
+    for chunk in bytes.utf8_chunks() {
+        key.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            key.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    (key, bytes.to_vec())
+}
+
+#[cfg(unix)]
+fn from_raw(raw: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(raw).into()
+}
+
+// Stored as little-endian UTF-16 code units.
+#[cfg(windows)]
+fn escape(path: &Path) -> (String, Vec<u8>) {
+    use std::os::windows::ffi::OsStrExt;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().collect();
+    let mut key = String::new();
+    for unit in char::decode_utf16(wide.iter().copied()) {
+        match unit {
+            Ok(c) => key.push(c),
+            Err(err) => key.push_str(&format!("\\u{{{:04x}}}", err.unpaired_surrogate())),
+        }
+    }
+    (
+        key,
+        wide.iter().flat_map(|unit| unit.to_le_bytes()).collect(),
+    )
+}
+
+#[cfg(windows)]
+fn from_raw(raw: &[u8]) -> PathBuf {
+    use std::os::windows::ffi::OsStringExt;
+
+    let wide: Vec<u16> = raw
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    std::ffi::OsString::from_wide(&wide).into()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn escape(path: &Path) -> (String, Vec<u8>) {
+    let key = path.to_string_lossy().to_string();
+    let raw = key.clone().into_bytes();
+    (key, raw)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn from_raw(raw: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(raw).to_string())
+}
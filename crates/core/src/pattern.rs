@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+const GLOB_META: &[char] = &['*', '?', '[', '{', '\\'];
+
+// SQL constraints implied by a glob, letting SQLite narrow the candidates
+// with an index before every remaining path is matched against it.
+pub(crate) struct GlobPlan {
+    // Pattern as handed to `glob_match`, anchored to the whole path.
+    pub(crate) pattern: String,
+    // Literal directory the pattern starts with, if it is absolute.
+    pub(crate) dir: Option<String>,
+    // The whole pattern when it contains no wildcards at all.
+    pub(crate) exact: Option<String>,
+    // Lowercased literal extension every match must end with. A name that
+    // is nothing but a dot and that extension has no `ext` of its own.
+    pub(crate) ext: Option<String>,
+}
+
+// Backs SQLite's `text REGEXP pattern` operator. The compiled pattern is
+// cached per statement, so it is built once per query.
+pub(crate) fn register(conn: &Connection) -> Result<()> {
+    conn.create_scalar_function(
+        "regexp",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let regex: Arc<Regex> = ctx.get_or_create_aux(0, |pattern| -> Result<_, BoxError> {
+                Ok(Regex::new(pattern.as_str()?)?)
+            })?;
+            let text: Option<String> = ctx.get(1)?;
+            Ok(text.is_some_and(|text| regex.is_match(&text)))
+        },
+    )?;
+    // glob_match(pattern, case_insensitive, path)
+    conn.create_scalar_function(
+        "glob_match",
+        3,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let case_insensitive: bool = ctx.get(1)?;
+            let matcher: Arc<GlobMatcher> =
+                ctx.get_or_create_aux(0, |pattern| -> Result<_, BoxError> {
+                    Ok(glob_matcher(pattern.as_str()?, case_insensitive)?)
+                })?;
+            let path: Option<String> = ctx.get(2)?;
+            Ok(path.is_some_and(|path| matcher.is_match(path)))
+        },
+    )?;
+    Ok(())
+}
+
+// `*` and `?` stay within one path component and `**` spans any number of
+// them. Patterns that do not start at the root match anywhere below it, so
+// `src/**/*.rs` behaves like it does in `fd --glob`.
+pub(crate) fn plan_glob(pattern: &str, case_insensitive: bool) -> Result<GlobPlan> {
+    let pattern = if pattern.starts_with('/') {
+        pattern.to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+    glob_matcher(&pattern, case_insensitive)?;
+
+    let literal_end = pattern.find(GLOB_META).unwrap_or(pattern.len());
+    let exact = (literal_end == pattern.len() && !case_insensitive).then(|| pattern.clone());
+    let dir = match pattern[..literal_end].rfind('/') {
+        Some(end) if end > 0 => Some(pattern[..end].to_string()),
+        _ => None,
+    };
+    let last = pattern.rsplit('/').next().unwrap_or_default();
+    let ext = last
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+        .filter(|ext| !ext.is_empty() && !ext.contains(GLOB_META))
+        .map(|ext| ext.to_ascii_lowercase());
+    Ok(GlobPlan {
+        pattern,
+        dir,
+        exact,
+        ext,
+    })
+}
+
+pub(crate) fn glob_matcher(pattern: &str, case_insensitive: bool) -> Result<GlobMatcher> {
+    Ok(GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .case_insensitive(case_insensitive)
+        .build()
+        .with_context(|| format!("invalid glob {:?}", pattern))?
+        .compile_matcher())
+}
+
+// Compiles `pattern` up front so a bad one is reported clearly rather than
+// as a failure inside the query.
+pub(crate) fn compile(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).with_context(|| format!("invalid regex {:?}", pattern))
+}
@@ -0,0 +1,113 @@
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::Result;
+use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
+use rusqlite::{Connection, OpenFlags};
+
+use crate::fold;
+use crate::pattern;
+
+// Idle read connections kept open for reuse.
+const MAX_IDLE_READERS: usize = 4;
+
+// One writer connection shared by every thread, plus read-only connections
+// so searches run alongside a long indexing transaction (the index uses
+// WAL, where readers never wait for the writer). The writer is reentrant
+// so a method holding it may call others that need it.
+pub(crate) struct Pool {
+    writer: ReentrantMutex<Connection>,
+    idle: Mutex<Vec<Connection>>,
+    // What readers open; `None` for in-memory databases, which other
+    // connections cannot see.
+    source: Option<String>,
+    key: Mutex<Option<String>>,
+}
+
+impl Pool {
+    pub(crate) fn new(writer: Connection, source: Option<String>) -> Self {
+        Self {
+            writer: ReentrantMutex::new(writer),
+            idle: Mutex::new(Vec::new()),
+            source: source.filter(|s| !s.is_empty()),
+            key: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn writer(&self) -> ReentrantMutexGuard<'_, Connection> {
+        self.writer.lock()
+    }
+
+    // `None` while another thread holds the writer.
+    pub(crate) fn try_writer(&self) -> Option<ReentrantMutexGuard<'_, Connection>> {
+        self.writer.try_lock()
+    }
+
+    pub(crate) fn reader(&self) -> Result<Reader<'_>> {
+        // Inside this thread's own transaction only the writer sees its
+        // uncommitted rows.
+        let Some(source) = self
+            .source
+            .as_deref()
+            .filter(|_| !self.writer.is_owned_by_current_thread())
+        else {
+            return Ok(Reader::Writer(self.writer()));
+        };
+        if let Some(conn) = self.lock_idle().pop() {
+            return Ok(Reader::Pooled(self, Some(conn)));
+        }
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = Connection::open_with_flags(source, flags)?;
+        if let Some(key) = self.lock_key().as_deref() {
+            conn.pragma_update(None, "key", key)?;
+        }
+        fold::register(&conn)?;
+        pattern::register(&conn)?;
+        Ok(Reader::Pooled(self, Some(conn)))
+    }
+
+    // Passphrase for readers of an encrypted index; idle readers opened
+    // with the old one are closed.
+    #[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+    pub(crate) fn set_key(&self, key: &str) {
+        *self.lock_key() = Some(key.to_string());
+        self.lock_idle().clear();
+    }
+
+    fn lock_idle(&self) -> MutexGuard<'_, Vec<Connection>> {
+        self.idle.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn lock_key(&self) -> MutexGuard<'_, Option<String>> {
+        self.key.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+pub(crate) enum Reader<'a> {
+    Pooled(&'a Pool, Option<Connection>),
+    Writer(ReentrantMutexGuard<'a, Connection>),
+}
+
+impl Deref for Reader<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            Reader::Pooled(_, conn) => conn.as_ref().expect("reader is open until dropped"),
+            Reader::Writer(conn) => conn,
+        }
+    }
+}
+
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        if let Reader::Pooled(pool, conn) = self {
+            let mut idle = pool.lock_idle();
+            if idle.len() < MAX_IDLE_READERS {
+                idle.extend(conn.take());
+            }
+        }
+    }
+}
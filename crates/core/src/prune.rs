@@ -0,0 +1,173 @@
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::archive;
+use crate::deleted;
+use crate::indexer::{subtree_bounds, FileIndexer};
+use crate::paths;
+
+// Path, raw path, size and volume of an indexed entry.
+type IndexedPath = (String, Option<Vec<u8>>, i64, Option<String>);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub checked: usize,
+    pub removed: usize,
+    pub removed_bytes: i64,
+    // Removed, or planned for removal in a dry run.
+    pub paths: Vec<String>,
+}
+
+impl FileIndexer {
+    pub fn prune(&self, root: Option<&Path>) -> Result<PruneReport> {
+        self.ensure_writable()?;
+        self.prune_entries(root, false)
+    }
+
+    // The entries `prune` would remove, without touching the index; works
+    // on a read-only database too.
+    pub fn prune_dry_run(&self, root: Option<&Path>) -> Result<PruneReport> {
+        self.prune_entries(root, true)
+    }
+
+    fn prune_entries(&self, root: Option<&Path>, dry_run: bool) -> Result<PruneReport> {
+        let root = root.map(std::path::absolute).transpose()?;
+        let root = root.as_deref();
+        // Files on an unplugged drive are not gone.
+        let offline: HashSet<String> = self
+            .disconnected_volumes()?
+            .into_iter()
+            .map(|volume| volume.id)
+            .collect();
+        let candidates = self.indexed_paths(root)?;
+        let mut report = PruneReport {
+            dry_run,
+            checked: candidates.len(),
+            ..PruneReport::default()
+        };
+        if dry_run {
+            for (path, raw_path, size, volume) in candidates {
+                if !volume.is_some_and(|volume| offline.contains(&volume))
+                    && is_gone(&path, raw_path)
+                {
+                    report.removed += 1;
+                    report.removed_bytes += size;
+                    report.paths.push(path);
+                }
+            }
+            return Ok(report);
+        }
+
+        let conn = self.conn();
+        let now = Utc::now().timestamp();
+        let txn = conn.unchecked_transaction()?;
+        for (path, raw_path, size, volume) in candidates {
+            if volume.is_some_and(|volume| offline.contains(&volume)) {
+                continue;
+            }
+            if is_gone(&path, raw_path) {
+                deleted::tombstone(&txn, &path, now)?;
+                txn.execute("DELETE FROM files WHERE path = ?", params![path])?;
+                report.removed += 1;
+                report.removed_bytes += size;
+                report.paths.push(path);
+            }
+        }
+        txn.commit()?;
+        self.invalidate_cache();
+        if report.removed > 0 {
+            match root {
+                Some(root) => self.refresh_dirs(root)?,
+                None => {
+                    for root in self.list_roots()? {
+                        self.refresh_dirs(Path::new(&root.path))?;
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn indexed_paths(&self, root: Option<&Path>) -> Result<Vec<IndexedPath>> {
+        let conn = self.reader()?;
+        let map_row =
+            |row: &rusqlite::Row<'_>| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?));
+        let rows = match root {
+            Some(root) => {
+                let root = paths::key(root);
+                let (lower, upper) = subtree_bounds(&root);
+                let mut stmt = conn.prepare(
+                    "SELECT path,raw_path,size,volume FROM files
+                     WHERE (path = ? OR (path >= ? AND path < ?)) AND scheme IS NULL",
+                )?;
+                let rows = stmt.query_map(params![root, lower, upper], map_row)?;
+                rows.filter_map(|r| r.ok()).collect()
+            }
+            None => {
+                // Remote entries are not on disk; their sources drop them.
+                let mut stmt = conn
+                    .prepare("SELECT path,raw_path,size,volume FROM files WHERE scheme IS NULL")?;
+                let rows = stmt.query_map([], map_row)?;
+                rows.filter_map(|r| r.ok()).collect()
+            }
+        };
+        Ok(rows)
+    }
+}
+
+// Only a definite "not found" counts as missing; permission errors or an
+// unmounted share should not wipe the rows. Relative paths, stored by older
+// versions, would be looked up from the wrong directory and are kept.
+fn is_gone(path: &str, raw_path: Option<Vec<u8>>) -> bool {
+    let fs_path = match raw_path {
+        Some(raw) => paths::decode(path, Some(&raw)),
+        None => PathBuf::from(archive::physical_path(path)),
+    };
+    fs_path.is_absolute()
+        && matches!(fs_path.symlink_metadata(), Err(err) if err.kind() == ErrorKind::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::indexer::IndexOptions;
+
+    #[test]
+    fn removes_only_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("kept.txt"), "a").unwrap();
+        fs::write(dir.path().join("gone.txt"), "bb").unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let indexer = FileIndexer::new(db.path().join("index.db")).unwrap();
+        indexer
+            .index_dir(dir.path(), &IndexOptions::default())
+            .unwrap();
+        fs::remove_file(dir.path().join("gone.txt")).unwrap();
+        let gone = paths::key(&dir.path().join("gone.txt"));
+
+        let plan = indexer.prune_dry_run(None).unwrap();
+        assert_eq!((plan.checked, plan.removed), (2, 1));
+        assert_eq!(plan.paths, std::slice::from_ref(&gone));
+        assert_eq!(indexer.prune_dry_run(None).unwrap().removed, 1);
+
+        let report = indexer.prune(Some(dir.path())).unwrap();
+        assert_eq!((report.removed, report.removed_bytes), (1, 2));
+        assert_eq!(report.paths, [gone]);
+        assert_eq!(indexer.prune(None).unwrap().removed, 0);
+    }
+
+    #[test]
+    fn keeps_relative_paths() {
+        assert!(!is_gone("no/such/relative/file", None));
+        assert!(is_gone("/no/such/absolute/file", None));
+    }
+}
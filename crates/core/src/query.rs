@@ -0,0 +1,473 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{Days, NaiveDate};
+
+use crate::indexer::{EntryKind, SearchQuery, SortKey};
+
+// Parses a compact search expression such as
+//
+//     ext:pdf,docx size>10MB modified>=2023-01-01 name~report
+//
+// into a `SearchQuery`. Terms are separated by whitespace and all must
+// match; double quotes keep spaces in a value (`name:"annual report"`).
+// Words without a field are joined into the name fragment.
+impl FromStr for SearchQuery {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let mut q = SearchQuery::default();
+        let mut words: Vec<String> = Vec::new();
+        for token in tokenize(input)? {
+            let Some((field, op, value)) = split_term(&token) else {
+                words.push(token);
+                continue;
+            };
+            apply(&mut q, &field.to_ascii_lowercase(), op, value)
+                .with_context(|| format!("in query term `{}`", token))?;
+        }
+        if !words.is_empty() {
+            let mut name = q.name_like.take().map(|n| vec![n]).unwrap_or_default();
+            name.extend(words);
+            q.name_like = Some(name.join(" "));
+        }
+        Ok(q)
+    }
+}
+
+const FIELDS: &[&str] = &[
+    "name",
+    "regex",
+    "path",
+    "glob",
+    "ext",
+    "kind",
+    "mime",
+    "content",
+    "note",
+    "in",
+    "artist",
+    "album",
+    "title",
+    "hash",
+    "hashed",
+    "xattr",
+    "case",
+    "fuzzy",
+    "size",
+    "duration",
+    "height",
+    "modified",
+    "added",
+    "rating",
+    "allocated",
+    "volume",
+    "sort",
+    "order",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    // `:` or `=`
+    Is,
+    // `~`
+    Like,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+fn apply(q: &mut SearchQuery, field: &str, op: Op, value: String) -> Result<()> {
+    match (field, op) {
+        ("name", Op::Is | Op::Like) => q.name_like = Some(value),
+        ("regex", Op::Is | Op::Like) => q.name_regex = Some(value),
+        ("path", Op::Is) => q.path_prefix = Some(value),
+        ("path", Op::Like) => q.path_regex = Some(value),
+        ("glob", Op::Is | Op::Like) => q.glob = Some(value),
+        ("ext", Op::Is) => q.ext_in.extend(value.split(',').map(str::to_string)),
+        ("kind", Op::Is) => q.kind = Some(value.parse::<EntryKind>()?),
+        ("mime", Op::Is) => q.mime = Some(value),
+        ("content", Op::Is | Op::Like) => q.content = Some(value),
+        ("note", Op::Is | Op::Like) => q.note = Some(value),
+        ("in", Op::Is) => q.collection = Some(value),
+        ("tag", Op::Is) => q.tags.push(value),
+        ("artist", Op::Is | Op::Like) => q.artist = Some(value),
+        ("album", Op::Is | Op::Like) => q.album = Some(value),
+        ("title", Op::Is | Op::Like) => q.title = Some(value),
+        ("hash", Op::Is) => q.hash = Some(value),
+        ("hashed", Op::Is) => q.has_hash = Some(parse_bool(&value)?),
+        ("xattr", Op::Is) => q.has_xattr = Some(value),
+        ("volume", Op::Is) => q.volume = Some(value),
+        ("case", Op::Is) => {
+            q.case_sensitive = Some(match value.as_str() {
+                "sensitive" => true,
+                "insensitive" => false,
+                other => return Err(anyhow!("expected sensitive or insensitive, got {}", other)),
+            })
+        }
+        ("fuzzy", Op::Is) => q.fuzzy = parse_bool(&value)?,
+        ("rating", Op::Is | Op::Ge | Op::Gt) => {
+            let rating: u8 = value.parse().context("expected a rating from 0 to 5")?;
+            q.min_rating = Some(if op == Op::Gt {
+                rating.saturating_add(1)
+            } else {
+                rating
+            });
+        }
+        ("size", _) => {
+            let size = parse_size(&value)?;
+            let (min, max) = bounds(op, size)?;
+            q.min_size = min.or(q.min_size);
+            q.max_size = max.or(q.max_size);
+        }
+        ("allocated", _) => {
+            let size = parse_size(&value)?;
+            let (min, max) = bounds(op, size)?;
+            q.min_allocated = min.or(q.min_allocated);
+            q.max_allocated = max.or(q.max_allocated);
+        }
+        ("duration", _) => {
+            let secs: i64 = value.parse().context("expected a number of seconds")?;
+            let (min, max) = bounds(op, secs)?;
+            q.min_duration = min.or(q.min_duration);
+            q.max_duration = max.or(q.max_duration);
+        }
+        ("height", Op::Ge | Op::Gt | Op::Is) => {
+            let height: i64 = value.parse().context("expected a height in pixels")?;
+            q.min_height = Some(if op == Op::Gt { height + 1 } else { height });
+        }
+        ("modified", _) => {
+            let (from, to) = date_bounds(op, &value)?;
+            q.date_from = from.or(q.date_from);
+            q.date_to = to.or(q.date_to);
+        }
+        ("added", _) => {
+            let (from, to) = date_bounds(op, &value)?;
+            q.added_from = from.or(q.added_from);
+            q.added_to = to.or(q.added_to);
+        }
+        // `sort:size,name` sorts by size, then by name.
+        ("sort", Op::Is) => {
+            let mut keys = value.split(',');
+            q.sort_key = keys.next().map(parse_sort_key).transpose()?;
+            q.then_sort = keys.next().map(parse_sort_key).transpose()?;
+            if keys.next().is_some() {
+                return Err(anyhow!("at most two sort keys are supported"));
+            }
+        }
+        // `order:desc,asc` gives the direction of each sort key.
+        ("order", Op::Is) => {
+            let mut orders = value.split(',');
+            q.desc = orders.next().map(parse_desc).transpose()?.unwrap_or(false);
+            q.then_desc = orders.next().map(parse_desc).transpose()?.unwrap_or(false);
+            if orders.next().is_some() {
+                return Err(anyhow!("at most two sort orders are supported"));
+            }
+        }
+        _ if FIELDS.contains(&field) => {
+            return Err(anyhow!("unsupported comparison for {}", field))
+        }
+        _ => return Err(anyhow!("unknown field {}", field)),
+    }
+    Ok(())
+}
+
+// Splits on whitespace outside double quotes and drops the quotes.
+fn tokenize(input: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in input.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    tokens.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if quoted {
+        return Err(anyhow!("unterminated quote in query"));
+    }
+    if started {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+// `field`, operator and value of a term, or `None` for a plain word.
+fn split_term(token: &str) -> Option<(String, Op, String)> {
+    let at = token.find([':', '~', '<', '>', '='])?;
+    let field = &token[..at];
+    if field.is_empty() || !field.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let rest = &token[at..];
+    let (op, len) = if rest.starts_with(">=") {
+        (Op::Ge, 2)
+    } else if rest.starts_with("<=") {
+        (Op::Le, 2)
+    } else {
+        match rest.as_bytes()[0] {
+            b'>' => (Op::Gt, 1),
+            b'<' => (Op::Lt, 1),
+            b'~' => (Op::Like, 1),
+            _ => (Op::Is, 1),
+        }
+    };
+    Some((field.to_string(), op, rest[len..].to_string()))
+}
+
+// Inclusive (min, max) for a comparison against `value`.
+fn bounds(op: Op, value: i64) -> Result<(Option<i64>, Option<i64>)> {
+    Ok(match op {
+        Op::Is => (Some(value), Some(value)),
+        Op::Gt => (Some(value + 1), None),
+        Op::Ge => (Some(value), None),
+        Op::Lt => (None, Some(value - 1)),
+        Op::Le => (None, Some(value)),
+        Op::Like => return Err(anyhow!("`~` only applies to text fields")),
+    })
+}
+
+fn date_bounds(op: Op, value: &str) -> Result<(Option<NaiveDate>, Option<NaiveDate>)> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("expected a YYYY-MM-DD date, got {}", value))?;
+    let day = Days::new(1);
+    Ok(match op {
+        Op::Is => (Some(date), Some(date)),
+        Op::Gt => (date.checked_add_days(day), None),
+        Op::Ge => (Some(date), None),
+        Op::Lt => (None, date.checked_sub_days(day)),
+        Op::Le => (None, Some(date)),
+        Op::Like => return Err(anyhow!("`~` only applies to text fields")),
+    })
+}
+
+// Bytes, optionally with a binary unit: 512, 1.5k, 10MB, 2GiB.
+fn parse_size(value: &str) -> Result<i64> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("expected a size, got {}", value))?;
+    let scale: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        other => return Err(anyhow!("unknown size unit {}", other)),
+    };
+    Ok((number * scale as f64).round() as i64)
+}
+
+fn parse_sort_key(value: &str) -> Result<SortKey> {
+    Ok(match value {
+        "name" => SortKey::Name,
+        "size" => SortKey::Size,
+        "modified" => SortKey::Modified,
+        "relevance" => SortKey::Relevance,
+        "rating" => SortKey::Rating,
+        "ext" => SortKey::Ext,
+        "path" => SortKey::Path,
+        "added" => SortKey::AddedAt,
+        "allocated" => SortKey::Allocated,
+        other => return Err(anyhow!("unknown sort key {}", other)),
+    })
+}
+
+fn parse_desc(value: &str) -> Result<bool> {
+    match value {
+        "asc" => Ok(false),
+        "desc" => Ok(true),
+        other => Err(anyhow!("expected asc or desc, got {}", other)),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value {
+        "true" | "yes" => Ok(true),
+        "false" | "no" => Ok(false),
+        other => Err(anyhow!("expected true or false, got {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> SearchQuery {
+        input.parse().unwrap()
+    }
+
+    fn error(input: &str) -> String {
+        format!("{:#}", input.parse::<SearchQuery>().unwrap_err())
+    }
+
+    fn date(s: &str) -> Option<NaiveDate> {
+        Some(NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap())
+    }
+
+    #[test]
+    fn parses_compound_expressions() {
+        let q = parse("ext:pdf,docx size>10MB modified>=2023-01-01 name~report");
+        assert_eq!(
+            q,
+            SearchQuery {
+                ext_in: vec!["pdf".into(), "docx".into()],
+                min_size: Some((10 << 20) + 1),
+                date_from: date("2023-01-01"),
+                name_like: Some("report".into()),
+                ..SearchQuery::default()
+            }
+        );
+    }
+
+    #[test]
+    fn empty_input_is_the_default_query() {
+        assert_eq!(parse(""), SearchQuery::default());
+        assert_eq!(parse("  \t "), SearchQuery::default());
+    }
+
+    #[test]
+    fn plain_words_join_the_name() {
+        assert_eq!(
+            parse("annual report").name_like.as_deref(),
+            Some("annual report")
+        );
+        assert_eq!(
+            parse("name:annual 2023 ext:pdf").name_like.as_deref(),
+            Some("annual 2023")
+        );
+        // Not a field name, so the whole token is a word.
+        assert_eq!(parse("10:30").name_like.as_deref(), Some("10:30"));
+    }
+
+    #[test]
+    fn quotes_keep_spaces() {
+        let q = parse(r#"name:"annual report" "two words""#);
+        assert_eq!(q.name_like.as_deref(), Some("annual report two words"));
+        assert_eq!(parse(r#"path:"""#).path_prefix.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn fields_are_case_insensitive() {
+        assert_eq!(parse("EXT:pdf").ext_in, ["pdf"]);
+    }
+
+    #[test]
+    fn comparisons_set_inclusive_bounds() {
+        let q = parse("size>=1k size<2k");
+        assert_eq!((q.min_size, q.max_size), (Some(1024), Some(2047)));
+        let q = parse("size:1.5k");
+        assert_eq!((q.min_size, q.max_size), (Some(1536), Some(1536)));
+        let q = parse("size<=2GiB allocated>0");
+        assert_eq!(q.max_size, Some(2 << 30));
+        assert_eq!(q.min_allocated, Some(1));
+        let q = parse("duration>60 duration<=120 height>1079");
+        assert_eq!((q.min_duration, q.max_duration), (Some(61), Some(120)));
+        assert_eq!(q.min_height, Some(1080));
+        let q = parse("modified>2023-12-31 modified<2024-02-01");
+        assert_eq!(
+            (q.date_from, q.date_to),
+            (date("2024-01-01"), date("2024-01-31"))
+        );
+        let q = parse("added:2024-05-06");
+        assert_eq!(
+            (q.added_from, q.added_to),
+            (date("2024-05-06"), date("2024-05-06"))
+        );
+        assert_eq!(parse("rating>3").min_rating, Some(4));
+        assert_eq!(parse("rating>=3").min_rating, Some(3));
+    }
+
+    #[test]
+    fn parses_text_and_flag_fields() {
+        let q = parse(
+            "regex:^a path:/data path~tmp$ glob:**/*.rs kind:symlink mime:image \
+             content:invoice note:todo in:work tag:red tag:blue hash:ABC hashed:yes \
+             xattr:user.x volume:disk case:sensitive fuzzy:true \
+             artist:Bach album~Suites title:Prelude",
+        );
+        assert_eq!(
+            q,
+            SearchQuery {
+                name_regex: Some("^a".into()),
+                path_prefix: Some("/data".into()),
+                path_regex: Some("tmp$".into()),
+                glob: Some("**/*.rs".into()),
+                kind: Some(EntryKind::Symlink),
+                mime: Some("image".into()),
+                content: Some("invoice".into()),
+                note: Some("todo".into()),
+                collection: Some("work".into()),
+                tags: vec!["red".into(), "blue".into()],
+                hash: Some("ABC".into()),
+                has_hash: Some(true),
+                has_xattr: Some("user.x".into()),
+                volume: Some("disk".into()),
+                case_sensitive: Some(true),
+                fuzzy: true,
+                artist: Some("Bach".into()),
+                album: Some("Suites".into()),
+                title: Some("Prelude".into()),
+                ..SearchQuery::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_sort_keys_and_orders() {
+        let q = parse("sort:size,name order:desc,asc");
+        assert_eq!(q.sort_key, Some(SortKey::Size));
+        assert_eq!(q.then_sort, Some(SortKey::Name));
+        assert!(q.desc);
+        assert!(!q.then_desc);
+        let q = parse("sort:added order:asc");
+        assert_eq!((q.sort_key, q.then_sort), (Some(SortKey::AddedAt), None));
+        assert!(!q.desc);
+    }
+
+    #[test]
+    fn parsed_queries_survive_serialization() {
+        let q = parse("ext:rs size>1k modified<=2024-01-01 sort:modified order:desc main");
+        let json = serde_json::to_string(&q).unwrap();
+        assert_eq!(serde_json::from_str::<SearchQuery>(&json).unwrap(), q);
+    }
+
+    #[test]
+    fn rejects_malformed_terms() {
+        assert!(error(r#"name:"open"#).contains("unterminated quote"));
+        assert!(error("colour:red").contains("unknown field colour"));
+        assert!(error("ext<pdf").contains("unsupported comparison for ext"));
+        assert!(error("height<100").contains("unsupported comparison for height"));
+        assert!(error("size~10").contains("only applies to text fields"));
+        assert!(error("size>10XB").contains("unknown size unit xb"));
+        assert!(error("size>big").contains("expected a size"));
+        assert!(error("modified>yesterday").contains("expected a YYYY-MM-DD date"));
+        assert!(error("rating>=high").contains("expected a rating"));
+        assert!(error("kind:folder").contains("unknown entry kind"));
+        assert!(error("hashed:maybe").contains("expected true or false"));
+        assert!(error("case:upper").contains("expected sensitive or insensitive"));
+        assert!(error("sort:colour").contains("unknown sort key colour"));
+        assert!(error("sort:size,name,ext").contains("at most two sort keys"));
+        assert!(error("order:up").contains("expected asc or desc"));
+        assert!(error("order:asc,asc,asc").contains("at most two sort orders"));
+        // The failing term is named.
+        assert!(error("ext:pdf size>big").contains("in query term `size>big`"));
+    }
+}
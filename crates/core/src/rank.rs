@@ -0,0 +1,68 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::FileRecord;
+
+const MATCH_WEIGHT: f64 = 10.0;
+const RECENCY_WEIGHT: f64 = 3.0;
+const DEPTH_WEIGHT: f64 = 2.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoredRecord {
+    #[serde(flatten)]
+    pub record: FileRecord,
+    pub score: f64,
+}
+
+pub fn relevance_score(rec: &FileRecord, name: Option<&str>, now: DateTime<Utc>) -> f64 {
+    let quality = match name.filter(|n| !n.is_empty()) {
+        Some(needle) => match_quality(&rec.name, needle),
+        None => 0.0,
+    };
+    let age_days = (now - rec.modified).num_seconds().max(0) as f64 / 86_400.0;
+    let recency = 1.0 / (1.0 + age_days / 30.0);
+    let depth = Path::new(&rec.path).components().count() as f64;
+    let shallowness = 1.0 / (1.0 + depth);
+    quality * MATCH_WEIGHT + recency * RECENCY_WEIGHT + shallowness * DEPTH_WEIGHT
+}
+
+fn match_quality(file_name: &str, needle: &str) -> f64 {
+    let file_name = file_name.to_lowercase();
+    let needle = needle.to_lowercase();
+    let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name.as_str(), |(stem, _)| stem);
+    if file_name == needle || stem == needle {
+        3.0
+    } else if file_name.starts_with(&needle) {
+        2.0
+    } else if file_name.contains(&needle) {
+        1.0
+    } else {
+        0.5
+    }
+}
+
+pub(crate) fn sort_by_relevance(
+    rows: Vec<FileRecord>,
+    name: Option<&str>,
+    desc: bool,
+) -> Vec<FileRecord> {
+    let now = Utc::now();
+    let mut scored: Vec<(f64, FileRecord)> = rows
+        .into_iter()
+        .map(|rec| (relevance_score(&rec, name, now), rec))
+        .collect();
+    scored.sort_by(|a, b| {
+        let ord = b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal);
+        if desc {
+            ord.reverse()
+        } else {
+            ord
+        }
+    });
+    scored.into_iter().map(|(_, rec)| rec).collect()
+}
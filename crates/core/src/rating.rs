@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use rusqlite::params;
+
+use crate::indexer::FileIndexer;
+use crate::paths;
+
+pub const MAX_RATING: u8 = 5;
+
+impl FileIndexer {
+    // Sets the user rating of an indexed entry, 0 (unrated) to `MAX_RATING`.
+    // Re-indexing leaves it alone and detected renames carry it over.
+    pub fn set_rating<P: AsRef<Path>>(&self, path: P, rating: u8) -> Result<()> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        if rating > MAX_RATING {
+            return Err(anyhow!(
+                "rating must be 0 to {}, got {}",
+                MAX_RATING,
+                rating
+            ));
+        }
+        let path = paths::key(path.as_ref());
+        let updated = conn.execute(
+            "UPDATE files SET rating = ? WHERE path = ?",
+            params![rating, path],
+        )?;
+        if updated == 0 {
+            return Err(anyhow!("{} is not indexed", path));
+        }
+        self.invalidate_cache();
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use std::cmp::Reverse;
+use std::path::{Path, MAIN_SEPARATOR};
+
+use anyhow::Result;
+use redb::{Database, ReadableTable, TableDefinition};
+
+use crate::archive;
+use crate::fold;
+use crate::fuzzy;
+use crate::indexer::{subtree_bounds, DuplicateGroup, DuplicateSort, FileRecord, SearchQuery};
+use crate::paths;
+use crate::store::{self, IndexStore};
+
+const FILES: TableDefinition<&str, &[u8]> = TableDefinition::new("files");
+
+pub struct RedbStore {
+    db: Database,
+}
+
+impl RedbStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = Database::create(path)?;
+        let txn = db.begin_write()?;
+        txn.open_table(FILES)?;
+        txn.commit()?;
+        Ok(Self { db })
+    }
+
+    fn all_records(&self) -> Result<Vec<FileRecord>> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(FILES)?;
+        let mut out = Vec::new();
+        for entry in table.iter()? {
+            let (_, value) = entry?;
+            out.push(serde_json::from_slice(value.value())?);
+        }
+        Ok(out)
+    }
+
+    // The records `q` can match by path: only the subtree for a directory
+    // filter, and only keys that contain the name filter, since a record's
+    // name is the last component of its path. `filter_records` applies the
+    // rest of the query.
+    fn candidates(&self, q: &SearchQuery) -> Result<Vec<FileRecord>> {
+        let exact = q.case_sensitive == Some(true) && !q.fuzzy;
+        let name = q.name_like.as_deref().filter(|s| !s.is_empty()).map(|s| {
+            if exact {
+                s.to_string()
+            } else {
+                fold::fold(s)
+            }
+        });
+        let mut out = Vec::new();
+        let mut keep = |key: &str, value: &[u8]| -> Result<()> {
+            let named = name.as_deref().is_none_or(|n| {
+                if q.fuzzy {
+                    fuzzy::matches(n, key)
+                } else if exact {
+                    key.contains(n)
+                } else {
+                    fold::fold(key).contains(n)
+                }
+            });
+            if named {
+                out.push(serde_json::from_slice(value)?);
+            }
+            Ok(())
+        };
+
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(FILES)?;
+        match q.path_prefix.as_deref().filter(|s| !s.is_empty()) {
+            Some(dir) => {
+                let (lower, upper) = subtree_bounds(dir);
+                let dir = lower.trim_end_matches(MAIN_SEPARATOR);
+                if let Some(value) = table.get(dir)? {
+                    keep(dir, value.value())?;
+                }
+                for entry in table.range(lower.as_str()..upper.as_str())? {
+                    let (key, value) = entry?;
+                    keep(key.value(), value.value())?;
+                }
+            }
+            None => {
+                for entry in table.iter()? {
+                    let (key, value) = entry?;
+                    keep(key.value(), value.value())?;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl IndexStore for RedbStore {
+    fn upsert(&self, rec: &FileRecord) -> Result<()> {
+        self.upsert_many(std::slice::from_ref(rec))
+    }
+
+    fn upsert_many(&self, recs: &[FileRecord]) -> Result<()> {
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(FILES)?;
+            for rec in recs {
+                let mut rec = rec.clone();
+                if let Some(existing) = table.get(rec.path.as_str())? {
+                    let existing: FileRecord = serde_json::from_slice(existing.value())?;
+                    rec.added_at = existing.added_at;
+                }
+                let bytes = serde_json::to_vec(&rec)?;
+                table.insert(rec.path.as_str(), bytes.as_slice())?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> Result<usize> {
+        let path = paths::key(path);
+        let (prefix, _) = subtree_bounds(&path);
+        let (members, _) = archive::member_bounds(&path);
+        let mut removed = 0;
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(FILES)?;
+            table.retain(|key, _| {
+                let gone = key == path || key.starts_with(&prefix) || key.starts_with(&members);
+                removed += usize::from(gone);
+                !gone
+            })?;
+        }
+        txn.commit()?;
+        Ok(removed)
+    }
+
+    fn search(&self, q: &SearchQuery) -> Result<Vec<FileRecord>> {
+        store::filter_records(q, self.candidates(q)?)
+    }
+
+    fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>> {
+        let mut rows = self.all_records()?;
+        rows.sort_by_key(|r| Reverse(r.added_at));
+        rows.truncate(limit.max(0) as usize);
+        Ok(rows)
+    }
+
+    fn duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>> {
+        Ok(store::group_duplicates(
+            self.all_records()?,
+            limit,
+            offset,
+            sort,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::imported_record;
+    use crate::indexer::IndexOptions;
+
+    fn paths(records: Vec<FileRecord>) -> Vec<String> {
+        let mut paths: Vec<String> = records.into_iter().map(|r| r.path).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn batched_upserts_keep_added_at() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RedbStore::new(dir.path().join("index.redb")).unwrap();
+        let first = imported_record("/data/a.txt", 1, 1_700_000_000);
+        store.upsert_many(std::slice::from_ref(&first)).unwrap();
+
+        let mut again = imported_record("/data/a.txt", 2, 1_700_000_000);
+        again.added_at = first.added_at + chrono::Duration::days(1);
+        let other = imported_record("/data/b.txt", 3, 1_700_000_000);
+        store.upsert_many(&[again, other]).unwrap();
+
+        let all = store.search(&SearchQuery::default()).unwrap();
+        assert_eq!(all.len(), 2);
+        let a = all.iter().find(|r| r.path == "/data/a.txt").unwrap();
+        assert_eq!(a.size, 2);
+        assert_eq!(a.added_at, first.added_at);
+    }
+
+    #[test]
+    fn indexes_across_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        for i in 0..5 {
+            std::fs::write(root.join(format!("{}.txt", i)), "x").unwrap();
+        }
+        let store = RedbStore::new(dir.path().join("index.redb")).unwrap();
+        let opts = IndexOptions {
+            batch_size: 2,
+            ..IndexOptions::default()
+        };
+        store.index_dir(&root, &opts).unwrap();
+        let found = store
+            .search(&SearchQuery {
+                ext: Some("txt".to_string()),
+                ..SearchQuery::default()
+            })
+            .unwrap();
+        assert_eq!(found.len(), 5);
+    }
+
+    #[test]
+    fn prefix_and_name_searches_read_matching_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RedbStore::new(dir.path().join("index.redb")).unwrap();
+        let sep = MAIN_SEPARATOR;
+        let path = |rest: &str| format!("{sep}data{}", rest.replace('/', &sep.to_string()));
+        let records: Vec<FileRecord> = ["", "/Report.pdf", "/sub/report-2.txt", "x/notes.txt"]
+            .iter()
+            .map(|rest| imported_record(&path(rest), 1, 1_700_000_000))
+            .collect();
+        store.upsert_many(&records).unwrap();
+
+        let under = |name: Option<&str>| SearchQuery {
+            path_prefix: Some(path("")),
+            name_like: name.map(str::to_string),
+            ..SearchQuery::default()
+        };
+        // The directory itself and its subtree, not the sibling `datax`.
+        assert_eq!(
+            paths(store.candidates(&under(None)).unwrap()),
+            vec![path(""), path("/Report.pdf"), path("/sub/report-2.txt")]
+        );
+        assert_eq!(
+            paths(store.search(&under(Some("report"))).unwrap()),
+            vec![path("/Report.pdf"), path("/sub/report-2.txt")]
+        );
+        // Keys without the name are skipped before decoding.
+        let named = SearchQuery {
+            name_like: Some("notes".to_string()),
+            ..SearchQuery::default()
+        };
+        assert_eq!(
+            paths(store.candidates(&named).unwrap()),
+            vec![path("x/notes.txt")]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{subtree_bounds, FileIndexer};
+use crate::paths;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenameRecord {
+    pub old_path: String,
+    pub new_path: String,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl FileIndexer {
+    pub fn recent_renames(&self, limit: i64) -> Result<Vec<RenameRecord>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT old_path,new_path,detected_at FROM renames
+             ORDER BY detected_at DESC, rowid DESC LIMIT ?",
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            let detected_at: i64 = row.get(2)?;
+            Ok(RenameRecord {
+                old_path: row.get(0)?,
+                new_path: row.get(1)?,
+                detected_at: DateTime::<Utc>::from_timestamp(detected_at, 0).unwrap_or_default(),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // Pairs rows first inserted by the scan of `root` that started at
+    // `since` with older rows of the same hash and size whose file is gone.
+    // The new row inherits `added_at`, the rating, any note, tags and
+    // collection memberships, and the stale row is dropped.
+    pub(crate) fn detect_renames(&self, root: &Path, since: i64) -> Result<usize> {
+        let conn = self.conn();
+        let root = paths::key(root);
+        let (lower, upper) = subtree_bounds(&root);
+        let added: Vec<(String, String, i64)> = {
+            let mut stmt = conn.prepare(
+                "SELECT path,hash,size FROM files
+                 WHERE kind = 'file' AND hash IS NOT NULL AND added_at >= ?
+                   AND path >= ? AND path < ?",
+            )?;
+            let rows = stmt.query_map(params![since, lower, upper], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        if added.is_empty() {
+            return Ok(0);
+        }
+
+        let now = Utc::now().timestamp();
+        let txn = conn.unchecked_transaction()?;
+        let mut renamed = 0usize;
+        {
+            let mut candidates = txn.prepare(
+                "SELECT path,raw_path,added_at FROM files
+                 WHERE hash = ? AND size = ? AND kind = 'file' AND added_at < ?
+                 ORDER BY added_at",
+            )?;
+            for (new_path, hash, size) in added {
+                let olds: Vec<(String, Option<Vec<u8>>, i64)> = candidates
+                    .query_map(params![hash, size, since], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                    })?
+                    .collect::<rusqlite::Result<_>>()?;
+                // Only a definite "not found" marks the old copy as moved.
+                let Some((old_path, _, added_at)) = olds.into_iter().find(|(path, raw, _)| {
+                    matches!(
+                        paths::decode(path, raw.as_deref()).symlink_metadata(),
+                        Err(err) if err.kind() == ErrorKind::NotFound
+                    )
+                }) else {
+                    continue;
+                };
+                // Moved first, so the delete trigger leaves it alone.
+                txn.execute(
+                    "UPDATE notes SET path = ? WHERE path = ?",
+                    params![new_path, old_path],
+                )?;
+                txn.execute(
+                    "UPDATE OR IGNORE collection_items SET path = ? WHERE path = ?",
+                    params![new_path, old_path],
+                )?;
+                txn.execute(
+                    "UPDATE OR IGNORE tags SET path = ? WHERE path = ?",
+                    params![new_path, old_path],
+                )?;
+                txn.execute(
+                    "UPDATE files SET (added_at, rating) =
+                         (?1, (SELECT rating FROM files WHERE path = ?2))
+                     WHERE path = ?3",
+                    params![added_at, old_path, new_path],
+                )?;
+                txn.execute("DELETE FROM files WHERE path = ?", params![old_path])?;
+                txn.execute(
+                    "INSERT INTO renames(old_path,new_path,detected_at) VALUES (?,?,?)",
+                    params![old_path, new_path, now],
+                )?;
+                renamed += 1;
+            }
+        }
+        txn.commit()?;
+        if renamed > 0 {
+            self.invalidate_cache();
+        }
+        Ok(renamed)
+    }
+}
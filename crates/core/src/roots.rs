@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancellationToken;
+use crate::indexer::{FileIndexer, IndexOptions, IndexReport};
+use crate::paths;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootInfo {
+    pub path: String,
+    pub options: IndexOptions,
+    pub last_scan: Option<DateTime<Utc>>,
+    pub file_count: i64,
+}
+
+impl FileIndexer {
+    pub fn list_roots(&self) -> Result<Vec<RootInfo>> {
+        let conn = self.reader()?;
+        let mut stmt =
+            conn.prepare("SELECT path,options,last_scan,file_count FROM roots ORDER BY path")?;
+        let rows = stmt.query_map([], |row| {
+            let options: Option<String> = row.get(1)?;
+            let last_scan: Option<i64> = row.get(2)?;
+            Ok(RootInfo {
+                path: row.get(0)?,
+                options: options
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+                last_scan: last_scan.and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0)),
+                file_count: row.get(3)?,
+            })
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    pub fn forget_root<P: AsRef<Path>>(&self, root: P) -> Result<usize> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let root = root.as_ref();
+        conn.execute(
+            "DELETE FROM roots WHERE path = ?",
+            params![paths::key(root)],
+        )?;
+        let removed = self.remove(root)?;
+        self.forget_dirs(root)?;
+        Ok(removed)
+    }
+
+    pub fn reindex_all(&self, cancel: &CancellationToken) -> Result<IndexReport> {
+        self.ensure_writable()?;
+        let mut total = IndexReport::default();
+        for root in self.list_roots()? {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let opts = IndexOptions {
+                cancel: cancel.clone(),
+                ..root.options
+            };
+            let report = self.index_dir(&root.path, &opts)?;
+            total.indexed += report.indexed;
+            total.skipped += report.skipped;
+            total.errors.extend(report.errors);
+        }
+        Ok(total)
+    }
+
+    pub(crate) fn root_skip_hidden(&self, root: &Path) -> Result<Option<bool>> {
+        let conn = self.reader()?;
+        Ok(conn
+            .query_row(
+                "SELECT skip_hidden FROM roots WHERE path = ?",
+                params![paths::key(root)],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    pub(crate) fn record_root(&self, root: &Path, opts: &IndexOptions) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO roots(path,skip_hidden,options) VALUES(?,?,?)
+             ON CONFLICT(path) DO UPDATE SET
+                 skip_hidden=excluded.skip_hidden,
+                 -- A journal position only stands for scans with the same options.
+                 usn_journal=CASE WHEN options IS excluded.options THEN usn_journal END,
+                 usn_next=CASE WHEN options IS excluded.options THEN usn_next END,
+                 options=excluded.options",
+            params![
+                paths::key(root),
+                opts.skip_hidden.unwrap_or(false),
+                serde_json::to_string(opts)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn finish_root_scan(&self, root: &Path, count: usize) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE roots SET last_scan = ?, file_count = ? WHERE path = ?",
+            params![Utc::now().timestamp(), count as i64, paths::key(root)],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use std::io::Read;
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::source::{CommandReader, FolderTags, ScanSource, SourceEntry};
+
+// Objects listed per call of the AWS CLI.
+const PAGE_SIZE: usize = 1000;
+
+// A bucket of Amazon S3 or a compatible store (MinIO, Ceph, R2, ...), or
+// the objects below a key prefix. Listing goes through the AWS CLI, which
+// brings credentials, regions, SSO and TLS along, so it has to be
+// installed. Objects are only downloaded to hash them, with
+// `IndexOptions::hash`.
+#[derive(Debug, Clone, Default)]
+pub struct S3Source {
+    pub bucket: String,
+    // Raw key prefix: `photos/` lists a folder, `photos/2024` also matches
+    // `photos/2024-01.jpg`.
+    pub prefix: String,
+    // Endpoint of an S3-compatible store, e.g. `http://localhost:9000`.
+    pub endpoint: Option<String>,
+    // Named profile of the AWS configuration; `None` uses the default chain.
+    pub profile: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Page {
+    #[serde(default)]
+    contents: Vec<Object>,
+    next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Object {
+    key: String,
+    size: i64,
+    last_modified: String,
+    #[serde(rename = "ETag")]
+    etag: Option<String>,
+}
+
+impl S3Source {
+    // From `s3://bucket` or `s3://bucket/prefix`.
+    pub fn parse(url: &str) -> Result<Self> {
+        let rest = url
+            .strip_prefix("s3://")
+            .ok_or_else(|| anyhow!("not an s3:// URL: {}", url))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(anyhow!("no bucket in {}", url));
+        }
+        Ok(Self {
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+            ..Self::default()
+        })
+    }
+
+    fn aws(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new("aws");
+        cmd.args(args);
+        if let Some(endpoint) = &self.endpoint {
+            cmd.args(["--endpoint-url", endpoint]);
+        }
+        if let Some(profile) = &self.profile {
+            cmd.args(["--profile", profile]);
+        }
+        cmd
+    }
+
+    fn page(&self, token: Option<&str>) -> Result<Page> {
+        let page_size = PAGE_SIZE.to_string();
+        let mut cmd = self.aws(&["s3api", "list-objects-v2", "--output", "json"]);
+        cmd.args(["--bucket", &self.bucket])
+            .args(["--max-items", &page_size]);
+        if !self.prefix.is_empty() {
+            cmd.args(["--prefix", &self.prefix]);
+        }
+        if let Some(token) = token {
+            cmd.args(["--starting-token", token]);
+        }
+        let output = cmd
+            .output()
+            .context("running the AWS CLI (aws), which lists S3 buckets")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "listing {}: {}",
+                self.root(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        // An empty listing prints nothing at all.
+        if output.stdout.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Page::default());
+        }
+        serde_json::from_slice(&output.stdout).context("reading the AWS CLI listing")
+    }
+
+    fn entry(&self, object: Object) -> Result<SourceEntry> {
+        let modified = DateTime::parse_from_rfc3339(&object.last_modified)
+            .with_context(|| format!("bad modification time of {}", object.key))?;
+        Ok(SourceEntry {
+            path: format!("s3://{}/{}", self.bucket, object.key),
+            size: object.size,
+            modified: modified.with_timezone(&Utc),
+            etag: object.etag.map(|tag| tag.trim_matches('"').to_string()),
+            hash: None,
+            hash_algo: None,
+            folder: false,
+        })
+    }
+}
+
+impl ScanSource for S3Source {
+    fn scheme(&self) -> &str {
+        "s3"
+    }
+
+    fn volume(&self) -> &str {
+        &self.bucket
+    }
+
+    fn root(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+    }
+
+    fn entries<'a>(
+        &'a self,
+        _known: &'a FolderTags,
+    ) -> Result<Box<dyn Iterator<Item = Result<SourceEntry>> + 'a>> {
+        Ok(Box::new(Listing {
+            source: self,
+            objects: Vec::new().into_iter(),
+            token: None,
+            done: false,
+        }))
+    }
+
+    fn open(&self, entry: &SourceEntry) -> Result<Option<Box<dyn Read + '_>>> {
+        if !entry.path.starts_with(&format!("s3://{}/", self.bucket)) {
+            return Ok(None);
+        }
+        let cmd = self.aws(&["s3", "cp", "--only-show-errors", &entry.path, "-"]);
+        Ok(Some(Box::new(CommandReader::spawn(cmd, None)?)))
+    }
+}
+
+// Fetches a page whenever the previous one is used up.
+struct Listing<'a> {
+    source: &'a S3Source,
+    objects: std::vec::IntoIter<Object>,
+    token: Option<String>,
+    done: bool,
+}
+
+impl Iterator for Listing<'_> {
+    type Item = Result<SourceEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(object) = self.objects.next() {
+                // Zero-byte keys ending in a slash are folder placeholders.
+                if object.key.ends_with('/') {
+                    continue;
+                }
+                return Some(self.source.entry(object));
+            }
+            if self.done {
+                return None;
+            }
+            match self.source.page(self.token.as_deref()) {
+                Ok(page) => {
+                    self.done = page.next_token.is_none();
+                    self.token = page.next_token;
+                    self.objects = page.contents.into_iter();
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
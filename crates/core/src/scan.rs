@@ -0,0 +1,597 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{DirEntry, Walk, WalkBuilder};
+
+use crate::archive;
+use crate::chunks::{self, Chunk};
+use crate::extract::Extractors;
+use crate::indexer::{
+    build_record, build_symlink_record, unreachable_record, EntryKind, FileRecord, IndexOptions,
+    IndexReport,
+};
+use crate::paths;
+use crate::watchdog::Progress;
+use crate::xattrs::{self, Xattr};
+
+const CHANNEL_CAPACITY: usize = 1024;
+// Skipped entries beyond this are only counted, so a tree full of
+// unreadable files cannot exhaust memory.
+const MAX_REPORTED_ERRORS: usize = 1000;
+// How often idle workers and a writer with an IO timeout look up from
+// waiting, to notice the end of the scan or a stalled read.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// Reads given up on before the share counts as gone and the scan ends
+// with what it has.
+const MAX_ABANDONED: usize = 32;
+// Wait before the first retry of a transient error.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+pub(crate) struct ScannedFile {
+    pub(crate) record: FileRecord,
+    pub(crate) content: Option<String>,
+    pub(crate) chunks: Option<Vec<Chunk>>,
+    pub(crate) xattrs: Option<Vec<Xattr>>,
+}
+
+// Nearly every message is a file, so boxing would only add an allocation
+// per file.
+#[allow(clippy::large_enum_variant)]
+enum Scanned {
+    File(ScannedFile),
+    Dir(String),
+    Failed(PathBuf, anyhow::Error),
+    // Timed out, or still failing after the retries of the options.
+    Unreachable(PathBuf, anyhow::Error),
+}
+
+// An entry for the workers: from the directory walk, or from a volume
+// listing (see `ntfs`) that has already applied the filters of the options.
+pub(crate) enum Found {
+    Walked(std::result::Result<DirEntry, ignore::Error>),
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Dir(PathBuf),
+    #[cfg_attr(not(windows), allow(dead_code))]
+    File(PathBuf),
+}
+
+impl Found {
+    fn path(&self, root: &Path) -> PathBuf {
+        match self {
+            Found::Walked(Ok(entry)) => entry.path().to_path_buf(),
+            Found::Walked(Err(err)) => error_path(err, root),
+            Found::Dir(path) | Found::File(path) => path.clone(),
+        }
+    }
+}
+
+pub(crate) struct ScanOutput {
+    pub(crate) report: IndexReport,
+    pub(crate) dirs: Vec<String>,
+}
+
+type Job = (usize, Found);
+type Done = (usize, Vec<Scanned>);
+
+// State of one scan shared with its threads. Threads are not scoped: a
+// read stuck on a dead share cannot be interrupted, so with an IO timeout
+// the scan goes on without it and leaves the thread behind.
+struct Shared {
+    root: PathBuf,
+    opts: IndexOptions,
+    extractors: Option<Arc<Extractors>>,
+    jobs: Mutex<mpsc::Receiver<Job>>,
+    // Entries being read; only tracked with an IO timeout.
+    in_progress: Mutex<HashMap<usize, (PathBuf, Arc<Progress>)>>,
+    // Entries the writer stopped waiting for; their workers exit when the
+    // read returns after all.
+    abandoned: Mutex<HashSet<usize>>,
+    // Number of entries, once the walk is complete.
+    walked: OnceLock<usize>,
+    // Set when the writer is done, for workers waiting on a stalled walk.
+    finished: AtomicBool,
+}
+
+enum Stall {
+    Entry(PathBuf),
+    Walk,
+}
+
+// Walks `root` (or goes through `listing`) on a producer thread and builds records (metadata + optional
+// hash, document text, chunks and xattrs) on `in_flight` worker threads,
+// then hands them to `sink` on the calling thread in walk order, so the
+// caller can own the single database writer and rows land in path order.
+// The walk runs at most `CHANNEL_CAPACITY` entries ahead of the writer,
+// which bounds memory while one large file holds up those behind it.
+// Entries that cannot be read are skipped and reported; only a missing root
+// or a failing `sink` ends the scan early, or with `io_timeout` a walk or
+// too many reads that stopped responding.
+pub(crate) fn scan<F>(
+    root: &Path,
+    opts: &IndexOptions,
+    extractors: Option<Arc<Extractors>>,
+    listing: Option<Vec<Found>>,
+    mut sink: F,
+) -> Result<ScanOutput>
+where
+    F: FnMut(ScannedFile) -> Result<()>,
+{
+    fs::metadata(root).with_context(|| format!("reading {}", root.display()))?;
+    let found: Box<dyn Iterator<Item = Found> + Send> = match listing {
+        Some(listing) => Box::new(listing.into_iter()),
+        None => Box::new(walker(root, opts)?.map(Found::Walked)),
+    };
+    let (job_tx, job_rx) = mpsc::channel::<Job>();
+    let shared = Arc::new(Shared {
+        root: root.to_path_buf(),
+        opts: opts.clone(),
+        extractors,
+        jobs: Mutex::new(job_rx),
+        in_progress: Mutex::new(HashMap::new()),
+        abandoned: Mutex::new(HashSet::new()),
+        walked: OnceLock::new(),
+        finished: AtomicBool::new(false),
+    });
+
+    let (tx, rx) = mpsc::channel::<Done>();
+    // One permit per entry between the walk and the writer.
+    let (permit_tx, permit_rx) = mpsc::sync_channel::<()>(CHANNEL_CAPACITY);
+    for _ in 0..CHANNEL_CAPACITY {
+        permit_tx.send(())?;
+    }
+    let walk = Arc::clone(&shared);
+    thread::spawn(move || {
+        let mut sent = 0;
+        for job in found.enumerate() {
+            // A closed channel means the writer stopped; abandon the walk.
+            if walk.opts.cancel.is_cancelled()
+                || permit_rx.recv().is_err()
+                || job_tx.send(job).is_err()
+            {
+                break;
+            }
+            sent += 1;
+        }
+        let _ = walk.walked.set(sent);
+    });
+    for _ in 0..workers(opts) {
+        spawn_worker(Arc::clone(&shared), tx.clone());
+    }
+    // Replacements for stuck workers need a sender; without a timeout the
+    // channel must close once the workers are done.
+    let spare = opts.io_timeout.is_some().then_some(tx);
+
+    let output = write_in_order(&shared, rx, spare, permit_tx, &mut sink);
+    shared.finished.store(true, Ordering::Relaxed);
+    output
+}
+
+fn write_in_order<F>(
+    shared: &Arc<Shared>,
+    rx: mpsc::Receiver<Done>,
+    spare: Option<mpsc::Sender<Done>>,
+    permit_tx: mpsc::SyncSender<()>,
+    sink: &mut F,
+) -> Result<ScanOutput>
+where
+    F: FnMut(ScannedFile) -> Result<()>,
+{
+    let opts = &shared.opts;
+    let mut report = IndexReport::default();
+    let mut total_bytes = 0u64;
+    let mut dirs = Vec::new();
+    // Entries finished ahead of an earlier one still being read.
+    let mut waiting = BTreeMap::new();
+    let mut next = 0;
+    let mut last_result = Instant::now();
+    let mut abandoned = 0;
+    'entries: loop {
+        if shared.walked.get().is_some_and(|&walked| next >= walked) {
+            break;
+        }
+        let received = match opts.io_timeout {
+            Some(timeout) => rx.recv_timeout(timeout.min(POLL_INTERVAL)),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let mut give_up = None;
+        match received {
+            Ok((seq, scanned)) => {
+                waiting.insert(seq, scanned);
+                last_result = Instant::now();
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                let timeout = opts.io_timeout.unwrap_or(Duration::MAX);
+                match stall(shared, next, timeout, last_result) {
+                    Some(Stall::Entry(path)) => {
+                        let err = anyhow!("no response for {:?}", timeout);
+                        waiting.insert(next, vec![Scanned::Unreachable(path, err)]);
+                        abandoned += 1;
+                        if abandoned >= MAX_ABANDONED {
+                            give_up = Some("reads stopped responding");
+                        } else if let Some(tx) = &spare {
+                            spawn_worker(Arc::clone(shared), tx.clone());
+                        }
+                    }
+                    Some(Stall::Walk) => give_up = Some("listing stopped responding"),
+                    None => {}
+                }
+            }
+        }
+        while let Some(entry) = waiting.remove(&next) {
+            next += 1;
+            let _ = permit_tx.send(());
+            for scanned in entry {
+                if opts.cancel.is_cancelled() {
+                    break 'entries;
+                }
+                let scanned = match scanned {
+                    Scanned::File(scanned) => scanned,
+                    Scanned::Dir(dir) => {
+                        dirs.push(dir);
+                        continue;
+                    }
+                    Scanned::Failed(path, err) => {
+                        skip(&mut report, path, err);
+                        continue;
+                    }
+                    Scanned::Unreachable(path, err) => {
+                        if opts.record_unreachable {
+                            // Only a path without a file name has no record.
+                            if let Ok(record) = unreachable_record(&path) {
+                                sink(ScannedFile {
+                                    record,
+                                    content: None,
+                                    chunks: None,
+                                    xattrs: None,
+                                })?;
+                                report.unreachable += 1;
+                            }
+                        }
+                        skip(&mut report, path, err);
+                        continue;
+                    }
+                };
+                if opts.max_files.is_some_and(|max| report.indexed >= max) {
+                    break 'entries;
+                }
+                let size = scanned.record.size.max(0) as u64;
+                if opts
+                    .max_total_bytes
+                    .is_some_and(|max| total_bytes + size > max)
+                {
+                    break 'entries;
+                }
+                sink(scanned)?;
+                report.indexed += 1;
+                total_bytes += size;
+            }
+        }
+        if let Some(reason) = give_up {
+            let err = anyhow!("{}; the scan ended early", reason);
+            skip(&mut report, shared.root.clone(), err);
+            break;
+        }
+    }
+    Ok(ScanOutput { report, dirs })
+}
+
+fn skip(report: &mut IndexReport, path: PathBuf, err: anyhow::Error) {
+    report.skipped += 1;
+    if report.errors.len() < MAX_REPORTED_ERRORS {
+        report.errors.push((path, err));
+    }
+}
+
+// Whether the writer should stop waiting: for entry `next`, read by a
+// worker that made no progress within `timeout`, or for the walk, when no
+// entry is being read and none arrived within `timeout`.
+fn stall(shared: &Shared, next: usize, timeout: Duration, last_result: Instant) -> Option<Stall> {
+    let in_progress = lock(&shared.in_progress);
+    if let Some((path, progress)) = in_progress.get(&next) {
+        if progress.idle() < timeout {
+            return None;
+        }
+        // Under the `in_progress` lock, so the worker sees it when done.
+        lock(&shared.abandoned).insert(next);
+        return Some(Stall::Entry(path.clone()));
+    }
+    // Abandoned entries stay in `in_progress` while their reads hang.
+    let reading = in_progress.keys().any(|&seq| seq > next);
+    let stalled = !reading && shared.walked.get().is_none() && last_result.elapsed() >= timeout;
+    stalled.then_some(Stall::Walk)
+}
+
+fn spawn_worker(shared: Arc<Shared>, tx: mpsc::Sender<Done>) {
+    thread::spawn(move || loop {
+        let job = lock(&shared.jobs).recv_timeout(POLL_INTERVAL);
+        let (seq, found) = match job {
+            Ok(job) => job,
+            Err(RecvTimeoutError::Timeout) if !shared.finished.load(Ordering::Relaxed) => continue,
+            Err(_) => break,
+        };
+        let opts = &shared.opts;
+        let scanned = if opts.cancel.is_cancelled() {
+            Vec::new()
+        } else if opts.io_timeout.is_some() {
+            let progress = Progress::enter();
+            let path = found.path(&shared.root);
+            lock(&shared.in_progress).insert(seq, (path, progress));
+            let scanned = scan_entry(found, &shared.root, opts, shared.extractors.as_deref());
+            Progress::leave();
+            let mut in_progress = lock(&shared.in_progress);
+            in_progress.remove(&seq);
+            // The writer went on without this entry and started another
+            // worker in place of this one.
+            if lock(&shared.abandoned).remove(&seq) {
+                break;
+            }
+            drop(in_progress);
+            scanned
+        } else {
+            scan_entry(found, &shared.root, opts, shared.extractors.as_deref())
+        };
+        if tx.send((seq, scanned)).is_err() {
+            break;
+        }
+    });
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn workers(opts: &IndexOptions) -> usize {
+    match (opts.in_flight, opts.threads) {
+        (0, 0) => thread::available_parallelism().map_or(1, |n| n.get()),
+        (0, threads) => threads,
+        (in_flight, _) => in_flight,
+    }
+}
+
+// The directory, the file, or the file followed by its archive members.
+fn scan_entry(
+    found: Found,
+    root: &Path,
+    opts: &IndexOptions,
+    extractors: Option<&Extractors>,
+) -> Vec<Scanned> {
+    let (path, record) = match found {
+        Found::Walked(Ok(entry)) if entry.file_type().is_some_and(|t| t.is_dir()) => {
+            return vec![Scanned::Dir(paths::key(entry.path()))];
+        }
+        Found::Walked(Ok(entry)) => {
+            let record = with_retries(opts, || build_entry(&entry, opts));
+            (entry.path().to_path_buf(), record)
+        }
+        Found::Walked(Err(err)) => (error_path(&err, root), walk_error(err, opts)),
+        Found::Dir(path) => return vec![Scanned::Dir(paths::key(&path))],
+        Found::File(path) => {
+            let record = with_retries(opts, || build_listed(&path, opts));
+            (path, record)
+        }
+    };
+    let record = match record {
+        Some(Ok(record)) => record,
+        Some(Err(err)) if transient(&err) => return vec![Scanned::Unreachable(path, err)],
+        Some(Err(err)) => return vec![Scanned::Failed(path, err)],
+        None => return Vec::new(),
+    };
+    let is_file = record.kind == EntryKind::File;
+    // Unreadable archives are still indexed as plain files.
+    let members = if opts.index_archives && is_file && archive::is_archive(&record.name) {
+        archive::read_members(&record, opts).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let fs_path = record.fs_path();
+    let content = extractors
+        .filter(|_| is_file)
+        .and_then(|ex| ex.extract(&fs_path, record.ext.as_deref()));
+    let chunks = (is_file && chunks::wants_chunks(&record, opts)).then(|| {
+        // An unreadable file just keeps no chunk list.
+        chunks::compute_chunks(&fs_path, opts.hash_algo).unwrap_or_default()
+    });
+    let xattrs =
+        (is_file && opts.xattrs).then(|| xattrs::read_xattrs(&fs_path).unwrap_or_default());
+    let mut scanned = vec![Scanned::File(ScannedFile {
+        record,
+        content,
+        chunks,
+        xattrs,
+    })];
+    scanned.extend(members.into_iter().map(|member| {
+        Scanned::File(ScannedFile {
+            record: member,
+            content: None,
+            chunks: None,
+            xattrs: None,
+        })
+    }));
+    scanned
+}
+
+fn build_entry(entry: &DirEntry, opts: &IndexOptions) -> Option<Result<FileRecord>> {
+    let file_type = entry.file_type()?;
+    if file_type.is_symlink() {
+        // Only reached when links are not followed.
+        return opts
+            .record_symlinks
+            .then(|| build_symlink_record(entry.path()));
+    }
+    if !file_type.is_file() {
+        return None;
+    }
+    if opts.record_symlinks && entry.path_is_symlink() {
+        return Some(build_symlink_record(entry.path()));
+    }
+    Some(build_record(entry.path(), opts))
+}
+
+// Builds the record again after transient errors, waiting twice as long
+// before each further attempt.
+fn with_retries<B>(opts: &IndexOptions, build: B) -> Option<Result<FileRecord>>
+where
+    B: Fn() -> Option<Result<FileRecord>>,
+{
+    let mut delay = RETRY_DELAY;
+    for _ in 0..opts.io_retries {
+        match build() {
+            Some(Err(err)) if transient(&err) && !opts.cancel.is_cancelled() => {
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            built => return built,
+        }
+    }
+    build()
+}
+
+// Errors of network filesystems that may clear up on their own.
+fn transient(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|err| {
+            matches!(
+                err.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::NotConnected
+                    | ErrorKind::BrokenPipe
+            ) || err.raw_os_error().is_some_and(transient_os_error)
+        })
+}
+
+#[cfg(unix)]
+fn transient_os_error(code: i32) -> bool {
+    [
+        libc::EIO,
+        libc::ESTALE,
+        libc::EHOSTDOWN,
+        libc::EHOSTUNREACH,
+        libc::ENETDOWN,
+        libc::ENETUNREACH,
+        libc::ENETRESET,
+    ]
+    .contains(&code)
+}
+
+#[cfg(windows)]
+fn transient_os_error(code: i32) -> bool {
+    use windows_sys::Win32::Foundation::{
+        ERROR_BAD_NETPATH, ERROR_NETNAME_DELETED, ERROR_NETWORK_BUSY, ERROR_NETWORK_UNREACHABLE,
+        ERROR_SEM_TIMEOUT, ERROR_UNEXP_NET_ERR,
+    };
+    [
+        ERROR_BAD_NETPATH,
+        ERROR_NETNAME_DELETED,
+        ERROR_NETWORK_BUSY,
+        ERROR_NETWORK_UNREACHABLE,
+        ERROR_SEM_TIMEOUT,
+        ERROR_UNEXP_NET_ERR,
+    ]
+    .iter()
+    .any(|&known| known as i32 == code)
+}
+
+// Listings never follow links.
+fn build_listed(path: &Path, opts: &IndexOptions) -> Option<Result<FileRecord>> {
+    match path.symlink_metadata() {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            opts.record_symlinks.then(|| build_symlink_record(path))
+        }
+        _ => Some(build_record(path, opts)),
+    }
+}
+
+fn walk_error(err: ignore::Error, opts: &IndexOptions) -> Option<Result<FileRecord>> {
+    if is_loop(&err) {
+        return None;
+    }
+    // Dangling links show up as "not found" once links are followed.
+    if opts.follow_symlinks
+        && err
+            .io_error()
+            .is_some_and(|e| e.kind() == ErrorKind::NotFound)
+    {
+        return None;
+    }
+    Some(Err(err.into()))
+}
+
+fn error_path(err: &ignore::Error, root: &Path) -> PathBuf {
+    match err {
+        ignore::Error::WithPath { path, .. } => path.clone(),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err, root)
+        }
+        _ => root.to_path_buf(),
+    }
+}
+
+fn is_loop(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => is_loop(err),
+        _ => false,
+    }
+}
+
+// The include and exclude globs of `opts`.
+pub(crate) fn overrides(root: &Path, opts: &IndexOptions) -> Result<Override> {
+    let mut overrides = OverrideBuilder::new(root);
+    for glob in &opts.include {
+        overrides.add(glob)?;
+    }
+    for glob in &opts.exclude {
+        overrides.add(&format!("!{}", glob))?;
+    }
+    Ok(overrides.build()?)
+}
+
+fn walker(root: &Path, opts: &IndexOptions) -> Result<Walk> {
+    let ignore_files = opts.respect_gitignore;
+    let skip_hidden = opts.skip_hidden.unwrap_or(false);
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .standard_filters(false)
+        .git_ignore(ignore_files)
+        .git_exclude(ignore_files)
+        .git_global(ignore_files)
+        .ignore(ignore_files)
+        .parents(ignore_files)
+        .require_git(false)
+        .follow_links(opts.follow_symlinks)
+        .max_depth(opts.max_depth)
+        .hidden(skip_hidden)
+        .overrides(overrides(root, opts)?);
+    // `hidden` covers dotfiles and the Windows hidden attribute; system files
+    // need their own check.
+    #[cfg(windows)]
+    if skip_hidden {
+        builder.filter_entry(|entry| !is_system_file(entry));
+    }
+    Ok(builder.build())
+}
+
+#[cfg(windows)]
+fn is_system_file(entry: &DirEntry) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    entry
+        .metadata()
+        .is_ok_and(|m| m.file_attributes() & crate::attributes::SYSTEM != 0)
+}
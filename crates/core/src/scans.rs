@@ -0,0 +1,287 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{FileIndexer, FileRecord};
+use crate::paths;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanInfo {
+    pub id: i64,
+    pub root: String,
+    pub started_at: DateTime<Utc>,
+    // `None` for runs that were cancelled or failed.
+    pub finished_at: Option<DateTime<Utc>>,
+    pub file_count: i64,
+    // Whether the files seen by the run were recorded for `diff_scans`.
+    pub snapshot: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanEntry {
+    pub path: String,
+    pub size: i64,
+    pub modified: DateTime<Utc>,
+    pub hash: Option<String>,
+}
+
+// An entry a scan had to skip.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    pub scan_id: i64,
+    pub path: String,
+    // The `std::io::ErrorKind` name when the cause was an I/O error, such as
+    // "PermissionDenied", otherwise "Other".
+    pub kind: String,
+    pub message: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanDiff {
+    pub added: Vec<ScanEntry>,
+    pub removed: Vec<ScanEntry>,
+    // Before and after states of paths present on both sides: those whose
+    // size changed, and those that kept their size but not their time or
+    // content hash.
+    pub resized: Vec<(ScanEntry, ScanEntry)>,
+    pub modified: Vec<(ScanEntry, ScanEntry)>,
+}
+
+impl ScanDiff {
+    fn changed(&mut self, before: ScanEntry, after: ScanEntry) {
+        let rehashed = matches!((&before.hash, &after.hash), (Some(x), Some(y)) if x != y);
+        if before.size != after.size {
+            self.resized.push((before, after));
+        } else if before.modified != after.modified || rehashed {
+            self.modified.push((before, after));
+        }
+    }
+}
+
+impl FileIndexer {
+    pub fn list_scans(&self, root: Option<&Path>, limit: i64) -> Result<Vec<ScanInfo>> {
+        let conn = self.reader()?;
+        let root = root.map(paths::key);
+        let mut stmt = conn.prepare(
+            "SELECT id,root,started_at,finished_at,file_count,snapshot FROM scans
+             WHERE ?1 IS NULL OR root = ?1
+             ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![root, limit], scan_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn scan_errors(&self, scan_id: i64) -> Result<Vec<ScanError>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT scan_id,path,kind,message,occurred_at FROM errors
+             WHERE scan_id = ? ORDER BY path",
+        )?;
+        let rows = stmt.query_map(params![scan_id], |row| {
+            let occurred_at: i64 = row.get(4)?;
+            Ok(ScanError {
+                scan_id: row.get(0)?,
+                path: row.get(1)?,
+                kind: row.get(2)?,
+                message: row.get(3)?,
+                occurred_at: DateTime::<Utc>::from_timestamp(occurred_at, 0).unwrap_or_default(),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // Compares the files seen by two snapshot scans, `a` being the older one.
+    pub fn diff_scans(&self, a: i64, b: i64) -> Result<ScanDiff> {
+        let conn = self.reader()?;
+        for id in [a, b] {
+            let snapshot: Option<bool> = conn
+                .query_row(
+                    "SELECT snapshot FROM scans WHERE id = ?",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match snapshot {
+                None => return Err(anyhow!("no scan with id {}", id)),
+                Some(false) => return Err(anyhow!("scan {} was not recorded as a snapshot", id)),
+                Some(true) => {}
+            }
+        }
+
+        let only_in = |keep: i64, other: i64| -> Result<Vec<ScanEntry>> {
+            let mut stmt = conn.prepare(
+                "SELECT path,size,modified,hash FROM scan_files x
+                 WHERE scan_id = ?1 AND NOT EXISTS (
+                     SELECT 1 FROM scan_files y WHERE y.scan_id = ?2 AND y.path = x.path
+                 )
+                 ORDER BY path",
+            )?;
+            let rows = stmt.query_map(params![keep, other], |row| entry_from_row(row, 0))?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT x.path,x.size,x.modified,x.hash, y.path,y.size,y.modified,y.hash
+             FROM scan_files x JOIN scan_files y ON y.path = x.path
+             WHERE x.scan_id = ?1 AND y.scan_id = ?2
+               AND (x.size != y.size OR x.modified != y.modified
+                    OR (x.hash IS NOT NULL AND y.hash IS NOT NULL AND x.hash != y.hash))
+             ORDER BY x.path",
+        )?;
+        let changed = stmt.query_map(params![a, b], |row| {
+            Ok((entry_from_row(row, 0)?, entry_from_row(row, 4)?))
+        })?;
+
+        let mut diff = ScanDiff {
+            added: only_in(b, a)?,
+            removed: only_in(a, b)?,
+            ..Default::default()
+        };
+        for pair in changed {
+            let (before, after) = pair?;
+            diff.changed(before, after);
+        }
+        Ok(diff)
+    }
+
+    // Compares the entries of this index with those of `newer`, such as a
+    // copy of a file server's database taken before last night's backup
+    // with today's. Both are walked in path order side by side.
+    pub fn diff_index(&self, newer: &FileIndexer) -> Result<ScanDiff> {
+        let sql = "SELECT path,size,modified,hash FROM files ORDER BY path";
+        let old_conn = self.reader()?;
+        let new_conn = newer.reader()?;
+        let mut old_stmt = old_conn.prepare(sql)?;
+        let mut new_stmt = new_conn.prepare(sql)?;
+        let mut old_rows = old_stmt
+            .query_map([], |row| entry_from_row(row, 0))?
+            .peekable();
+        let mut new_rows = new_stmt
+            .query_map([], |row| entry_from_row(row, 0))?
+            .peekable();
+
+        let mut diff = ScanDiff::default();
+        loop {
+            let order = match (old_rows.peek(), new_rows.peek()) {
+                (None, None) => break,
+                (Some(Ok(before)), Some(Ok(after))) => before.path.cmp(&after.path),
+                // A failed row is taken first so its error is returned.
+                (Some(Err(_)), _) | (Some(_), None) => Ordering::Less,
+                (_, Some(_)) => Ordering::Greater,
+            };
+            let before = old_rows
+                .next_if(|_| order != Ordering::Greater)
+                .transpose()?;
+            let after = new_rows.next_if(|_| order != Ordering::Less).transpose()?;
+            match (before, after) {
+                (Some(before), Some(after)) => diff.changed(before, after),
+                (Some(before), None) => diff.removed.push(before),
+                (None, Some(after)) => diff.added.push(after),
+                (None, None) => {}
+            }
+        }
+        Ok(diff)
+    }
+
+    pub(crate) fn begin_scan(&self, root: &Path, snapshot: bool) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO scans(root,started_at,snapshot) VALUES (?,?,?)",
+            params![paths::key(root), Utc::now().timestamp(), snapshot],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub(crate) fn finish_scan(&self, id: i64, count: usize) -> Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE scans SET finished_at = ?, file_count = ? WHERE id = ?",
+            params![Utc::now().timestamp(), count as i64, id],
+        )?;
+        Ok(())
+    }
+
+    // Only the errors kept in the report are stored; `skipped` may be larger.
+    pub(crate) fn record_scan_errors(
+        &self,
+        id: i64,
+        errors: &[(PathBuf, anyhow::Error)],
+    ) -> Result<()> {
+        let conn = self.conn();
+        let now = Utc::now().timestamp();
+        let txn = conn.unchecked_transaction()?;
+        {
+            let mut stmt = txn.prepare(
+                "INSERT INTO errors(scan_id,path,kind,message,occurred_at) VALUES (?,?,?,?,?)",
+            )?;
+            for (path, err) in errors {
+                stmt.execute(params![
+                    id,
+                    paths::key(path),
+                    error_kind(err),
+                    format!("{:#}", err),
+                    now
+                ])?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    pub(crate) fn write_scan_entry(&self, id: i64, record: &FileRecord) -> Result<()> {
+        let conn = self.conn();
+        conn.prepare_cached(
+            "INSERT OR REPLACE INTO scan_files(scan_id,path,size,modified,hash)
+                 VALUES (?,?,?,?,?)",
+        )?
+        .execute(params![
+            id,
+            record.path,
+            record.size,
+            record.modified.timestamp(),
+            record.hash
+        ])?;
+        Ok(())
+    }
+}
+
+fn scan_from_row(row: &Row<'_>) -> rusqlite::Result<ScanInfo> {
+    let started_at: i64 = row.get(2)?;
+    let finished_at: Option<i64> = row.get(3)?;
+    Ok(ScanInfo {
+        id: row.get(0)?,
+        root: row.get(1)?,
+        started_at: DateTime::<Utc>::from_timestamp(started_at, 0).unwrap_or_default(),
+        finished_at: finished_at.and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0)),
+        file_count: row.get(4)?,
+        snapshot: row.get(5)?,
+    })
+}
+
+fn error_kind(err: &anyhow::Error) -> String {
+    for cause in err.chain() {
+        let io = match cause.downcast_ref::<ignore::Error>() {
+            Some(err) => err.io_error(),
+            None => cause.downcast_ref::<std::io::Error>(),
+        };
+        if let Some(io) = io {
+            return format!("{:?}", io.kind());
+        }
+    }
+    "Other".to_string()
+}
+
+fn entry_from_row(row: &Row<'_>, offset: usize) -> rusqlite::Result<ScanEntry> {
+    let modified: i64 = row.get(offset + 2)?;
+    Ok(ScanEntry {
+        path: row.get(offset)?,
+        size: row.get(offset + 1)?,
+        modified: DateTime::<Utc>::from_timestamp(modified, 0).unwrap_or_default(),
+        hash: row.get(offset + 3)?,
+    })
+}
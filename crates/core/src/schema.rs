@@ -0,0 +1,235 @@
+use anyhow::Result;
+use rusqlite::Connection;
+
+use crate::indexer::RECORD_COLUMNS;
+
+pub(crate) fn init(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "PRAGMA journal_mode=WAL;\n
+         CREATE TABLE IF NOT EXISTS files (
+             path TEXT PRIMARY KEY,
+             name TEXT NOT NULL,
+             ext TEXT,
+             size INTEGER NOT NULL,
+             modified INTEGER NOT NULL,
+             added_at INTEGER NOT NULL DEFAULT (strftime('%s','now')),
+             hash TEXT
+         );
+         CREATE INDEX IF NOT EXISTS idx_files_name ON files(name);
+         CREATE INDEX IF NOT EXISTS idx_files_modified ON files(modified);
+         CREATE INDEX IF NOT EXISTS idx_files_hash ON files(hash);
+         CREATE TABLE IF NOT EXISTS roots (
+             path TEXT PRIMARY KEY,
+             skip_hidden INTEGER NOT NULL DEFAULT 0
+         )
+        ",
+    )?;
+    ensure_column(conn, "files", "kind", "TEXT NOT NULL DEFAULT 'file'")?;
+    ensure_column(conn, "files", "link_target", "TEXT")?;
+    ensure_column(conn, "files", "mime", "TEXT")?;
+    ensure_column(conn, "files", "detected_mime", "TEXT")?;
+    ensure_column(conn, "files", "type_mismatch", "INTEGER NOT NULL DEFAULT 0")?;
+    for (column, decl) in [
+        ("duration", "REAL"),
+        ("bitrate", "INTEGER"),
+        ("width", "INTEGER"),
+        ("height", "INTEGER"),
+        ("codec", "TEXT"),
+        ("artist", "TEXT"),
+        ("album", "TEXT"),
+        ("title", "TEXT"),
+    ] {
+        ensure_column(conn, "files", column, decl)?;
+    }
+    ensure_column(conn, "roots", "options", "TEXT")?;
+    ensure_column(conn, "roots", "last_scan", "INTEGER")?;
+    ensure_column(conn, "roots", "file_count", "INTEGER NOT NULL DEFAULT 0")?;
+    // Change journal id and position reached by the last NTFS scan.
+    ensure_column(conn, "roots", "usn_journal", "INTEGER")?;
+    ensure_column(conn, "roots", "usn_next", "INTEGER")?;
+    ensure_column(conn, "files", "quick_hash", "TEXT")?;
+    ensure_column(conn, "files", "hash_algo", "TEXT")?;
+    ensure_column(conn, "files", "uid", "INTEGER")?;
+    ensure_column(conn, "files", "gid", "INTEGER")?;
+    ensure_column(conn, "files", "mode", "INTEGER")?;
+    ensure_column(conn, "files", "attributes", "INTEGER")?;
+    ensure_column(conn, "files", "image_hash", "INTEGER")?;
+    ensure_column(conn, "files", "similarity", "TEXT")?;
+    ensure_column(conn, "files", "raw_path", "BLOB")?;
+    ensure_column(conn, "files", "rating", "INTEGER NOT NULL DEFAULT 0")?;
+    ensure_column(conn, "files", "hash_skipped", "TEXT")?;
+    // Entries of a `ScanSource`; see `FileRecord::scheme`.
+    ensure_column(conn, "files", "scheme", "TEXT")?;
+    ensure_column(conn, "files", "volume", "TEXT")?;
+    ensure_column(conn, "files", "etag", "TEXT")?;
+    ensure_column(conn, "files", "allocated", "INTEGER")?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_files_mime ON files(mime);
+         CREATE INDEX IF NOT EXISTS idx_files_rating ON files(rating);
+         CREATE INDEX IF NOT EXISTS idx_files_quick_hash ON files(quick_hash);
+         -- Covers extension filters combined with size and date ranges, and
+         -- replaces the index on ext alone, which is its prefix.
+         CREATE INDEX IF NOT EXISTS idx_files_ext_size_modified ON files(ext, size, modified);
+         DROP INDEX IF EXISTS idx_files_ext;
+         CREATE INDEX IF NOT EXISTS idx_files_size ON files(size);
+         CREATE INDEX IF NOT EXISTS idx_files_name_nocase ON files(name COLLATE NOCASE);
+         CREATE INDEX IF NOT EXISTS idx_files_added_at ON files(added_at);
+         CREATE TABLE IF NOT EXISTS content_docs (
+             id INTEGER PRIMARY KEY,
+             path TEXT NOT NULL UNIQUE
+         );
+         CREATE VIRTUAL TABLE IF NOT EXISTS content_fts USING fts5(body);
+         CREATE TRIGGER IF NOT EXISTS files_drop_content AFTER DELETE ON files BEGIN
+             DELETE FROM content_fts
+              WHERE rowid = (SELECT id FROM content_docs WHERE path = old.path);
+             DELETE FROM content_docs WHERE path = old.path;
+         END;
+         CREATE TABLE IF NOT EXISTS chunks (
+             path TEXT NOT NULL,
+             offset INTEGER NOT NULL,
+             length INTEGER NOT NULL,
+             hash TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_chunks_hash ON chunks(hash);
+         CREATE INDEX IF NOT EXISTS idx_chunks_path ON chunks(path);
+         CREATE TRIGGER IF NOT EXISTS files_drop_chunks AFTER DELETE ON files BEGIN
+             DELETE FROM chunks WHERE path = old.path;
+         END;
+         CREATE TABLE IF NOT EXISTS deleted_files (
+             path TEXT NOT NULL,
+             name TEXT NOT NULL,
+             ext TEXT,
+             size INTEGER NOT NULL,
+             modified INTEGER NOT NULL,
+             added_at INTEGER NOT NULL,
+             hash TEXT,
+             deleted_at INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_deleted_files_at ON deleted_files(deleted_at);
+         CREATE TABLE IF NOT EXISTS scans (
+             id INTEGER PRIMARY KEY,
+             root TEXT NOT NULL,
+             started_at INTEGER NOT NULL,
+             finished_at INTEGER,
+             file_count INTEGER NOT NULL DEFAULT 0,
+             snapshot INTEGER NOT NULL DEFAULT 0
+         );
+         CREATE TABLE IF NOT EXISTS scan_files (
+             scan_id INTEGER NOT NULL,
+             path TEXT NOT NULL,
+             size INTEGER NOT NULL,
+             modified INTEGER NOT NULL,
+             hash TEXT,
+             PRIMARY KEY (scan_id, path)
+         ) WITHOUT ROWID;
+         CREATE TABLE IF NOT EXISTS errors (
+             scan_id INTEGER NOT NULL,
+             path TEXT NOT NULL,
+             kind TEXT NOT NULL,
+             message TEXT NOT NULL,
+             occurred_at INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_errors_scan ON errors(scan_id);
+         CREATE TABLE IF NOT EXISTS renames (
+             old_path TEXT NOT NULL,
+             new_path TEXT NOT NULL,
+             detected_at INTEGER NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS dirs (
+             path TEXT PRIMARY KEY,
+             size INTEGER NOT NULL,
+             file_count INTEGER NOT NULL,
+             modified INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_dirs_size ON dirs(size);
+         CREATE INDEX IF NOT EXISTS idx_files_volume ON files(volume);
+         CREATE TABLE IF NOT EXISTS volumes (
+             id TEXT PRIMARY KEY,
+             label TEXT,
+             mount TEXT,
+             last_seen INTEGER NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS source_folders (
+             path TEXT PRIMARY KEY,
+             scheme TEXT NOT NULL,
+             etag TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS xattrs (
+             path TEXT NOT NULL,
+             key TEXT NOT NULL,
+             value BLOB,
+             PRIMARY KEY (path, key)
+         );
+         CREATE INDEX IF NOT EXISTS idx_xattrs_key ON xattrs(key);
+         CREATE TRIGGER IF NOT EXISTS files_drop_xattrs AFTER DELETE ON files BEGIN
+             DELETE FROM xattrs WHERE path = old.path;
+         END;
+         CREATE TABLE IF NOT EXISTS notes (
+             id INTEGER PRIMARY KEY,
+             path TEXT NOT NULL UNIQUE,
+             body TEXT NOT NULL,
+             updated_at INTEGER NOT NULL
+         );
+         CREATE VIRTUAL TABLE IF NOT EXISTS notes_fts USING fts5(body);
+         CREATE TABLE IF NOT EXISTS collections (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL UNIQUE,
+             created_at INTEGER NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS collection_items (
+             collection_id INTEGER NOT NULL,
+             path TEXT NOT NULL,
+             added_at INTEGER NOT NULL,
+             PRIMARY KEY (collection_id, path)
+         ) WITHOUT ROWID;
+         CREATE INDEX IF NOT EXISTS idx_collection_items_path ON collection_items(path);
+         CREATE TRIGGER IF NOT EXISTS files_drop_collection_items AFTER DELETE ON files BEGIN
+             DELETE FROM collection_items WHERE path = old.path;
+         END;
+         CREATE TABLE IF NOT EXISTS tags (
+             path TEXT NOT NULL,
+             tag TEXT NOT NULL COLLATE NOCASE,
+             added_at INTEGER NOT NULL,
+             PRIMARY KEY (path, tag)
+         ) WITHOUT ROWID;
+         CREATE INDEX IF NOT EXISTS idx_tags_tag ON tags(tag);
+         CREATE TRIGGER IF NOT EXISTS files_drop_tags AFTER DELETE ON files BEGIN
+             DELETE FROM tags WHERE path = old.path;
+         END;
+         CREATE TRIGGER IF NOT EXISTS files_drop_notes AFTER DELETE ON files BEGIN
+             DELETE FROM notes_fts
+              WHERE rowid = (SELECT id FROM notes WHERE path = old.path);
+             DELETE FROM notes WHERE path = old.path;
+         END;",
+    )?;
+    ensure_column(conn, "dirs", "allocated", "INTEGER NOT NULL DEFAULT 0")?;
+    Ok(())
+}
+
+// Fails unless the newest tables and columns from `init` exist; keep it in
+// step with new migrations.
+pub(crate) fn check(conn: &Connection) -> Result<()> {
+    conn.prepare(&format!("SELECT {} FROM files LIMIT 0", RECORD_COLUMNS))?;
+    conn.prepare("SELECT collection_id, path, added_at FROM collection_items LIMIT 0")?;
+    conn.prepare("SELECT path, scheme, etag FROM source_folders LIMIT 0")?;
+    conn.prepare("SELECT path, tag, added_at FROM tags LIMIT 0")?;
+    conn.prepare("SELECT allocated FROM dirs LIMIT 0")?;
+    conn.prepare("SELECT id, label, mount, last_seen FROM volumes LIMIT 0")?;
+    Ok(())
+}
+
+// Adds a column to databases created before it existed.
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, decl
+        ))?;
+    }
+    Ok(())
+}
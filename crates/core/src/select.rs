@@ -0,0 +1,327 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use rusqlite::types::Value;
+
+use crate::filetype::MimeFilter;
+use crate::fold;
+use crate::indexer::{normalized_exts, subtree_bounds, SearchQuery, SortKey, RECORD_COLUMNS};
+use crate::pattern;
+use crate::rating::MAX_RATING;
+
+// SQL for a `SearchQuery`, shared by everything that lists or counts
+// matches. Every value is bound as a parameter; only column names and
+// operators written in this file end up in the statement text.
+pub(crate) struct Select {
+    conds: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl Select {
+    // Every filter of `q` except fuzzy name matching, which happens on the
+    // rows read.
+    pub(crate) fn filtered(q: &SearchQuery) -> Result<Self> {
+        validate(q)?;
+        let mut s = Select {
+            conds: Vec::new(),
+            params: Vec::new(),
+        };
+        let text = |s: &str| Value::Text(s.to_string());
+
+        if let Some(name) = q.name_like.as_ref().filter(|s| !s.is_empty() && !q.fuzzy) {
+            if q.case_sensitive == Some(true) {
+                let glob = fold::like_to_glob(&format!("%{}%", name));
+                s.push("name GLOB ?", [Value::Text(glob)]);
+            } else {
+                let like = format!("%{}%", fold::fold(name));
+                s.push("fold(name) LIKE ?", [Value::Text(like)]);
+            }
+        }
+        for (cond, regex) in [
+            ("name REGEXP ?", &q.name_regex),
+            ("path REGEXP ?", &q.path_regex),
+        ] {
+            if let Some(regex) = regex.as_ref().filter(|s| !s.is_empty()) {
+                pattern::compile(regex)?;
+                s.push(cond, [text(regex)]);
+            }
+        }
+        if let Some(dir) = q.path_prefix.as_ref().filter(|s| !s.is_empty()) {
+            let (lower, upper) = subtree_bounds(dir);
+            s.push(
+                "(path = ? OR (path >= ? AND path < ?))",
+                [
+                    text(dir.trim_end_matches(['/', '\\'])),
+                    Value::Text(lower),
+                    Value::Text(upper),
+                ],
+            );
+        }
+        if let Some(glob) = q.glob.as_ref().filter(|s| !s.is_empty()) {
+            let case_insensitive = q.case_sensitive != Some(true);
+            let plan = pattern::plan_glob(glob, case_insensitive)?;
+            if let Some(exact) = plan.exact {
+                s.push("path = ?", [Value::Text(exact)]);
+            } else if let Some(dir) = plan.dir {
+                let (lower, upper) = subtree_bounds(&dir);
+                s.push(
+                    "path >= ? AND path < ?",
+                    [Value::Text(lower), Value::Text(upper)],
+                );
+            }
+            if let Some(ext) = plan.ext {
+                let dotted = format!(".{}", ext);
+                s.push(
+                    "(ext = ? OR (ext IS NULL AND name LIKE ?))",
+                    [Value::Text(ext), Value::Text(dotted)],
+                );
+            }
+            s.push(
+                "glob_match(?, ?, path)",
+                [
+                    Value::Text(plan.pattern),
+                    Value::Integer(case_insensitive.into()),
+                ],
+            );
+        }
+        if let Some(ext) = q.ext.as_ref().filter(|s| !s.is_empty()) {
+            s.push("ext = ?", [Value::Text(ext.to_ascii_lowercase())]);
+        }
+        let exts = normalized_exts(&q.ext_in);
+        if !exts.is_empty() {
+            let marks = vec!["?"; exts.len()].join(",");
+            s.push(
+                format!("ext IN ({})", marks),
+                exts.into_iter().map(Value::Text),
+            );
+        }
+        if let Some(hash) = q.hash.as_ref().filter(|s| !s.is_empty()) {
+            s.push("hash = ?", [Value::Text(hash.trim().to_ascii_lowercase())]);
+        }
+        if let Some(name) = q.collection.as_ref().filter(|s| !s.is_empty()) {
+            s.push(
+                "path IN (SELECT i.path FROM collection_items i
+                          JOIN collections c ON c.id = i.collection_id
+                          WHERE c.name = ?)",
+                [text(name.trim())],
+            );
+        }
+        for tag in &q.tags {
+            s.push("path IN (SELECT path FROM tags WHERE tag = ?)", [text(tag)]);
+        }
+        if let Some(min) = q.min_rating.filter(|min| *min > 0) {
+            s.push("rating >= ?", [Value::Integer(min.into())]);
+        }
+        match q.has_hash {
+            Some(true) => s.push("hash IS NOT NULL", []),
+            Some(false) => s.push("hash IS NULL", []),
+            None => {}
+        }
+        if let Some(kind) = q.kind {
+            s.push("kind = ?", [text(kind.as_str())]);
+        }
+        match q.mime.as_deref().and_then(MimeFilter::parse) {
+            Some(MimeFilter::Exact(mime)) => s.push("mime = ?", [Value::Text(mime)]),
+            Some(MimeFilter::TopLevel(top)) => {
+                s.push("mime LIKE ?", [Value::Text(format!("{}/%", top))])
+            }
+            None => {}
+        }
+        if q.type_mismatch {
+            s.push("type_mismatch = 1", []);
+        }
+        s.range("duration", q.min_duration, q.max_duration);
+        s.range("height", q.min_height, None);
+        for (cond, value) in [
+            ("artist LIKE ?", &q.artist),
+            ("album LIKE ?", &q.album),
+            ("title LIKE ?", &q.title),
+        ] {
+            if let Some(value) = value.as_ref().filter(|s| !s.is_empty()) {
+                s.push(cond, [Value::Text(format!("%{}%", value))]);
+            }
+        }
+        if let Some(uid) = q.owner {
+            s.push("uid = ?", [Value::Integer(uid.into())]);
+        }
+        if let Some(gid) = q.group {
+            s.push("gid = ?", [Value::Integer(gid.into())]);
+        }
+        if q.world_writable {
+            // Link permissions are always 0777 and never checked.
+            s.push(
+                "(mode & 2) != 0 AND kind NOT IN ('symlink', 'broken_symlink')",
+                [],
+            );
+        }
+        if let Some(bits) = q.attributes.filter(|bits| *bits != 0) {
+            let bits = Value::Integer(bits.into());
+            s.push("(attributes & ?) = ?", [bits.clone(), bits]);
+        }
+        if let Some(key) = q.has_xattr.as_ref().filter(|s| !s.is_empty()) {
+            s.push(
+                "path IN (SELECT path FROM xattrs WHERE key GLOB ?)",
+                [text(key)],
+            );
+        }
+        if let Some(content) = q.content.as_ref().filter(|s| !s.is_empty()) {
+            s.push(
+                "path IN (SELECT d.path FROM content_docs d
+                          JOIN content_fts ON content_fts.rowid = d.id
+                          WHERE content_fts MATCH ?)",
+                [text(content)],
+            );
+        }
+        if let Some(note) = q.note.as_ref().filter(|s| !s.is_empty()) {
+            s.push(
+                "path IN (SELECT n.path FROM notes n
+                          JOIN notes_fts ON notes_fts.rowid = n.id
+                          WHERE notes_fts MATCH ?)",
+                [text(note)],
+            );
+        }
+        if let Some(volume) = q.volume.as_ref().filter(|s| !s.is_empty()) {
+            s.push(
+                "(volume = ? OR volume IN (SELECT id FROM volumes WHERE label = ?))",
+                [text(volume), text(volume)],
+            );
+        }
+        s.range("size", q.min_size, q.max_size);
+        s.range(
+            SortKey::Allocated.column(),
+            q.min_allocated,
+            q.max_allocated,
+        );
+        s.date_range("modified", q.date_from, q.date_to)?;
+        s.date_range("added_at", q.added_from, q.added_to)?;
+        Ok(s)
+    }
+
+    // Matching rows in the order of `q`, after its cursor, with its limit
+    // and offset unless the caller has to apply them (see `post_page`).
+    pub(crate) fn rows(mut self, q: &SearchQuery) -> Result<(String, Vec<Value>)> {
+        let order = q.sort_order()?;
+        if let Some(cursor) = &q.after {
+            let (cond, values) = cursor.condition(&order)?;
+            self.push(cond, values);
+        }
+        let mut sql = format!(
+            "SELECT {} FROM files{}",
+            RECORD_COLUMNS,
+            self.where_clause()
+        );
+
+        // Path breaks ties so pages never overlap or skip rows.
+        let ranked = order[0].0 == SortKey::Relevance;
+        let direction = |desc: bool| if desc && !ranked { " DESC" } else { "" };
+        let columns: Vec<String> = order
+            .iter()
+            .map(|(key, desc)| format!("{}{}", key.column(), direction(*desc)))
+            .chain([format!("path{}", direction(order[0].1))])
+            .collect();
+        sql.push_str(" ORDER BY ");
+        sql.push_str(&columns.join(", "));
+
+        if !post_page(q) && (q.limit.is_some() || q.offset.is_some()) {
+            // A negative limit means none; SQLite needs one for OFFSET.
+            sql.push_str(" LIMIT ? OFFSET ?");
+            self.params.push(Value::Integer(q.limit.unwrap_or(-1)));
+            self.params.push(Value::Integer(q.offset.unwrap_or(0)));
+        }
+        Ok((sql, self.params))
+    }
+
+    pub(crate) fn count(self) -> (String, Vec<Value>) {
+        let sql = format!("SELECT count(*) FROM files{}", self.where_clause());
+        (sql, self.params)
+    }
+
+    fn push(&mut self, cond: impl Into<String>, params: impl IntoIterator<Item = Value>) {
+        self.conds.push(cond.into());
+        self.params.extend(params);
+    }
+
+    fn range(&mut self, column: &str, min: Option<i64>, max: Option<i64>) {
+        if let Some(min) = min {
+            self.push(format!("{} >= ?", column), [Value::Integer(min)]);
+        }
+        if let Some(max) = max {
+            self.push(format!("{} <= ?", column), [Value::Integer(max)]);
+        }
+    }
+
+    // Whole days: from midnight of `from` to the last second of `to`.
+    fn date_range(
+        &mut self,
+        column: &str,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<()> {
+        let ts = |date: NaiveDate, h, m, s| {
+            date.and_hms_opt(h, m, s)
+                .map(|t| t.and_utc().timestamp())
+                .ok_or_else(|| anyhow!("invalid date {}", date))
+        };
+        let min = from.map(|d| ts(d, 0, 0, 0)).transpose()?;
+        let max = to.map(|d| ts(d, 23, 59, 59)).transpose()?;
+        self.range(column, min, max);
+        Ok(())
+    }
+
+    fn where_clause(&self) -> String {
+        if self.conds.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.conds.join(" AND "))
+        }
+    }
+}
+
+// Whether `Select::rows` leaves LIMIT and OFFSET to the caller.
+pub(crate) fn post_page(q: &SearchQuery) -> bool {
+    (q.fuzzy && q.name_like.as_ref().is_some_and(|s| !s.is_empty()))
+        || q.sort_key == Some(SortKey::Relevance)
+}
+
+// Rejects queries that can only be a mistake rather than silently
+// returning nothing.
+fn validate(q: &SearchQuery) -> Result<()> {
+    if q.limit.is_some_and(|l| l < 0) {
+        return Err(anyhow!("the limit must not be negative"));
+    }
+    if q.offset.is_some_and(|o| o < 0) {
+        return Err(anyhow!("the offset must not be negative"));
+    }
+    if q.min_rating.is_some_and(|r| r > MAX_RATING) {
+        return Err(anyhow!("ratings go up to {}", MAX_RATING));
+    }
+    let inverted = [
+        (
+            "size",
+            q.min_size.zip(q.max_size).is_some_and(|(a, b)| a > b),
+        ),
+        (
+            "allocated size",
+            q.min_allocated
+                .zip(q.max_allocated)
+                .is_some_and(|(a, b)| a > b),
+        ),
+        (
+            "duration",
+            q.min_duration
+                .zip(q.max_duration)
+                .is_some_and(|(a, b)| a > b),
+        ),
+        (
+            "modified date",
+            q.date_from.zip(q.date_to).is_some_and(|(a, b)| a > b),
+        ),
+        (
+            "added date",
+            q.added_from.zip(q.added_to).is_some_and(|(a, b)| a > b),
+        ),
+    ];
+    if let Some((what, _)) = inverted.iter().find(|(_, bad)| *bad) {
+        return Err(anyhow!("the minimum {} is above the maximum", what));
+    }
+    Ok(())
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::DateTime;
+
+use crate::hash::HashAlgorithm;
+use crate::import::parse_find_line;
+use crate::source::{CommandReader, FolderTags, ScanSource, SourceEntry};
+
+// A directory tree on a server reachable over SSH. Nothing has to be
+// installed there: the listing is a GNU `find` run through the system's
+// `ssh` client, which uses its keys, agent and `~/.ssh/config`. Password
+// prompts are disabled, so the login has to work without one.
+#[derive(Debug, Clone, Default)]
+pub struct SftpSource {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    // Absolute path of the directory on the server.
+    pub root: String,
+    pub identity: Option<PathBuf>,
+    // Hash files on the server with `b3sum` or `sha256sum`. Without the
+    // tool there, `IndexOptions::hash` streams them over SSH instead.
+    pub hash: Option<HashAlgorithm>,
+}
+
+impl SftpSource {
+    // From `sftp://[user@]host[:port]/absolute/path`.
+    pub fn parse(url: &str) -> Result<Self> {
+        let rest = url
+            .strip_prefix("sftp://")
+            .ok_or_else(|| anyhow!("not an sftp:// URL: {}", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host),
+            None => (None, authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse()
+                    .with_context(|| format!("bad port in {}", url))?;
+                (host, Some(port))
+            }
+            None => (host_port, None),
+        };
+        if host.is_empty() {
+            return Err(anyhow!("no host in {}", url));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            user,
+            port,
+            root: path.to_string(),
+            ..Self::default()
+        })
+    }
+
+    fn ssh(&self, script: &str) -> Command {
+        let mut cmd = Command::new("ssh");
+        cmd.args(["-o", "BatchMode=yes"]);
+        if let Some(port) = self.port {
+            cmd.args(["-p", &port.to_string()]);
+        }
+        if let Some(identity) = &self.identity {
+            cmd.arg("-i").arg(identity);
+        }
+        let destination = match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        };
+        cmd.arg(destination).arg("--").arg(script);
+        cmd
+    }
+
+    fn root_dir(&self) -> &str {
+        match self.root.trim_end_matches('/') {
+            "" => "/",
+            root => root,
+        }
+    }
+
+    // Hashes by remote path; empty when the tool is missing on the server.
+    fn remote_hashes(&self, algo: HashAlgorithm) -> Result<HashMap<String, String>> {
+        let tool = match algo {
+            HashAlgorithm::Blake3 => "b3sum",
+            HashAlgorithm::Sha256 => "sha256sum",
+            HashAlgorithm::Xxh3 => return Ok(HashMap::new()),
+        };
+        let script = format!(
+            "command -v {tool} >/dev/null 2>&1 || exit 0; \
+             find {} -type f -print0 | xargs -0 -r {tool}",
+            quote(self.root_dir()),
+        );
+        let output = self
+            .ssh(&script)
+            .stdin(Stdio::null())
+            .output()
+            .context("running ssh")?;
+        let mut hashes = HashMap::new();
+        // Unreadable files only leave their line out.
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            // Names with a newline or backslash come escaped; skip them.
+            if line.starts_with('\\') {
+                continue;
+            }
+            if let Some((hash, path)) = line.split_once("  ") {
+                hashes.insert(path.to_string(), hash.to_ascii_lowercase());
+            }
+        }
+        Ok(hashes)
+    }
+}
+
+impl ScanSource for SftpSource {
+    fn scheme(&self) -> &str {
+        "sftp"
+    }
+
+    fn volume(&self) -> &str {
+        &self.host
+    }
+
+    fn root(&self) -> String {
+        format!(
+            "sftp://{}{}/",
+            self.host,
+            self.root_dir().trim_end_matches('/')
+        )
+    }
+
+    fn entries<'a>(
+        &'a self,
+        _known: &'a FolderTags,
+    ) -> Result<Box<dyn Iterator<Item = Result<SourceEntry>> + 'a>> {
+        if !self.root.starts_with('/') {
+            return Err(anyhow!("the remote path must be absolute: {}", self.root));
+        }
+        let hashes = match self.hash {
+            Some(algo) => self.remote_hashes(algo)?,
+            None => HashMap::new(),
+        };
+        let script = format!(
+            "find {} -type f -printf '%s\\t%T@\\t%p\\0'",
+            quote(self.root_dir())
+        );
+        let mut child = self
+            .ssh(&script)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("running ssh")?;
+        let stdout = BufReader::new(child.stdout.take().context("no ssh output")?);
+        // Drained on its own thread so many warnings cannot block the listing.
+        let mut stderr = child.stderr.take().context("no ssh error output")?;
+        let errors = thread::spawn(move || {
+            let mut text = String::new();
+            let _ = stderr.read_to_string(&mut text);
+            text
+        });
+        Ok(Box::new(Listing {
+            source: self,
+            child,
+            stdout,
+            errors: Some(errors),
+            hashes,
+        }))
+    }
+
+    fn open(&self, entry: &SourceEntry) -> Result<Option<Box<dyn Read + '_>>> {
+        let Some(path) = entry.path.strip_prefix(&format!("sftp://{}", self.host)) else {
+            return Ok(None);
+        };
+        let cmd = self.ssh(&format!("cat -- {}", quote(path)));
+        Ok(Some(Box::new(CommandReader::spawn(cmd, None)?)))
+    }
+}
+
+struct Listing<'a> {
+    source: &'a SftpSource,
+    child: Child,
+    stdout: BufReader<ChildStdout>,
+    errors: Option<JoinHandle<String>>,
+    hashes: HashMap<String, String>,
+}
+
+impl Listing<'_> {
+    fn entry(&mut self, line: &[u8]) -> Result<SourceEntry> {
+        let line = String::from_utf8_lossy(line);
+        let (size, modified, path) = parse_find_line(&line)?;
+        let hash = self.hashes.remove(path);
+        Ok(SourceEntry {
+            path: format!("sftp://{}{}", self.source.host, path),
+            size,
+            modified: DateTime::from_timestamp(modified, 0).unwrap_or_default(),
+            etag: None,
+            hash_algo: hash.as_ref().and(self.source.hash),
+            hash,
+            folder: false,
+        })
+    }
+
+    // An error when `find` or the connection failed; the listing may then
+    // be incomplete.
+    fn finish(&mut self) -> Option<Result<SourceEntry>> {
+        let errors = self.errors.take()?.join().unwrap_or_default();
+        match self.child.wait() {
+            Ok(status) if status.success() => None,
+            Ok(_) => Some(Err(anyhow!(
+                "listing {}: {}",
+                self.source.root(),
+                errors.trim()
+            ))),
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+impl Iterator for Listing<'_> {
+    type Item = Result<SourceEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        match self.stdout.read_until(0, &mut line) {
+            Ok(0) => self.finish(),
+            Ok(_) => {
+                if line.last() == Some(&0) {
+                    line.pop();
+                }
+                Some(self.entry(&line))
+            }
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+impl Drop for Listing<'_> {
+    // Stops a listing that was not read to the end.
+    fn drop(&mut self) {
+        if self.errors.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+// Single-quoted for the remote shell.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::indexer::{record_from_row, FileIndexer, FileRecord, RECORD_COLUMNS};
+use crate::paths;
+
+// Number of piece hashes kept per digest. More makes scores steadier at the
+// cost of 16 hex characters each.
+const SKETCH_SIZE: usize = 64;
+// A piece ends where the top bits of the rolling hash are all zero, giving
+// pieces of about 64 bytes.
+const BOUNDARY_MASK: u64 = 0x3f << 58;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarFile {
+    #[serde(flatten)]
+    pub record: FileRecord,
+    // Estimated share of content in common, 0-100.
+    pub score: u32,
+}
+
+// Context-triggered piecewise digest in the spirit of ssdeep: the file is cut
+// where a rolling hash of the last bytes hits a pattern, so an edit only
+// changes the pieces it touches. The digest keeps the smallest piece hashes
+// (a bottom-k sketch), which compares files of any size against each other.
+pub(crate) fn digest(path: &Path) -> Result<Option<String>> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut sketch = BTreeSet::new();
+    let mut piece = Vec::new();
+    let mut rolling = 0u64;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buf)
+            .with_context(|| format!("reading {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        for &byte in &buf[..read] {
+            piece.push(byte);
+            rolling = (rolling << 1).wrapping_add(GEAR[byte as usize]);
+            if rolling & BOUNDARY_MASK == 0 {
+                keep_smallest(&mut sketch, xxh3_64(&piece));
+                piece.clear();
+            }
+        }
+    }
+    if !piece.is_empty() {
+        keep_smallest(&mut sketch, xxh3_64(&piece));
+    }
+    if sketch.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(sketch.iter().map(|h| format!("{:016x}", h)).collect()))
+}
+
+fn keep_smallest(sketch: &mut BTreeSet<u64>, hash: u64) {
+    sketch.insert(hash);
+    if sketch.len() > SKETCH_SIZE {
+        sketch.pop_last();
+    }
+}
+
+fn parse(digest: &str) -> Option<BTreeSet<u64>> {
+    if digest.is_empty() || !digest.len().is_multiple_of(16) {
+        return None;
+    }
+    (0..digest.len())
+        .step_by(16)
+        .map(|at| u64::from_str_radix(digest.get(at..at + 16)?, 16).ok())
+        .collect()
+}
+
+// Jaccard estimate over the smallest hashes of the union of both sketches.
+fn score(a: &BTreeSet<u64>, b: &BTreeSet<u64>) -> u32 {
+    let union: Vec<u64> = a.union(b).copied().take(SKETCH_SIZE).collect();
+    if union.is_empty() {
+        return 0;
+    }
+    let shared = union
+        .iter()
+        .filter(|h| a.contains(h) && b.contains(h))
+        .count();
+    (shared * 100 / union.len()) as u32
+}
+
+impl FileIndexer {
+    // Files whose similarity digest scores at least `threshold` (0-100)
+    // against `path`, best match first. `path` itself need not be indexed.
+    pub fn similar_to(&self, path: &Path, threshold: u32) -> Result<Vec<SimilarFile>> {
+        let conn = self.reader()?;
+        let key = paths::key(path);
+        let stored: Option<String> = conn
+            .query_row(
+                "SELECT similarity FROM files WHERE path = ?",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        let digest = match stored {
+            Some(digest) => digest,
+            None => digest(path)?.ok_or_else(|| anyhow!("{} is empty", path.display()))?,
+        };
+        let target = parse(&digest).ok_or_else(|| anyhow!("malformed similarity digest"))?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files WHERE similarity IS NOT NULL AND path != ?",
+            RECORD_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![key], record_from_row)?;
+        let mut out = Vec::new();
+        for record in rows {
+            let record = record?;
+            let Some(other) = record.similarity.as_deref().and_then(parse) else {
+                continue;
+            };
+            let score = score(&target, &other);
+            if score >= threshold {
+                out.push(SimilarFile { record, score });
+            }
+        }
+        out.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.record.path.cmp(&b.record.path))
+        });
+        Ok(out)
+    }
+}
+
+// Random values mixed into the rolling hash, one per byte value.
+static GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut idx = 0;
+    while idx < 256 {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[idx] = z ^ (z >> 31);
+        idx += 1;
+    }
+    table
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::hash::{self, HashAlgorithm};
+use crate::import::imported_record;
+use crate::indexer::{
+    hash_skip, FileIndexer, FileRecord, IndexOptions, IndexReport, DEFAULT_BATCH_SIZE,
+};
+
+// An entry listed by a `ScanSource`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceEntry {
+    // The path as stored and searched, starting with the scheme, e.g.
+    // `s3://bucket/photos/a.jpg`.
+    pub path: String,
+    pub size: i64,
+    pub modified: DateTime<Utc>,
+    pub etag: Option<String>,
+    // Computed by the source itself, e.g. on the server, which saves
+    // reading the file through `ScanSource::open`.
+    pub hash: Option<String>,
+    pub hash_algo: Option<HashAlgorithm>,
+    // A folder rather than a file. Folders are not stored as files; their
+    // ETags let the next listing skip the ones that did not change.
+    #[serde(default)]
+    pub folder: bool,
+}
+
+// ETags of the folders under the root that the last complete listing
+// returned, by path ending in `/`.
+pub type FolderTags = HashMap<String, String>;
+
+// Storage other than the local file system whose listing goes into the
+// same `files` table, so remote and local files are searched together.
+// Entries carry the scheme and volume of their source, which keeps them
+// out of pruning and other operations that expect files on disk. A
+// connector (FTP, a cloud drive API, ...) only lists and opens files; the
+// indexer takes care of hashing, batching and deletions.
+pub trait ScanSource {
+    // Short lowercase name, e.g. `s3`.
+    fn scheme(&self) -> &str;
+    // The bucket, host or share the entries belong to.
+    fn volume(&self) -> &str;
+    // Prefix of every entry path. Indexed entries below it that a complete
+    // listing no longer returns are dropped.
+    fn root(&self) -> String;
+    // Every entry; an error ends the listing. A source whose folder ETags
+    // change with their contents may return a folder that kept its tag in
+    // `known` without descending into it; its indexed entries are kept.
+    fn entries<'a>(
+        &'a self,
+        known: &'a FolderTags,
+    ) -> Result<Box<dyn Iterator<Item = Result<SourceEntry>> + 'a>>;
+    // The contents of a file entry, for `IndexOptions::hash` when the entry
+    // brings no hash; `None` if the source cannot read files. Only entries
+    // whose size, time or ETag changed since they were hashed are opened.
+    fn open(&self, _entry: &SourceEntry) -> Result<Option<Box<dyn Read + '_>>> {
+        Ok(None)
+    }
+}
+
+impl FileIndexer {
+    // Indexes a listing, honoring the batch size, file limit, hash options
+    // and cancellation of `opts`. Sources are read as streams, so
+    // `HashMode::Quick` hashes whole files too. Only a listing that ran to
+    // the end removes entries that disappeared from the source and saves
+    // folder ETags for the next one.
+    pub fn index_source<S: ScanSource + ?Sized>(
+        &self,
+        source: &S,
+        opts: &IndexOptions,
+    ) -> Result<IndexReport> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let batch_size = match opts.batch_size {
+            0 => DEFAULT_BATCH_SIZE,
+            n => n,
+        };
+        conn.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS listed(path TEXT PRIMARY KEY);
+             CREATE TEMP TABLE IF NOT EXISTS kept(prefix TEXT PRIMARY KEY);
+             CREATE TEMP TABLE IF NOT EXISTS folders(path TEXT PRIMARY KEY, etag TEXT NOT NULL);
+             DELETE FROM temp.listed;
+             DELETE FROM temp.kept;
+             DELETE FROM temp.folders;",
+        )?;
+        let root = source.root();
+        let upper = format!("{}\u{10FFFF}", root);
+        let known: FolderTags = conn
+            .prepare(
+                "SELECT path, etag FROM source_folders
+                 WHERE path >= ?1 AND path < ?2 AND scheme = ?3",
+            )?
+            .query_map(params![root, upper, source.scheme()], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut report = IndexReport::default();
+        let mut complete = true;
+        let mut txn = conn.unchecked_transaction()?;
+        for entry in source.entries(&known)? {
+            if opts.cancel.is_cancelled() || opts.max_files.is_some_and(|max| report.indexed >= max)
+            {
+                complete = false;
+                break;
+            }
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    report.skipped += 1;
+                    report.errors.push((root.clone().into(), err));
+                    complete = false;
+                    break;
+                }
+            };
+            if entry.folder {
+                let Some(etag) = entry.etag else { continue };
+                let prefix = format!("{}/", entry.path.trim_end_matches('/'));
+                if known.get(&prefix) == Some(&etag) {
+                    txn.prepare_cached("INSERT OR IGNORE INTO temp.kept(prefix) VALUES(?)")?
+                        .execute(params![prefix])?;
+                }
+                txn.prepare_cached("INSERT OR REPLACE INTO temp.folders(path, etag) VALUES(?, ?)")?
+                    .execute(params![prefix, etag])?;
+                continue;
+            }
+            let mut record = source_record(source, &entry);
+            txn.prepare_cached("INSERT OR IGNORE INTO temp.listed(path) VALUES(?)")?
+                .execute(params![record.path])?;
+            if opts.hash && record.hash.is_none() {
+                // Skipped like an unreadable local file; its row stays as it was.
+                if let Err(err) = self.hash_entry(source, &entry, &mut record, opts) {
+                    report.skipped += 1;
+                    report.errors.push((entry.path.into(), err));
+                    continue;
+                }
+            }
+            self.write_record(&record)?;
+            report.indexed += 1;
+            if report.indexed % batch_size == 0 {
+                txn.commit()?;
+                txn = conn.unchecked_transaction()?;
+            }
+        }
+        if complete {
+            let gone = "FROM files WHERE path >= ?1 AND path < ?2 AND scheme = ?3
+                        AND path NOT IN (SELECT path FROM temp.listed)
+                        AND NOT EXISTS (SELECT 1 FROM temp.kept
+                                        WHERE files.path >= prefix
+                                          AND files.path < prefix || char(1114111))";
+            txn.execute(
+                &format!(
+                    "INSERT INTO deleted_files(path,name,ext,size,modified,added_at,hash,deleted_at)
+                     SELECT path,name,ext,size,modified,added_at,hash,?4 {}",
+                    gone
+                ),
+                params![root, upper, source.scheme(), Utc::now().timestamp()],
+            )?;
+            txn.execute(
+                &format!("DELETE {}", gone),
+                params![root, upper, source.scheme()],
+            )?;
+            // Folders inside kept ones were not listed but keep their tags.
+            txn.execute(
+                "DELETE FROM source_folders
+                 WHERE path >= ?1 AND path < ?2 AND scheme = ?3
+                   AND NOT EXISTS (SELECT 1 FROM temp.kept
+                                   WHERE source_folders.path > prefix
+                                     AND source_folders.path < prefix || char(1114111))",
+                params![root, upper, source.scheme()],
+            )?;
+            txn.execute(
+                "INSERT OR REPLACE INTO source_folders(path, scheme, etag)
+                 SELECT path, ?1, etag FROM temp.folders",
+                params![source.scheme()],
+            )?;
+        }
+        txn.commit()?;
+        conn.execute_batch(
+            "DELETE FROM temp.listed; DELETE FROM temp.kept; DELETE FROM temp.folders",
+        )?;
+        self.invalidate_cache();
+        Ok(report)
+    }
+
+    fn hash_entry<S: ScanSource + ?Sized>(
+        &self,
+        source: &S,
+        entry: &SourceEntry,
+        record: &mut FileRecord,
+        opts: &IndexOptions,
+    ) -> Result<()> {
+        record.hash_skipped = hash_skip(opts, record);
+        if record.hash_skipped.is_some() {
+            return Ok(());
+        }
+        // Reading a remote file again costs a download; keep the hash of
+        // an unchanged one.
+        let previous = if self.may_contain_path(&record.path)? {
+            self.conn()
+                .prepare_cached(
+                    "SELECT hash, hash_algo, size, modified, etag FROM files WHERE path = ?",
+                )?
+                .query_row(params![record.path], |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<HashAlgorithm>>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                })
+                .optional()?
+        } else {
+            None
+        };
+        if let Some((Some(hash), Some(algo), size, modified, etag)) = previous {
+            if algo == opts.hash_algo
+                && size == record.size
+                && modified == record.modified.timestamp()
+                && etag == record.etag
+            {
+                record.hash = Some(hash);
+                record.hash_algo = Some(algo);
+                return Ok(());
+            }
+        }
+        let Some(mut reader) = source.open(entry)? else {
+            return Ok(());
+        };
+        record.hash = Some(
+            hash::hash_reader(&mut reader, opts.hash_algo)
+                .with_context(|| format!("hashing {}", entry.path))?,
+        );
+        record.hash_algo = Some(opts.hash_algo);
+        Ok(())
+    }
+}
+
+fn source_record<S: ScanSource + ?Sized>(source: &S, entry: &SourceEntry) -> FileRecord {
+    let mut record = imported_record(&entry.path, entry.size, entry.modified.timestamp());
+    record.scheme = Some(source.scheme().to_string());
+    record.volume = Some(source.volume().to_string());
+    record.etag = entry.etag.clone();
+    record.hash_algo = entry.hash.is_some().then_some(entry.hash_algo).flatten();
+    record.hash = entry.hash.clone();
+    record
+}
+
+// Standard output of a command that streams a remote file, for
+// `ScanSource::open`, with `input` written to its standard input. A
+// command that fails shows up as a read error at the end, so a broken
+// download is not hashed as if it were complete.
+pub(crate) struct CommandReader {
+    child: Child,
+    stdout: ChildStdout,
+    done: bool,
+}
+
+impl CommandReader {
+    pub(crate) fn spawn(mut cmd: Command, input: Option<&str>) -> Result<Self> {
+        let stdin = match input {
+            Some(_) => Stdio::piped(),
+            None => Stdio::null(),
+        };
+        let mut child = cmd
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("running {:?}", cmd.get_program()))?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input.as_bytes())?;
+        }
+        let stdout = child.stdout.take().context("no command output")?;
+        Ok(Self {
+            child,
+            stdout,
+            done: false,
+        })
+    }
+}
+
+impl Read for CommandReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() && !self.done {
+            self.done = true;
+            let status = self.child.wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("download failed ({})", status)));
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for CommandReader {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{record_from_row, FileIndexer, FileRecord, RECORD_COLUMNS};
+
+// Lower bounds of the size distribution buckets.
+const SIZE_BUCKETS: [i64; 5] = [0, 1 << 10, 1 << 20, 100 << 20, 1 << 30];
+
+// Lower bounds, in days since last modification, of the age buckets:
+// last week, last month, last year, older.
+const AGE_BUCKETS: [i64; 4] = [0, 7, 30, 365];
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexStats {
+    pub total_files: i64,
+    pub total_bytes: i64,
+    // Bytes on disk, below `total_bytes` with sparse or compressed files.
+    #[serde(default)]
+    pub allocated_bytes: i64,
+    pub hashed_files: i64,
+    pub unhashed_files: i64,
+    // Unhashed files the hash policy left out; see `FileRecord::hash_skipped`.
+    pub hash_skipped_files: i64,
+    pub oldest_modified: Option<DateTime<Utc>>,
+    pub newest_modified: Option<DateTime<Utc>>,
+    // Largest total size first.
+    pub by_extension: Vec<ExtensionStats>,
+    pub size_distribution: Vec<SizeBucket>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgeBucket {
+    pub min_days: i64,
+    // Exclusive; `None` for the open-ended last bucket.
+    pub max_days: Option<i64>,
+    pub count: i64,
+    pub bytes: i64,
+}
+
+// Space that deleting all but one copy of every duplicate group would free.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WastedSpace {
+    pub groups: i64,
+    pub redundant_files: i64,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtensionStats {
+    pub ext: Option<String>,
+    pub count: i64,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeBucket {
+    pub min: i64,
+    // Exclusive; `None` for the open-ended last bucket.
+    pub max: Option<i64>,
+    pub count: i64,
+    pub bytes: i64,
+}
+
+impl FileIndexer {
+    // Covers regular files only; links and archive members take no space.
+    pub fn stats(&self) -> Result<IndexStats> {
+        let conn = self.reader()?;
+        let (
+            total_files,
+            total_bytes,
+            allocated_bytes,
+            hashed_files,
+            hash_skipped_files,
+            oldest,
+            newest,
+        ) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(size), 0), COALESCE(SUM(COALESCE(allocated, size)), 0),
+                    COUNT(hash), COUNT(hash_skipped), MIN(modified), MAX(modified)
+             FROM files WHERE kind = 'file'",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                    row.get::<_, Option<i64>>(6)?,
+                ))
+            },
+        )?;
+
+        let mut stmt = conn.prepare(
+            "SELECT ext, COUNT(*), SUM(size) FROM files WHERE kind = 'file'
+             GROUP BY ext ORDER BY SUM(size) DESC, COUNT(*) DESC",
+        )?;
+        let by_extension = stmt
+            .query_map([], |row| {
+                Ok(ExtensionStats {
+                    ext: row.get(0)?,
+                    count: row.get(1)?,
+                    bytes: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM files
+             WHERE kind = 'file' AND size >= ?1 AND (?2 IS NULL OR size < ?2)",
+        )?;
+        let mut size_distribution = Vec::new();
+        for (idx, &min) in SIZE_BUCKETS.iter().enumerate() {
+            let max = SIZE_BUCKETS.get(idx + 1).copied();
+            let (count, bytes) =
+                stmt.query_row(params![min, max], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            size_distribution.push(SizeBucket {
+                min,
+                max,
+                count,
+                bytes,
+            });
+        }
+
+        Ok(IndexStats {
+            total_files,
+            total_bytes,
+            allocated_bytes,
+            hashed_files,
+            unhashed_files: total_files - hashed_files,
+            hash_skipped_files,
+            oldest_modified: oldest.and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0)),
+            newest_modified: newest.and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0)),
+            by_extension,
+            size_distribution,
+        })
+    }
+
+    // Files with a modification time in the future count as modified now.
+    pub fn age_histogram(&self) -> Result<Vec<AgeBucket>> {
+        let conn = self.reader()?;
+        let now = Utc::now().timestamp();
+        let mut stmt = conn.prepare(
+            "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM files
+             WHERE kind = 'file' AND modified <= ?1 AND (?2 IS NULL OR modified > ?2)",
+        )?;
+        let mut buckets = Vec::new();
+        for (idx, &min_days) in AGE_BUCKETS.iter().enumerate() {
+            let max_days = AGE_BUCKETS.get(idx + 1).copied();
+            let newest = if min_days == 0 {
+                i64::MAX
+            } else {
+                now - min_days * SECONDS_PER_DAY
+            };
+            let oldest = max_days.map(|days| now - days * SECONDS_PER_DAY);
+            let (count, bytes) = stmt.query_row(params![newest, oldest], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+            buckets.push(AgeBucket {
+                min_days,
+                max_days,
+                count,
+                bytes,
+            });
+        }
+        Ok(buckets)
+    }
+
+    pub fn largest_files(&self, limit: i64) -> Result<Vec<FileRecord>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM files WHERE kind = 'file' ORDER BY size DESC, path LIMIT ?",
+            RECORD_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![limit], record_from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // Only files with a full hash count; run a verified duplicate pass first
+    // when the index was built with quick hashes.
+    pub fn wasted_space(&self) -> Result<WastedSpace> {
+        let conn = self.reader()?;
+        Ok(conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(c - 1), 0), COALESCE(SUM((c - 1) * size), 0)
+             FROM (SELECT size, COUNT(*) AS c FROM files
+                   WHERE kind = 'file' AND hash IS NOT NULL
+                   GROUP BY hash, size HAVING c > 1)",
+            [],
+            |row| {
+                Ok(WastedSpace {
+                    groups: row.get(0)?,
+                    redundant_files: row.get(1)?,
+                    bytes: row.get(2)?,
+                })
+            },
+        )?)
+    }
+}
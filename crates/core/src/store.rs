@@ -0,0 +1,351 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, MAIN_SEPARATOR};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+
+use crate::filetype::MimeFilter;
+use crate::fold;
+use crate::fuzzy;
+use crate::indexer::{
+    normalized_exts, subtree_bounds, DuplicateGroup, DuplicateSort, EntryKind, FileRecord,
+    IndexOptions, IndexReport, SearchQuery, SortKey, DEFAULT_BATCH_SIZE,
+};
+use crate::media::MediaInfo;
+use crate::pattern;
+use crate::rank;
+use crate::scan;
+
+// Persistence of indexed records. SQLite (`FileIndexer`) is the default;
+// other backends sit behind feature flags, like `RedbStore` with `redb`.
+// A backend that can list its records gets the search semantics of the
+// SQLite index from `filter_records` and `group_duplicates` instead of
+// translating `SearchQuery` itself.
+pub trait IndexStore {
+    // Inserts or replaces the record at its path, keeping `added_at` of
+    // the record it replaces.
+    fn upsert(&self, rec: &FileRecord) -> Result<()>;
+    // `upsert` for each record; backends with transactions write the batch
+    // in one.
+    fn upsert_many(&self, recs: &[FileRecord]) -> Result<()> {
+        for rec in recs {
+            self.upsert(rec)?;
+        }
+        Ok(())
+    }
+    // Drops the entry at `path`, everything below it and the members of an
+    // archive there; returns how many entries went.
+    fn remove(&self, path: &Path) -> Result<usize>;
+    fn search(&self, q: &SearchQuery) -> Result<Vec<FileRecord>>;
+    fn recently_added(&self, limit: i64) -> Result<Vec<FileRecord>>;
+    // A page of duplicate groups, largest by `sort` first.
+    fn duplicate_groups(
+        &self,
+        limit: i64,
+        offset: i64,
+        sort: DuplicateSort,
+    ) -> Result<Vec<DuplicateGroup>>;
+
+    // Matches of `q` ignoring its limit, offset and cursor.
+    fn count(&self, q: &SearchQuery) -> Result<i64> {
+        let q = SearchQuery {
+            limit: None,
+            offset: None,
+            after: None,
+            ..q.clone()
+        };
+        Ok(self.search(&q)?.len() as i64)
+    }
+
+    fn find_by_hash(&self, hash: &str) -> Result<Vec<FileRecord>> {
+        self.search(&SearchQuery {
+            hash: Some(hash.to_string()),
+            ..SearchQuery::default()
+        })
+    }
+
+    fn index_dir(&self, root: &Path, opts: &IndexOptions) -> Result<IndexReport> {
+        let batch_size = match opts.batch_size {
+            0 => DEFAULT_BATCH_SIZE,
+            n => n,
+        };
+        let mut batch = Vec::with_capacity(batch_size);
+        let output = scan::scan(root, opts, None, None, |scanned| {
+            batch.push(scanned.record);
+            if batch.len() >= batch_size {
+                self.upsert_many(&batch)?;
+                batch.clear();
+            }
+            Ok(())
+        })?;
+        self.upsert_many(&batch)?;
+        Ok(output.report)
+    }
+}
+
+// The records matching `q`, sorted and paged as `FileIndexer::search`
+// would return them. Filters on data outside `FileRecord` (content,
+// notes, collections, tags, xattrs) are refused; volumes match by id only.
+pub fn filter_records<I>(q: &SearchQuery, records: I) -> Result<Vec<FileRecord>>
+where
+    I: IntoIterator<Item = FileRecord>,
+{
+    if q.content.as_ref().is_some_and(|c| !c.is_empty()) {
+        return Err(anyhow!("content search is not supported by this store"));
+    }
+    if q.collection.as_ref().is_some_and(|c| !c.is_empty()) {
+        return Err(anyhow!("collections are not supported by this store"));
+    }
+    if q.tags.iter().any(|t| !t.trim().is_empty()) {
+        return Err(anyhow!("tags are not supported by this store"));
+    }
+    if q.note.as_ref().is_some_and(|n| !n.is_empty()) {
+        return Err(anyhow!("note search is not supported by this store"));
+    }
+    if q.has_xattr.as_ref().is_some_and(|k| !k.is_empty()) {
+        return Err(anyhow!("xattr search is not supported by this store"));
+    }
+    let day_start = |date: Option<NaiveDate>| {
+        date.map(|date| {
+            date.and_hms_opt(0, 0, 0)
+                .map(|time| time.and_utc().timestamp())
+                .ok_or_else(|| anyhow!("invalid from date"))
+        })
+        .transpose()
+    };
+    let day_end = |date: Option<NaiveDate>| {
+        date.map(|date| {
+            date.and_hms_opt(23, 59, 59)
+                .map(|time| time.and_utc().timestamp())
+                .ok_or_else(|| anyhow!("invalid to date"))
+        })
+        .transpose()
+    };
+    let from = day_start(q.date_from)?;
+    let to = day_end(q.date_to)?;
+    let added_from = day_start(q.added_from)?;
+    let added_to = day_end(q.added_to)?;
+    let subtree = q
+        .path_prefix
+        .as_deref()
+        .filter(|s| !s.is_empty())
+        .map(|dir| {
+            let (lower, _) = subtree_bounds(dir);
+            (lower.trim_end_matches(MAIN_SEPARATOR).to_string(), lower)
+        });
+    let exact = q.case_sensitive == Some(true) && !q.fuzzy;
+    let name = q.name_like.as_ref().filter(|s| !s.is_empty()).map(|s| {
+        if exact {
+            s.clone()
+        } else {
+            fold::fold(s)
+        }
+    });
+    let ext = q
+        .ext
+        .as_ref()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_ascii_lowercase());
+
+    let exts = normalized_exts(&q.ext_in);
+    let hash = q
+        .hash
+        .as_ref()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.trim().to_ascii_lowercase());
+    let mime = q.mime.as_deref().and_then(MimeFilter::parse);
+    let compile = |regex: &Option<String>| {
+        regex
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .map(pattern::compile)
+            .transpose()
+    };
+    let name_regex = compile(&q.name_regex)?;
+    let path_regex = compile(&q.path_regex)?;
+    let glob = q
+        .glob
+        .as_deref()
+        .filter(|s| !s.is_empty())
+        .map(|glob| {
+            let case_insensitive = q.case_sensitive != Some(true);
+            let plan = pattern::plan_glob(glob, case_insensitive)?;
+            pattern::glob_matcher(&plan.pattern, case_insensitive)
+        })
+        .transpose()?;
+
+    let mut rows: Vec<FileRecord> = records
+        .into_iter()
+        .filter(|rec| {
+            name.as_ref().is_none_or(|n| {
+                if q.fuzzy {
+                    fuzzy::matches(n, &rec.name)
+                } else if exact {
+                    rec.name.contains(n.as_str())
+                } else {
+                    fold::fold(&rec.name).contains(n.as_str())
+                }
+            }) && name_regex.as_ref().is_none_or(|re| re.is_match(&rec.name))
+                && path_regex.as_ref().is_none_or(|re| re.is_match(&rec.path))
+                && glob.as_ref().is_none_or(|glob| glob.is_match(&rec.path))
+                && subtree.as_ref().is_none_or(|(dir, lower)| {
+                    rec.path == *dir || rec.path.starts_with(lower.as_str())
+                })
+                && ext.as_ref().is_none_or(|e| rec.ext.as_ref() == Some(e))
+                && (exts.is_empty() || rec.ext.as_ref().is_some_and(|e| exts.contains(e)))
+                && q.kind.is_none_or(|kind| rec.kind == kind)
+                && q.volume
+                    .as_ref()
+                    .is_none_or(|v| rec.volume.as_ref() == Some(v))
+                && hash.as_ref().is_none_or(|h| rec.hash.as_ref() == Some(h))
+                && q.has_hash.is_none_or(|has| rec.hash.is_some() == has)
+                && q.min_rating.is_none_or(|min| rec.rating >= min)
+                && mime.as_ref().is_none_or(|m| m.matches(rec.mime.as_deref()))
+                && (!q.type_mismatch || rec.type_mismatch)
+                && q.owner.is_none_or(|uid| rec.uid == Some(uid))
+                && q.group.is_none_or(|gid| rec.gid == Some(gid))
+                && (!q.world_writable || is_world_writable(rec))
+                && q.attributes.is_none_or(|bits| {
+                    bits == 0 || rec.attributes.is_some_and(|a| a & bits == bits)
+                })
+                && media_matches(q, rec.media.as_ref())
+                && q.min_size.is_none_or(|min| rec.size >= min)
+                && q.max_size.is_none_or(|max| rec.size <= max)
+                && q.min_allocated
+                    .is_none_or(|min| rec.allocated_size() >= min)
+                && q.max_allocated
+                    .is_none_or(|max| rec.allocated_size() <= max)
+                && from.is_none_or(|ts| rec.modified.timestamp() >= ts)
+                && to.is_none_or(|ts| rec.modified.timestamp() <= ts)
+                && added_from.is_none_or(|ts| rec.added_at.timestamp() >= ts)
+                && added_to.is_none_or(|ts| rec.added_at.timestamp() <= ts)
+        })
+        .collect();
+
+    let order = q.sort_order()?;
+    if order[0].0 == SortKey::Relevance {
+        rows.sort_by(|a, b| a.name.cmp(&b.name));
+        rows = rank::sort_by_relevance(rows, name.as_deref(), q.desc);
+    } else {
+        let directed = |ord: Ordering, desc: bool| if desc { ord.reverse() } else { ord };
+        rows.sort_by(|a, b| {
+            order
+                .iter()
+                .fold(Ordering::Equal, |ord, &(key, desc)| {
+                    ord.then_with(|| directed(compare(a, b, key), desc))
+                })
+                .then_with(|| directed(a.path.cmp(&b.path), order[0].1))
+        });
+    }
+
+    if let Some(cursor) = &q.after {
+        let mut kept = Vec::with_capacity(rows.len());
+        for rec in rows {
+            if cursor.precedes(q, &rec)? {
+                kept.push(rec);
+            }
+        }
+        rows = kept;
+    }
+
+    let offset = q.offset.unwrap_or(0).max(0) as usize;
+    let limit = q.limit.map_or(usize::MAX, |l| l.max(0) as usize);
+    Ok(rows.into_iter().skip(offset).take(limit).collect())
+}
+
+// Groups of two or more records with the same full hash and size, ordered
+// by `sort` as in the SQLite index.
+pub fn group_duplicates<I>(
+    records: I,
+    limit: i64,
+    offset: i64,
+    sort: DuplicateSort,
+) -> Vec<DuplicateGroup>
+where
+    I: IntoIterator<Item = FileRecord>,
+{
+    let mut groups: HashMap<(String, i64), Vec<FileRecord>> = HashMap::new();
+    for rec in records {
+        if let Some(hash) = rec.hash.clone() {
+            groups.entry((hash, rec.size)).or_default().push(rec);
+        }
+    }
+
+    let mut out: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .filter(|(_, recs)| recs.len() > 1)
+        .map(|((hash, size), mut recs)| {
+            recs.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.path.cmp(&b.path)));
+            let count = recs.len() as i64;
+            DuplicateGroup {
+                hash,
+                size,
+                count,
+                wasted: (count - 1) * size,
+                paths: recs.into_iter().map(|r| r.path).collect(),
+            }
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        sort.key(b)
+            .cmp(&sort.key(a))
+            .then_with(|| b.count.cmp(&a.count))
+            .then_with(|| a.hash.cmp(&b.hash))
+            .then_with(|| a.size.cmp(&b.size))
+    });
+    out.into_iter()
+        .skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .collect()
+}
+
+fn compare(a: &FileRecord, b: &FileRecord, key: SortKey) -> Ordering {
+    match key {
+        SortKey::Name | SortKey::Relevance => a.name.cmp(&b.name),
+        SortKey::Size => a.size.cmp(&b.size),
+        SortKey::Modified => a.modified.cmp(&b.modified),
+        SortKey::Rating => a.rating.cmp(&b.rating),
+        SortKey::Ext => a
+            .ext
+            .as_deref()
+            .unwrap_or("")
+            .cmp(b.ext.as_deref().unwrap_or("")),
+        SortKey::Path => a.path.cmp(&b.path),
+        SortKey::AddedAt => a.added_at.cmp(&b.added_at),
+        SortKey::Allocated => a.allocated_size().cmp(&b.allocated_size()),
+    }
+}
+
+fn is_world_writable(rec: &FileRecord) -> bool {
+    matches!(rec.kind, EntryKind::File | EntryKind::ArchiveMember)
+        && rec.mode.is_some_and(|mode| mode & 0o002 != 0)
+}
+
+fn media_matches(q: &SearchQuery, media: Option<&MediaInfo>) -> bool {
+    let wants_media = q.min_duration.is_some()
+        || q.max_duration.is_some()
+        || q.min_height.is_some()
+        || [&q.artist, &q.album, &q.title]
+            .iter()
+            .any(|f| f.as_ref().is_some_and(|s| !s.is_empty()));
+    let Some(media) = media else {
+        return !wants_media;
+    };
+    let tag_matches = |filter: &Option<String>, value: &Option<String>| {
+        filter.as_ref().filter(|s| !s.is_empty()).is_none_or(|f| {
+            value
+                .as_ref()
+                .is_some_and(|v| v.to_lowercase().contains(&f.to_lowercase()))
+        })
+    };
+    q.min_duration
+        .is_none_or(|min| media.duration.is_some_and(|d| d >= min as f64))
+        && q.max_duration
+            .is_none_or(|max| media.duration.is_some_and(|d| d <= max as f64))
+        && q.min_height
+            .is_none_or(|min| media.height.is_some_and(|h| h >= min))
+        && tag_matches(&q.artist, &media.artist)
+        && tag_matches(&q.album, &media.album)
+        && tag_matches(&q.title, &media.title)
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::path::MAIN_SEPARATOR;
+
+use anyhow::Result;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::FileIndexer;
+
+// Names read to rank token suggestions; enough for the counts to be telling
+// while bounding the work per keystroke.
+const NAME_SAMPLE: i64 = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Suggestion {
+    pub value: String,
+    pub count: i64,
+}
+
+impl FileIndexer {
+    pub fn suggest_extensions(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT ext, COUNT(*) as c
+             FROM files
+             WHERE ext >= ?1 AND ext < ?2
+             GROUP BY ext
+             ORDER BY c DESC, ext
+             LIMIT ?3",
+        )?;
+        let (lower, upper) = prefix_bounds(&partial.trim_start_matches('.').to_ascii_lowercase());
+        let rows = stmt.query_map(params![lower, upper, limit], |row| {
+            Ok(Suggestion {
+                value: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    // Directories directly inside the one `partial` names, counted by the
+    // files below them, from the per-directory totals. Those above the
+    // indexed roots have no totals of their own and add up their roots'.
+    pub fn suggest_dirs(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        let roots: Vec<String> = self.list_roots()?.into_iter().map(|r| r.path).collect();
+        let conn = self.reader()?;
+        let base_len = partial.rfind(['/', '\\']).map_or(0, |i| i + 1);
+        let (lower, upper) = prefix_bounds(partial);
+        let mut stmt =
+            conn.prepare("SELECT path, file_count FROM dirs WHERE path >= ? AND path < ?")?;
+        let dirs = stmt.query_map(params![lower, upper], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+
+        let mut counts: HashMap<String, i64> = HashMap::new();
+        let mut above_roots: HashMap<String, i64> = HashMap::new();
+        for (path, files) in dirs.filter_map(|r| r.ok()) {
+            let rest = &path[base_len..];
+            match rest.find(['/', '\\']) {
+                None if !rest.is_empty() => {
+                    counts.insert(format!("{}{}", path, MAIN_SEPARATOR), files);
+                }
+                Some(end) if roots.contains(&path) => {
+                    let dir = &path[..base_len + end + 1];
+                    *above_roots.entry(dir.to_string()).or_default() += files;
+                }
+                _ => {}
+            }
+        }
+        for (dir, files) in above_roots {
+            counts.entry(dir).or_insert(files);
+        }
+        Ok(top_suggestions(counts, limit))
+    }
+
+    // Tokens starting with `partial`, from names that start with it too so
+    // an index finds them: typing a word suggests how names continue it.
+    pub fn suggest_name_tokens(&self, partial: &str, limit: i64) -> Result<Vec<Suggestion>> {
+        let conn = self.reader()?;
+        let partial = partial.to_lowercase();
+        if partial.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (lower, upper) = prefix_bounds(&partial);
+        let mut stmt = conn.prepare(
+            "SELECT name FROM files
+             WHERE name COLLATE NOCASE >= ?1 AND name COLLATE NOCASE < ?2
+             LIMIT ?3",
+        )?;
+        let names = stmt.query_map(params![lower, upper, NAME_SAMPLE], |row| {
+            row.get::<_, String>(0)
+        })?;
+
+        let mut counts: HashMap<String, i64> = HashMap::new();
+        for name in names.filter_map(|r| r.ok()) {
+            for token in name_tokens(&name) {
+                if token.starts_with(&partial) {
+                    *counts.entry(token).or_default() += 1;
+                }
+            }
+        }
+        Ok(top_suggestions(counts, limit))
+    }
+}
+
+// Half-open range of the strings starting with `prefix`.
+fn prefix_bounds(prefix: &str) -> (String, String) {
+    (prefix.to_string(), format!("{}\u{10FFFF}", prefix))
+}
+
+fn name_tokens(name: &str) -> Vec<String> {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    stem.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+fn top_suggestions(counts: HashMap<String, i64>, limit: i64) -> Vec<Suggestion> {
+    let mut out: Vec<Suggestion> = counts
+        .into_iter()
+        .map(|(value, count)| Suggestion { value, count })
+        .collect();
+    out.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    out.truncate(limit.max(0) as usize);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::indexer::IndexOptions;
+    use crate::paths;
+
+    fn indexed(files: &[&str]) -> (tempfile::TempDir, tempfile::TempDir, FileIndexer) {
+        let dir = tempfile::tempdir().unwrap();
+        for name in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x").unwrap();
+        }
+        let db = tempfile::tempdir().unwrap();
+        let indexer = FileIndexer::new(db.path().join("index.db")).unwrap();
+        indexer
+            .index_dir(dir.path(), &IndexOptions::default())
+            .unwrap();
+        (dir, db, indexer)
+    }
+
+    fn values(suggestions: Vec<Suggestion>) -> Vec<(String, i64)> {
+        suggestions
+            .into_iter()
+            .map(|s| (s.value, s.count))
+            .collect()
+    }
+
+    #[test]
+    fn suggests_extensions_by_prefix() {
+        let (_dir, _db, indexer) = indexed(&["a.txt", "b.txt", "c.tar", "d.pdf"]);
+        assert_eq!(
+            values(indexer.suggest_extensions(".T", 5).unwrap()),
+            [("txt".into(), 2), ("tar".into(), 1)]
+        );
+        // Wildcards are plain characters.
+        assert!(indexer.suggest_extensions("%", 5).unwrap().is_empty());
+        assert!(indexer.suggest_extensions("t_t", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn suggests_name_tokens_by_prefix() {
+        let (_dir, _db, indexer) = indexed(&[
+            "Report-2024.pdf",
+            "report_final.doc",
+            "reports.txt",
+            "annual-report.pdf",
+            "100%_done.txt",
+        ]);
+        assert_eq!(
+            values(indexer.suggest_name_tokens("REP", 5).unwrap()),
+            [("report".into(), 2), ("reports".into(), 1)]
+        );
+        assert!(indexer.suggest_name_tokens("%", 5).unwrap().is_empty());
+        assert!(indexer.suggest_name_tokens("r_port", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn suggests_child_directories() {
+        let (dir, _db, indexer) = indexed(&["docs/a.txt", "docs/b.txt", "music/c.mp3", "x.txt"]);
+        let root = paths::key(dir.path());
+        let inside = format!("{}{}", root, MAIN_SEPARATOR);
+        assert_eq!(
+            values(indexer.suggest_dirs(&inside, 5).unwrap()),
+            [
+                (format!("{}docs{}", inside, MAIN_SEPARATOR), 2),
+                (format!("{}music{}", inside, MAIN_SEPARATOR), 1)
+            ]
+        );
+        // Above the root, the root's own total counts.
+        let parent = &root[..root.rfind(MAIN_SEPARATOR).unwrap() + 1];
+        assert_eq!(
+            values(indexer.suggest_dirs(parent, 100).unwrap())
+                .into_iter()
+                .find(|(value, _)| *value == format!("{}{}", root, MAIN_SEPARATOR)),
+            Some((format!("{}{}", root, MAIN_SEPARATOR), 4))
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::{subtree_bounds, FileIndexer};
+use crate::paths;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub name: String,
+    pub file_count: i64,
+    pub bytes: i64,
+}
+
+// Tags are short labels on indexed entries, compared case-insensitively.
+// Unlike collections they need no creating: a tag exists while some entry
+// carries it. They are dropped with the entry but follow it across
+// detected renames.
+impl FileIndexer {
+    pub fn list_tags(&self) -> Result<Vec<TagInfo>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT t.tag, COUNT(*), COALESCE(SUM(f.size), 0)
+             FROM tags t JOIN files f ON f.path = t.path
+             GROUP BY t.tag
+             ORDER BY t.tag",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(TagInfo {
+                name: row.get(0)?,
+                file_count: row.get(1)?,
+                bytes: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn tags_of<P: AsRef<Path>>(&self, path: P) -> Result<Vec<String>> {
+        let conn = self.reader()?;
+        let path = paths::key(path.as_ref());
+        let mut stmt = conn.prepare("SELECT tag FROM tags WHERE path = ? ORDER BY tag")?;
+        let rows = stmt.query_map(params![path], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // Tags indexed entries; a directory tags everything indexed below it.
+    // Returns how many entries did not have the tag yet.
+    pub fn add_tag<P: AsRef<Path>>(&self, tag: &str, paths: &[P]) -> Result<usize> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let tag = valid_tag(tag)?;
+        let now = Utc::now().timestamp();
+        // Nothing is tagged if any path fails.
+        let txn = conn.unchecked_transaction()?;
+        let mut added = 0;
+        {
+            let mut insert = txn.prepare(
+                "INSERT OR IGNORE INTO tags(path,tag,added_at)
+                 SELECT path, ?1, ?2 FROM files
+                 WHERE path = ?3 OR (path >= ?4 AND path < ?5)",
+            )?;
+            for path in paths {
+                let path = paths::key(path.as_ref());
+                let (lower, upper) = subtree_bounds(&path);
+                let matched: bool = txn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM files
+                                   WHERE path = ?1 OR (path >= ?2 AND path < ?3))",
+                    params![path, lower, upper],
+                    |row| row.get(0),
+                )?;
+                if !matched {
+                    return Err(anyhow!("{} is not indexed", path));
+                }
+                added += insert.execute(params![tag, now, path, lower, upper])?;
+            }
+        }
+        txn.commit()?;
+        self.invalidate_cache();
+        Ok(added)
+    }
+
+    // Untags entries, or everything below a directory.
+    pub fn remove_tag<P: AsRef<Path>>(&self, tag: &str, paths: &[P]) -> Result<usize> {
+        self.ensure_writable()?;
+        let conn = self.conn();
+        let tag = valid_tag(tag)?;
+        let mut removed = 0;
+        for path in paths {
+            let path = paths::key(path.as_ref());
+            let (lower, upper) = subtree_bounds(&path);
+            removed += conn.execute(
+                "DELETE FROM tags
+                 WHERE tag = ?1 AND (path = ?2 OR (path >= ?3 AND path < ?4))",
+                params![tag, path, lower, upper],
+            )?;
+        }
+        self.invalidate_cache();
+        Ok(removed)
+    }
+
+    // Takes the tag off every entry.
+    pub fn delete_tag(&self, tag: &str) -> Result<usize> {
+        self.ensure_writable()?;
+        let removed = self
+            .conn()
+            .execute("DELETE FROM tags WHERE tag = ?", params![valid_tag(tag)?])?;
+        self.invalidate_cache();
+        Ok(removed)
+    }
+}
+
+fn valid_tag(tag: &str) -> Result<&str> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(anyhow!("tag must not be empty"));
+    }
+    Ok(tag)
+}
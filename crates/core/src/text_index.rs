@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rusqlite::{params, OptionalExtension};
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, FuzzyTermQuery, Occur, Query, QueryParser};
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, ReloadPolicy, TantivyDocument, Term};
+
+use crate::indexer::{record_from_row, FileIndexer, SearchQuery, SortKey, RECORD_COLUMNS};
+use crate::rank::{self, ScoredRecord};
+use crate::store;
+
+// Matches taken from Tantivy before the other filters of a query apply.
+const MAX_CANDIDATES: usize = 10_000;
+// Changed files indexed per Tantivy commit.
+const SYNC_BATCH: i64 = 10_000;
+const WRITER_MEMORY: usize = 50_000_000;
+
+// Files whose name or extracted text changed since the Tantivy index last
+// caught up, filled by triggers so writes from any process are seen.
+const CHANGE_TRACKING: &str = "
+    CREATE TABLE IF NOT EXISTS text_changes (path TEXT PRIMARY KEY);
+    CREATE TRIGGER IF NOT EXISTS files_text_insert AFTER INSERT ON files BEGIN
+        INSERT OR IGNORE INTO text_changes(path) VALUES (new.path);
+    END;
+    CREATE TRIGGER IF NOT EXISTS files_text_update AFTER UPDATE ON files
+    WHEN old.name IS NOT new.name OR old.size IS NOT new.size
+      OR old.modified IS NOT new.modified BEGIN
+        INSERT OR IGNORE INTO text_changes(path) VALUES (new.path);
+    END;
+    CREATE TRIGGER IF NOT EXISTS files_text_delete AFTER DELETE ON files BEGIN
+        INSERT OR IGNORE INTO text_changes(path) VALUES (old.path);
+    END;
+    -- Text extracted for the first time from a file that did not change.
+    CREATE TRIGGER IF NOT EXISTS content_text_insert AFTER INSERT ON content_docs BEGIN
+        INSERT OR IGNORE INTO text_changes(path) VALUES (new.path);
+    END;";
+
+// A Tantivy index of file names and extracted text next to the SQLite
+// database, for typo-tolerant name search and ranked full-text queries.
+// SQLite stays the source of truth; the Tantivy index catches up with it
+// before each search and can be deleted at any time to be rebuilt.
+pub(crate) struct TextIndex {
+    index: Index,
+    reader: IndexReader,
+    path: Field,
+    name: Field,
+    content: Field,
+}
+
+impl TextIndex {
+    fn open(dir: &Path) -> Result<(Self, bool)> {
+        let mut schema = Schema::builder();
+        let path = schema.add_text_field("path", STRING | STORED);
+        let name = schema.add_text_field("name", TEXT);
+        let content = schema.add_text_field("content", TEXT);
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        let created = !dir.join("meta.json").exists();
+        let index = Index::open_or_create(MmapDirectory::open(dir)?, schema.build())
+            .with_context(|| format!("opening the text index in {}", dir.display()))?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let text = Self {
+            index,
+            reader,
+            path,
+            name,
+            content,
+        };
+        Ok((text, created))
+    }
+
+    fn query(&self, q: &SearchQuery) -> Result<Option<BooleanQuery>> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        if let Some(content) = q.content.as_deref().filter(|c| !c.is_empty()) {
+            let parser = QueryParser::for_index(&self.index, vec![self.content]);
+            clauses.push((Occur::Must, parser.parse_query(content)?));
+        }
+        if let Some(name) = q.name_like.as_deref().filter(|_| q.fuzzy) {
+            // Each word matches the start of a word in the name, with a typo
+            // or two allowed in longer words.
+            let mut analyzer = self.index.tokenizer_for_field(self.name)?;
+            let mut stream = analyzer.token_stream(name);
+            while let Some(token) = stream.next() {
+                let distance = match token.text.chars().count() {
+                    0..=3 => 0,
+                    4..=7 => 1,
+                    _ => 2,
+                };
+                let term = Term::from_field_text(self.name, &token.text);
+                let fuzzy = FuzzyTermQuery::new_prefix(term, distance, true);
+                clauses.push((Occur::Must, Box::new(fuzzy)));
+            }
+        }
+        Ok((!clauses.is_empty()).then(|| BooleanQuery::new(clauses)))
+    }
+}
+
+impl FileIndexer {
+    // Keeps a Tantivy index in `dir` alongside the database, which searches
+    // then use for fuzzy names and content queries. A new
+    // or deleted index is built from the database on the next sync.
+    pub fn enable_text_index<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        let (text, created) = TextIndex::open(dir.as_ref())?;
+        if !self.is_read_only() {
+            let conn = self.conn();
+            conn.execute_batch(CHANGE_TRACKING)?;
+            if created {
+                conn.execute(
+                    "INSERT OR IGNORE INTO text_changes(path) SELECT path FROM files",
+                    [],
+                )?;
+            }
+        }
+        self.text = Some(text);
+        Ok(())
+    }
+
+    // Brings the text index up to date with the database; returns how
+    // many files it had to look at again.
+    pub fn sync_text_index(&self) -> Result<usize> {
+        let Some(text) = &self.text else {
+            return Ok(0);
+        };
+        self.ensure_writable()?;
+        // Held throughout, so no write of this process slips in between
+        // reading a change and clearing it.
+        let conn = self.conn();
+        let pending: i64 =
+            conn.query_row("SELECT count(*) FROM text_changes", [], |row| row.get(0))?;
+        if pending == 0 {
+            return Ok(0);
+        }
+        let mut writer = text.index.writer::<TantivyDocument>(WRITER_MEMORY)?;
+        let mut synced = 0;
+        loop {
+            let txn = conn.unchecked_transaction()?;
+            let paths: Vec<String> = txn
+                .prepare("SELECT path FROM text_changes LIMIT ?")?
+                .query_map(params![SYNC_BATCH], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+            if paths.is_empty() {
+                break;
+            }
+            for path in &paths {
+                writer.delete_term(Term::from_field_text(text.path, path));
+                let row: Option<(String, Option<String>)> = txn
+                    .prepare_cached(
+                        "SELECT f.name, (SELECT b.body FROM content_docs d
+                                         JOIN content_fts b ON b.rowid = d.id
+                                         WHERE d.path = f.path)
+                         FROM files f WHERE f.path = ?",
+                    )?
+                    .query_row(params![path], |row| Ok((row.get(0)?, row.get(1)?)))
+                    .optional()?;
+                if let Some((name, content)) = row {
+                    writer.add_document(doc!(
+                        text.path => path.as_str(),
+                        text.name => name,
+                        text.content => content.unwrap_or_default(),
+                    ))?;
+                }
+            }
+            writer.commit()?;
+            for path in &paths {
+                txn.prepare_cached("DELETE FROM text_changes WHERE path = ?")?
+                    .execute(params![path])?;
+            }
+            txn.commit()?;
+            synced += paths.len();
+        }
+        text.reader.reload()?;
+        Ok(synced)
+    }
+
+    // `q` answered from the text index, or `None` if SQLite should answer
+    // it. The text index takes content queries and fuzzy names, unless the
+    // query also filters on notes, collections, xattrs or volumes, which
+    // only SQLite knows.
+    pub(crate) fn search_text(&self, q: &SearchQuery) -> Result<Option<Vec<ScoredRecord>>> {
+        let set = |value: &Option<String>| value.as_ref().is_some_and(|v| !v.is_empty());
+        let wanted = set(&q.content) || (q.fuzzy && set(&q.name_like));
+        let unsupported = set(&q.note) || set(&q.collection) || set(&q.has_xattr) || set(&q.volume);
+        let Some(text) = self.text.as_ref().filter(|_| wanted && !unsupported) else {
+            return Ok(None);
+        };
+        if !self.is_read_only() {
+            self.sync_text_index()?;
+        }
+        let Some(query) = text.query(q)? else {
+            return Ok(None);
+        };
+        let searcher = text.reader.searcher();
+        let mut scores = HashMap::new();
+        for (score, address) in searcher.search(&query, &TopDocs::with_limit(MAX_CANDIDATES))? {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            if let Some(path) = doc.get_first(text.path).and_then(|v| v.as_str()) {
+                scores.insert(path.to_string(), score as f64);
+            }
+        }
+
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM files WHERE path = ?",
+            RECORD_COLUMNS
+        ))?;
+        let mut records = Vec::with_capacity(scores.len());
+        for path in scores.keys() {
+            // Rows deleted by another process since the last sync drop out.
+            if let Some(record) = stmt.query_row(params![path], record_from_row).optional()? {
+                records.push(record);
+            }
+        }
+
+        // The text engine has matched names and content; the remaining
+        // filters behave as in SQLite.
+        let by_score = matches!(q.sort_key, None | Some(SortKey::Relevance));
+        let rest = SearchQuery {
+            content: None,
+            name_like: q.name_like.clone().filter(|_| !q.fuzzy),
+            fuzzy: false,
+            limit: q.limit.filter(|_| !by_score),
+            offset: q.offset.filter(|_| !by_score),
+            after: q.after.clone().filter(|_| !by_score),
+            ..q.clone()
+        };
+        let now = Utc::now();
+        let name = q.name_like.as_deref();
+        let mut rows: Vec<ScoredRecord> = store::filter_records(&rest, records)?
+            .into_iter()
+            .map(|record| ScoredRecord {
+                score: scores[&record.path] + rank::relevance_score(&record, name, now),
+                record,
+            })
+            .collect();
+        if by_score {
+            rows.sort_by(|a, b| {
+                let ord = b.score.total_cmp(&a.score);
+                if q.desc {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            });
+            rows = rows
+                .into_iter()
+                .skip(q.offset.unwrap_or(0).max(0) as usize)
+                .take(q.limit.map_or(usize::MAX, |l| l.max(0) as usize))
+                .collect();
+        }
+        Ok(Some(rows))
+    }
+}
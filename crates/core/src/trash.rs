@@ -0,0 +1,300 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::indexer::FileIndexer;
+
+impl FileIndexer {
+    // Moves `path` to the trash and tombstones its index entries, so the
+    // deletion shows up in `recently_deleted` and can be undone from the
+    // file manager.
+    pub fn trash_file(&self, path: &Path) -> Result<()> {
+        self.trash_with(path, move_to_trash)
+    }
+
+    fn trash_with(&self, path: &Path, trash: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+        self.ensure_writable()?;
+        trash(path)?;
+        self.remove_deleted(path)?;
+        Ok(())
+    }
+}
+
+// Follows the freedesktop.org trash specification: files on the home volume
+// go to $XDG_DATA_HOME/Trash, others to $topdir/.Trash-$uid on their own
+// volume, each with a .trashinfo file recording where it came from.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn move_to_trash(path: &Path) -> Result<()> {
+    move_to_trash_in(path, &home_trash()?)
+}
+
+// `move_to_trash` with the home trash at `home`.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn move_to_trash_in(path: &Path, home: &Path) -> Result<()> {
+    use std::fs::{self, OpenOptions};
+    use std::io::{ErrorKind, Write};
+    use std::os::unix::fs::MetadataExt;
+
+    let path = absolute(path)?;
+    let dev = path
+        .symlink_metadata()
+        .with_context(|| format!("reading metadata for {}", path.display()))?
+        .dev();
+    let home = home.to_path_buf();
+    fs::create_dir_all(&home).with_context(|| format!("creating {}", home.display()))?;
+    let home_meta = fs::metadata(&home)?;
+    let (trash, origin) = if home_meta.dev() == dev {
+        (home, path.clone())
+    } else {
+        let top = volume_top(&path, dev);
+        let trash = top.join(format!(".Trash-{}", home_meta.uid()));
+        let origin = path.strip_prefix(&top).unwrap_or(&path).to_path_buf();
+        (trash, origin)
+    };
+    let files = trash.join("files");
+    let info = trash.join("info");
+    for dir in [&files, &info] {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))?
+        .to_string_lossy()
+        .to_string();
+    // The info file is created exclusively first; it reserves the name.
+    let mut attempt = 1;
+    let (stored, info_path) = loop {
+        let stored = if attempt == 1 {
+            name.clone()
+        } else {
+            format!("{}.{}", name, attempt)
+        };
+        let info_path = info.join(format!("{}.trashinfo", stored));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(mut file) => {
+                write!(
+                    file,
+                    "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                    percent_encode(&origin),
+                    chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+                )?;
+                break (stored, info_path);
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => {
+                return Err(err).with_context(|| format!("creating {}", info_path.display()))
+            }
+        }
+    };
+    if let Err(err) = fs::rename(&path, files.join(&stored)) {
+        let _ = fs::remove_file(&info_path);
+        return Err(err).with_context(|| format!("moving {} to the trash", path.display()));
+    }
+    Ok(())
+}
+
+// Files on the home volume go to ~/.Trash, others to .Trashes/$uid at the
+// top of their own volume, since the Trash cannot be reached by a rename
+// from another volume.
+#[cfg(target_os = "macos")]
+pub fn move_to_trash(path: &Path) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = absolute(path)?;
+    let dev = path
+        .symlink_metadata()
+        .with_context(|| format!("reading metadata for {}", path.display()))?
+        .dev();
+    let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+    let home = Path::new(&home);
+    let home_meta =
+        std::fs::metadata(home).with_context(|| format!("reading {}", home.display()))?;
+    let trash = if home_meta.dev() == dev {
+        home.join(".Trash")
+    } else {
+        let trash = volume_top(&path, dev)
+            .join(".Trashes")
+            .join(home_meta.uid().to_string());
+        std::fs::create_dir_all(&trash).with_context(|| format!("creating {}", trash.display()))?;
+        trash
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))?
+        .to_string_lossy()
+        .to_string();
+    let mut target = trash.join(&name);
+    let mut attempt = 1;
+    while target.symlink_metadata().is_ok() {
+        attempt += 1;
+        target = trash.join(format!("{} {}", name, attempt));
+    }
+    std::fs::rename(&path, &target)
+        .with_context(|| format!("moving {} to the trash", path.display()))
+}
+
+// Sends the file to the Recycle Bin through the shell. Volumes without one,
+// such as network shares, make Windows delete the file outright.
+#[cfg(windows)]
+pub fn move_to_trash(path: &Path) -> Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::UI::Shell::{
+        SHFileOperationW, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT, FO_DELETE,
+        SHFILEOPSTRUCTW,
+    };
+
+    // The shell takes neither relative nor `\\?\` paths, in a list that
+    // ends with two NULs.
+    let path = std::path::absolute(path)?;
+    path.symlink_metadata()
+        .with_context(|| format!("reading metadata for {}", path.display()))?;
+    let from: Vec<u16> = path.as_os_str().encode_wide().chain([0, 0]).collect();
+    let mut op = SHFILEOPSTRUCTW {
+        hwnd: std::ptr::null_mut(),
+        wFunc: FO_DELETE,
+        pFrom: from.as_ptr(),
+        pTo: std::ptr::null(),
+        fFlags: (FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT) as u16,
+        fAnyOperationsAborted: 0,
+        hNameMappings: std::ptr::null_mut(),
+        lpszProgressTitle: std::ptr::null(),
+    };
+    let code = unsafe { SHFileOperationW(&mut op) };
+    if code != 0 || op.fAnyOperationsAborted != 0 {
+        return Err(anyhow!(
+            "moving {} to the trash failed (shell error {:#x})",
+            path.display(),
+            code
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn move_to_trash(path: &Path) -> Result<()> {
+    Err(anyhow!(
+        "moving {} to the trash is not supported on this platform",
+        path.display()
+    ))
+}
+
+// Makes `path` absolute without resolving a final symlink, which must be
+// trashed itself rather than its target.
+#[cfg(unix)]
+fn absolute(path: &Path) -> Result<std::path::PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent = parent
+        .canonicalize()
+        .with_context(|| format!("resolving {}", parent.display()))?;
+    Ok(parent.join(name))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn home_trash() -> Result<std::path::PathBuf> {
+    let data = match std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => dir.into(),
+        None => {
+            let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+            Path::new(&home).join(".local/share")
+        }
+    };
+    Ok(data.join("Trash"))
+}
+
+// Highest ancestor of `path` still on device `dev`, i.e. its mount point.
+#[cfg(unix)]
+fn volume_top(path: &Path, dev: u64) -> std::path::PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut top = path.parent().unwrap_or(path).to_path_buf();
+    for ancestor in path.ancestors().skip(2) {
+        match std::fs::metadata(ancestor) {
+            Ok(meta) if meta.dev() == dev => top = ancestor.to_path_buf(),
+            _ => break,
+        }
+    }
+    top
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn percent_encode(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut out = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::indexer::{IndexOptions, SearchQuery};
+
+    #[test]
+    fn encodes_origin_paths() {
+        assert_eq!(percent_encode(Path::new("/a/b-c_d.txt")), "/a/b-c_d.txt");
+        assert_eq!(
+            percent_encode(Path::new("/a/my file%é")),
+            "/a/my%20file%25%C3%A9"
+        );
+    }
+
+    #[test]
+    fn trashes_files_and_tombstones_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = dir.path().join("data/Trash");
+        let docs = dir.path().join("docs");
+        fs::create_dir_all(docs.join("sub")).unwrap();
+        fs::write(docs.join("a.txt"), "one").unwrap();
+        fs::write(docs.join("sub/a.txt"), "two").unwrap();
+
+        let db = tempfile::tempdir().unwrap();
+        let indexer = FileIndexer::new(db.path().join("index.db")).unwrap();
+        indexer.index_dir(&docs, &IndexOptions::default()).unwrap();
+        let trash_file =
+            |path: &Path| indexer.trash_with(path, |path| move_to_trash_in(path, &trash));
+        trash_file(&docs.join("a.txt")).unwrap();
+        trash_file(&docs.join("sub/a.txt")).unwrap();
+
+        assert!(!docs.join("a.txt").exists());
+        assert_eq!(
+            fs::read_to_string(trash.join("files/a.txt")).unwrap(),
+            "one"
+        );
+        assert_eq!(
+            fs::read_to_string(trash.join("files/a.txt.2")).unwrap(),
+            "two"
+        );
+        let info = fs::read_to_string(trash.join("info/a.txt.2.trashinfo")).unwrap();
+        let origin = docs.canonicalize().unwrap().join("sub/a.txt");
+        assert!(info.starts_with("[Trash Info]\n"));
+        assert!(info.contains(&format!("Path={}\n", percent_encode(&origin))));
+
+        assert!(indexer.search(&SearchQuery::default()).unwrap().is_empty());
+        assert_eq!(indexer.recently_deleted(10).unwrap().len(), 2);
+
+        // A missing file leaves neither a trash entry nor a tombstone.
+        assert!(trash_file(&docs.join("gone.txt")).is_err());
+        assert!(!trash.join("info/gone.txt.trashinfo").exists());
+        assert_eq!(indexer.recently_deleted(10).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::hash::HashAlgorithm;
+
+// Reads for `IndexOptions::uncached_io`, which keep a cold scan of a large
+// tree from evicting everything else from the OS page cache.
+
+// Hashes `path` with O_DIRECT. `None` when the filesystem refuses direct IO
+// (tmpfs, many FUSE mounts), leaving it to `open` and `drop_cache`.
+#[cfg(target_os = "linux")]
+pub(crate) fn hash_direct(
+    path: &Path,
+    algo: HashAlgorithm,
+    buffer_size: usize,
+) -> Result<Option<String>> {
+    use std::io::Read;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    // Direct reads need the buffer address and length aligned to the
+    // device block size; 4 KiB covers common devices.
+    const ALIGN: usize = 4096;
+    let Ok(mut file) = File::options()
+        .read(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path)
+    else {
+        return Ok(None);
+    };
+    let size = buffer_size.div_ceil(ALIGN).max(1) * ALIGN;
+    let mut storage = vec![0u8; size + ALIGN];
+    let start = storage.as_ptr().align_offset(ALIGN);
+    let buf = &mut storage[start..start + size];
+    let mut hasher = algo.hasher();
+    loop {
+        match file.read(buf) {
+            Ok(0) => break,
+            Ok(read) => {
+                hasher.update(&buf[..read]);
+                crate::watchdog::tick();
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            // Refused part-way, e.g. after a short read left the offset
+            // unaligned; start over with ordinary reads.
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(Some(hasher.finish_hex()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn hash_direct(
+    _path: &Path,
+    _algo: HashAlgorithm,
+    _buffer_size: usize,
+) -> Result<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn open(path: &Path) -> io::Result<File> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::open(path)?;
+    // Only a hint; reads work either way.
+    unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) };
+    Ok(file)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn open(path: &Path) -> io::Result<File> {
+    File::open(path)
+}
+
+// Evicts the pages of a file that was read through the cache anyway.
+#[cfg(target_os = "linux")]
+pub(crate) fn drop_cache(file: &File) {
+    use std::os::unix::io::AsRawFd;
+
+    unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn drop_cache(_file: &File) {}
@@ -0,0 +1,141 @@
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::hash::{self, HashAlgorithm, HashIo};
+use crate::indexer::{subtree_bounds, FileIndexer};
+use crate::paths;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyReport {
+    // Files re-hashed because their size and mtime still match the index.
+    pub checked: usize,
+    pub ok: usize,
+    // Unchanged metadata but different contents: likely silent corruption.
+    pub corrupted: Vec<HashMismatch>,
+    // Size or mtime differ, so a different hash is expected; not re-hashed.
+    pub changed: Vec<String>,
+    pub missing: Vec<String>,
+    pub unreadable: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashMismatch {
+    pub path: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+enum Outcome {
+    Ok,
+    Corrupted(HashMismatch),
+    Changed(String),
+    Missing(String),
+    Unreadable(String),
+}
+
+struct Stored {
+    path: String,
+    raw_path: Option<Vec<u8>>,
+    size: i64,
+    modified: i64,
+    hash: Option<String>,
+    quick_hash: Option<String>,
+    algo: Option<HashAlgorithm>,
+}
+
+impl FileIndexer {
+    // Re-hashes indexed files under `root` (everything when `None`) that
+    // still have the recorded size and mtime. Only files hashed during
+    // indexing can be checked; quick hashes are compared as quick hashes.
+    pub fn verify(&self, root: Option<&Path>) -> Result<VerifyReport> {
+        let conn = self.reader()?;
+        let root = root.map(paths::key);
+        let (lower, upper) = match &root {
+            Some(root) => {
+                let (lower, upper) = subtree_bounds(root);
+                (Some(lower), Some(upper))
+            }
+            None => (None, None),
+        };
+        let mut stmt = conn.prepare(
+            "SELECT path,raw_path,size,modified,hash,quick_hash,hash_algo FROM files
+             WHERE kind = 'file' AND (hash IS NOT NULL OR quick_hash IS NOT NULL)
+               AND (?1 IS NULL OR path = ?1 OR (path >= ?2 AND path < ?3))",
+        )?;
+        let rows: Vec<Stored> = stmt
+            .query_map(params![root, lower, upper], |row| {
+                Ok(Stored {
+                    path: row.get(0)?,
+                    raw_path: row.get(1)?,
+                    size: row.get(2)?,
+                    modified: row.get(3)?,
+                    hash: row.get(4)?,
+                    quick_hash: row.get(5)?,
+                    algo: row.get(6)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let outcomes: Vec<Outcome> = rows.into_par_iter().map(check).collect();
+        let mut report = VerifyReport::default();
+        for outcome in outcomes {
+            match outcome {
+                Outcome::Ok => {
+                    report.checked += 1;
+                    report.ok += 1;
+                }
+                Outcome::Corrupted(mismatch) => {
+                    report.checked += 1;
+                    report.corrupted.push(mismatch);
+                }
+                Outcome::Changed(path) => report.changed.push(path),
+                Outcome::Missing(path) => report.missing.push(path),
+                Outcome::Unreadable(path) => report.unreadable.push(path),
+            }
+        }
+        report.corrupted.sort_by(|a, b| a.path.cmp(&b.path));
+        report.changed.sort();
+        report.missing.sort();
+        report.unreadable.sort();
+        Ok(report)
+    }
+}
+
+fn check(stored: Stored) -> Outcome {
+    let fs_path = paths::decode(&stored.path, stored.raw_path.as_deref());
+    let path = fs_path.as_path();
+    let metadata = match path.metadata() {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Outcome::Missing(stored.path),
+        Err(_) => return Outcome::Unreadable(stored.path),
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .map(|time| DateTime::<Utc>::from(time).timestamp());
+    if metadata.len() as i64 != stored.size || modified != Some(stored.modified) {
+        return Outcome::Changed(stored.path);
+    }
+
+    let algo = stored.algo.unwrap_or_default();
+    let (expected, actual) = match (stored.hash, stored.quick_hash) {
+        (Some(expected), _) => (expected, hash::hash_file(path, algo, HashIo::default())),
+        (None, Some(expected)) => (expected, hash::quick_hash_file(path, stored.size, algo)),
+        (None, None) => return Outcome::Ok,
+    };
+    match actual {
+        Ok(actual) if actual == expected => Outcome::Ok,
+        Ok(actual) => Outcome::Corrupted(HashMismatch {
+            path: stored.path,
+            expected,
+            actual,
+        }),
+        Err(_) => Outcome::Unreadable(stored.path),
+    }
+}
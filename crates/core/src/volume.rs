@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::FileIndexer;
+
+// A drive or share that indexed files live on, identified by something
+// that survives unplugging it: the file system UUID on Linux and macOS,
+// the volume serial number on Windows, or the source of a network mount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Volume {
+    pub(crate) id: String,
+    pub(crate) label: Option<String>,
+    pub(crate) mount: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeInfo {
+    // Stored in `FileRecord::volume` of the files on it.
+    pub id: String,
+    pub label: Option<String>,
+    // Where it was mounted when last indexed.
+    pub mount: Option<String>,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub file_count: i64,
+    pub bytes: i64,
+    // Mounted right now, at the recorded place.
+    pub connected: bool,
+}
+
+impl VolumeInfo {
+    // The label if it has one, for messages such as "on drive 'Backup'".
+    pub fn name(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.id)
+    }
+}
+
+// Volumes looked up by this process, by device (Unix) or path prefix
+// (Windows). Files are looked up one by one during scans, so each volume
+// is resolved once.
+static SEEN: OnceLock<Mutex<HashMap<String, Option<Volume>>>> = OnceLock::new();
+
+// The volume of a local file; `None` for file systems without a stable
+// identity, such as tmpfs, or platforms this is not implemented for.
+pub(crate) fn of(path: &Path, metadata: &Metadata) -> Option<Volume> {
+    let key = cache_key(path, metadata)?;
+    let seen = SEEN.get_or_init(Default::default);
+    if let Some(volume) = seen.lock().get(&key) {
+        return volume.clone();
+    }
+    let volume = resolve(path, metadata);
+    seen.lock().insert(key, volume.clone());
+    volume
+}
+
+fn is_connected(volume: &VolumeInfo) -> bool {
+    volume.mount.as_deref().is_some_and(|mount| {
+        let mount = Path::new(mount);
+        mount
+            .metadata()
+            .ok()
+            .and_then(|metadata| of(mount, &metadata))
+            .is_some_and(|found| found.id == volume.id)
+    })
+}
+
+fn seen() -> Vec<Volume> {
+    SEEN.get()
+        .map(|seen| seen.lock().values().flatten().cloned().collect())
+        .unwrap_or_default()
+}
+
+impl FileIndexer {
+    // Local volumes that indexed files live on, connected or not, with
+    // the number and total size of their files.
+    pub fn volumes(&self) -> Result<Vec<VolumeInfo>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT f.volume, v.label, v.mount, v.last_seen, COUNT(*), COALESCE(SUM(f.size), 0)
+             FROM files f LEFT JOIN volumes v ON v.id = f.volume
+             WHERE f.scheme IS NULL AND f.volume IS NOT NULL
+             GROUP BY f.volume
+             ORDER BY COALESCE(v.label, f.volume)",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(VolumeInfo {
+                id: row.get(0)?,
+                label: row.get(1)?,
+                mount: row.get(2)?,
+                last_seen: row
+                    .get::<_, Option<i64>>(3)?
+                    .and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0)),
+                file_count: row.get(4)?,
+                bytes: row.get(5)?,
+                connected: false,
+            })
+        })?;
+        let mut volumes: Vec<VolumeInfo> = rows.collect::<rusqlite::Result<_>>()?;
+        for volume in &mut volumes {
+            volume.connected = is_connected(volume);
+        }
+        Ok(volumes)
+    }
+
+    // Volumes recorded by earlier scans that are not mounted where they
+    // were. Unlike `volumes` this leaves the files alone, so `file_count`
+    // and `bytes` are 0; cheap enough to check before every search.
+    pub fn disconnected_volumes(&self) -> Result<Vec<VolumeInfo>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare("SELECT id, label, mount, last_seen FROM volumes")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(VolumeInfo {
+                id: row.get(0)?,
+                label: row.get(1)?,
+                mount: row.get(2)?,
+                last_seen: DateTime::<Utc>::from_timestamp(row.get(3)?, 0),
+                file_count: 0,
+                bytes: 0,
+                connected: false,
+            })
+        })?;
+        let volumes: Vec<VolumeInfo> = rows.collect::<rusqlite::Result<_>>()?;
+        Ok(volumes
+            .into_iter()
+            .filter(|volume| !is_connected(volume))
+            .collect())
+    }
+
+    // Remembers label and mount point of the volumes scans came across.
+    pub(crate) fn record_volumes(&self) -> Result<()> {
+        let conn = self.conn();
+        let now = Utc::now().timestamp();
+        for volume in seen() {
+            conn.prepare_cached(
+                "INSERT OR REPLACE INTO volumes(id, label, mount, last_seen) VALUES (?, ?, ?, ?)",
+            )?
+            .execute(params![volume.id, volume.label, volume.mount, now])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn cache_key(_path: &Path, metadata: &Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev().to_string())
+}
+
+// Drive letter or UNC share; volumes mounted into folders count as the
+// drive they are mounted on.
+#[cfg(windows)]
+fn cache_key(path: &Path, _metadata: &Metadata) -> Option<String> {
+    match path.components().next()? {
+        std::path::Component::Prefix(prefix) => {
+            Some(prefix.as_os_str().to_string_lossy().to_ascii_uppercase())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn cache_key(_path: &Path, _metadata: &Metadata) -> Option<String> {
+    None
+}
+
+// The mount whose device matches the file's in /proc/self/mountinfo,
+// identified by the UUID udev links to its source device.
+#[cfg(target_os = "linux")]
+fn resolve(_path: &Path, metadata: &Metadata) -> Option<Volume> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = metadata.dev();
+    let major = ((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0xfff);
+    let minor = ((dev >> 12) & 0xffff_ff00) | (dev & 0xff);
+    let device = format!("{}:{}", major, minor);
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    // Bind mounts share the device; prefer the mount of the whole file system.
+    let (mount, fstype, source) = mountinfo
+        .lines()
+        .filter_map(|line| {
+            let (fields, rest) = line.split_once(" - ")?;
+            let fields: Vec<&str> = fields.split(' ').collect();
+            if fields.get(2) != Some(&device.as_str()) {
+                return None;
+            }
+            let mut rest = rest.split(' ');
+            let (fstype, source) = (rest.next()?, rest.next()?);
+            let whole = fields.get(3) == Some(&"/");
+            Some((whole, unescape_mount(fields.get(4)?), fstype, source))
+        })
+        .max_by_key(|(whole, ..)| *whole)
+        .map(|(_, mount, fstype, source)| (mount, fstype, source))?;
+
+    let linked = |dir: &str| {
+        let target = std::fs::canonicalize(source).ok()?;
+        std::fs::read_dir(dir).ok()?.flatten().find_map(|link| {
+            (std::fs::canonicalize(link.path()).ok()? == target)
+                .then(|| unescape_udev(&link.file_name().to_string_lossy()))
+        })
+    };
+    let network = matches!(
+        fstype,
+        "nfs" | "nfs4" | "cifs" | "smb3" | "smbfs" | "fuse.sshfs"
+    );
+    let id = match linked("/dev/disk/by-uuid") {
+        Some(uuid) => uuid,
+        // The export stays the same wherever it is mounted.
+        None if network => source.to_string(),
+        None => return None,
+    };
+    Some(Volume {
+        id,
+        label: linked("/dev/disk/by-label"),
+        mount,
+    })
+}
+
+// `diskutil` knows the UUID and name of the volume `statfs` finds.
+#[cfg(target_os = "macos")]
+fn resolve(path: &Path, _metadata: &Metadata) -> Option<Volume> {
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::Command;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid out pointer.
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // SAFETY: the kernel NUL-terminates the mount point.
+    let mount = unsafe { CStr::from_ptr(stat.f_mntonname.as_ptr()) }
+        .to_string_lossy()
+        .into_owned();
+    let output = Command::new("diskutil")
+        .args(["info", "-plist", &mount])
+        .output()
+        .ok()?;
+    let plist = String::from_utf8_lossy(&output.stdout);
+    let value = |key: &str| {
+        let after = plist.split(&format!("<key>{}</key>", key)).nth(1)?;
+        let start = after.find("<string>")? + "<string>".len();
+        let end = after[start..].find("</string>")?;
+        Some(after[start..start + end].to_string()).filter(|v| !v.is_empty())
+    };
+    Some(Volume {
+        id: value("VolumeUUID")?,
+        label: value("VolumeName"),
+        mount,
+    })
+}
+
+// The serial number `vol` shows, and the drive label.
+#[cfg(windows)]
+fn resolve(path: &Path, _metadata: &Metadata) -> Option<Volume> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{GetVolumeInformationW, GetVolumePathNameW};
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut root = [0u16; 261];
+    // SAFETY: `wide` is NUL-terminated and `root` is as long as claimed.
+    if unsafe { GetVolumePathNameW(wide.as_ptr(), root.as_mut_ptr(), root.len() as u32) } == 0 {
+        return None;
+    }
+    let mut label = [0u16; 261];
+    let mut serial = 0u32;
+    // SAFETY: the buffers are as long as claimed; unused outputs are null.
+    let ok = unsafe {
+        GetVolumeInformationW(
+            root.as_ptr(),
+            label.as_mut_ptr(),
+            label.len() as u32,
+            &mut serial,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+    let text = |buf: &[u16]| {
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        String::from_utf16_lossy(&buf[..len])
+    };
+    Some(Volume {
+        id: format!("{:04X}-{:04X}", serial >> 16, serial & 0xffff),
+        label: Some(text(&label)).filter(|label| !label.is_empty()),
+        mount: text(&root),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn resolve(_path: &Path, _metadata: &Metadata) -> Option<Volume> {
+    None
+}
+
+// Mount points in mountinfo escape spaces and a few other bytes as `\ooo`.
+#[cfg(target_os = "linux")]
+fn unescape_mount(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|digits| {
+            let digits = std::str::from_utf8(digits).ok()?;
+            u8::from_str_radix(digits, 8).ok()
+        });
+        match (bytes[i], octal) {
+            (b'\\', Some(byte)) => {
+                out.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// udev link names escape spaces and slashes in labels as `\xNN`.
+#[cfg(target_os = "linux")]
+fn unescape_udev(text: &str) -> String {
+    let mut out = Vec::with_capacity(text.len());
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i..i + 4)
+            .filter(|escape| escape.starts_with(b"\\x"))
+            .and_then(|escape| std::str::from_utf8(&escape[2..]).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+        match hex {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
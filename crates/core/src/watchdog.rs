@@ -0,0 +1,50 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// When the entry a scan worker is reading last made progress, for the IO
+// timeout of `scan`. Long reads call `tick` between blocks, so a large file
+// on a slow share is not mistaken for one that stopped answering.
+pub(crate) struct Progress {
+    started: Instant,
+    // Milliseconds after `started`.
+    last: AtomicU64,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Progress>>> = const { RefCell::new(None) };
+}
+
+impl Progress {
+    // Starts tracking the entry the calling thread is about to read.
+    pub(crate) fn enter() -> Arc<Self> {
+        let progress = Arc::new(Self {
+            started: Instant::now(),
+            last: AtomicU64::new(0),
+        });
+        CURRENT.with(|current| *current.borrow_mut() = Some(Arc::clone(&progress)));
+        progress
+    }
+
+    pub(crate) fn leave() {
+        CURRENT.with(|current| current.borrow_mut().take());
+    }
+
+    pub(crate) fn idle(&self) -> Duration {
+        let last = self.started + Duration::from_millis(self.last.load(Ordering::Relaxed));
+        last.elapsed()
+    }
+}
+
+// Marks progress on the entry of the calling thread, if it is tracked.
+pub(crate) fn tick() {
+    CURRENT.with(|current| {
+        if let Some(progress) = &*current.borrow() {
+            let millis = progress.started.elapsed().as_millis();
+            progress
+                .last
+                .store(u64::try_from(millis).unwrap_or(u64::MAX), Ordering::Relaxed);
+        }
+    });
+}
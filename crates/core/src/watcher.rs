@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{
+    Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher as _,
+    WatcherKind,
+};
+
+use crate::indexer::{FileIndexer, IndexOptions};
+
+// Quiet time after the last event before the pending changes are applied.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+// An endless stream of events is still applied this many debounce periods
+// after the first one.
+const MAX_DELAY_FACTOR: u32 = 10;
+// How often the polling fallback walks the roots for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    Upserted(PathBuf),
+    Removed(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+    // The backend lost events (its queue overflowed), so the directory was
+    // indexed and pruned again.
+    Rescanned(PathBuf),
+    Error(String),
+}
+
+pub struct Watcher {
+    inner: Option<Box<dyn notify::Watcher + Send>>,
+    kind: WatcherKind,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    pub fn start<P, F>(
+        db_path: P,
+        roots: &[PathBuf],
+        opts: IndexOptions,
+        callback: F,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(WatchEvent) + Send + 'static,
+    {
+        Self::start_debounced(db_path, roots, opts, DEFAULT_DEBOUNCE, callback)
+    }
+
+    // Watches with the platform's own mechanism (inotify, FSEvents,
+    // ReadDirectoryChangesW) and falls back to polling when it cannot cover
+    // the roots, e.g. past the inotify watch limit. Events for a path are
+    // coalesced until none arrived for `debounce`, so a file written in
+    // many chunks or a burst of temporary files is indexed once.
+    pub fn start_debounced<P, F>(
+        db_path: P,
+        roots: &[PathBuf],
+        opts: IndexOptions,
+        debounce: Duration,
+        callback: F,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(WatchEvent) + Send + 'static,
+    {
+        // Open once up front so a bad database path fails here, not in the worker.
+        let db_path = db_path.as_ref().to_path_buf();
+        FileIndexer::new(&db_path)?;
+
+        let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+        let (inner, kind) = native_or_polling(tx, roots)?;
+        let roots = roots.to_vec();
+
+        let worker = thread::spawn(move || {
+            let mut callback = callback;
+            let indexer = match FileIndexer::new(&db_path) {
+                Ok(indexer) => indexer,
+                Err(err) => {
+                    callback(WatchEvent::Error(err.to_string()));
+                    return;
+                }
+            };
+            // Most deletions reported are of files the index never had,
+            // e.g. editors' temporary files.
+            indexer.enable_path_filter();
+            let mut batch = Batch::default();
+            loop {
+                let received = match batch.deadline(debounce) {
+                    Some(deadline) => {
+                        rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(Ok(event)) => batch.add(event, &roots),
+                    Ok(Err(err)) => {
+                        callback(WatchEvent::Error(err.to_string()));
+                        // Whatever the backend dropped is recovered by a rescan.
+                        let dirs = if err.paths.is_empty() {
+                            &roots
+                        } else {
+                            &err.paths
+                        };
+                        batch.rescan(dirs);
+                    }
+                    Err(RecvTimeoutError::Timeout) => batch.apply(&indexer, &opts, &mut callback),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        batch.apply(&indexer, &opts, &mut callback);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            inner: Some(inner),
+            kind,
+            worker: Some(worker),
+        })
+    }
+
+    // Name of the mechanism in use, for status lines and logs.
+    pub fn backend(&self) -> &'static str {
+        match self.kind {
+            WatcherKind::Inotify => "inotify",
+            WatcherKind::Fsevent => "FSEvents",
+            WatcherKind::Kqueue => "kqueue",
+            WatcherKind::ReadDirectoryChangesWatcher => "ReadDirectoryChangesW",
+            WatcherKind::PollWatcher => "polling",
+            _ => "other",
+        }
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        // Dropping the notify watcher closes the event channel, which ends the worker.
+        self.inner.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn native_or_polling(
+    tx: mpsc::Sender<notify::Result<Event>>,
+    roots: &[PathBuf],
+) -> Result<(Box<dyn notify::Watcher + Send>, WatcherKind)> {
+    let mut native = notify::recommended_watcher(tx.clone())?;
+    let failed = roots
+        .iter()
+        .find_map(|root| native.watch(root, RecursiveMode::Recursive).err());
+    match failed {
+        None => Ok((Box::new(native), RecommendedWatcher::kind())),
+        Some(err) if matches!(err.kind, notify::ErrorKind::MaxFilesWatch) => {
+            drop(native);
+            let config = Config::default().with_poll_interval(POLL_INTERVAL);
+            let mut poll = PollWatcher::new(tx, config)?;
+            for root in roots {
+                poll.watch(root, RecursiveMode::Recursive)?;
+            }
+            Ok((Box::new(poll), WatcherKind::PollWatcher))
+        }
+        Some(err) => Err(err.into()),
+    }
+}
+
+// Changes collected since the last flush. Only the latest state of a path
+// matters, so paths are deduplicated and checked on disk when applied.
+#[derive(Default)]
+struct Batch {
+    first: Option<Instant>,
+    last: Option<Instant>,
+    paths: BTreeSet<PathBuf>,
+    renames: BTreeMap<PathBuf, PathBuf>,
+    rescans: BTreeSet<PathBuf>,
+}
+
+impl Batch {
+    fn deadline(&self, debounce: Duration) -> Option<Instant> {
+        let (first, last) = (self.first?, self.last?);
+        Some((last + debounce).min(first + debounce * MAX_DELAY_FACTOR))
+    }
+
+    fn touch(&mut self) {
+        let now = Instant::now();
+        self.first.get_or_insert(now);
+        self.last = Some(now);
+    }
+
+    fn add(&mut self, event: Event, roots: &[PathBuf]) {
+        if event.need_rescan() {
+            let dirs = if event.paths.is_empty() {
+                roots
+            } else {
+                &event.paths
+            };
+            self.rescan(dirs);
+            return;
+        }
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let (from, to) = (event.paths[0].clone(), event.paths[1].clone());
+                self.paths.remove(&from);
+                self.renames.insert(from, to);
+            }
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
+                self.paths.extend(event.paths);
+            }
+            _ => return,
+        }
+        self.touch();
+    }
+
+    fn rescan(&mut self, dirs: &[PathBuf]) {
+        for dir in dirs {
+            // A file path means its directory lost events.
+            let dir = if dir.is_dir() {
+                dir.clone()
+            } else {
+                dir.parent().unwrap_or(dir).to_path_buf()
+            };
+            self.rescans.insert(dir);
+        }
+        self.touch();
+    }
+
+    fn apply<F>(&mut self, indexer: &FileIndexer, opts: &IndexOptions, callback: &mut F)
+    where
+        F: FnMut(WatchEvent),
+    {
+        let batch = std::mem::take(self);
+        let mut report = |result: Result<Option<WatchEvent>>| match result {
+            Ok(Some(event)) => callback(event),
+            Ok(None) => {}
+            Err(err) => callback(WatchEvent::Error(err.to_string())),
+        };
+
+        // Nested directories are covered by the outermost one.
+        let mut rescanned: Vec<PathBuf> = Vec::new();
+        for dir in batch.rescans {
+            if rescanned.iter().any(|done| dir.starts_with(done)) {
+                continue;
+            }
+            report(rescan(indexer, opts, &dir).map(|()| Some(WatchEvent::Rescanned(dir.clone()))));
+            rescanned.push(dir);
+        }
+        let covered = |path: &Path| rescanned.iter().any(|dir| path.starts_with(dir));
+
+        let mut moved = BTreeSet::new();
+        for (from, to) in batch.renames {
+            moved.insert(to.clone());
+            if covered(&from) && covered(&to) {
+                continue;
+            }
+            report(indexer.remove(&from).and_then(|_| {
+                if !to.exists() {
+                    // Moved again, or out of the watched roots; a later event
+                    // or the remove below covers the new place.
+                    return Ok(None);
+                }
+                refresh(indexer, opts, &to)?;
+                Ok(Some(WatchEvent::Renamed { from, to }))
+            }));
+        }
+
+        for path in batch.paths {
+            // The backend reports the new name of a rename on its own too.
+            if covered(&path) || moved.contains(&path) {
+                continue;
+            }
+            // Decided by what is on disk now, not by the last event seen, so
+            // a file created and deleted within one batch leaves no trace.
+            if path.exists() {
+                report(refresh(indexer, opts, &path).map(|()| Some(WatchEvent::Upserted(path))));
+            } else {
+                report(
+                    indexer
+                        .remove_deleted(&path)
+                        .map(|removed| (removed > 0).then_some(WatchEvent::Removed(path))),
+                );
+            }
+        }
+    }
+}
+
+fn refresh(indexer: &FileIndexer, opts: &IndexOptions, path: &Path) -> Result<()> {
+    if path.is_dir() {
+        indexer.index_subtree(path, opts, None)?;
+    } else if path.is_file() {
+        indexer.index_file(path, opts)?;
+    }
+    Ok(())
+}
+
+// Indexes the directory again and drops entries that vanished while events
+// were being lost.
+fn rescan(indexer: &FileIndexer, opts: &IndexOptions, dir: &Path) -> Result<()> {
+    if dir.is_dir() {
+        indexer.index_subtree(dir, opts, None)?;
+    }
+    indexer.prune(Some(dir))?;
+    Ok(())
+}
@@ -0,0 +1,358 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::source::{CommandReader, FolderTags, ScanSource, SourceEntry};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getcontentlength/>
+    <d:getlastmodified/>
+    <d:getetag/>
+  </d:prop>
+</d:propfind>"#;
+
+// Characters escaped when a stored path is turned back into a URL.
+const PATH_ESCAPES: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+// A folder on a WebDAV server such as Nextcloud or ownCloud, e.g.
+// `https://cloud.example.com/remote.php/dav/files/me/Photos/`. Requests go
+// through `curl`, which brings TLS, proxies and `~/.netrc` along. Servers
+// whose folder ETags change with their contents (Nextcloud, ownCloud) are
+// synced incrementally: unchanged folders are not listed again.
+#[derive(Debug, Clone, Default)]
+pub struct WebDavSource {
+    pub url: String,
+    // Without a user, credentials come from `~/.netrc` if at all.
+    pub user: Option<String>,
+    pub password: Option<String>,
+}
+
+// One `<d:response>` of a PROPFIND answer.
+#[derive(Default)]
+struct Resource {
+    href: String,
+    collection: bool,
+    size: Option<i64>,
+    modified: Option<String>,
+    etag: Option<String>,
+}
+
+impl WebDavSource {
+    // From an `http://` or `https://` URL of a folder.
+    pub fn parse(url: &str) -> Result<Self> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(anyhow!("not an http(s):// URL: {}", url));
+        }
+        let source = Self {
+            url: url.to_string(),
+            ..Self::default()
+        };
+        if source.authority().is_empty() {
+            return Err(anyhow!("no host in {}", url));
+        }
+        Ok(source)
+    }
+
+    // `https://host:port`, without a trailing slash.
+    fn origin(&self) -> &str {
+        let start = self.url.find("://").map_or(0, |i| i + 3);
+        match self.url[start..].find('/') {
+            Some(slash) => &self.url[..start + slash],
+            None => &self.url,
+        }
+    }
+
+    fn authority(&self) -> &str {
+        let origin = self.origin();
+        let authority = origin.find("://").map_or(origin, |i| &origin[i + 3..]);
+        // Credentials in the URL are not part of the volume name.
+        authority.rsplit('@').next().unwrap_or(authority)
+    }
+
+    // The path part of the URL, still percent-encoded, ending in `/`.
+    fn root_href(&self) -> String {
+        let path = &self.url[self.origin().len()..];
+        format!("{}/", path.trim_end_matches('/'))
+    }
+
+    // Where an href is stored and searched: origin plus decoded path.
+    fn entry_path(&self, href: &str) -> String {
+        format!(
+            "{}{}",
+            self.origin(),
+            percent_decode_str(href).decode_utf8_lossy()
+        )
+    }
+
+    // Credentials go to curl as a config on stdin, which keeps the password
+    // out of the process list; see `credentials`.
+    fn curl(&self) -> Command {
+        let mut cmd = Command::new("curl");
+        cmd.args(["--silent", "--show-error", "--fail", "--netrc-optional"]);
+        if self.user.is_some() {
+            cmd.args(["--config", "-"]);
+        }
+        cmd
+    }
+
+    fn credentials(&self) -> Option<String> {
+        let user = self.user.as_ref()?;
+        let credentials = format!("{}:{}", user, self.password.as_deref().unwrap_or(""));
+        Some(format!("user = \"{}\"\n", config_escape(&credentials)))
+    }
+
+    fn propfind(&self, href: &str) -> Result<Vec<Resource>> {
+        let mut cmd = self.curl();
+        cmd.args(["--request", "PROPFIND"])
+            .args(["--header", "Depth: 1"])
+            .args(["--header", "Content-Type: application/xml; charset=utf-8"])
+            .args(["--data-binary", PROPFIND_BODY])
+            .arg(format!("{}{}", self.origin(), href))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let credentials = self.credentials();
+        cmd.stdin(match credentials {
+            Some(_) => Stdio::piped(),
+            None => Stdio::null(),
+        });
+        let mut child = cmd
+            .spawn()
+            .context("running curl, which talks to WebDAV servers")?;
+        if let (Some(credentials), Some(mut stdin)) = (credentials, child.stdin.take()) {
+            stdin.write_all(credentials.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "listing {}: {}",
+                self.entry_path(href),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        parse_multistatus(&output.stdout)
+            .with_context(|| format!("reading the listing of {}", self.entry_path(href)))
+    }
+}
+
+impl ScanSource for WebDavSource {
+    fn scheme(&self) -> &str {
+        "webdav"
+    }
+
+    fn volume(&self) -> &str {
+        self.authority()
+    }
+
+    fn root(&self) -> String {
+        self.entry_path(&self.root_href())
+    }
+
+    fn entries<'a>(
+        &'a self,
+        known: &'a FolderTags,
+    ) -> Result<Box<dyn Iterator<Item = Result<SourceEntry>> + 'a>> {
+        Ok(Box::new(Listing {
+            source: self,
+            known,
+            folders: vec![self.root_href()],
+            ready: VecDeque::new(),
+            failed: false,
+        }))
+    }
+
+    fn open(&self, entry: &SourceEntry) -> Result<Option<Box<dyn Read + '_>>> {
+        let Some(path) = entry.path.strip_prefix(self.origin()) else {
+            return Ok(None);
+        };
+        let mut cmd = self.curl();
+        cmd.arg(format!(
+            "{}{}",
+            self.origin(),
+            utf8_percent_encode(path, PATH_ESCAPES)
+        ));
+        let credentials = self.credentials();
+        Ok(Some(Box::new(CommandReader::spawn(
+            cmd,
+            credentials.as_deref(),
+        )?)))
+    }
+}
+
+// Lists one folder whenever the entries of the previous one are used up.
+struct Listing<'a> {
+    source: &'a WebDavSource,
+    known: &'a FolderTags,
+    // Hrefs of folders still to list.
+    folders: Vec<String>,
+    ready: VecDeque<SourceEntry>,
+    failed: bool,
+}
+
+impl Listing<'_> {
+    fn list(&mut self, folder: &str) -> Result<()> {
+        let is_root = folder == self.source.root_href();
+        let folder_path = decoded_dir(folder);
+        for resource in self.source.propfind(folder)? {
+            let href = href_path(&resource.href);
+            let is_self = decoded_dir(href) == folder_path;
+            if is_self && !is_root {
+                // Reported by the parent already.
+                continue;
+            }
+            let path = self.source.entry_path(href);
+            let etag = resource
+                .etag
+                .as_deref()
+                .map(|tag| tag.trim_start_matches("W/").trim_matches('"').to_string());
+            if resource.collection {
+                let dir = format!("{}/", path.trim_end_matches('/'));
+                let unchanged = etag.is_some() && self.known.get(&dir) == etag.as_ref();
+                self.ready.push_back(SourceEntry {
+                    path: dir,
+                    size: 0,
+                    modified: DateTime::default(),
+                    etag,
+                    hash: None,
+                    hash_algo: None,
+                    folder: true,
+                });
+                if unchanged && is_self {
+                    // Nothing below the root changed.
+                    return Ok(());
+                }
+                if !unchanged && !is_self {
+                    self.folders
+                        .push(format!("{}/", href.trim_end_matches('/')));
+                }
+                continue;
+            }
+            let modified = match &resource.modified {
+                Some(text) => DateTime::parse_from_rfc2822(text)
+                    .with_context(|| format!("bad modification time of {}", path))?
+                    .with_timezone(&Utc),
+                None => DateTime::default(),
+            };
+            self.ready.push_back(SourceEntry {
+                path,
+                size: resource.size.unwrap_or(0),
+                modified,
+                etag,
+                hash: None,
+                hash_algo: None,
+                folder: false,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Listing<'_> {
+    type Item = Result<SourceEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.ready.pop_front() {
+                return Some(Ok(entry));
+            }
+            if self.failed {
+                return None;
+            }
+            let folder = self.folders.pop()?;
+            if let Err(err) = self.list(&folder) {
+                self.failed = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+fn parse_multistatus(xml: &[u8]) -> Result<Vec<Resource>> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    let mut resources = Vec::new();
+    let mut current: Option<Resource> = None;
+    let mut element = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(start) => {
+                let name = start.local_name().as_ref().to_vec();
+                match name.as_slice() {
+                    b"response" => current = Some(Resource::default()),
+                    b"collection" => {
+                        if let Some(resource) = &mut current {
+                            resource.collection = true;
+                        }
+                    }
+                    _ => {}
+                }
+                element = name;
+            }
+            Event::Empty(empty) if empty.local_name().as_ref() == b"collection" => {
+                if let Some(resource) = &mut current {
+                    resource.collection = true;
+                }
+            }
+            Event::Text(text) => {
+                let (Some(resource), text) = (&mut current, text.unescape()?) else {
+                    continue;
+                };
+                let text = text.trim();
+                match element.as_slice() {
+                    b"href" => resource.href.push_str(text),
+                    b"getcontentlength" => resource.size = text.parse().ok(),
+                    b"getlastmodified" => resource.modified = Some(text.to_string()),
+                    b"getetag" => resource.etag = Some(text.to_string()),
+                    _ => {}
+                }
+            }
+            Event::End(end) => {
+                if end.local_name().as_ref() == b"response" {
+                    resources.extend(current.take());
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(resources)
+}
+
+// Servers may answer with full URLs instead of absolute paths.
+fn href_path(href: &str) -> &str {
+    match href.find("://") {
+        Some(i) => href[i + 3..]
+            .find('/')
+            .map_or("/", |slash| &href[i + 3 + slash..]),
+        None => href,
+    }
+}
+
+fn decoded_dir(href: &str) -> String {
+    let path = percent_decode_str(href).decode_utf8_lossy();
+    format!("{}/", path.trim_end_matches('/'))
+}
+
+fn config_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
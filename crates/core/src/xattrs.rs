@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use anyhow::Result;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::FileIndexer;
+use crate::paths;
+
+// Larger values (e.g. macOS resource forks) are recorded by key only.
+const MAX_VALUE_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Xattr {
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+}
+
+#[cfg(unix)]
+pub(crate) fn read_xattrs(path: &Path) -> Result<Vec<Xattr>> {
+    let mut out = Vec::new();
+    for key in xattr::list_deref(path)? {
+        let value = xattr::get_deref(path, &key)?.filter(|v| v.len() <= MAX_VALUE_BYTES);
+        out.push(Xattr {
+            key: key.to_string_lossy().to_string(),
+            value,
+        });
+    }
+    Ok(out)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn read_xattrs(_path: &Path) -> Result<Vec<Xattr>> {
+    Ok(Vec::new())
+}
+
+impl FileIndexer {
+    pub fn xattrs<P: AsRef<Path>>(&self, path: P) -> Result<Vec<Xattr>> {
+        let conn = self.reader()?;
+        let path = paths::key(path.as_ref());
+        let mut stmt = conn.prepare("SELECT key,value FROM xattrs WHERE path = ? ORDER BY key")?;
+        let rows = stmt.query_map(params![path], |row| {
+            Ok(Xattr {
+                key: row.get(0)?,
+                value: row.get(1)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub(crate) fn write_xattrs(&self, path: &str, xattrs: &[Xattr]) -> Result<()> {
+        let conn = self.conn();
+        conn.prepare_cached("DELETE FROM xattrs WHERE path = ?")?
+            .execute(params![path])?;
+        let mut stmt = conn.prepare_cached("INSERT INTO xattrs(path,key,value) VALUES (?,?,?)")?;
+        for xattr in xattrs {
+            stmt.execute(params![path, xattr.key, xattr.value])?;
+        }
+        Ok(())
+    }
+}
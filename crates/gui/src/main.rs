@@ -1,9 +1,36 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{Local, NaiveDate};
 use eframe::{egui, App as EguiApp, Frame, NativeOptions};
-use fsindex_core::{FileIndexer, FileRecord, SearchQuery, SortKey};
+use fsindex_core::{
+    ActivityGroup, ActivityKind, CancellationToken, DuplicateGroup, DuplicateSort, FileIndexer,
+    FileRecord, IndexOptions, IndexReport, IndexStats, MediaInfo, SearchQuery, SortKey, MAX_RATING,
+};
+
+const SORT_LABELS: [&str; 9] = [
+    "Name",
+    "Size",
+    "Modified",
+    "Relevance",
+    "Rating",
+    "Ext",
+    "Path",
+    "Added",
+    "On disk",
+];
+
+const DUPLICATE_SORTS: [(&str, DuplicateSort); 3] = [
+    ("Most copies", DuplicateSort::Count),
+    ("Most wasted", DuplicateSort::Wasted),
+    ("Largest files", DuplicateSort::Size),
+];
+
+// Pause in typing after which suggestions are looked up.
+const SUGGEST_DELAY: Duration = Duration::from_millis(250);
 
 fn main() -> Result<()> {
     let options = NativeOptions::default();
@@ -18,42 +45,108 @@ fn main() -> Result<()> {
 
 struct FsIndexApp {
     db_path: String,
+    passphrase: String,
+    read_only: bool,
     index_dir: Option<PathBuf>,
     index_hash: bool,
+    index_exclude: String,
+    index_gitignore: bool,
+    index_follow_links: bool,
+    index_skip_hidden: bool,
+    index_content: bool,
+    index_media: bool,
+    index_archives: bool,
+    query_text: String,
     name_like: String,
+    fuzzy: bool,
+    match_case: bool,
+    favorites_only: bool,
     ext: String,
+    mime: String,
+    content: String,
     min_size: String,
     max_size: String,
     from: String,
     to: String,
     sort_idx: usize,
     desc: bool,
+    // Secondary sort; `None` leaves ties in path order.
+    then_sort_idx: Option<usize>,
+    then_desc: bool,
     limit: String,
     offset: String,
     results: Vec<FileRecord>,
+    // Query that produced `results`, kept to fetch the page after them.
+    results_query: Option<SearchQuery>,
+    // Matches of `results_query` across all pages.
+    results_total: Option<i64>,
+    duplicates: Vec<DuplicateGroup>,
+    duplicate_sort: DuplicateSort,
+    stats: Option<IndexStats>,
     status: String,
     tab: usize,
+    activity: Vec<ActivityGroup>,
+    activity_days: String,
+    activity_modified: bool,
+    indexer: Option<(String, Arc<FileIndexer>)>,
+    indexing: Option<(CancellationToken, JoinHandle<Result<IndexReport>>)>,
+    suggest_input: (String, String),
+    // When to look up suggestions for the latest input.
+    suggest_due: Option<Instant>,
+    name_suggestions: Vec<String>,
+    ext_suggestions: Vec<String>,
 }
 
 impl Default for FsIndexApp {
     fn default() -> Self {
         Self {
             db_path: "index.db".into(),
+            passphrase: String::new(),
+            read_only: false,
             index_dir: None,
             index_hash: true,
+            index_exclude: String::new(),
+            index_gitignore: false,
+            index_follow_links: false,
+            index_skip_hidden: false,
+            index_content: false,
+            index_media: false,
+            index_archives: false,
+            query_text: String::new(),
             name_like: String::new(),
+            fuzzy: false,
+            match_case: false,
+            favorites_only: false,
             ext: String::new(),
+            mime: String::new(),
+            content: String::new(),
             min_size: String::new(),
             max_size: String::new(),
             from: String::new(),
             to: String::new(),
             sort_idx: 0,
             desc: false,
+            then_sort_idx: None,
+            then_desc: false,
             limit: "50".into(),
             offset: "0".into(),
             results: Vec::new(),
+            results_query: None,
+            results_total: None,
+            duplicates: Vec::new(),
+            duplicate_sort: DuplicateSort::Wasted,
+            stats: None,
             status: String::new(),
             tab: 0,
+            activity: Vec::new(),
+            activity_days: "7".into(),
+            activity_modified: false,
+            indexer: None,
+            indexing: None,
+            suggest_input: (String::new(), String::new()),
+            suggest_due: None,
+            name_suggestions: Vec::new(),
+            ext_suggestions: Vec::new(),
         }
     }
 }
@@ -69,21 +162,74 @@ impl EguiApp for FsIndexApp {
                         self.db_path = path.to_string_lossy().to_string();
                     }
                 }
+                if ui.checkbox(&mut self.read_only, "Read-only").changed() {
+                    self.indexer = None;
+                }
+                #[cfg(feature = "encryption")]
+                {
+                    ui.label("Passphrase");
+                    let field = egui::TextEdit::singleline(&mut self.passphrase)
+                        .password(true)
+                        .desired_width(100.0)
+                        .hint_text("unencrypted");
+                    if ui.add(field).changed() {
+                        self.indexer = None;
+                    }
+                }
                 if ui.button("Pick Folder").clicked() {
                     self.index_dir = rfd::FileDialog::new().pick_folder();
                 }
                 ui.checkbox(&mut self.index_hash, "Hash");
-                if ui.button("Index").clicked() {
-                    match FileIndexer::new(&self.db_path).and_then(|idx| {
-                        idx.index_dir(
-                            self.index_dir.clone().unwrap_or_else(|| PathBuf::from(".")),
-                            self.index_hash,
-                        )
-                    }) {
-                        Ok(count) => self.status = format!("Indexed {} files", count),
+                ui.label("Exclude");
+                ui.text_edit_singleline(&mut self.index_exclude);
+                ui.checkbox(&mut self.index_gitignore, ".gitignore");
+                ui.checkbox(&mut self.index_follow_links, "Follow links");
+                ui.checkbox(&mut self.index_skip_hidden, "Skip hidden");
+                ui.checkbox(&mut self.index_content, "Extract text");
+                ui.checkbox(&mut self.index_media, "Media info");
+                ui.checkbox(&mut self.index_archives, "Archives");
+                if self.indexing.is_some() {
+                    if ui.button("Cancel").clicked() {
+                        if let Some((token, _)) = &self.indexing {
+                            token.cancel();
+                        }
+                        self.status = "Cancelling...".into();
+                    }
+                } else if ui
+                    .add_enabled(!self.read_only, egui::Button::new("Index"))
+                    .clicked()
+                {
+                    let root = self.index_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+                    let token = CancellationToken::new();
+                    let opts = IndexOptions {
+                        hash: self.index_hash,
+                        exclude: self
+                            .index_exclude
+                            .split(',')
+                            .map(|glob| glob.trim().to_string())
+                            .filter(|glob| !glob.is_empty())
+                            .collect(),
+                        respect_gitignore: self.index_gitignore,
+                        follow_symlinks: self.index_follow_links,
+                        skip_hidden: Some(self.index_skip_hidden),
+                        extract_content: self.index_content,
+                        media_metadata: self.index_media,
+                        index_archives: self.index_archives,
+                        cancel: token.clone(),
+                        ..IndexOptions::default()
+                    };
+                    // Index on a worker so the UI stays responsive, can
+                    // cancel and keeps searching while it runs.
+                    match self.shared_indexer() {
+                        Ok(indexer) => {
+                            let handle = thread::spawn(move || indexer.index_dir(root, &opts));
+                            self.indexing = Some((token, handle));
+                            self.status = "Indexing...".into();
+                        }
                         Err(err) => self.status = err.to_string(),
                     }
                 }
+                self.poll_indexing(ctx);
                 ui.label(&self.status);
             });
         });
@@ -95,20 +241,70 @@ impl EguiApp for FsIndexApp {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut self.tab, 0, "Search");
-                ui.selectable_value(&mut self.tab, 1, "Recent");
+                ui.selectable_value(&mut self.tab, 1, "Activity");
                 ui.selectable_value(&mut self.tab, 2, "Duplicates");
+                ui.selectable_value(&mut self.tab, 3, "Overview");
             });
 
             match self.tab {
                 0 => self.ui_search(ui),
                 1 => self.ui_recent(ui),
-                _ => self.ui_duplicates(ui),
+                2 => self.ui_duplicates(ui),
+                _ => self.ui_overview(ui),
             }
         });
     }
 }
 
+// An empty passphrase opens an unencrypted database.
+#[cfg(feature = "encryption")]
+fn open_db(path: &str, passphrase: &str, read_only: bool) -> Result<FileIndexer> {
+    if read_only {
+        FileIndexer::open_read_only(path)
+    } else if passphrase.is_empty() {
+        FileIndexer::new(path)
+    } else {
+        FileIndexer::new_encrypted(path, passphrase)
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open_db(path: &str, _passphrase: &str, read_only: bool) -> Result<FileIndexer> {
+    if read_only {
+        FileIndexer::open_read_only(path)
+    } else {
+        FileIndexer::new(path)
+    }
+}
+
 impl FsIndexApp {
+    fn poll_indexing(&mut self, ctx: &egui::Context) {
+        let finished = self
+            .indexing
+            .as_ref()
+            .is_some_and(|(_, handle)| handle.is_finished());
+        if !finished {
+            if self.indexing.is_some() {
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+            return;
+        }
+        if let Some((token, handle)) = self.indexing.take() {
+            self.status = match handle.join() {
+                Ok(Ok(report)) if token.is_cancelled() => {
+                    format!("Cancelled after {} files", report.indexed)
+                }
+                Ok(Ok(report)) if report.skipped > 0 => format!(
+                    "Indexed {} files, skipped {} unreadable",
+                    report.indexed, report.skipped
+                ),
+                Ok(Ok(report)) => format!("Indexed {} files", report.indexed),
+                Ok(Err(err)) => err.to_string(),
+                Err(_) => "Indexing thread panicked".into(),
+            };
+        }
+    }
+
     fn parse_num(text: &str) -> Option<i64> {
         text.trim().parse::<i64>().ok()
     }
@@ -117,22 +313,178 @@ impl FsIndexApp {
         NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()
     }
 
-    fn current_indexer(&self) -> Option<FileIndexer> {
-        FileIndexer::new(&self.db_path).ok()
+    fn shared_indexer(&mut self) -> Result<Arc<FileIndexer>> {
+        let stale = match &self.indexer {
+            Some((path, _)) => *path != self.db_path,
+            None => true,
+        };
+        if stale {
+            let indexer = open_db(&self.db_path, &self.passphrase, self.read_only)?;
+            self.indexer = Some((self.db_path.clone(), Arc::new(indexer)));
+        }
+        Ok(Arc::clone(
+            &self.indexer.as_ref().expect("indexer was just opened").1,
+        ))
+    }
+
+    fn open_indexer(&mut self) -> Result<&FileIndexer> {
+        self.shared_indexer()?;
+        Ok(&self.indexer.as_ref().expect("indexer was just opened").1)
+    }
+
+    fn current_indexer(&mut self) -> Option<&FileIndexer> {
+        self.open_indexer().ok()
+    }
+
+    fn refresh_suggestions(&mut self) {
+        let name = self.name_like.trim().to_string();
+        // Only the extension being typed, after any earlier ones.
+        let ext = self
+            .ext
+            .rsplit(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let (names, exts) = match self.current_indexer() {
+            Some(indexer) => (
+                indexer.suggest_name_tokens(&name, 5).unwrap_or_default(),
+                if ext.is_empty() {
+                    Vec::new()
+                } else {
+                    indexer.suggest_extensions(&ext, 5).unwrap_or_default()
+                },
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        self.name_suggestions = names.into_iter().map(|s| s.value).collect();
+        self.ext_suggestions = exts.into_iter().map(|s| s.value).collect();
+    }
+
+    // The query box when filled in, otherwise the individual fields. Sorting
+    // and paging always come from the controls unless the query sorts.
+    fn search_query(&self) -> Result<SearchQuery> {
+        let sort_key = |idx: usize| match idx {
+            0 => SortKey::Name,
+            1 => SortKey::Size,
+            2 => SortKey::Modified,
+            3 => SortKey::Relevance,
+            4 => SortKey::Rating,
+            5 => SortKey::Ext,
+            6 => SortKey::Path,
+            7 => SortKey::AddedAt,
+            _ => SortKey::Allocated,
+        };
+        let mut query = if self.query_text.trim().is_empty() {
+            let mut query = SearchQuery::default();
+            if !self.name_like.trim().is_empty() {
+                query.name_like = Some(self.name_like.clone());
+            }
+            query.ext_in = self.ext.split(',').map(|e| e.trim().to_string()).collect();
+            if !self.mime.trim().is_empty() {
+                query.mime = Some(self.mime.clone());
+            }
+            if !self.content.trim().is_empty() {
+                query.content = Some(self.content.clone());
+            }
+            query.min_size = Self::parse_num(&self.min_size);
+            query.max_size = Self::parse_num(&self.max_size);
+            query.date_from = Self::parse_date(&self.from);
+            query.date_to = Self::parse_date(&self.to);
+            query.fuzzy = self.fuzzy;
+            query.case_sensitive = Some(self.match_case);
+            query.min_rating = self.favorites_only.then_some(1);
+            query
+        } else {
+            self.query_text.parse::<SearchQuery>()?
+        };
+        if query.sort_key.is_none() {
+            query.sort_key = Some(sort_key(self.sort_idx));
+            query.desc = self.desc;
+            query.then_sort = self
+                .then_sort_idx
+                .filter(|_| self.sort_idx != 3)
+                .map(sort_key);
+            query.then_desc = self.then_desc;
+        }
+        query.limit = Self::parse_num(&self.limit);
+        query.offset = Self::parse_num(&self.offset);
+        Ok(query)
+    }
+
+    fn run_search(&mut self, query: SearchQuery) {
+        let found = self.current_indexer().and_then(|indexer| {
+            let rows = indexer.search(&query).ok()?;
+            Some((rows, indexer.count(&query).ok()))
+        });
+        if let Some((rows, total)) = found {
+            self.results = rows;
+            self.results_total = total;
+            self.results_query = Some(query);
+        }
     }
 
     fn ui_search(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Query");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.query_text)
+                    .hint_text("ext:pdf size>10MB name~report (replaces the fields below)")
+                    .desired_width(480.0),
+            );
+        });
         ui.horizontal(|ui| {
             ui.label("Name");
             ui.text_edit_singleline(&mut self.name_like);
+            ui.checkbox(&mut self.fuzzy, "Fuzzy");
+            ui.checkbox(&mut self.match_case, "Match case");
+            ui.checkbox(&mut self.favorites_only, "Rated only");
             ui.label("Ext");
             ui.text_edit_singleline(&mut self.ext);
+            ui.label("MIME");
+            ui.text_edit_singleline(&mut self.mime);
+            ui.label("Content");
+            ui.text_edit_singleline(&mut self.content);
             ui.label("Min");
             ui.text_edit_singleline(&mut self.min_size);
             ui.label("Max");
             ui.text_edit_singleline(&mut self.max_size);
         });
 
+        // Wait for a pause in typing rather than query on every keystroke.
+        let input = (self.name_like.clone(), self.ext.clone());
+        if input != self.suggest_input {
+            self.suggest_input = input;
+            self.suggest_due = Some(Instant::now() + SUGGEST_DELAY);
+        }
+        if let Some(due) = self.suggest_due {
+            let now = Instant::now();
+            if now >= due {
+                self.suggest_due = None;
+                self.refresh_suggestions();
+            } else {
+                ui.ctx().request_repaint_after(due - now);
+            }
+        }
+        if !self.name_suggestions.is_empty() || !self.ext_suggestions.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label("Suggestions");
+                for token in &self.name_suggestions {
+                    if ui.small_button(token).clicked() {
+                        self.name_like = token.clone();
+                    }
+                }
+                for ext in &self.ext_suggestions {
+                    if ui.small_button(format!(".{}", ext)).clicked() {
+                        self.ext = match self.ext.rsplit_once(',') {
+                            Some((earlier, _)) => format!("{}, {}", earlier, ext),
+                            None => ext.clone(),
+                        };
+                    }
+                }
+            });
+        }
+
         ui.horizontal(|ui| {
             ui.label("From");
             ui.text_edit_singleline(&mut self.from);
@@ -140,140 +492,328 @@ impl FsIndexApp {
             ui.text_edit_singleline(&mut self.to);
             ui.label("Sort");
             egui::ComboBox::from_id_source("sort")
-                .selected_text(["Name", "Size", "Modified"][self.sort_idx])
+                .selected_text(SORT_LABELS[self.sort_idx])
                 .show_ui(ui, |ui| {
-                    for (idx, label) in ["Name", "Size", "Modified"].iter().enumerate() {
+                    for (idx, label) in SORT_LABELS.iter().enumerate() {
                         if ui.selectable_label(self.sort_idx == idx, *label).clicked() {
                             self.sort_idx = idx;
                         }
                     }
                 });
             ui.checkbox(&mut self.desc, "Desc");
+            ui.label("Then");
+            egui::ComboBox::from_id_source("then_sort")
+                .selected_text(self.then_sort_idx.map_or("-", |idx| SORT_LABELS[idx]))
+                .show_ui(ui, |ui| {
+                    if ui
+                        .selectable_label(self.then_sort_idx.is_none(), "-")
+                        .clicked()
+                    {
+                        self.then_sort_idx = None;
+                    }
+                    // Relevance only works as the primary order.
+                    for (idx, label) in SORT_LABELS.iter().enumerate().filter(|(i, _)| *i != 3) {
+                        if ui
+                            .selectable_label(self.then_sort_idx == Some(idx), *label)
+                            .clicked()
+                        {
+                            self.then_sort_idx = Some(idx);
+                        }
+                    }
+                });
+            ui.checkbox(&mut self.then_desc, "Desc");
             ui.label("Limit");
             ui.text_edit_singleline(&mut self.limit);
             ui.label("Offset");
             ui.text_edit_singleline(&mut self.offset);
             if ui.button("Search").clicked() {
-                if let Some(indexer) = self.current_indexer() {
-                    let mut query = SearchQuery::default();
-                    if !self.name_like.trim().is_empty() {
-                        query.name_like = Some(self.name_like.clone());
-                    }
-                    if !self.ext.trim().is_empty() {
-                        query.ext = Some(self.ext.clone());
-                    }
-                    query.min_size = Self::parse_num(&self.min_size);
-                    query.max_size = Self::parse_num(&self.max_size);
-                    query.date_from = Self::parse_date(&self.from);
-                    query.date_to = Self::parse_date(&self.to);
-                    query.sort_key = Some(match self.sort_idx {
-                        0 => SortKey::Name,
-                        1 => SortKey::Size,
-                        _ => SortKey::Modified,
-                    });
-                    query.desc = self.desc;
-                    query.limit = Self::parse_num(&self.limit);
-                    query.offset = Self::parse_num(&self.offset);
-
-                    if let Ok(rows) = indexer.search(&query) {
-                        self.results = rows;
+                let query = match self.search_query() {
+                    Ok(query) => query,
+                    Err(err) => {
+                        self.status = format!("{:#}", err);
+                        return;
                     }
+                };
+                self.run_search(query);
+            }
+            let next = self.results_query.as_ref().and_then(|query| {
+                let full = query.limit.is_some_and(|l| self.results.len() as i64 >= l);
+                let last = self.results.last().filter(|_| full)?;
+                let after = query.cursor_after(last).ok()?;
+                Some(SearchQuery {
+                    after: Some(after),
+                    offset: None,
+                    ..query.clone()
+                })
+            });
+            if let Some(next) = next {
+                if ui.button("Next page").clicked() {
+                    self.run_search(next);
                 }
             }
+            if let Some(total) = self.results_total {
+                ui.label(format!("{} results", total));
+            }
         });
 
+        let show_media = self.results.iter().any(|r| r.media.is_some());
+        let mut rated = None;
         egui::ScrollArea::vertical().show(ui, |ui| {
             egui::Grid::new("results").striped(true).show(ui, |ui| {
                 ui.heading("Name");
                 ui.heading("Ext");
                 ui.heading("Size");
                 ui.heading("Modified");
+                ui.heading("Rating");
+                if show_media {
+                    ui.heading("Duration");
+                    ui.heading("Media");
+                }
                 ui.heading("Path");
                 ui.end_row();
 
-                for record in &self.results {
+                for (idx, record) in self.results.iter().enumerate() {
                     ui.label(&record.name);
                     ui.label(record.ext.clone().unwrap_or_default());
                     ui.label(human_bytes(record.size as u64));
                     ui.label(record.modified.format("%Y-%m-%d %H:%M:%S").to_string());
+                    ui.horizontal(|ui| {
+                        for star in 1..=MAX_RATING {
+                            let icon = if star <= record.rating { "★" } else { "☆" };
+                            if ui.small_button(icon).clicked() {
+                                // Clicking the current rating clears it.
+                                let rating = if star == record.rating { 0 } else { star };
+                                rated = Some((idx, rating));
+                            }
+                        }
+                    });
+                    if show_media {
+                        let media = record.media.clone().unwrap_or_default();
+                        ui.label(media.duration.map(format_duration).unwrap_or_default());
+                        ui.label(media_summary(&media));
+                    }
                     ui.label(&record.path);
                     ui.end_row();
                 }
             });
         });
+
+        if let Some((idx, rating)) = rated {
+            let path = self.results[idx].fs_path();
+            let result = self
+                .open_indexer()
+                .and_then(|indexer| indexer.set_rating(&path, rating));
+            match result {
+                Ok(()) => self.results[idx].rating = rating,
+                Err(err) => self.status = err.to_string(),
+            }
+        }
     }
 
     fn ui_recent(&mut self, ui: &mut egui::Ui) {
-        if ui.button("Reload").clicked() {
-            if let Some(indexer) = self.current_indexer() {
-                if let Ok(rows) = indexer.recently_added(200) {
-                    self.results = rows;
+        ui.horizontal(|ui| {
+            ui.label("Days");
+            ui.text_edit_singleline(&mut self.activity_days);
+            ui.checkbox(&mut self.activity_modified, "By modified time");
+            if ui.button("Reload").clicked() {
+                let kind = if self.activity_modified {
+                    ActivityKind::Modified
+                } else {
+                    ActivityKind::Added
+                };
+                let days = Self::parse_num(&self.activity_days).unwrap_or(7);
+                let groups = self
+                    .current_indexer()
+                    .and_then(|indexer| indexer.activity(kind, days, 500).ok());
+                if let Some(groups) = groups {
+                    self.activity = groups;
                 }
             }
-        }
+        });
 
+        let today = Local::now().date_naive();
         egui::ScrollArea::vertical().show(ui, |ui| {
-            egui::Grid::new("recent").striped(true).show(ui, |ui| {
-                ui.heading("Added");
-                ui.heading("Name");
-                ui.heading("Path");
-                ui.end_row();
-
-                for record in &self.results {
-                    ui.label(record.added_at.format("%Y-%m-%d %H:%M:%S").to_string());
-                    ui.label(&record.name);
-                    ui.label(&record.path);
-                    ui.end_row();
-                }
-            });
+            for groups in self.activity.chunk_by(|a, b| a.day == b.day) {
+                let day = groups[0].day;
+                let total: i64 = groups.iter().map(|g| g.count).sum();
+                let label = match today.signed_duration_since(day).num_days() {
+                    0 => "today".to_string(),
+                    1 => "yesterday".to_string(),
+                    _ => day.to_string(),
+                };
+                egui::CollapsingHeader::new(format!("{}: {} files", label, total))
+                    .id_source(day)
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        egui::Grid::new(("activity", day))
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for group in groups {
+                                    ui.label(format!("{} files", group.count));
+                                    ui.label(human_bytes(group.bytes as u64));
+                                    ui.label(&group.dir);
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            }
         });
     }
 
     fn ui_duplicates(&mut self, ui: &mut egui::Ui) {
-        if ui.button("Find").clicked() {
-            if let Some(indexer) = self.current_indexer() {
-                if let Ok(groups) = indexer.duplicate_groups(100) {
-                    self.results.clear();
-                    let epoch = epoch_time();
-                    for group in groups {
-                        let hash = group.hash.clone();
-                        let size = group.size;
-                        for path in group.paths {
-                            self.results.push(FileRecord {
-                                path,
-                                name: String::new(),
-                                ext: None,
-                                size,
-                                modified: epoch,
-                                added_at: epoch,
-                                hash: Some(hash.clone()),
-                            });
+        let mut find = false;
+        ui.horizontal(|ui| {
+            find = ui.button("Find").clicked();
+            let selected = DUPLICATE_SORTS
+                .iter()
+                .find(|(_, sort)| *sort == self.duplicate_sort)
+                .map_or("", |(label, _)| *label);
+            egui::ComboBox::from_id_source("duplicate_sort")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (label, sort) in DUPLICATE_SORTS {
+                        if ui
+                            .selectable_label(self.duplicate_sort == sort, label)
+                            .clicked()
+                        {
+                            self.duplicate_sort = sort;
+                            find = true;
                         }
                     }
-                }
+                });
+        });
+        if find {
+            let sort = self.duplicate_sort;
+            let groups = self
+                .current_indexer()
+                .and_then(|indexer| indexer.duplicate_groups(100, 0, sort).ok());
+            if let Some(groups) = groups {
+                self.duplicates = groups;
             }
         }
 
+        let mut trash = None;
         egui::ScrollArea::vertical().show(ui, |ui| {
             egui::Grid::new("duplicates").striped(true).show(ui, |ui| {
                 ui.heading("Hash");
                 ui.heading("Size");
+                ui.heading("Wasted");
                 ui.heading("Path");
+                ui.heading("");
                 ui.end_row();
 
-                for record in &self.results {
-                    ui.label(record.hash.clone().unwrap_or_default());
-                    ui.label(human_bytes(record.size as u64));
-                    ui.label(&record.path);
-                    ui.end_row();
+                for group in &self.duplicates {
+                    for path in &group.paths {
+                        ui.label(&group.hash);
+                        ui.label(human_bytes(group.size as u64));
+                        ui.label(human_bytes(group.wasted as u64));
+                        ui.label(path);
+                        if ui.button("Move to trash").clicked() {
+                            trash = Some(path.clone());
+                        }
+                        ui.end_row();
+                    }
                 }
             });
         });
+
+        // Deletions from the GUI always go through the trash so they can be
+        // undone.
+        if let Some(path) = trash {
+            let result = self
+                .open_indexer()
+                .and_then(|indexer| indexer.trash_file(Path::new(&path)));
+            match result {
+                Ok(()) => {
+                    for group in &mut self.duplicates {
+                        group.paths.retain(|p| *p != path);
+                        group.count = group.paths.len() as i64;
+                        group.wasted = (group.count - 1).max(0) * group.size;
+                    }
+                    self.duplicates.retain(|group| group.paths.len() > 1);
+                    self.status = format!("Moved {} to the trash", path);
+                }
+                Err(err) => self.status = err.to_string(),
+            }
+        }
     }
-}
 
-fn epoch_time() -> DateTime<Utc> {
-    DateTime::<Utc>::from_timestamp(0, 0).unwrap()
+    fn ui_overview(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Reload").clicked() {
+            let stats = self
+                .current_indexer()
+                .and_then(|indexer| indexer.stats().ok());
+            if stats.is_some() {
+                self.stats = stats;
+            }
+        }
+        let Some(stats) = &self.stats else {
+            return;
+        };
+
+        ui.label(format!(
+            "{} files, {} ({} on disk; {} hashed, {} unhashed)",
+            stats.total_files,
+            human_bytes(stats.total_bytes as u64),
+            human_bytes(stats.allocated_bytes as u64),
+            stats.hashed_files,
+            stats.unhashed_files
+        ));
+        if stats.hash_skipped_files > 0 {
+            ui.label(format!(
+                "{} files left unhashed by the size or extension limits",
+                stats.hash_skipped_files
+            ));
+        }
+        if let (Some(oldest), Some(newest)) = (stats.oldest_modified, stats.newest_modified) {
+            ui.label(format!(
+                "Modified between {} and {}",
+                oldest.format("%Y-%m-%d"),
+                newest.format("%Y-%m-%d")
+            ));
+        }
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.columns(2, |cols| {
+                egui::Grid::new("stats_ext")
+                    .striped(true)
+                    .show(&mut cols[0], |ui| {
+                        ui.heading("Ext");
+                        ui.heading("Files");
+                        ui.heading("Size");
+                        ui.end_row();
+                        for ext in &stats.by_extension {
+                            ui.label(ext.ext.as_deref().unwrap_or("(none)"));
+                            ui.label(ext.count.to_string());
+                            ui.label(human_bytes(ext.bytes as u64));
+                            ui.end_row();
+                        }
+                    });
+                egui::Grid::new("stats_size")
+                    .striped(true)
+                    .show(&mut cols[1], |ui| {
+                        ui.heading("File size");
+                        ui.heading("Files");
+                        ui.heading("Total");
+                        ui.end_row();
+                        for bucket in &stats.size_distribution {
+                            let range = match bucket.max {
+                                Some(max) => format!(
+                                    "{} – {}",
+                                    human_bytes(bucket.min as u64),
+                                    human_bytes(max as u64)
+                                ),
+                                None => format!("≥ {}", human_bytes(bucket.min as u64)),
+                            };
+                            ui.label(range);
+                            ui.label(bucket.count.to_string());
+                            ui.label(human_bytes(bucket.bytes as u64));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+    }
 }
 
 fn human_bytes(bytes: u64) -> String {
@@ -292,3 +832,31 @@ fn human_bytes(bytes: u64) -> String {
         format!("{:.2} {}", value, UNITS[unit])
     }
 }
+
+fn format_duration(seconds: f64) -> String {
+    let total = seconds.round() as i64;
+    let (hours, minutes, secs) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+fn media_summary(media: &MediaInfo) -> String {
+    let mut parts = Vec::new();
+    if let (Some(w), Some(h)) = (media.width, media.height) {
+        parts.push(format!("{}x{}", w, h));
+    }
+    if let Some(codec) = &media.codec {
+        parts.push(codec.clone());
+    }
+    let tags: Vec<&str> = [&media.artist, &media.album, &media.title]
+        .into_iter()
+        .filter_map(|tag| tag.as_deref())
+        .collect();
+    if !tags.is_empty() {
+        parts.push(tags.join(" - "));
+    }
+    parts.join(", ")
+}